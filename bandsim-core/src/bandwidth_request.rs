@@ -0,0 +1,445 @@
+use crate::chain::{ShardUId, SimulationConfig};
+
+pub const BANDWIDTH_REQUEST_VALUES_NUM: usize = 40;
+
+#[derive(Clone, Debug)]
+pub struct BandwidthRequest {
+    pub to_shard: ShardUId,
+    pub grant_options_bitmap: BandwidthRequestBitmap,
+}
+
+/// Size of a serialized `BandwidthRequest` - the receiving shard id (u16) followed by the bitmap.
+pub const BANDWIDTH_REQUEST_SERIALIZED_SIZE: usize =
+    std::mem::size_of::<u16>() + BANDWIDTH_REQUEST_BITMAP_ARRAY_SIZE;
+
+/// Bandwidth requests in a chunk are a versioned list - one byte of version and a u32 length,
+/// followed by the requests.
+pub const BANDWIDTH_REQUESTS_HEADER_SIZE: usize = 1 + std::mem::size_of::<u32>();
+
+/// Number of bytes taken by all bandwidth requests in a single chunk.
+pub fn serialized_requests_size(requests: &[BandwidthRequest]) -> usize {
+    BANDWIDTH_REQUESTS_HEADER_SIZE + requests.len() * BANDWIDTH_REQUEST_SERIALIZED_SIZE
+}
+
+/// Alternative encoding of all bandwidth requests in a chunk as one compact message.
+/// Instead of a list of independent requests, there's a shared header - a version byte and a bitmap
+/// of shards that have a request - followed by the request bitmaps, in the order of the shard layout.
+/// The receiving shard ids aren't stored, they are implied by the presence bitmap.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AggregatedBandwidthRequests {
+    /// Bit `i` is set when there's a request to the `i`th shard in the layout
+    pub shard_presence: Vec<u8>,
+    pub bitmaps: Vec<BandwidthRequestBitmap>,
+}
+
+impl AggregatedBandwidthRequests {
+    /// Aggregate requests to shards from `shard_ids` (which should be all shards in the layout, sorted).
+    pub fn new(
+        shard_ids: &[ShardUId],
+        requests: &[BandwidthRequest],
+    ) -> AggregatedBandwidthRequests {
+        let mut shard_presence = vec![0; shard_ids.len().div_ceil(8)];
+        let mut bitmaps = Vec::new();
+        for (index, shard_id) in shard_ids.iter().enumerate() {
+            if let Some(request) = requests.iter().find(|r| r.to_shard == *shard_id) {
+                shard_presence[index / 8] |= 1 << (index % 8);
+                bitmaps.push(request.grant_options_bitmap.clone());
+            }
+        }
+        AggregatedBandwidthRequests {
+            shard_presence,
+            bitmaps,
+        }
+    }
+
+    /// Number of bytes taken by the aggregated requests of a chunk in a layout with this many shards.
+    pub fn serialized_size(num_shards: usize, num_requests: usize) -> usize {
+        1 + num_shards.div_ceil(8) + num_requests * BANDWIDTH_REQUEST_BITMAP_ARRAY_SIZE
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0];
+        bytes.extend_from_slice(&self.shard_presence);
+        for bitmap in &self.bitmaps {
+            bytes.extend_from_slice(&bitmap.to_bytes());
+        }
+        bytes
+    }
+
+    /// Decode the requests, `shard_ids` must be the same as the ones used to encode them.
+    pub fn from_bytes(shard_ids: &[ShardUId], bytes: &[u8]) -> Vec<BandwidthRequest> {
+        assert_eq!(
+            bytes[0], 0,
+            "Unknown version of aggregated bandwidth requests"
+        );
+        let (shard_presence, mut bitmaps) = bytes[1..].split_at(shard_ids.len().div_ceil(8));
+        let mut requests = Vec::new();
+        for (index, shard_id) in shard_ids.iter().enumerate() {
+            if shard_presence[index / 8] & (1 << (index % 8)) == 0 {
+                continue;
+            }
+            let (bitmap, rest) = bitmaps.split_at(BANDWIDTH_REQUEST_BITMAP_ARRAY_SIZE);
+            bitmaps = rest;
+            requests.push(BandwidthRequest {
+                to_shard: *shard_id,
+                grant_options_bitmap: BandwidthRequestBitmap::from_bytes(
+                    bitmap.try_into().unwrap(),
+                ),
+            });
+        }
+        assert!(
+            bitmaps.is_empty(),
+            "Trailing bytes in aggregated bandwidth requests"
+        );
+        requests
+    }
+}
+
+impl BandwidthRequest {
+    pub fn to_bytes(&self) -> [u8; BANDWIDTH_REQUEST_SERIALIZED_SIZE] {
+        let mut bytes = [0; BANDWIDTH_REQUEST_SERIALIZED_SIZE];
+        let to_shard: u16 = self.to_shard.shard_id.try_into().unwrap();
+        bytes[..2].copy_from_slice(&to_shard.to_le_bytes());
+        bytes[2..].copy_from_slice(&self.grant_options_bitmap.to_bytes());
+        bytes
+    }
+
+    pub fn from_receipt_sizes(
+        to_shard: ShardUId,
+        receipt_sizes: impl Iterator<Item = usize>,
+        base_bandwidth: usize,
+        config: &SimulationConfig,
+    ) -> Option<BandwidthRequest> {
+        let values = BandwidthRequestValues::new(base_bandwidth, config);
+        let mut bitmap = BandwidthRequestBitmap::new();
+
+        let mut total_size = 0;
+        let mut cur_value = 0;
+        for receipt_size in receipt_sizes {
+            total_size += receipt_size;
+
+            if total_size <= base_bandwidth {
+                continue;
+            }
+
+            // Find a value that is at least as big as the total_size
+            while cur_value < values.0.len() && values.0[cur_value] < total_size {
+                cur_value += 1;
+            }
+
+            if cur_value == values.0.len() {
+                bitmap.set_bit(bitmap.len() - 1, true);
+                break;
+            }
+
+            // Request the value thath is at least as large as total_size
+            bitmap.set_bit(cur_value, true);
+        }
+
+        if bitmap.is_all_false() {
+            return None;
+        }
+
+        Some(BandwidthRequest {
+            to_shard,
+            grant_options_bitmap: bitmap,
+        })
+    }
+}
+
+impl BandwidthRequest {
+    /// Same as `from_receipt_sizes`, but doesn't look at every receipt.
+    /// `first_prefix_sum_above(x)` should return the smallest sum of sizes of the first k receipts
+    /// that is larger than x, or None if there is no such sum. Needs to be called only once per request value.
+    pub fn from_prefix_sums(
+        to_shard: ShardUId,
+        first_prefix_sum_above: impl Fn(usize) -> Option<usize>,
+        base_bandwidth: usize,
+        config: &SimulationConfig,
+    ) -> Option<BandwidthRequest> {
+        let values = BandwidthRequestValues::new(base_bandwidth, config);
+        let mut bitmap = BandwidthRequestBitmap::new();
+
+        // The nth value is requested when some prefix sum falls between the previous value and the nth value.
+        // The last value is also requested when there are prefix sums above it.
+        let mut lower_bound = base_bandwidth;
+        for (i, value) in values.0.iter().enumerate() {
+            let Some(prefix_sum) = first_prefix_sum_above(lower_bound) else {
+                break;
+            };
+            if prefix_sum <= *value || i == values.0.len() - 1 {
+                bitmap.set_bit(i, true);
+            }
+            lower_bound = *value;
+        }
+
+        if bitmap.is_all_false() {
+            return None;
+        }
+
+        Some(BandwidthRequest {
+            to_shard,
+            grant_options_bitmap: bitmap,
+        })
+    }
+}
+
+/// Bandwidth values that can be requested in a BandwidthRequest.
+/// nth bit in the bitmap is set when the shard requests the nth value as one of the options.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BandwidthRequestValues(pub [usize; BANDWIDTH_REQUEST_VALUES_NUM]);
+
+impl BandwidthRequestValues {
+    pub fn new(base_bandwidth: usize, config: &SimulationConfig) -> BandwidthRequestValues {
+        let max_bandwidth = config.max_shard_bandwidth;
+        let max_receipt_size = config.max_receipt_size;
+        // values[-1] = base_bandwidth
+        // values[values.len() - 1] = max_bandwidth
+        // values[i] = linear interpolation between values[-1] and values[values.len() - 1]
+        let mut values = [0; BANDWIDTH_REQUEST_VALUES_NUM];
+        for i in 0..values.len() {
+            values[i] = base_bandwidth + (max_bandwidth - base_bandwidth) * (i + 1) / values.len();
+        }
+
+        // The value that is closest to the max receipt size is set to the max receipt size.
+        // This ensures that the value corresponding to max size receipts can be granted after base bandwidth is granted.
+        let mut closest_to_max: usize = 0;
+        for value in &values {
+            if value.abs_diff(max_receipt_size) < closest_to_max.abs_diff(max_receipt_size) {
+                closest_to_max = *value;
+            }
+        }
+        for value in values.iter_mut() {
+            if *value == closest_to_max {
+                *value = max_receipt_size;
+            }
+        }
+
+        let values_sorted = values.windows(2).all(|w| w[0] < w[1]);
+        if !values_sorted {
+            panic!(
+                "Values not sorted! base_bandwidth = {}, max_bandwidth = {}",
+                base_bandwidth, max_bandwidth
+            );
+        }
+
+        BandwidthRequestValues(values)
+    }
+}
+
+/// Number of bytes needed to serialize the bitmap.
+pub const BANDWIDTH_REQUEST_BITMAP_ARRAY_SIZE: usize = BANDWIDTH_REQUEST_VALUES_NUM.div_ceil(8);
+
+/// Bitmap stored in a single u64, bit `i` is the `i`-th bit of the u64.
+/// The serialized format is an array of bytes, where bit `i` is stored in byte `i / 8` at position `i % 8`.
+#[allow(clippy::len_without_is_empty)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct BandwidthRequestBitmap(u64);
+
+const _: () = assert!(BANDWIDTH_REQUEST_VALUES_NUM <= u64::BITS as usize);
+
+impl BandwidthRequestBitmap {
+    pub fn new() -> BandwidthRequestBitmap {
+        BandwidthRequestBitmap(0)
+    }
+
+    pub fn set_bit(&mut self, index: usize, value: bool) {
+        if index >= BANDWIDTH_REQUEST_VALUES_NUM {
+            panic!(
+                "set_bit index out of bounds: {} >= {}",
+                index, BANDWIDTH_REQUEST_VALUES_NUM
+            );
+        }
+
+        if value {
+            self.0 |= 1_u64 << index;
+        } else {
+            self.0 &= !(1_u64 << index);
+        }
+    }
+
+    pub fn get_bit(&self, index: usize) -> bool {
+        if index >= BANDWIDTH_REQUEST_VALUES_NUM {
+            panic!(
+                "get_bit index out of bounds: {} >= {}",
+                index, BANDWIDTH_REQUEST_VALUES_NUM
+            );
+        }
+
+        (self.0 >> index) & 1 == 1
+    }
+
+    pub fn len(&self) -> usize {
+        BANDWIDTH_REQUEST_VALUES_NUM
+    }
+
+    pub fn is_all_false(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Number of unset bits before the first set bit. Equal to `len()` when no bit is set.
+    pub fn trailing_zeros(&self) -> usize {
+        std::cmp::min(
+            self.0.trailing_zeros() as usize,
+            BANDWIDTH_REQUEST_VALUES_NUM,
+        )
+    }
+
+    /// Number of unset bits after the last set bit. Equal to `len()` when no bit is set.
+    pub fn leading_zeros(&self) -> usize {
+        self.0.leading_zeros() as usize - (u64::BITS as usize - BANDWIDTH_REQUEST_VALUES_NUM)
+    }
+
+    pub fn to_bytes(&self) -> [u8; BANDWIDTH_REQUEST_BITMAP_ARRAY_SIZE] {
+        let mut bytes = [0; BANDWIDTH_REQUEST_BITMAP_ARRAY_SIZE];
+        bytes.copy_from_slice(&self.0.to_le_bytes()[..BANDWIDTH_REQUEST_BITMAP_ARRAY_SIZE]);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; BANDWIDTH_REQUEST_BITMAP_ARRAY_SIZE]) -> BandwidthRequestBitmap {
+        let mut le_bytes = [0; 8];
+        le_bytes[..BANDWIDTH_REQUEST_BITMAP_ARRAY_SIZE].copy_from_slice(bytes);
+        let value = u64::from_le_bytes(le_bytes);
+        assert!(
+            value >> BANDWIDTH_REQUEST_VALUES_NUM == 0,
+            "Bits set beyond the bitmap length: {:?}",
+            bytes
+        );
+        BandwidthRequestBitmap(value)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BandwidthRequestOptions(pub Vec<usize>);
+
+impl BandwidthRequestOptions {
+    pub fn from_bitmap(
+        bitmap: &BandwidthRequestBitmap,
+        base_bandwidth: usize,
+        config: &SimulationConfig,
+    ) -> BandwidthRequestOptions {
+        let values = BandwidthRequestValues::new(base_bandwidth, config);
+        let mut options = Vec::new();
+        for i in bitmap.trailing_zeros()..bitmap.len() - bitmap.leading_zeros() {
+            if bitmap.get_bit(i) {
+                options.push(values.0[i]);
+            }
+        }
+        BandwidthRequestOptions(options)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use rand::seq::SliceRandom;
+    use rand::Rng;
+
+    use crate::rng::rng_from_seed;
+
+    use crate::chain::ShardUId;
+
+    use super::{
+        serialized_requests_size, AggregatedBandwidthRequests, BandwidthRequest,
+        BandwidthRequestBitmap, BANDWIDTH_REQUESTS_HEADER_SIZE,
+        BANDWIDTH_REQUEST_BITMAP_ARRAY_SIZE, BANDWIDTH_REQUEST_VALUES_NUM,
+    };
+
+    #[test]
+    fn test_bandwidth_request_bitmap() {
+        let mut bitmap = BandwidthRequestBitmap::new();
+        assert_eq!(bitmap.len(), BANDWIDTH_REQUEST_VALUES_NUM);
+
+        let mut fake_bitmap = vec![false; bitmap.len()];
+
+        let mut rng = rng_from_seed(0);
+
+        for _ in 0..1000 {
+            let index = rng.gen_range(0..bitmap.len());
+            let value = rng.gen_bool(0.5);
+
+            bitmap.set_bit(index, value);
+            fake_bitmap[index] = value;
+
+            let mut indexes: Vec<usize> = (0..bitmap.len()).collect();
+            indexes.shuffle(&mut rng);
+            for i in indexes {
+                assert_eq!(bitmap.get_bit(i), fake_bitmap[i]);
+            }
+
+            let first_set = fake_bitmap.iter().position(|b| *b);
+            let last_set = fake_bitmap.iter().rposition(|b| *b);
+            assert_eq!(bitmap.trailing_zeros(), first_set.unwrap_or(bitmap.len()));
+            assert_eq!(
+                bitmap.leading_zeros(),
+                last_set.map_or(bitmap.len(), |last| bitmap.len() - 1 - last)
+            );
+            assert_eq!(bitmap.is_all_false(), first_set.is_none());
+            assert_eq!(
+                BandwidthRequestBitmap::from_bytes(&bitmap.to_bytes()),
+                bitmap
+            );
+        }
+    }
+
+    #[test]
+    fn test_bandwidth_request_bitmap_wire_format() {
+        assert_eq!(BANDWIDTH_REQUEST_BITMAP_ARRAY_SIZE, 5);
+
+        let mut bitmap = BandwidthRequestBitmap::new();
+        assert_eq!(bitmap.to_bytes(), [0; BANDWIDTH_REQUEST_BITMAP_ARRAY_SIZE]);
+        assert_eq!(bitmap.trailing_zeros(), bitmap.len());
+        assert_eq!(bitmap.leading_zeros(), bitmap.len());
+
+        // Bit `i` is stored in byte `i / 8` at position `i % 8`
+        bitmap.set_bit(0, true);
+        bitmap.set_bit(9, true);
+        bitmap.set_bit(BANDWIDTH_REQUEST_VALUES_NUM - 1, true);
+        assert_eq!(bitmap.to_bytes(), [0b1, 0b10, 0, 0, 0b1000_0000]);
+        assert_eq!(bitmap.trailing_zeros(), 0);
+        assert_eq!(bitmap.leading_zeros(), 0);
+    }
+
+    #[test]
+    fn test_aggregated_requests_roundtrip() {
+        let shard_ids: Vec<ShardUId> = (0..10).map(ShardUId::new).collect();
+        let requests: Vec<BandwidthRequest> = [1, 8, 9]
+            .into_iter()
+            .map(|shard| {
+                let mut grant_options_bitmap = BandwidthRequestBitmap::new();
+                grant_options_bitmap.set_bit(shard, true);
+                BandwidthRequest {
+                    to_shard: ShardUId::new(shard),
+                    grant_options_bitmap,
+                }
+            })
+            .collect();
+
+        let aggregated = AggregatedBandwidthRequests::new(&shard_ids, &requests);
+        assert_eq!(aggregated.shard_presence, vec![0b10, 0b11]);
+        let bytes = aggregated.to_bytes();
+        assert_eq!(
+            bytes.len(),
+            AggregatedBandwidthRequests::serialized_size(10, 3)
+        );
+        let decoded = AggregatedBandwidthRequests::from_bytes(&shard_ids, &bytes);
+        assert_eq!(
+            decoded.iter().map(|r| r.to_bytes()).collect::<Vec<_>>(),
+            requests.iter().map(|r| r.to_bytes()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_bandwidth_request_wire_format() {
+        let mut grant_options_bitmap = BandwidthRequestBitmap::new();
+        grant_options_bitmap.set_bit(9, true);
+        let request = BandwidthRequest {
+            to_shard: ShardUId::new(258),
+            grant_options_bitmap,
+        };
+        assert_eq!(request.to_bytes(), [2, 1, 0, 0b10, 0, 0, 0]);
+        assert_eq!(
+            serialized_requests_size(&[request.clone(), request]),
+            BANDWIDTH_REQUESTS_HEADER_SIZE + 2 * 7
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::BTreeMap;
 
-use crate::bandsim::chain::{ShardLink, ShardUId};
+use crate::chain::{ShardLink, ShardUId};
 
 /// Magic algorithm which distributes the remaining bandwidth in a fair way (∩ ͡° ͜ʖ ͡°)⊃━☆ﾟ. * ･ ｡ﾟ,
 /// The arguments describe how much spare bandwidth there is on the left (sending) shards and right (receiving) shards.
@@ -9,8 +9,8 @@ pub fn distribute_remaining_bandwidth(
     left: &BTreeMap<ShardUId, usize>,
     right: &BTreeMap<ShardUId, usize>,
 ) -> BTreeMap<ShardLink, usize> {
-    let left_sum: usize = left.iter().map(|(_shard, bandwidth)| bandwidth).sum();
-    let right_sum: usize = right.iter().map(|(_shard, bandwidth)| bandwidth).sum();
+    let left_sum: usize = left.values().sum();
+    let right_sum: usize = right.values().sum();
 
     if right_sum < left_sum {
         let flipped_res = distribute_remaining_bandwidth(right, left);
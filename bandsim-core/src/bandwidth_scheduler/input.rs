@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+
+use crate::bandwidth_request::BandwidthRequest;
+use crate::chain::{Block, ShardLink, ShardUId};
+
+/// Which shards exist and which of them produced a chunk at the previous height.
+/// Implemented by every chain model that drives the scheduler.
+pub trait ChunkPresence {
+    /// Shards which exist at this height, sorted and without duplicates.
+    fn shards(&self) -> &[ShardUId];
+
+    /// Whether the previous chunk of the shard is present. Nothing can be sent to shards with a missing chunk.
+    fn is_chunk_present(&self, shard: ShardUId) -> bool;
+
+    fn contains_shard(&self, shard: ShardUId) -> bool {
+        self.shards().binary_search(&shard).is_ok()
+    }
+
+    /// All links between the shards, including links from a shard to itself.
+    fn all_links(&self) -> impl Iterator<Item = ShardLink> + '_ {
+        let shards = self.shards();
+        shards.iter().flat_map(move |from| {
+            shards.iter().map(move |to| ShardLink {
+                from: *from,
+                to: *to,
+            })
+        })
+    }
+}
+
+/// Bandwidth requests made at the previous height.
+/// Implemented by every chain model that drives the scheduler.
+pub trait RequestsSource {
+    /// Requests of every shard with a present chunk, in the order in which they appear in the chunk.
+    /// Shards must be in increasing order, all nodes have to process the requests in the same order
+    /// to arrive at the same grants.
+    fn shard_requests(&self) -> impl Iterator<Item = (ShardUId, &[BandwidthRequest])> + '_;
+
+    /// Quotas advertised by the receivers, by link, see `ReceiverQuotas`.
+    /// Links without a quota are limited only by the bandwidth of the shards.
+    fn receiver_quotas(&self) -> BTreeMap<ShardLink, usize> {
+        BTreeMap::new()
+    }
+}
+
+/// Everything that `BandwidthScheduler::run` needs to know about the previous block, as plain data.
+/// Useful for hand-built inputs, other chain models can implement `ChunkPresence` and `RequestsSource` directly.
+pub struct SchedulerInput<'a> {
+    /// Shards which exist at this height, sorted and without duplicates.
+    pub shards: &'a [ShardUId],
+    /// Whether the previous chunk of every shard is present.
+    pub chunk_present: BTreeMap<ShardUId, bool>,
+    /// Bandwidth requests of every shard with a present chunk, in the order in which they appear in the chunk.
+    pub requests: BTreeMap<ShardUId, &'a [BandwidthRequest]>,
+}
+
+impl ChunkPresence for SchedulerInput<'_> {
+    fn shards(&self) -> &[ShardUId] {
+        self.shards
+    }
+
+    fn is_chunk_present(&self, shard: ShardUId) -> bool {
+        self.chunk_present.get(&shard).copied().unwrap_or(false)
+    }
+}
+
+impl RequestsSource for SchedulerInput<'_> {
+    fn shard_requests(&self) -> impl Iterator<Item = (ShardUId, &[BandwidthRequest])> + '_ {
+        self.requests
+            .iter()
+            .map(|(shard, requests)| (*shard, *requests))
+    }
+}
+
+impl ChunkPresence for Block {
+    fn shards(&self) -> &[ShardUId] {
+        self.shard_layout.shard_ids()
+    }
+
+    fn is_chunk_present(&self, shard: ShardUId) -> bool {
+        matches!(self.chunks.get(&shard), Some(Some(_)))
+    }
+}
+
+impl RequestsSource for Block {
+    fn shard_requests(&self) -> impl Iterator<Item = (ShardUId, &[BandwidthRequest])> + '_ {
+        self.chunks.iter().filter_map(|(shard, chunk_opt)| {
+            let chunk = chunk_opt.as_ref()?;
+            Some((*shard, chunk.bandwidth_requests.as_slice()))
+        })
+    }
+
+    fn receiver_quotas(&self) -> BTreeMap<ShardLink, usize> {
+        let mut quotas = BTreeMap::new();
+        for (receiver, chunk) in self
+            .chunks
+            .iter()
+            .filter_map(|(shard, chunk_opt)| Some((*shard, chunk_opt.as_ref()?)))
+        {
+            for (sender, quota) in &chunk.receiver_quotas {
+                let link = ShardLink {
+                    from: *sender,
+                    to: receiver,
+                };
+                quotas.insert(link, *quota);
+            }
+        }
+        quotas
+    }
+}
+
+impl<'a> From<&'a Block> for SchedulerInput<'a> {
+    fn from(block: &'a Block) -> Self {
+        let mut chunk_present = BTreeMap::new();
+        let mut requests = BTreeMap::new();
+        for (shard, chunk_opt) in &block.chunks {
+            chunk_present.insert(*shard, chunk_opt.is_some());
+            if let Some(chunk) = chunk_opt {
+                requests.insert(*shard, chunk.bandwidth_requests.as_slice());
+            }
+        }
+        SchedulerInput {
+            shards: block.shard_layout.shard_ids(),
+            chunk_present,
+            requests,
+        }
+    }
+}
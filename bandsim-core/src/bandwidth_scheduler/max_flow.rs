@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+
+/// Flow network for Dinic's max flow algorithm.
+/// Edges are added one by one, after running the algorithm the flow on every edge can be read back.
+pub struct FlowNetwork {
+    /// Indexes of the edges that leave every node.
+    adjacency: Vec<Vec<usize>>,
+    /// Edges are stored in pairs, edge `2k` is a forward edge and `2k + 1` is its reverse (residual) edge.
+    edges: Vec<FlowEdge>,
+}
+
+struct FlowEdge {
+    to: usize,
+    /// Remaining capacity of the edge.
+    capacity: usize,
+}
+
+impl FlowNetwork {
+    pub fn new(num_nodes: usize) -> FlowNetwork {
+        FlowNetwork {
+            adjacency: vec![Vec::new(); num_nodes],
+            edges: Vec::new(),
+        }
+    }
+
+    /// Add an edge with the given capacity, returns its id which can be passed to `flow`.
+    pub fn add_edge(&mut self, from: usize, to: usize, capacity: usize) -> usize {
+        let id = self.edges.len();
+        self.edges.push(FlowEdge { to, capacity });
+        self.edges.push(FlowEdge {
+            to: from,
+            capacity: 0,
+        });
+        self.adjacency[from].push(id);
+        self.adjacency[to].push(id + 1);
+        id
+    }
+
+    /// Flow that goes through the edge, the capacity of the reverse edge.
+    pub fn flow(&self, edge: usize) -> usize {
+        self.edges[edge + 1].capacity
+    }
+
+    /// Push as much flow as possible from the source to the sink, returns the total flow.
+    /// Can be called only once, the capacities are used up by the flow.
+    pub fn max_flow(&mut self, source: usize, sink: usize) -> usize {
+        let mut total_flow = 0;
+        while let Some(levels) = self.levels(source, sink) {
+            // Index of the next edge to try on every node, edges which can't reach the sink are skipped for good.
+            let mut next_edge = vec![0; self.adjacency.len()];
+            loop {
+                let flow = self.augment(source, sink, usize::MAX, &levels, &mut next_edge);
+                if flow == 0 {
+                    break;
+                }
+                total_flow += flow;
+            }
+        }
+        total_flow
+    }
+
+    /// Distance of every node from the source over edges with spare capacity, `None` when the sink is unreachable.
+    fn levels(&self, source: usize, sink: usize) -> Option<Vec<Option<usize>>> {
+        let mut levels = vec![None; self.adjacency.len()];
+        levels[source] = Some(0);
+        let mut queue = VecDeque::from([source]);
+        while let Some(node) = queue.pop_front() {
+            for &edge in &self.adjacency[node] {
+                let FlowEdge { to, capacity } = self.edges[edge];
+                if capacity > 0 && levels[to].is_none() {
+                    levels[to] = Some(levels[node].unwrap() + 1);
+                    queue.push_back(to);
+                }
+            }
+        }
+        levels[sink].map(|_| levels)
+    }
+
+    /// Find a path to the sink which goes one level deeper at every step and push as much flow as possible through it.
+    fn augment(
+        &mut self,
+        node: usize,
+        sink: usize,
+        limit: usize,
+        levels: &[Option<usize>],
+        next_edge: &mut [usize],
+    ) -> usize {
+        if node == sink {
+            return limit;
+        }
+        while next_edge[node] < self.adjacency[node].len() {
+            let edge = self.adjacency[node][next_edge[node]];
+            let FlowEdge { to, capacity } = self.edges[edge];
+            if capacity > 0 && levels[to] == levels[node].map(|level| level + 1) {
+                let flow = self.augment(to, sink, limit.min(capacity), levels, next_edge);
+                if flow > 0 {
+                    self.edges[edge].capacity -= flow;
+                    self.edges[edge ^ 1].capacity += flow;
+                    return flow;
+                }
+            }
+            next_edge[node] += 1;
+        }
+        0
+    }
+}
+
+#[test]
+fn test_dinic_max_flow() {
+    // Two senders (1, 2) and two receivers (3, 4), sender 1 can send to both receivers.
+    let (source, sink) = (0, 5);
+    let mut network = FlowNetwork::new(6);
+    network.add_edge(source, 1, 100);
+    network.add_edge(source, 2, 100);
+    let edge_1_3 = network.add_edge(1, 3, 100);
+    let edge_1_4 = network.add_edge(1, 4, 100);
+    let edge_2_3 = network.add_edge(2, 3, 100);
+    network.add_edge(3, sink, 100);
+    network.add_edge(4, sink, 30);
+
+    // The flow from 1 has to be rerouted to 4 to make space for 2 on 3.
+    assert_eq!(network.max_flow(source, sink), 130);
+    assert_eq!(network.flow(edge_1_3) + network.flow(edge_2_3), 100);
+    assert_eq!(network.flow(edge_1_4), 30);
+
+    // Unreachable sink
+    let mut network = FlowNetwork::new(3);
+    network.add_edge(0, 1, 100);
+    assert_eq!(network.max_flow(0, 2), 0);
+}
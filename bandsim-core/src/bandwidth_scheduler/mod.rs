@@ -0,0 +1,1113 @@
+pub mod distribute_remaining;
+pub mod input;
+pub mod max_flow;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::bandwidth_request::{
+    BandwidthRequest, BandwidthRequestOptions, BANDWIDTH_REQUEST_VALUES_NUM,
+};
+use crate::chain::{CongestionInfo, ShardLink, ShardUId, SimulationConfig};
+use crate::rng::DefaultRng;
+
+use self::input::{ChunkPresence, RequestsSource};
+use self::max_flow::FlowNetwork;
+
+/// The maximum size of "base" bandwidth that is granted to all shards.
+const MAX_BASE_BANDWIDTH: usize = 100_000;
+
+/// Tunable parameters of the bandwidth scheduler.
+/// All shards must use the same parameters, otherwise their scheduler states would diverge.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchedulerParams {
+    /// How much allowance the links acquire at every height and how much they can hold.
+    /// Used only by `SchedulerAlgorithm::Allowance`.
+    pub allowance: AllowancePolicy,
+    /// The maximum size of "base" bandwidth that is granted to all shards.
+    pub max_base_bandwidth: usize,
+    /// Bandwidth which isn't granted on any shard, it's left for unstoppable receipts which are sent
+    /// regardless of grants. Reduces both the incoming and outgoing limit of every shard.
+    pub unstoppable_reserve: usize,
+    /// How the scheduler decides which links get the bandwidth first.
+    pub algorithm: SchedulerAlgorithm,
+    /// Shards in maintenance mode, nothing is granted in the direction that is under maintenance.
+    pub maintenance: BTreeMap<ShardUId, Maintenance>,
+    /// Max total number of bytes granted on all links at one height, models an aggregate network budget
+    /// which the per-shard limits can't express. When the grants exceed it, all of them are scaled down
+    /// proportionally. Receipts larger than the scaled grant of their link can't be sent, a budget that is
+    /// small compared to the number of links stalls the big receipts. `None` means that there's no global budget.
+    pub global_budget: Option<usize>,
+    /// Receiver-driven flow control, an alternative to the design where only the senders say what they need.
+    /// When set, every shard advertises a quota for every sender in its chunk and no link is granted more
+    /// than its quota. `None` means that the receivers don't advertise any quotas.
+    pub receiver_quotas: Option<ReceiverQuotas>,
+    /// Limit on the total size of a chunk, which is separate from the grants. The scheduler doesn't look at it,
+    /// the shards stop sending receipts when the chunk is full even if they have some grant left.
+    /// `None` means that only the grants limit the receipts in a chunk.
+    pub chunk_size_limit: Option<ChunkSizeLimit>,
+    /// Max number of bytes that the shard can send and receive at a single height, for shards which don't use
+    /// the limits from `SimulationConfig`. Allows to model shards with better, worse or asymmetric network links.
+    pub shard_bandwidth: BTreeMap<ShardUId, ShardBandwidth>,
+}
+
+/// How the links acquire allowance, see `SchedulerParams::allowance`.
+/// A bigger increment or a lower cap makes the links which waited lose their advantage sooner,
+/// the allowances converge faster but the scheduler remembers less of the past.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AllowancePolicy {
+    /// Max allowance that a ShardLink can acquire
+    pub max_allowance: usize,
+    /// How much allowance every link gets at every height.
+    pub per_height_increment: AllowanceIncrement,
+}
+
+/// Allowance added to every link at every height, see `AllowancePolicy`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AllowanceIncrement {
+    /// The max shard bandwidth divided by the number of shards. When all links from a shard are busy,
+    /// the shard gets as much allowance as it can send.
+    #[default]
+    FairShare,
+    /// The same number of bytes regardless of the number of shards.
+    Fixed(usize),
+}
+
+impl Default for AllowancePolicy {
+    fn default() -> Self {
+        AllowancePolicy::for_config(&SimulationConfig::default())
+    }
+}
+
+impl AllowancePolicy {
+    /// A link can acquire at most the max shard bandwidth of allowance, it gets its fair share at every height.
+    pub fn for_config(config: &SimulationConfig) -> Self {
+        AllowancePolicy {
+            max_allowance: config.max_shard_bandwidth,
+            per_height_increment: AllowanceIncrement::default(),
+        }
+    }
+
+    /// Allowance added to every link at every height.
+    pub fn increment(&self, config: &SimulationConfig, num_shards: usize) -> usize {
+        match self.per_height_increment {
+            AllowanceIncrement::FairShare => config.max_shard_bandwidth / num_shards,
+            AllowanceIncrement::Fixed(increment) => increment,
+        }
+    }
+}
+
+/// Capacity of a single shard, see `SchedulerParams::shard_bandwidth`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShardBandwidth {
+    /// Max number of bytes that the shard can send at a single height.
+    pub outgoing: usize,
+    /// Max number of bytes that the shard can receive at a single height.
+    pub incoming: usize,
+}
+
+impl ShardBandwidth {
+    /// The same capacity in both directions.
+    pub fn symmetric(max_bandwidth: usize) -> ShardBandwidth {
+        ShardBandwidth {
+            outgoing: max_bandwidth,
+            incoming: max_bandwidth,
+        }
+    }
+}
+
+/// Total size of everything in a chunk, see `SchedulerParams::chunk_size_limit`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkSizeLimit {
+    /// Max total size of the outgoing receipts and transactions in a chunk.
+    pub max_chunk_size: usize,
+    /// Space taken by the transactions in every chunk, the receipts get the rest.
+    pub transactions_size: usize,
+}
+
+impl ChunkSizeLimit {
+    /// How many bytes of receipts (unstoppable receipts included) fit into a chunk.
+    pub fn receipts_space(&self) -> usize {
+        self.max_chunk_size.saturating_sub(self.transactions_size)
+    }
+}
+
+/// How the receivers compute the quotas that they advertise, see `SchedulerParams::receiver_quotas`.
+/// A receiver is willing to take as much as fits into `max_backlog` on top of its current incoming backlog,
+/// split equally between all senders. It doesn't know which senders have something to send.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReceiverQuotas {
+    /// The largest incoming backlog that the receiver is willing to have.
+    pub max_backlog: usize,
+}
+
+impl ReceiverQuotas {
+    /// Quota for every sender, computed by the receiver from the congestion info of its latest chunk.
+    pub fn quotas(
+        &self,
+        congestion_info: &CongestionInfo,
+        senders: &[ShardUId],
+    ) -> BTreeMap<ShardUId, usize> {
+        let total = self
+            .max_backlog
+            .saturating_sub(congestion_info.incoming_backlog_size);
+        let per_sender = total / senders.len().max(1);
+        senders.iter().map(|sender| (*sender, per_sender)).collect()
+    }
+}
+
+/// Which capacity of a shard in maintenance mode is zero.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Maintenance {
+    /// The shard can't receive anything.
+    NoIncoming,
+    /// The shard can't send anything.
+    NoOutgoing,
+    /// The shard can't send or receive anything.
+    NoTraffic,
+}
+
+impl Maintenance {
+    pub fn blocks_incoming(&self) -> bool {
+        matches!(self, Maintenance::NoIncoming | Maintenance::NoTraffic)
+    }
+
+    pub fn blocks_outgoing(&self) -> bool {
+        matches!(self, Maintenance::NoOutgoing | Maintenance::NoTraffic)
+    }
+}
+
+/// Algorithm used to prioritize the bandwidth requests.
+/// The alternatives to `Allowance` aren't meant for the protocol, they're comparison points
+/// that show what the allowance mechanism buys.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SchedulerAlgorithm {
+    /// Links with the most allowance go first. Every link gets the same allowance at every height
+    /// and pays for the granted bandwidth with it, so links that waited long get priority.
+    #[default]
+    Allowance,
+    /// Every link gets a random priority at every height, there's no allowance bookkeeping.
+    RandomPriority,
+    /// Links take turns in a fixed order, the link with the highest priority changes at every height.
+    /// The only state is the height, which the chain has anyway.
+    RoundRobin,
+    /// Deficit round robin, the standard networking answer to sharing a link between queues.
+    /// The links with requests take turns, in every round each of them adds `quantum` to its deficit
+    /// and is granted the requested increases that fit into the deficit. Links keep their deficit
+    /// only as long as they have unfulfilled requests, so the state is kept only for busy links.
+    DeficitRoundRobin { quantum: usize },
+    /// Grants the requested bandwidth which maximizes the total grant at this height, computed as the max flow
+    /// from the senders to the receivers (Dinic's algorithm). Fairness is ignored and a link can get any amount,
+    /// not only one of the requested options, so this is a reference upper bound on the utilization
+    /// that a scheduler can reach with the same requests, not a candidate for the protocol.
+    /// Links which keep losing get starved, and a grant smaller than the next receipt can't be used at all.
+    MaxFlow,
+}
+
+impl Default for SchedulerParams {
+    fn default() -> Self {
+        SchedulerParams::for_config(&SimulationConfig::default())
+    }
+}
+
+impl SchedulerParams {
+    /// Default parameters for a simulation with these limits.
+    pub fn for_config(config: &SimulationConfig) -> Self {
+        SchedulerParams {
+            allowance: AllowancePolicy::for_config(config),
+            max_base_bandwidth: MAX_BASE_BANDWIDTH,
+            unstoppable_reserve: 0,
+            algorithm: SchedulerAlgorithm::default(),
+            maintenance: BTreeMap::new(),
+            global_budget: None,
+            receiver_quotas: None,
+            chunk_size_limit: None,
+            shard_bandwidth: BTreeMap::new(),
+        }
+    }
+
+    /// Max number of bytes that the shard can send at a single height.
+    pub fn max_outgoing_bandwidth(&self, shard_uid: ShardUId, config: &SimulationConfig) -> usize {
+        match self.shard_bandwidth.get(&shard_uid) {
+            Some(shard_bandwidth) => shard_bandwidth.outgoing,
+            None => config.max_outgoing_bandwidth(),
+        }
+    }
+
+    /// Max number of bytes of receipts that the shard can send in a single chunk - the outgoing bandwidth,
+    /// capped by the space for receipts in a chunk when there's a `chunk_size_limit`.
+    pub fn max_sent_per_chunk(&self, shard_uid: ShardUId, config: &SimulationConfig) -> usize {
+        let max_bandwidth = self.max_outgoing_bandwidth(shard_uid, config);
+        match &self.chunk_size_limit {
+            Some(chunk_size_limit) => max_bandwidth.min(chunk_size_limit.receipts_space()),
+            None => max_bandwidth,
+        }
+    }
+
+    /// Max number of bytes that the shard can receive at a single height.
+    pub fn max_incoming_bandwidth(&self, shard_uid: ShardUId, config: &SimulationConfig) -> usize {
+        match self.shard_bandwidth.get(&shard_uid) {
+            Some(shard_bandwidth) => shard_bandwidth.incoming,
+            None => config.max_incoming_bandwidth(),
+        }
+    }
+
+    /// The smallest capacity of a shard in any direction, the base bandwidth has to fit into it on every link.
+    pub fn min_shard_bandwidth(&self, config: &SimulationConfig) -> usize {
+        self.shard_bandwidth
+            .values()
+            .flat_map(|shard_bandwidth| [shard_bandwidth.outgoing, shard_bandwidth.incoming])
+            .fold(
+                config
+                    .max_outgoing_bandwidth()
+                    .min(config.max_incoming_bandwidth()),
+                usize::min,
+            )
+    }
+
+    /// Panics when the parameters don't fit the limits, a receipt of every allowed size must fit
+    /// into the bandwidth of every shard, next to the unstoppable reserve.
+    pub fn validate(&self, config: &SimulationConfig) {
+        let default_bandwidth = [
+            config.max_outgoing_bandwidth(),
+            config.max_incoming_bandwidth(),
+        ]
+        .map(|max_bandwidth| ("the default shard".to_string(), max_bandwidth));
+        let shard_bandwidth =
+            self.shard_bandwidth
+                .iter()
+                .flat_map(|(shard_uid, shard_bandwidth)| {
+                    [shard_bandwidth.outgoing, shard_bandwidth.incoming]
+                        .map(|max_bandwidth| (format!("shard {:?}", shard_uid), max_bandwidth))
+                });
+        for (shard, max_bandwidth) in default_bandwidth.into_iter().chain(shard_bandwidth) {
+            assert!(
+                config.max_receipt_size + self.unstoppable_reserve <= max_bandwidth,
+                "Max bandwidth {} of {} doesn't fit the max receipt size {} and the unstoppable reserve {}",
+                max_bandwidth,
+                shard,
+                config.max_receipt_size,
+                self.unstoppable_reserve
+            );
+            // The scheduler stores bandwidth increases as u32.
+            assert!(
+                max_bandwidth <= u32::MAX as usize,
+                "Max bandwidth {} of {} doesn't fit in u32",
+                max_bandwidth,
+                shard
+            );
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct BandwidthScheduler {
+    params: SchedulerParams,
+    /// Protocol limits, all shards must use the same ones.
+    config: SimulationConfig,
+    /// How much allowance every shard has accumulated. This information is persistend in the shard state on every shard
+    /// and must be kept in sync between all shards.
+    allowances: BTreeMap<ShardLink, usize>,
+    /// How much bandwidth will be granted on every shard.
+    granted_bandwdith: BTreeMap<ShardLink, usize>,
+    /// How much more the shard is able to send before hitting max sending bandwidth.
+    incoming_limits: BTreeMap<ShardUId, usize>,
+    /// How much more the shard is able to receive before hitting max receiving bandwidth.
+    outgoing_limits: BTreeMap<ShardUId, usize>,
+    /// How much more can be granted on the links with a quota advertised by the receiver.
+    quota_limits: BTreeMap<ShardLink, usize>,
+    /// Malformed requests found in the last processed block, see `MalformedRequest`.
+    malformed_requests: Vec<MalformedRequest>,
+    /// Requested bandwidth increases which couldn't be granted in the last run.
+    denials: Vec<GrantDenial>,
+    /// Number of runs so far, rotates the link priorities of `SchedulerAlgorithm::RoundRobin`
+    /// and the first link of `SchedulerAlgorithm::DeficitRoundRobin` and `SchedulerAlgorithm::MaxFlow`.
+    runs: usize,
+    /// Deficits of `SchedulerAlgorithm::DeficitRoundRobin`, only for links with unfulfilled requests.
+    /// Like allowances, they're persisted in the shard state and must be kept in sync between all shards.
+    deficits: BTreeMap<ShardLink, usize>,
+    /// Total grants before they were scaled down to the global budget in the last run,
+    /// `None` when the grants fit into the budget.
+    over_budget: Option<usize>,
+}
+
+/// A bandwidth request which the scheduler can't process as-is.
+/// Chunks are produced by other shards, so the scheduler can't assume that their requests are
+/// well-formed. Malformed requests are handled in a deterministic way, so that all shards
+/// still arrive at the same grants.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MalformedRequest {
+    /// There was already a request for this link in the chunk. Only the first well-formed request is used,
+    /// the later ones are ignored.
+    Duplicate(ShardLink),
+    /// The receiving shard isn't in the shard layout of the block. The request is ignored.
+    UnknownShard(ShardLink),
+    /// No bandwidth option is requested. The request is ignored, the link still gets the base bandwidth.
+    EmptyBitmap(ShardLink),
+}
+
+/// A requested bandwidth increase which couldn't be granted, because the sending or the receiving shard
+/// didn't have enough bandwidth left. The link doesn't get any more increases at this height.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GrantDenial {
+    pub link: ShardLink,
+    pub reason: DenialReason,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DenialReason {
+    /// The sending shard doesn't have enough outgoing bandwidth left.
+    SenderLimit,
+    /// The receiving shard doesn't have enough incoming bandwidth left.
+    ReceiverLimit,
+    /// Neither of the shards has enough bandwidth left.
+    BothLimits,
+    /// The quota advertised by the receiving shard for this sender is used up, see `ReceiverQuotas`.
+    ReceiverQuota,
+}
+
+/// Number of links with their allowance at the cap and at zero.
+/// A link at the cap doesn't get any more allowance, the scheduler forgets how long it has been waiting.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AllowanceSaturation {
+    pub links: usize,
+    pub at_max: usize,
+    pub at_zero: usize,
+}
+
+/// How much of the total allowance is held by the richest links.
+/// A few links holding most of the allowance for a long time means that they keep out-prioritizing the rest.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AllowanceConcentration {
+    /// Sum of allowances of all links.
+    pub total: usize,
+    /// Sum of allowances of the richest 10% of links (at least one link).
+    pub top_decile: usize,
+}
+
+impl AllowanceConcentration {
+    /// Share of the total allowance held by the top 10% of links, `None` when no link has any allowance.
+    pub fn top_decile_share(&self) -> Option<f64> {
+        if self.total == 0 {
+            return None;
+        }
+        Some(self.top_decile as f64 / self.total as f64)
+    }
+}
+
+impl BandwidthScheduler {
+    pub fn new(params: SchedulerParams, config: SimulationConfig) -> BandwidthScheduler {
+        BandwidthScheduler {
+            params,
+            config,
+            allowances: BTreeMap::new(),
+            granted_bandwdith: BTreeMap::new(),
+            incoming_limits: BTreeMap::new(),
+            outgoing_limits: BTreeMap::new(),
+            quota_limits: BTreeMap::new(),
+            malformed_requests: Vec::new(),
+            denials: Vec::new(),
+            runs: 0,
+            deficits: BTreeMap::new(),
+            over_budget: None,
+        }
+    }
+
+    pub fn run(
+        &mut self,
+        input: &(impl ChunkPresence + RequestsSource),
+        rng: &mut DefaultRng,
+    ) -> BTreeMap<ShardLink, usize> {
+        self.runs += 1;
+        let all_shards = input.shards();
+        if all_shards.is_empty() {
+            // No chunks, no bandwidth grants.
+            return BTreeMap::new();
+        }
+
+        // Reset stuff
+        self.granted_bandwdith = BTreeMap::new();
+        self.incoming_limits = BTreeMap::new();
+        self.outgoing_limits = BTreeMap::new();
+        self.quota_limits = input.receiver_quotas();
+        self.malformed_requests = Vec::new();
+        self.denials = Vec::new();
+        self.over_budget = None;
+
+        // New height - grant everyone a fair share of allowance
+        let base_bandwidth = self.get_base_bandwidth(all_shards.len());
+        let allowance_per_height = self
+            .params
+            .allowance
+            .increment(&self.config, all_shards.len());
+        if self.params.algorithm == SchedulerAlgorithm::Allowance {
+            for shard_link in input.all_links() {
+                self.add_allowance(shard_link, allowance_per_height);
+            }
+        }
+
+        // First init the incoming and outgoing limits for every shard.
+        // The unstoppable reserve is taken from both directions.
+        for shard_uid in all_shards {
+            let maintenance = self.params.maintenance.get(shard_uid);
+            let max_outgoing_bandwidth = if maintenance.is_some_and(|m| m.blocks_outgoing()) {
+                0
+            } else {
+                self.params.max_outgoing_bandwidth(*shard_uid, &self.config)
+                    - self.params.unstoppable_reserve
+            };
+            self.outgoing_limits
+                .insert(*shard_uid, max_outgoing_bandwidth);
+
+            // BandwidthScheduler doesn't allow to send anything to shards where the previous chunk is missing
+            let max_incoming_bandwidth = if input.is_chunk_present(*shard_uid)
+                && !maintenance.is_some_and(|m| m.blocks_incoming())
+            {
+                self.params.max_incoming_bandwidth(*shard_uid, &self.config)
+                    - self.params.unstoppable_reserve
+            } else {
+                0
+            };
+            self.incoming_limits
+                .insert(*shard_uid, max_incoming_bandwidth);
+        }
+
+        // Grant the base bandwidth to everyone
+        for shard_link in input.all_links() {
+            // This fails for shards with a zero incoming or outgoing limit, ignore the error.
+            let _ = self.try_grant_additional_bandwidth(shard_link, base_bandwidth);
+        }
+
+        // Convert the badwidth requests to a format used in the algorithm.
+        let mut requests = Vec::new();
+        for (shard_uid, bandwidth_requests) in input.shard_requests() {
+            let mut requested_links = BTreeSet::new();
+            for bandwidth_request in bandwidth_requests {
+                let shard_link = ShardLink {
+                    from: shard_uid,
+                    to: bandwidth_request.to_shard,
+                };
+                if let Some(malformed) =
+                    Self::check_request(input, &requested_links, shard_link, bandwidth_request)
+                {
+                    self.malformed_requests.push(malformed);
+                    continue;
+                }
+                requested_links.insert(shard_link);
+                let internal_request = BandwidthIncreaseRequests::from_bandwidth_request(
+                    shard_link,
+                    bandwidth_request,
+                    base_bandwidth,
+                    &self.config,
+                );
+                requests.push(internal_request);
+            }
+        }
+
+        match self.params.algorithm {
+            SchedulerAlgorithm::DeficitRoundRobin { quantum } => {
+                self.grant_deficit_round_robin(requests, quantum)
+            }
+            SchedulerAlgorithm::MaxFlow => self.grant_max_flow(requests),
+            _ => self.grant_by_priority(requests, all_shards, rng),
+        }
+
+        // Distribute the remaining bandwidth equally between shards.
+        // These grants don't decrease allowance.
+        let remaining_bandwidth_grants = distribute_remaining::distribute_remaining_bandwidth(
+            &self.outgoing_limits,
+            &self.incoming_limits,
+        );
+        for (shard_link, grant) in remaining_bandwidth_grants {
+            // The remaining bandwidth is distributed without looking at the quotas, cut it down to fit.
+            let grant = match self.quota_limits.get(&shard_link) {
+                Some(quota_limit) => grant.min(*quota_limit),
+                None => grant,
+            };
+            self.try_grant_additional_bandwidth(shard_link, grant)
+                .expect("Distributing remaining bandwidth must succeed");
+        }
+
+        if let Some(budget) = self.params.global_budget {
+            self.apply_global_budget(budget);
+        }
+
+        std::mem::take(&mut self.granted_bandwdith)
+    }
+
+    /// Scale all grants down proportionally when their sum exceeds the global budget.
+    /// Rounding down keeps the sum within the budget, every link keeps the same share of it.
+    fn apply_global_budget(&mut self, budget: usize) {
+        let total_granted: usize = self.granted_bandwdith.values().sum();
+        if total_granted <= budget {
+            return;
+        }
+        for grant in self.granted_bandwdith.values_mut() {
+            *grant = (*grant as u128 * budget as u128 / total_granted as u128) as usize;
+        }
+        self.over_budget = Some(total_granted);
+    }
+
+    /// Run the main bandwidth scheduler algorithm.
+    /// Order the bandwidth requests by the link's priority (the allowance with `SchedulerAlgorithm::Allowance`),
+    /// the links with the highest priority are processed first.
+    fn grant_by_priority(
+        &mut self,
+        requests: Vec<BandwidthIncreaseRequests>,
+        all_shards: &[ShardUId],
+        rng: &mut DefaultRng,
+    ) {
+        let mut requests_by_allowance = RequestHeap::new();
+        for request in requests {
+            let priority = self.get_priority(request.shard_link, all_shards, rng);
+            requests_by_allowance.push(priority, request);
+        }
+
+        // Take the group with the most allowance
+        while let Some(mut request_group) = requests_by_allowance.pop_group() {
+            // Shuffle to keep things fair
+            request_group.requests.shuffle(rng);
+
+            // Try to assign next option from the list
+            for mut request in request_group.requests {
+                let Some(bandwidth_increase) = request.bandwidth_increases.pop_front() else {
+                    continue;
+                };
+                match self.try_grant_additional_bandwidth(request.shard_link, bandwidth_increase) {
+                    Ok(()) => {
+                        let new_priority = match self.params.algorithm {
+                            SchedulerAlgorithm::Allowance => {
+                                self.decrease_allowance(request.shard_link, bandwidth_increase);
+                                self.get_allowance(request.shard_link)
+                            }
+                            _ => request_group.priority,
+                        };
+                        requests_by_allowance.push(new_priority, request);
+                    }
+                    Err(NotEnoughBandwidthError { reason }) => self.denials.push(GrantDenial {
+                        link: request.shard_link,
+                        reason,
+                    }),
+                }
+            }
+        }
+    }
+
+    /// Deficit round robin over the links with requests, the first link changes at every height.
+    /// A link is granted its next bandwidth increase once its deficit covers it. Links which got everything
+    /// they requested forget their deficit, links which were denied keep it for the next height.
+    fn grant_deficit_round_robin(
+        &mut self,
+        mut requests: Vec<BandwidthIncreaseRequests>,
+        quantum: usize,
+    ) {
+        assert!(quantum > 0, "Deficit round robin needs a positive quantum!");
+        let requested_links: BTreeSet<ShardLink> =
+            requests.iter().map(|request| request.shard_link).collect();
+        self.deficits
+            .retain(|shard_link, _| requested_links.contains(shard_link));
+
+        requests.sort_by_key(|request| request.shard_link);
+        if !requests.is_empty() {
+            let first = self.runs % requests.len();
+            requests.rotate_left(first);
+        }
+        while !requests.is_empty() {
+            let mut still_active = Vec::new();
+            for mut request in requests {
+                let mut deficit = self.deficits.get(&request.shard_link).copied().unwrap_or(0);
+                deficit += quantum;
+                let mut active = true;
+                while let Some(bandwidth_increase) = request.bandwidth_increases.front() {
+                    if bandwidth_increase > deficit {
+                        break;
+                    }
+                    match self
+                        .try_grant_additional_bandwidth(request.shard_link, bandwidth_increase)
+                    {
+                        Ok(()) => {
+                            deficit -= bandwidth_increase;
+                            request.bandwidth_increases.pop_front();
+                        }
+                        Err(NotEnoughBandwidthError { reason }) => {
+                            self.denials.push(GrantDenial {
+                                link: request.shard_link,
+                                reason,
+                            });
+                            active = false;
+                            break;
+                        }
+                    }
+                }
+                if request.bandwidth_increases.is_empty() {
+                    self.deficits.remove(&request.shard_link);
+                    continue;
+                }
+                self.deficits.insert(request.shard_link, deficit);
+                if active {
+                    still_active.push(request);
+                }
+            }
+            requests = still_active;
+        }
+    }
+
+    /// Grant the max flow through the bipartite graph of senders and receivers. The capacity of a link is
+    /// everything that it requested, the capacities of the shards are their remaining limits.
+    /// Links which didn't get everything are denied because of the limit that the flow used up.
+    /// There are usually many flows with the maximum total, the one that is found depends on the order of the links.
+    /// The first link changes at every height, otherwise the same links would lose at every height.
+    fn grant_max_flow(&mut self, mut requests: Vec<BandwidthIncreaseRequests>) {
+        requests.sort_by_key(|request| request.shard_link);
+        if !requests.is_empty() {
+            let first = self.runs % requests.len();
+            requests.rotate_left(first);
+        }
+
+        let senders: Vec<ShardUId> = self.outgoing_limits.keys().copied().collect();
+        let receivers: Vec<ShardUId> = self.incoming_limits.keys().copied().collect();
+        let source = 0;
+        let sink = 1;
+        let sender_node = |shard: ShardUId| 2 + senders.binary_search(&shard).unwrap();
+        let receiver_node =
+            |shard: ShardUId| 2 + senders.len() + receivers.binary_search(&shard).unwrap();
+
+        let mut network = FlowNetwork::new(2 + senders.len() + receivers.len());
+        for (shard, limit) in &self.outgoing_limits {
+            network.add_edge(source, sender_node(*shard), *limit);
+        }
+        for (shard, limit) in &self.incoming_limits {
+            network.add_edge(receiver_node(*shard), sink, *limit);
+        }
+        // A link can't get more than the quota advertised by its receiver.
+        let link_edges: Vec<(ShardLink, usize, usize)> = requests
+            .iter()
+            .map(|request| {
+                let link = request.shard_link;
+                let requested = request.bandwidth_increases.total();
+                let capacity = match self.quota_limits.get(&link) {
+                    Some(quota_limit) => requested.min(*quota_limit),
+                    None => requested,
+                };
+                let edge =
+                    network.add_edge(sender_node(link.from), receiver_node(link.to), capacity);
+                (link, edge, requested)
+            })
+            .collect();
+        network.max_flow(source, sink);
+
+        let mut unfulfilled = Vec::new();
+        for (link, edge, requested) in link_edges {
+            let flow = network.flow(edge);
+            if flow > 0 {
+                self.try_grant_additional_bandwidth(link, flow)
+                    .expect("Max flow must fit into the limits");
+            }
+            if flow < requested {
+                unfulfilled.push(link);
+            }
+        }
+        for link in unfulfilled {
+            let reason = match (
+                self.outgoing_limits[&link.from] == 0,
+                self.incoming_limits[&link.to] == 0,
+            ) {
+                _ if self.quota_limits.get(&link) == Some(&0) => DenialReason::ReceiverQuota,
+                (true, false) => DenialReason::SenderLimit,
+                (false, true) => DenialReason::ReceiverLimit,
+                // A link with a spare sender, receiver and quota would be a path with spare capacity.
+                _ => DenialReason::BothLimits,
+            };
+            self.denials.push(GrantDenial { link, reason });
+        }
+    }
+
+    /// Malformed requests found in the block processed by the last `run`, in the order in which they
+    /// appeared in the chunks.
+    pub fn malformed_requests(&self) -> &[MalformedRequest] {
+        &self.malformed_requests
+    }
+
+    /// Bandwidth increases denied in the last `run`, in the order in which they were processed.
+    pub fn denials(&self) -> &[GrantDenial] {
+        &self.denials
+    }
+
+    /// Total grants before they were scaled down to the global budget in the last `run`,
+    /// `None` when they fit into the budget (or there's no budget).
+    pub fn over_budget(&self) -> Option<usize> {
+        self.over_budget
+    }
+
+    /// How many times `run` was called.
+    pub fn runs(&self) -> usize {
+        self.runs
+    }
+
+    /// Check whether a request can be processed. `requested_links` are the links for which the
+    /// chunk already had a valid request.
+    fn check_request(
+        input: &impl ChunkPresence,
+        requested_links: &BTreeSet<ShardLink>,
+        shard_link: ShardLink,
+        bandwidth_request: &BandwidthRequest,
+    ) -> Option<MalformedRequest> {
+        if !input.contains_shard(shard_link.to) {
+            return Some(MalformedRequest::UnknownShard(shard_link));
+        }
+        if requested_links.contains(&shard_link) {
+            return Some(MalformedRequest::Duplicate(shard_link));
+        }
+        if bandwidth_request.grant_options_bitmap.is_all_false() {
+            return Some(MalformedRequest::EmptyBitmap(shard_link));
+        }
+        None
+    }
+
+    pub fn params(&self) -> &SchedulerParams {
+        &self.params
+    }
+
+    pub fn config(&self) -> &SimulationConfig {
+        &self.config
+    }
+
+    /// Change the parameters between heights. All shards must change them at the same height.
+    pub fn set_params(&mut self, params: SchedulerParams) {
+        self.params = params;
+    }
+
+    /// Calculate the base bandwidth that is granted on all links.
+    /// It's the same on all links, so it has to fit into the bandwidth of the slowest shard,
+    /// without the unstoppable reserve.
+    pub fn get_base_bandwidth(&self, num_shards: usize) -> usize {
+        let mut base_bandwidth = (self.params.min_shard_bandwidth(&self.config)
+            - self.config.max_receipt_size
+            - self.params.unstoppable_reserve)
+            / num_shards;
+        if base_bandwidth > self.params.max_base_bandwidth {
+            base_bandwidth = self.params.max_base_bandwidth;
+        }
+        base_bandwidth
+    }
+
+    fn try_grant_additional_bandwidth(
+        &mut self,
+        shard_link: ShardLink,
+        bandwidth_increase: usize,
+    ) -> Result<(), NotEnoughBandwidthError> {
+        if self
+            .quota_limits
+            .get(&shard_link)
+            .is_some_and(|quota_limit| bandwidth_increase > *quota_limit)
+        {
+            return Err(NotEnoughBandwidthError {
+                reason: DenialReason::ReceiverQuota,
+            });
+        }
+        let outgoing_limit = self.outgoing_limits.entry(shard_link.from).or_insert(0);
+        let incoming_limit = self.incoming_limits.entry(shard_link.to).or_insert(0);
+
+        let reason = match (
+            bandwidth_increase > *outgoing_limit,
+            bandwidth_increase > *incoming_limit,
+        ) {
+            (false, false) => None,
+            (true, false) => Some(DenialReason::SenderLimit),
+            (false, true) => Some(DenialReason::ReceiverLimit),
+            (true, true) => Some(DenialReason::BothLimits),
+        };
+        if let Some(reason) = reason {
+            return Err(NotEnoughBandwidthError { reason });
+        }
+
+        *self.granted_bandwdith.entry(shard_link).or_insert(0) += bandwidth_increase;
+        *outgoing_limit -= bandwidth_increase;
+        *incoming_limit -= bandwidth_increase;
+        if let Some(quota_limit) = self.quota_limits.get_mut(&shard_link) {
+            *quota_limit -= bandwidth_increase;
+        }
+
+        Ok(())
+    }
+
+    /// Priority of the link's requests at this height, requests with a higher priority are processed first.
+    fn get_priority(
+        &mut self,
+        shard_link: ShardLink,
+        all_shards: &[ShardUId],
+        rng: &mut DefaultRng,
+    ) -> usize {
+        match self.params.algorithm {
+            SchedulerAlgorithm::Allowance => self.get_allowance(shard_link),
+            SchedulerAlgorithm::RandomPriority => rng.gen_range(0..usize::MAX),
+            SchedulerAlgorithm::RoundRobin => {
+                // Position of the link in `all_links()`, the link at position `runs % num_links`
+                // gets the highest priority, the one before it the lowest.
+                let shard_index = |shard| all_shards.binary_search(&shard).unwrap();
+                let num_links = all_shards.len() * all_shards.len();
+                let link_index =
+                    shard_index(shard_link.from) * all_shards.len() + shard_index(shard_link.to);
+                let turn = (link_index + num_links - self.runs % num_links) % num_links;
+                num_links - turn
+            }
+            SchedulerAlgorithm::DeficitRoundRobin { .. } | SchedulerAlgorithm::MaxFlow => {
+                unreachable!("{:?} doesn't prioritize the links", self.params.algorithm)
+            }
+        }
+    }
+
+    fn get_allowance(&mut self, shard_link: ShardLink) -> usize {
+        self.allowances
+            .get(&shard_link)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Allowance of every link, part of the state that must be kept in sync between all shards.
+    pub fn allowances(&self) -> &BTreeMap<ShardLink, usize> {
+        &self.allowances
+    }
+
+    pub fn deficits(&self) -> &BTreeMap<ShardLink, usize> {
+        &self.deficits
+    }
+
+    /// Number of per-link values which are kept between heights. In the protocol they'd be stored
+    /// in the state of every shard.
+    pub fn persistent_state_size(&self) -> usize {
+        self.allowances.len() + self.deficits.len()
+    }
+
+    /// Count the links with allowance at the max allowance and at zero.
+    pub fn allowance_saturation(&self) -> AllowanceSaturation {
+        let mut saturation = AllowanceSaturation {
+            links: self.allowances.len(),
+            ..AllowanceSaturation::default()
+        };
+        for allowance in self.allowances.values() {
+            if *allowance >= self.params.allowance.max_allowance {
+                saturation.at_max += 1;
+            }
+            if *allowance == 0 {
+                saturation.at_zero += 1;
+            }
+        }
+        saturation
+    }
+
+    /// Sum the allowances of all links and of the richest 10% of them.
+    pub fn allowance_concentration(&self) -> AllowanceConcentration {
+        let mut allowances: Vec<usize> = self.allowances.values().copied().collect();
+        allowances.sort_unstable_by(|a, b| b.cmp(a));
+        let top_links = allowances.len().div_ceil(10).max(1);
+        AllowanceConcentration {
+            total: allowances.iter().sum(),
+            top_decile: allowances.iter().take(top_links).sum(),
+        }
+    }
+
+    pub fn set_allowance(&mut self, shard_link: ShardLink, amount: usize) {
+        self.allowances.insert(shard_link, amount);
+    }
+
+    fn add_allowance(&mut self, shard_link: ShardLink, amount: usize) {
+        let mut cur_allowance = self.get_allowance(shard_link);
+        cur_allowance += amount;
+        if cur_allowance > self.params.allowance.max_allowance {
+            cur_allowance = self.params.allowance.max_allowance;
+        }
+
+        self.set_allowance(shard_link, cur_allowance);
+    }
+
+    fn decrease_allowance(&mut self, shard_link: ShardLink, amount: usize) {
+        let cur_allowance = self.get_allowance(shard_link);
+        let new_allowance = cur_allowance.saturating_sub(amount);
+        self.set_allowance(shard_link, new_allowance);
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct NotEnoughBandwidthError {
+    reason: DenialReason,
+}
+
+// Group of bandwidth requests with the same priority
+struct RequestGroup {
+    priority: usize,
+    requests: Vec<BandwidthIncreaseRequests>,
+}
+
+/// Max-heap of bandwidth requests ordered by the priority of their links (the allowance with
+/// `SchedulerAlgorithm::Allowance`).
+/// Requests with the same priority are popped together as one group, in the order in which they were pushed.
+struct RequestHeap {
+    heap: BinaryHeap<RequestHeapEntry>,
+    /// Sequence number of the next pushed request, used to keep the insertion order among equal priorities.
+    next_sequence_num: usize,
+}
+
+struct RequestHeapEntry {
+    priority: usize,
+    sequence_num: usize,
+    request: BandwidthIncreaseRequests,
+}
+
+impl RequestHeap {
+    fn new() -> RequestHeap {
+        RequestHeap {
+            heap: BinaryHeap::new(),
+            next_sequence_num: 0,
+        }
+    }
+
+    fn push(&mut self, priority: usize, request: BandwidthIncreaseRequests) {
+        self.heap.push(RequestHeapEntry {
+            priority,
+            sequence_num: self.next_sequence_num,
+            request,
+        });
+        self.next_sequence_num += 1;
+    }
+
+    /// Pop all requests with the highest priority.
+    fn pop_group(&mut self) -> Option<RequestGroup> {
+        let first = self.heap.pop()?;
+        let mut requests = vec![first.request];
+        while self
+            .heap
+            .peek()
+            .is_some_and(|entry| entry.priority == first.priority)
+        {
+            requests.push(self.heap.pop().unwrap().request);
+        }
+        Some(RequestGroup {
+            priority: first.priority,
+            requests,
+        })
+    }
+}
+
+impl Ord for RequestHeapEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Highest priority first, then the lowest sequence number
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.sequence_num.cmp(&self.sequence_num))
+    }
+}
+
+impl PartialOrd for RequestHeapEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for RequestHeapEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for RequestHeapEntry {}
+
+/// A BandwidthRequest translated to a format where each "option" is an increase over the previous option instead of an absolute granted value.
+#[derive(Debug)]
+struct BandwidthIncreaseRequests {
+    /// The shard link on which the bandwdith is requested.
+    shard_link: ShardLink,
+    /// Each of the entries in the queue describes how much additional bandwidth should be granted.
+    bandwidth_increases: BandwidthIncreases,
+}
+
+impl BandwidthIncreaseRequests {
+    fn from_bandwidth_request(
+        shard_link: ShardLink,
+        bandwidth_request: &BandwidthRequest,
+        base_bandwidth: usize,
+        config: &SimulationConfig,
+    ) -> BandwidthIncreaseRequests {
+        assert_eq!(shard_link.to, bandwidth_request.to_shard);
+        let mut bandwidth_increases = BandwidthIncreases::new();
+        let mut last_option = base_bandwidth;
+        // Get the absolute values of requested bandwidth from bandwidth request.
+        let grant_options = BandwidthRequestOptions::from_bitmap(
+            &bandwidth_request.grant_options_bitmap,
+            base_bandwidth,
+            config,
+        );
+        for bandwidth_option in grant_options.0 {
+            assert!(bandwidth_option > last_option);
+            bandwidth_increases.push_back(bandwidth_option - last_option);
+            last_option = bandwidth_option;
+        }
+
+        BandwidthIncreaseRequests {
+            shard_link,
+            bandwidth_increases,
+        }
+    }
+}
+
+/// Queue of bandwidth increases stored inline, without any heap allocations.
+/// A request can't have more options than there are values in the bitmap, so a fixed capacity is enough.
+/// Increases are never larger than the max shard bandwidth (see `SimulationConfig::validate`), so they're stored as u32 to keep the
+/// requests small - they're moved around a lot in the heap.
+#[derive(Debug)]
+struct BandwidthIncreases {
+    values: [u32; BANDWIDTH_REQUEST_VALUES_NUM],
+    start: u8,
+    end: u8,
+}
+
+impl BandwidthIncreases {
+    fn new() -> BandwidthIncreases {
+        BandwidthIncreases {
+            values: [0; BANDWIDTH_REQUEST_VALUES_NUM],
+            start: 0,
+            end: 0,
+        }
+    }
+
+    fn push_back(&mut self, value: usize) {
+        assert!(
+            usize::from(self.end) < BANDWIDTH_REQUEST_VALUES_NUM,
+            "More than {} bandwidth increases",
+            BANDWIDTH_REQUEST_VALUES_NUM
+        );
+        self.values[usize::from(self.end)] =
+            u32::try_from(value).expect("Bandwidth increase doesn't fit in u32");
+        self.end += 1;
+    }
+
+    fn pop_front(&mut self) -> Option<usize> {
+        if self.start == self.end {
+            return None;
+        }
+        let value = self.values[usize::from(self.start)];
+        self.start += 1;
+        Some(value as usize)
+    }
+
+    fn front(&self) -> Option<usize> {
+        if self.start == self.end {
+            return None;
+        }
+        Some(self.values[usize::from(self.start)] as usize)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Sum of the remaining increases.
+    fn total(&self) -> usize {
+        self.values[usize::from(self.start)..usize::from(self.end)]
+            .iter()
+            .map(|value| *value as usize)
+            .sum()
+    }
+}
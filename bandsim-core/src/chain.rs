@@ -0,0 +1,201 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use crate::bandwidth_request::BandwidthRequest;
+use crate::shard_layout::ShardLayout;
+
+/// Maximum number of bytes that a shard can send or receive at a single height
+pub const MAX_SHARD_BANDWIDTH: usize = 4_500_000;
+
+/// Minimum size of a single receipt
+pub const MIN_RECEIPT_SIZE: usize = 1_000;
+
+/// Maximum size of a single receipt
+pub const MAX_RECEIPT_SIZE: usize = 4_000_000;
+
+/// Protocol limits used by a simulation. The defaults are the limits of the real protocol
+/// (`MAX_SHARD_BANDWIDTH`, `MIN_RECEIPT_SIZE`, `MAX_RECEIPT_SIZE`), other values allow to study
+/// how the scheduler behaves when the limits change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SimulationConfig {
+    /// Maximum number of bytes that a shard can send at a single height,
+    /// and receive unless `max_shard_incoming_bandwidth` is set
+    pub max_shard_bandwidth: usize,
+    /// Maximum number of bytes that a shard can receive at a single height, for asymmetric network links
+    /// where it's different from what the shard can send. `None` means that it's `max_shard_bandwidth`.
+    pub max_shard_incoming_bandwidth: Option<usize>,
+    /// Minimum size of a single receipt
+    pub min_receipt_size: usize,
+    /// Maximum size of a single receipt
+    pub max_receipt_size: usize,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig {
+            max_shard_bandwidth: MAX_SHARD_BANDWIDTH,
+            max_shard_incoming_bandwidth: None,
+            min_receipt_size: MIN_RECEIPT_SIZE,
+            max_receipt_size: MAX_RECEIPT_SIZE,
+        }
+    }
+}
+
+impl SimulationConfig {
+    /// Maximum number of bytes that a shard can send at a single height.
+    pub fn max_outgoing_bandwidth(&self) -> usize {
+        self.max_shard_bandwidth
+    }
+
+    /// Maximum number of bytes that a shard can receive at a single height.
+    pub fn max_incoming_bandwidth(&self) -> usize {
+        self.max_shard_incoming_bandwidth
+            .unwrap_or(self.max_shard_bandwidth)
+    }
+
+    /// Panics when the limits don't fit together, a receipt of every allowed size must fit in one chunk.
+    pub fn validate(&self) {
+        assert!(
+            0 < self.min_receipt_size && self.min_receipt_size <= self.max_receipt_size,
+            "Invalid receipt size range {}..={}",
+            self.min_receipt_size,
+            self.max_receipt_size
+        );
+        assert!(
+            self.max_receipt_size <= self.max_shard_bandwidth,
+            "Max receipt size {} is larger than the max shard bandwidth {}",
+            self.max_receipt_size,
+            self.max_shard_bandwidth
+        );
+        // The scheduler stores bandwidth increases as u32.
+        assert!(
+            self.max_shard_bandwidth <= u32::MAX as usize,
+            "Max shard bandwidth {} doesn't fit in u32",
+            self.max_shard_bandwidth
+        );
+        if let Some(max_incoming) = self.max_shard_incoming_bandwidth {
+            assert!(
+                self.max_receipt_size <= max_incoming && max_incoming <= u32::MAX as usize,
+                "Max shard incoming bandwidth {} doesn't fit the max receipt size {} or u32",
+                max_incoming,
+                self.max_receipt_size
+            );
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ShardUId {
+    pub version: u32,
+    pub shard_id: u32,
+}
+
+impl ShardUId {
+    pub fn new(id: usize) -> ShardUId {
+        ShardUId {
+            version: 0,
+            shard_id: id as u32,
+        }
+    }
+}
+impl Debug for ShardUId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.version != 0 {
+            panic!("You actually used the version field!");
+        }
+        write!(f, "shard_{}", self.shard_id)
+    }
+}
+
+/// A link between two shards.
+/// Receipts are sent `from` some shard `to` some shard over some ShardLink.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ShardLink {
+    pub from: ShardUId,
+    pub to: ShardUId,
+}
+
+impl ShardLink {
+    pub fn is(&self, a: usize, b: usize) -> bool {
+        self.from == ShardUId::new(a) && self.to == ShardUId::new(b)
+    }
+}
+
+impl Debug for ShardLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{:?} -> {:?}]", self.from, self.to)
+    }
+}
+
+#[derive(Clone)]
+pub struct Chunk {
+    pub prev_incoming_receipts_size: usize,
+    pub prev_outgoing_receipts_size: BTreeMap<ShardUId, usize>,
+    /// Unstoppable receipts sent to other shards, not included in `prev_outgoing_receipts_size`.
+    pub prev_unstoppable_receipts_size: BTreeMap<ShardUId, usize>,
+    pub bandwidth_requests: Vec<BandwidthRequest>,
+    pub congestion_info: CongestionInfo,
+    /// Quota advertised by this shard for every sender, see `ReceiverQuotas`.
+    /// Empty unless the receivers advertise quotas.
+    pub receiver_quotas: BTreeMap<ShardUId, usize>,
+}
+
+/// Information about congestion on the shard, computed when applying the chunk.
+/// In the real protocol it's included in the chunk header, so other shards can see it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CongestionInfo {
+    /// Total size of incoming receipts which were received, but not processed yet.
+    pub incoming_backlog_size: usize,
+    /// For how many heights the oldest unprocessed incoming receipt has been waiting.
+    pub processing_delay: usize,
+}
+
+impl CongestionInfo {
+    /// Size of the serialized congestion info - two u64 values.
+    pub const SERIALIZED_SIZE: usize = 2 * std::mem::size_of::<u64>();
+}
+
+/// Size of a serialized map from shard id (u16) to a size (u64), with a u32 length in front.
+pub fn serialized_size_map_size(map: &BTreeMap<ShardUId, usize>) -> usize {
+    std::mem::size_of::<u32>()
+        + map.len() * (std::mem::size_of::<u16>() + std::mem::size_of::<u64>())
+}
+
+#[derive(Clone)]
+pub struct Block {
+    pub height: usize,
+    /// Shards which exist at this height. There's an entry in `chunks` for every one of them.
+    pub shard_layout: Arc<ShardLayout>,
+    pub chunks: BTreeMap<ShardUId, Option<Chunk>>,
+}
+
+/// Label attached to a receipt by its sender, e.g. a workload name or a priority class.
+/// Stats are reported separately for every tag.
+pub type ReceiptTag = Arc<str>;
+
+/// Priority class of a receipt. Outgoing queues keep a separate sub-queue for every class,
+/// the drain policy decides in which order they're sent.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ReceiptPriority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+impl ReceiptPriority {
+    /// All priorities, from the highest to the lowest.
+    pub const ALL: [ReceiptPriority; 3] = [
+        ReceiptPriority::High,
+        ReceiptPriority::Normal,
+        ReceiptPriority::Low,
+    ];
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Receipt {
+    pub size: usize,
+    pub tag: Option<ReceiptTag>,
+    pub priority: ReceiptPriority,
+}
@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::chain::{ShardLink, ShardUId};
+use crate::simulation::SimulationRun;
+
+/// Grant matrices of every non-missing height in a window, rendered as a sequence of images.
+/// Watching the frames shows transient events (e.g. catching up after an outage) which are
+/// averaged out in the aggregated stats.
+/// The frames can be turned into an animation with e.g. `ffmpeg -i grants_%06d.png grants.gif`.
+pub struct GrantFrames {
+    pub shards: Vec<ShardUId>,
+    pub frames: Vec<GrantFrame>,
+    /// Grant which is rendered as white, the max shard bandwidth of the simulation.
+    pub max_grant: usize,
+}
+
+pub struct GrantFrame {
+    pub height: usize,
+    pub grants: BTreeMap<ShardLink, usize>,
+}
+
+impl GrantFrames {
+    /// Frames of the non-missing heights in the window.
+    /// Requires a simulation which records grant history.
+    pub fn new(simulation_run: &SimulationRun, heights: Range<usize>) -> GrantFrames {
+        let simulation = &simulation_run.simulation;
+        assert!(
+            simulation.record_grant_history,
+            "Grant history wasn't recorded!"
+        );
+        let frames = simulation
+            .metrics
+            .iter()
+            .filter(|metrics| heights.contains(&metrics.height))
+            .map(|metrics| GrantFrame {
+                height: metrics.height,
+                grants: metrics.grants.clone(),
+            })
+            .collect();
+        GrantFrames {
+            shards: simulation.shards.keys().copied().collect(),
+            frames,
+            max_grant: simulation.config.max_shard_bandwidth,
+        }
+    }
+
+    /// Grayscale image of the grant matrix, one `cell_size` x `cell_size` square per link.
+    /// Rows are the sending shards, columns the receiving ones. Black is no grant,
+    /// white is a grant of `max_grant`.
+    /// Returns the width and height of the image and its pixels, row by row.
+    pub fn render(&self, frame: &GrantFrame, cell_size: usize) -> (usize, usize, Vec<u8>) {
+        let side = self.shards.len() * cell_size;
+        let mut pixels = vec![0; side * side];
+        for (row, from) in self.shards.iter().enumerate() {
+            for (col, to) in self.shards.iter().enumerate() {
+                let shard_link = ShardLink {
+                    from: *from,
+                    to: *to,
+                };
+                let grant = frame.grants.get(&shard_link).copied().unwrap_or(0);
+                let shade = (grant.min(self.max_grant) * 255 / self.max_grant) as u8;
+                for y in row * cell_size..(row + 1) * cell_size {
+                    pixels[y * side + col * cell_size..y * side + (col + 1) * cell_size]
+                        .fill(shade);
+                }
+            }
+        }
+        (side, side, pixels)
+    }
+
+    /// Write every frame to `dir` as `grants_<height>.png`, returns the paths in the order of heights.
+    pub fn write_png_frames(&self, dir: &Path, cell_size: usize) -> std::io::Result<Vec<PathBuf>> {
+        std::fs::create_dir_all(dir)?;
+        let mut paths = Vec::new();
+        for frame in &self.frames {
+            let (width, height, pixels) = self.render(frame, cell_size);
+            let path = dir.join(format!("grants_{:06}.png", frame.height));
+            std::fs::write(&path, encode_grayscale_png(width, height, &pixels))?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
+
+/// Minimal PNG encoder for 8-bit grayscale images. The image data isn't compressed (deflate with
+/// stored blocks), the grant matrices are small and this avoids depending on an image library.
+pub fn encode_grayscale_png(width: usize, height: usize, pixels: &[u8]) -> Vec<u8> {
+    assert_eq!(pixels.len(), width * height, "Wrong number of pixels!");
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // Bit depth 8, grayscale, deflate, no filtering, no interlacing
+    header.extend_from_slice(&[8, 0, 0, 0, 0]);
+
+    // Every row starts with the filter type, 0 means no filter.
+    let mut raw = Vec::with_capacity((width + 1) * height);
+    for row in pixels.chunks(width.max(1)).take(height) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    // zlib stream: header, deflate stored blocks of at most 65535 bytes, adler32 checksum.
+    let mut zlib = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = raw.chunks(u16::MAX as usize).collect();
+    for (i, block) in blocks.iter().enumerate() {
+        let is_last = i + 1 == blocks.len();
+        zlib.push(is_last as u8);
+        zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    if blocks.is_empty() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut png = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+    write_png_chunk(&mut png, b"IHDR", &header);
+    write_png_chunk(&mut png, b"IDAT", &zlib);
+    write_png_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_png_chunk(png: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let crc_start = png.len();
+    png.extend_from_slice(chunk_type);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[crc_start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffffffff_u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb88320 & mask);
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for byte in bytes {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[test]
+fn png_checksums() {
+    // Known values: CRC of the IEND chunk type and adler32 of "Wikipedia"
+    assert_eq!(crc32(b"IEND"), 0xae426082);
+    assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+
+    let png = encode_grayscale_png(2, 3, &[0, 255, 10, 20, 30, 40]);
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    assert_eq!(&png[12..16], b"IHDR");
+    assert_eq!(&png[16..20], &2_u32.to_be_bytes());
+    assert_eq!(&png[20..24], &3_u32.to_be_bytes());
+    assert_eq!(&png[png.len() - 8..], b"IEND\xae\x42\x60\x82");
+}
+
+#[test]
+fn grant_frames_of_busy_link() {
+    use crate::simulation::builder::SimulationBuilder;
+    use crate::simulation::receipt_sender::{FullSpeedReceiptSender, TypicalReceiptGenerator};
+
+    let simulation_run = SimulationBuilder::new(3)
+        .receipt_sender(0, 1, FullSpeedReceiptSender(TypicalReceiptGenerator::new()))
+        .record_grant_history()
+        .build()
+        .run_for(50);
+    let frames = GrantFrames::new(&simulation_run, 40..45);
+    assert_eq!(
+        frames.frames.iter().map(|f| f.height).collect::<Vec<_>>(),
+        vec![40, 41, 42, 43, 44]
+    );
+
+    // The busy link is granted most of the bandwidth, it's the brightest cell.
+    let (width, height, pixels) = frames.render(&frames.frames[0], 4);
+    assert_eq!((width, height), (12, 12));
+    let cell = |row: usize, col: usize| pixels[row * 4 * width + col * 4];
+    assert!(cell(0, 1) > 200);
+    assert!(cell(0, 1) > cell(1, 0));
+
+    let dir = std::env::temp_dir().join("bandsim_grant_frames");
+    let paths = frames.write_png_frames(&dir, 4).unwrap();
+    assert_eq!(paths.len(), 5);
+    assert!(paths[0].ends_with("grants_000040.png"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,46 @@
+// I don't like the .flatten() function, it's unintuitive
+#![allow(clippy::manual_flatten)]
+
+//! Simulator of the bandwidth scheduler, which decides how much every shard can send to every other shard.
+//! Contains the scheduler, the model of the chain and the simulation engine. Simulations are usually
+//! created with `SimulationBuilder` and run with `Simulation::run_for`, the stats and assertions
+//! on the finished runs are in the `bandsim-harness` crate.
+//!
+//! ```
+//! use bandsim_core::{
+//!     FullSpeedReceiptSender, ShardLink, ShardUId, SimulationBuilder, TypicalReceiptGenerator,
+//! };
+//!
+//! let simulation_run = SimulationBuilder::new(2)
+//!     .receipt_sender(0, 1, FullSpeedReceiptSender(TypicalReceiptGenerator::new()))
+//!     .build()
+//!     .run_for(50);
+//! let link = ShardLink {
+//!     from: ShardUId::new(0),
+//!     to: ShardUId::new(1),
+//! };
+//! assert!(simulation_run.simulation.total_granted[&link] > 0);
+//! ```
+
+pub mod bandwidth_request;
+pub mod bandwidth_scheduler;
+pub mod chain;
+pub mod grant_frames;
+pub mod optimal_throughput;
+pub mod rng;
+pub mod shard_layout;
+pub mod simulation;
+pub mod validation;
+
+pub use bandwidth_scheduler::{BandwidthScheduler, SchedulerAlgorithm, SchedulerParams};
+pub use chain::{
+    Block, Chunk, CongestionInfo, Receipt, ReceiptPriority, ReceiptTag, ShardLink, ShardUId,
+    SimulationConfig, MAX_RECEIPT_SIZE, MAX_SHARD_BANDWIDTH, MIN_RECEIPT_SIZE,
+};
+pub use simulation::builder::SimulationBuilder;
+pub use simulation::receipt_sender::{
+    FullSpeedReceiptSender, NoReceiptSender, OneSizeReceiptGenerator, RandomSizeReceiptGenerator,
+    ReceiptGenerator, ReceiptSender, TypicalReceiptGenerator,
+};
+pub use simulation::{Simulation, SimulationRun};
+pub use validation::ValidationLevel;
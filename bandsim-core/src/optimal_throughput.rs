@@ -0,0 +1,110 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::chain::{ShardLink, ShardUId};
+
+/// Calculate the maximum number of bytes that could be sent at a single height.
+/// `demand` describes how many bytes are waiting to be sent on every link, the limits describe
+/// how much every shard can send and receive at this height.
+/// Receipts are treated as if they could be split into arbitrarily small pieces, so the result is
+/// an upper bound on what any scheduler could achieve.
+pub fn optimal_throughput(
+    demand: &BTreeMap<ShardLink, usize>,
+    outgoing_limits: &BTreeMap<ShardUId, usize>,
+    incoming_limits: &BTreeMap<ShardUId, usize>,
+) -> usize {
+    // Flow network: source -> sender shards -> receiver shards -> sink
+    let senders: Vec<ShardUId> = outgoing_limits.keys().copied().collect();
+    let receivers: Vec<ShardUId> = incoming_limits.keys().copied().collect();
+    let source = 0;
+    let sink = 1;
+    let sender_node = |shard: ShardUId| 2 + senders.binary_search(&shard).unwrap();
+    let receiver_node =
+        |shard: ShardUId| 2 + senders.len() + receivers.binary_search(&shard).unwrap();
+
+    let num_nodes = 2 + senders.len() + receivers.len();
+    let mut capacities = vec![vec![0; num_nodes]; num_nodes];
+    for (shard, limit) in outgoing_limits {
+        capacities[source][sender_node(*shard)] = *limit;
+    }
+    for (shard, limit) in incoming_limits {
+        capacities[receiver_node(*shard)][sink] = *limit;
+    }
+    for (link, bytes) in demand {
+        if !outgoing_limits.contains_key(&link.from) || !incoming_limits.contains_key(&link.to) {
+            continue;
+        }
+        capacities[sender_node(link.from)][receiver_node(link.to)] += *bytes;
+    }
+
+    max_flow(capacities, source, sink)
+}
+
+/// Edmonds-Karp max flow on a graph described by a capacity matrix.
+fn max_flow(mut capacities: Vec<Vec<usize>>, source: usize, sink: usize) -> usize {
+    let num_nodes = capacities.len();
+    let mut total_flow = 0;
+    loop {
+        // Find the shortest path with some spare capacity
+        let mut parent: Vec<Option<usize>> = vec![None; num_nodes];
+        let mut queue = VecDeque::from([source]);
+        parent[source] = Some(source);
+        while let Some(node) = queue.pop_front() {
+            for next in 0..num_nodes {
+                if parent[next].is_none() && capacities[node][next] > 0 {
+                    parent[next] = Some(node);
+                    queue.push_back(next);
+                }
+            }
+        }
+        if parent[sink].is_none() {
+            return total_flow;
+        }
+
+        let mut path_flow = usize::MAX;
+        let mut node = sink;
+        while node != source {
+            let prev = parent[node].unwrap();
+            path_flow = std::cmp::min(path_flow, capacities[prev][node]);
+            node = prev;
+        }
+
+        let mut node = sink;
+        while node != source {
+            let prev = parent[node].unwrap();
+            capacities[prev][node] -= path_flow;
+            capacities[node][prev] += path_flow;
+            node = prev;
+        }
+        total_flow += path_flow;
+    }
+}
+
+#[test]
+fn test_optimal_throughput() {
+    let shards: BTreeMap<ShardUId, usize> =
+        [(ShardUId::new(0), 100), (ShardUId::new(1), 100)].into();
+    let link = |from, to| ShardLink {
+        from: ShardUId::new(from),
+        to: ShardUId::new(to),
+    };
+
+    // Nothing to send
+    assert_eq!(optimal_throughput(&BTreeMap::new(), &shards, &shards), 0);
+
+    // A single link is limited by the demand
+    let demand = [(link(0, 1), 30)].into();
+    assert_eq!(optimal_throughput(&demand, &shards, &shards), 30);
+
+    // Two senders to one receiver are limited by the incoming limit
+    let demand = [(link(0, 1), 80), (link(1, 1), 80)].into();
+    assert_eq!(optimal_throughput(&demand, &shards, &shards), 100);
+
+    // The flow has to be rerouted to reach the optimum
+    let demand = [(link(0, 0), 100), (link(0, 1), 100), (link(1, 0), 100)].into();
+    assert_eq!(optimal_throughput(&demand, &shards, &shards), 200);
+
+    // Receiver that can't receive anything
+    let no_incoming_on_1 = [(ShardUId::new(0), 100), (ShardUId::new(1), 0)].into();
+    let demand = [(link(0, 1), 100), (link(1, 1), 100), (link(1, 0), 20)].into();
+    assert_eq!(optimal_throughput(&demand, &shards, &no_incoming_on_1), 20);
+}
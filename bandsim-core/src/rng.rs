@@ -0,0 +1,369 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use rand::{RngCore, SeedableRng};
+
+use crate::chain::{Block, ShardLink, ShardUId};
+
+/// Random number generator used in the simulation.
+/// Behaves like `StdRng`, but consumers can get their own independent streams of random numbers (see `set_stream`),
+/// and all draws can be recorded, split by the consumer that made them, and replayed in a later run.
+#[derive(Clone)]
+pub struct DefaultRng {
+    inner: rand::rngs::StdRng,
+    /// Seed used to create this rng, streams are derived from it.
+    seed: u64,
+    /// Independent stream of the current consumer, see `set_stream`.
+    /// When there's no stream, the consumer draws from `inner`.
+    stream: Option<rand::rngs::StdRng>,
+    consumer: RngConsumer,
+    mode: RngMode,
+}
+
+/// Part of the simulation which draws random numbers.
+/// Each consumer gets its own recorded stream, so a replay still works when the order in which
+/// the consumers draw numbers changes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RngConsumer {
+    Unspecified,
+    MissingBlock,
+    MissingChunk(ShardUId),
+    ReceiptSender(ShardLink),
+    UnstoppableSender(ShardLink),
+    RequestFault(ShardUId),
+}
+
+#[derive(Clone)]
+enum RngMode {
+    Live,
+    Record(RngRecording),
+    Replay {
+        recording: RngRecording,
+        positions: BTreeMap<String, usize>,
+    },
+}
+
+/// Random bytes drawn by every consumer during a simulation run.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RngRecording {
+    streams: BTreeMap<String, Vec<u8>>,
+}
+
+impl DefaultRng {
+    /// Set the consumer which will make the next draws.
+    pub fn set_consumer(&mut self, consumer: RngConsumer) {
+        self.consumer = consumer;
+        self.stream = None;
+    }
+
+    /// Set the consumer which will make the next draws, and give it its own stream of random numbers,
+    /// derived from the seed, the consumer and the height. Draws made by other consumers don't affect
+    /// this stream, so e.g. adding a receipt sender doesn't change the receipts generated by the other senders.
+    pub fn set_stream(&mut self, consumer: RngConsumer, height: usize) {
+        self.set_stream_with_seed(consumer, height, self.seed);
+    }
+
+    /// Same as `set_stream`, but the stream is derived from the given seed instead of the seed of this rng.
+    /// Allows to keep the randomness of one consumer fixed while the main seed changes.
+    pub fn set_stream_with_seed(&mut self, consumer: RngConsumer, height: usize, seed: u64) {
+        self.consumer = consumer;
+        let stream_seed = [consumer.stream_id(), height as u64]
+            .into_iter()
+            .fold(splitmix64(seed), |acc, value| splitmix64(acc ^ value));
+        self.stream = Some(rng_from_seed(stream_seed).inner);
+    }
+
+    /// Start recording all draws.
+    pub fn start_recording(&mut self) {
+        self.mode = RngMode::Record(RngRecording::default());
+    }
+
+    /// Serve draws from the recording. A consumer which runs out of recorded bytes falls back to
+    /// the live generator.
+    pub fn replay(&mut self, recording: RngRecording) {
+        self.mode = RngMode::Replay {
+            recording,
+            positions: BTreeMap::new(),
+        };
+    }
+
+    /// Stop recording and return everything that was recorded so far.
+    pub fn take_recording(&mut self) -> Option<RngRecording> {
+        match std::mem::replace(&mut self.mode, RngMode::Live) {
+            RngMode::Record(recording) => Some(recording),
+            _ => None,
+        }
+    }
+
+    fn consumer_key(&self) -> String {
+        format!("{:?}", self.consumer)
+    }
+
+    fn draw(
+        &mut self,
+        dest: &mut [u8],
+        live_draw: impl FnOnce(&mut rand::rngs::StdRng, &mut [u8]),
+    ) {
+        let key = match &self.mode {
+            RngMode::Live => {
+                return live_draw(self.stream.as_mut().unwrap_or(&mut self.inner), dest)
+            }
+            _ => self.consumer_key(),
+        };
+        let live_rng = self.stream.as_mut().unwrap_or(&mut self.inner);
+        match &mut self.mode {
+            RngMode::Live => unreachable!(),
+            RngMode::Record(recording) => {
+                live_draw(live_rng, dest);
+                recording
+                    .streams
+                    .entry(key)
+                    .or_default()
+                    .extend_from_slice(dest);
+            }
+            RngMode::Replay {
+                recording,
+                positions,
+            } => {
+                let stream = recording
+                    .streams
+                    .get(&key)
+                    .map(Vec::as_slice)
+                    .unwrap_or(&[]);
+                let position = positions.entry(key).or_insert(0);
+                match stream.get(*position..*position + dest.len()) {
+                    Some(recorded) => {
+                        dest.copy_from_slice(recorded);
+                        *position += dest.len();
+                    }
+                    None => live_draw(live_rng, dest),
+                }
+            }
+        }
+    }
+}
+
+impl RngConsumer {
+    /// Number which identifies the consumer, used to derive its stream.
+    fn stream_id(&self) -> u64 {
+        let shard_id = |shard: &ShardUId| ((shard.version as u64) << 32) | shard.shard_id as u64;
+        let (kind, a, b) = match self {
+            RngConsumer::Unspecified => (0, 0, 0),
+            RngConsumer::MissingBlock => (1, 0, 0),
+            RngConsumer::MissingChunk(shard) => (2, shard_id(shard), 0),
+            RngConsumer::ReceiptSender(link) => (3, shard_id(&link.from), shard_id(&link.to)),
+            RngConsumer::UnstoppableSender(link) => (4, shard_id(&link.from), shard_id(&link.to)),
+            RngConsumer::RequestFault(shard) => (5, shard_id(shard), 0),
+        };
+        splitmix64(splitmix64(splitmix64(kind) ^ a) ^ b)
+    }
+}
+
+/// Where the bandwidth scheduler gets its randomness from. The scheduler shuffles the requests with it,
+/// all shards must derive the same rng from the previous block, otherwise they'd compute different grants.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GrantRngSource {
+    /// Seeded with the hash of the previous block's contents, like the block hash in production.
+    /// Nobody can tell how the requests will be shuffled before the block is produced.
+    #[default]
+    BlockHash,
+    /// Seeded with the height of the previous block. The shuffle at every height is known in advance.
+    Height,
+}
+
+impl GrantRngSource {
+    /// Rng used by the scheduler which runs after `prev_block`.
+    pub fn rng_for_block(&self, prev_block: &Block) -> DefaultRng {
+        match self {
+            GrantRngSource::BlockHash => rng_from_seed(block_hash(prev_block)),
+            GrantRngSource::Height => rng_from_seed(prev_block.height as u64),
+        }
+    }
+}
+
+/// Hash of everything in the block, stands in for the block hash.
+/// Depends only on the block's contents, so every shard computes the same value.
+pub fn block_hash(block: &Block) -> u64 {
+    let shard_id = |shard: &ShardUId| ((shard.version as u64) << 32) | shard.shard_id as u64;
+    let mut hash = splitmix64(block.height as u64);
+    let mut add = |value: u64| hash = splitmix64(hash ^ value);
+    for shard in block.shard_layout.shard_ids() {
+        add(shard_id(shard));
+    }
+    for (shard, chunk_opt) in &block.chunks {
+        add(shard_id(shard));
+        let Some(chunk) = chunk_opt else {
+            add(0);
+            continue;
+        };
+        add(1);
+        add(chunk.prev_incoming_receipts_size as u64);
+        for sizes in [
+            &chunk.prev_outgoing_receipts_size,
+            &chunk.prev_unstoppable_receipts_size,
+        ] {
+            add(sizes.len() as u64);
+            for (to_shard, size) in sizes {
+                add(shard_id(to_shard));
+                add(*size as u64);
+            }
+        }
+        add(chunk.bandwidth_requests.len() as u64);
+        for request in &chunk.bandwidth_requests {
+            for byte in request.to_bytes() {
+                add(byte as u64);
+            }
+        }
+        add(chunk.congestion_info.incoming_backlog_size as u64);
+        add(chunk.congestion_info.processing_delay as u64);
+    }
+    hash
+}
+
+/// SplitMix64 finalizer, turns similar inputs into very different outputs.
+fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+impl RngCore for DefaultRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        self.draw(&mut bytes, |rng, dest| {
+            dest.copy_from_slice(&rng.next_u32().to_le_bytes())
+        });
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        self.draw(&mut bytes, |rng, dest| {
+            dest.copy_from_slice(&rng.next_u64().to_le_bytes())
+        });
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.draw(dest, |rng, dest| rng.fill_bytes(dest));
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl RngRecording {
+    /// Number of bytes drawn by all consumers.
+    pub fn total_bytes(&self) -> usize {
+        self.streams.values().map(Vec::len).sum()
+    }
+
+    /// Save the recording to a file, one consumer per line.
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut contents = String::new();
+        for (consumer, stream) in &self.streams {
+            contents.push_str(consumer);
+            contents.push('\t');
+            for byte in stream {
+                contents.push_str(&format!("{:02x}", byte));
+            }
+            contents.push('\n');
+        }
+        std::fs::write(path, contents)
+    }
+
+    /// Load a recording saved with `save`.
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<RngRecording> {
+        let invalid = |line: &str| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid rng recording line: {}", line),
+            )
+        };
+        let mut streams = BTreeMap::new();
+        for line in std::fs::read_to_string(path)?.lines() {
+            let (consumer, hex) = line.split_once('\t').ok_or_else(|| invalid(line))?;
+            let stream = (0..hex.len())
+                .step_by(2)
+                .map(|i| {
+                    hex.get(i..i + 2)
+                        .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                        .ok_or_else(|| invalid(line))
+                })
+                .collect::<Result<Vec<u8>, _>>()?;
+            streams.insert(consumer.to_string(), stream);
+        }
+        Ok(RngRecording { streams })
+    }
+}
+
+pub fn rng_from_seed(seed: u64) -> DefaultRng {
+    let mut seed_bytes = Vec::new();
+    for _ in 0..4 {
+        seed_bytes.extend_from_slice(&seed.to_be_bytes());
+    }
+    DefaultRng {
+        inner: rand::rngs::StdRng::from_seed(seed_bytes.try_into().unwrap()),
+        seed,
+        stream: None,
+        consumer: RngConsumer::Unspecified,
+        mode: RngMode::Live,
+    }
+}
+
+#[test]
+fn test_replay_is_independent_of_consumer_order() {
+    let shard = ShardUId::new(0);
+    let mut rng = rng_from_seed(0);
+    rng.start_recording();
+    rng.set_consumer(RngConsumer::MissingBlock);
+    let block_draws: Vec<u64> = (0..3).map(|_| rng.next_u64()).collect();
+    rng.set_consumer(RngConsumer::MissingChunk(shard));
+    let chunk_draws: Vec<u32> = (0..3).map(|_| rng.next_u32()).collect();
+    let recording = rng.take_recording().unwrap();
+    assert_eq!(recording.total_bytes(), 3 * 8 + 3 * 4);
+
+    // Draw in a different order, the values for each consumer stay the same.
+    let mut replay_rng = rng_from_seed(1);
+    replay_rng.replay(recording);
+    replay_rng.set_consumer(RngConsumer::MissingChunk(shard));
+    let replayed_chunk_draws: Vec<u32> = (0..3).map(|_| replay_rng.next_u32()).collect();
+    replay_rng.set_consumer(RngConsumer::MissingBlock);
+    let replayed_block_draws: Vec<u64> = (0..3).map(|_| replay_rng.next_u64()).collect();
+    assert_eq!(replayed_chunk_draws, chunk_draws);
+    assert_eq!(replayed_block_draws, block_draws);
+
+    // The recorded stream is exhausted, further draws fall back to the live generator.
+    replay_rng.set_consumer(RngConsumer::MissingBlock);
+    replay_rng.next_u64();
+}
+
+#[test]
+fn test_streams_are_independent() {
+    let link = ShardLink {
+        from: ShardUId::new(0),
+        to: ShardUId::new(1),
+    };
+    let mut rng = rng_from_seed(0);
+    rng.set_stream(RngConsumer::ReceiptSender(link), 5);
+    let first = rng.next_u64();
+
+    // Draws from the main generator and other streams don't affect the stream.
+    let mut other_rng = rng_from_seed(0);
+    other_rng.set_consumer(RngConsumer::MissingBlock);
+    other_rng.next_u64();
+    other_rng.set_stream(RngConsumer::UnstoppableSender(link), 5);
+    other_rng.next_u64();
+    other_rng.set_stream(RngConsumer::ReceiptSender(link), 5);
+    assert_eq!(other_rng.next_u64(), first);
+
+    // Different heights and seeds give different streams.
+    other_rng.set_stream(RngConsumer::ReceiptSender(link), 6);
+    assert_ne!(other_rng.next_u64(), first);
+    let mut seed_rng = rng_from_seed(1);
+    seed_rng.set_stream(RngConsumer::ReceiptSender(link), 5);
+    assert_ne!(seed_rng.next_u64(), first);
+}
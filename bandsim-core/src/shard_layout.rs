@@ -0,0 +1,74 @@
+use crate::chain::{ShardLink, ShardUId};
+
+/// The set of shards which exist at some height.
+/// Every block carries the layout that was used to produce it, so the simulation, scheduler and
+/// validation don't have to guess the set of shards from the chunks in the block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShardLayout {
+    version: u32,
+    /// Sorted, without duplicates
+    shard_ids: Vec<ShardUId>,
+}
+
+impl ShardLayout {
+    pub fn new(version: u32, mut shard_ids: Vec<ShardUId>) -> ShardLayout {
+        shard_ids.sort();
+        shard_ids.dedup();
+        for shard_id in &shard_ids {
+            assert_eq!(
+                shard_id.version, version,
+                "Shard version doesn't match the layout version"
+            );
+        }
+        ShardLayout { version, shard_ids }
+    }
+
+    /// Layout with shards `0..num_shards`
+    pub fn with_num_shards(num_shards: usize) -> ShardLayout {
+        ShardLayout::new(0, (0..num_shards).map(ShardUId::new).collect())
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn shard_ids(&self) -> &[ShardUId] {
+        &self.shard_ids
+    }
+
+    pub fn num_shards(&self) -> usize {
+        self.shard_ids.len()
+    }
+
+    pub fn contains(&self, shard_id: ShardUId) -> bool {
+        self.shard_ids.binary_search(&shard_id).is_ok()
+    }
+
+    /// All links between shards in this layout, including links from a shard to itself.
+    pub fn all_links(&self) -> impl Iterator<Item = ShardLink> + '_ {
+        self.shard_ids.iter().flat_map(move |from| {
+            self.shard_ids.iter().map(move |to| ShardLink {
+                from: *from,
+                to: *to,
+            })
+        })
+    }
+}
+
+#[test]
+fn test_shard_layout() {
+    let layout = ShardLayout::new(
+        0,
+        vec![ShardUId::new(2), ShardUId::new(0), ShardUId::new(2)],
+    );
+    assert_eq!(layout.version(), 0);
+    assert_eq!(layout.shard_ids(), &[ShardUId::new(0), ShardUId::new(2)]);
+    assert_eq!(layout.num_shards(), 2);
+    assert!(layout.contains(ShardUId::new(2)));
+    assert!(!layout.contains(ShardUId::new(1)));
+    assert_eq!(layout.all_links().count(), 4);
+    assert_eq!(
+        ShardLayout::with_num_shards(2).shard_ids(),
+        &[ShardUId::new(0), ShardUId::new(1)]
+    );
+}
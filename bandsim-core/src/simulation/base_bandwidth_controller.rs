@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+
+use crate::chain::ShardLink;
+
+/// Experimental controller which adjusts `SchedulerParams::max_base_bandwidth` between heights.
+/// Base bandwidth is granted on every link, also on the ones that don't send anything, so too much of it
+/// is wasted. Too little of it makes links which don't request more bandwidth wait for their receipts.
+/// The controller looks at both at every height and moves the base bandwidth by `step` towards the side
+/// that suffers, the trajectory shows where (and whether) it settles.
+/// All shards get the same base bandwidth at the same height, their scheduler states stay in sync.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BaseBandwidthController {
+    pub min_base_bandwidth: usize,
+    pub max_base_bandwidth: usize,
+    /// How much the base bandwidth changes at a single height.
+    pub step: usize,
+    /// Decrease the base bandwidth when more than this fraction of the granted base bandwidth isn't used.
+    pub max_waste_ratio: f64,
+    /// Increase the base bandwidth when more than this fraction of the links with queued receipts
+    /// couldn't send all of them.
+    pub max_starvation_ratio: f64,
+}
+
+impl Default for BaseBandwidthController {
+    fn default() -> Self {
+        BaseBandwidthController {
+            min_base_bandwidth: 0,
+            max_base_bandwidth: 1_000_000,
+            step: 10_000,
+            max_waste_ratio: 0.5,
+            max_starvation_ratio: 0.1,
+        }
+    }
+}
+
+/// State of the controller at a single height.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TuningPoint {
+    pub height: usize,
+    /// `SchedulerParams::max_base_bandwidth` at this height. With many shards the scheduler grants less.
+    pub base_bandwidth: usize,
+    /// Fraction of the granted base bandwidth which wasn't used.
+    pub waste_ratio: f64,
+    /// Fraction of the links with queued receipts which couldn't send all of them.
+    pub starvation_ratio: f64,
+}
+
+impl BaseBandwidthController {
+    /// Observe the height and choose the base bandwidth for the next one.
+    /// `granted_base` is the base bandwidth granted on every link at this height, `links` are all links,
+    /// `sent` the bytes sent on every link and `queued_before_send` the bytes waiting in the outgoing queues
+    /// before sending. Unstoppable receipts aren't counted.
+    pub fn observe(
+        &self,
+        height: usize,
+        base_bandwidth: usize,
+        granted_base: usize,
+        links: impl Iterator<Item = ShardLink>,
+        sent: &BTreeMap<ShardLink, usize>,
+        queued_before_send: &BTreeMap<ShardLink, usize>,
+    ) -> (TuningPoint, usize) {
+        let (mut total_base, mut unused_base) = (0, 0);
+        for link in links {
+            total_base += granted_base;
+            unused_base += granted_base.saturating_sub(sent.get(&link).copied().unwrap_or(0));
+        }
+        let waste_ratio = unused_base as f64 / total_base.max(1) as f64;
+
+        let busy_links: Vec<(&ShardLink, &usize)> = queued_before_send
+            .iter()
+            .filter(|(_link, size)| **size > 0)
+            .collect();
+        let starved_links = busy_links
+            .iter()
+            .filter(|(link, size)| sent.get(link).copied().unwrap_or(0) < **size)
+            .count();
+        let starvation_ratio = starved_links as f64 / busy_links.len().max(1) as f64;
+
+        let next_base_bandwidth = if starvation_ratio > self.max_starvation_ratio {
+            base_bandwidth + self.step
+        } else if waste_ratio > self.max_waste_ratio {
+            base_bandwidth.saturating_sub(self.step)
+        } else {
+            base_bandwidth
+        };
+        let point = TuningPoint {
+            height,
+            base_bandwidth,
+            waste_ratio,
+            starvation_ratio,
+        };
+        let next_base_bandwidth =
+            next_base_bandwidth.clamp(self.min_base_bandwidth, self.max_base_bandwidth);
+        (point, next_base_bandwidth)
+    }
+}
+
+/// Print the base bandwidth and the observed waste and starvation, averaged over windows of `window_size` heights.
+pub fn print_trajectory(trajectory: &[TuningPoint], window_size: usize) {
+    println!("Base bandwidth trajectory:");
+    for window in trajectory.chunks(window_size) {
+        let len = window.len() as f64;
+        println!(
+            "heights {}..={}: base bandwidth = {:.0}, waste = {:.2}%, starvation = {:.2}%",
+            window[0].height,
+            window[window.len() - 1].height,
+            window.iter().map(|p| p.base_bandwidth as f64).sum::<f64>() / len,
+            window.iter().map(|p| p.waste_ratio).sum::<f64>() / len * 100.0,
+            window.iter().map(|p| p.starvation_ratio).sum::<f64>() / len * 100.0
+        );
+    }
+}
@@ -0,0 +1,689 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::bandwidth_scheduler::{Maintenance, SchedulerParams};
+use crate::chain::{Block, ShardLink, ShardUId, SimulationConfig};
+use crate::rng::{rng_from_seed, DefaultRng, GrantRngSource, RngRecording};
+use crate::shard_layout::ShardLayout;
+use crate::validation::ValidationLevel;
+
+use super::base_bandwidth_controller::BaseBandwidthController;
+use super::chunk_producers::ChunkProducers;
+use super::faults::{
+    GrantOveruse, ReceiptSizeMutation, RequestFault, SchedulerFault, StaleShardLayout,
+};
+use super::initial_state::InitialState;
+use super::metrics::HeightMetrics;
+use super::outgoing_queue::{
+    Backpressure, DrainPolicy, DropPolicy, QueueCap, RequestPolicy, WholeQueue,
+};
+use super::receipt_sender::{LoadPhase, ReceiptGenerator, ReceiptSender, StepLoadReceiptSender};
+use super::workload_trace::{TraceReceiptSender, WorkloadTrace};
+use super::{MetricObserver, MissingChunkGenerator, Simulation};
+
+pub struct SimulationBuilder {
+    shards: Vec<ShardUId>,
+    receipt_senders: BTreeMap<ShardLink, Box<dyn ReceiptSender>>,
+    unstoppable_senders: BTreeMap<ShardLink, Box<dyn ReceiptSender>>,
+    random_seed: u64,
+    default_sender_factory: Option<ReceiptSenderFactory>,
+    missing_chunk_generator: Option<MissingChunkGenerator>,
+    downtime: BTreeMap<ShardUId, Vec<Range<usize>>>,
+    missing_block_probability: f64,
+    scheduler_params: SchedulerParams,
+    config: SimulationConfig,
+    incoming_processing_limit: usize,
+    incoming_outages: BTreeMap<ShardUId, Vec<Range<usize>>>,
+    maintenance_windows: BTreeMap<ShardUId, Vec<(Range<usize>, Maintenance)>>,
+    request_faults: BTreeMap<ShardUId, RequestFault>,
+    grant_overuses: BTreeMap<ShardUId, GrantOveruse>,
+    scheduler_faults: BTreeMap<ShardUId, SchedulerFault>,
+    stale_shard_layouts: BTreeMap<ShardUId, StaleShardLayout>,
+    receipt_size_mutations: BTreeMap<ShardUId, ReceiptSizeMutation>,
+    backpressure: Option<Backpressure>,
+    queue_cap: Option<QueueCap>,
+    drain_policy: DrainPolicy,
+    request_policy: Box<dyn RequestPolicy>,
+    load_phase_starts: BTreeSet<usize>,
+    sender_seeds: BTreeMap<ShardLink, u64>,
+    record_rng: bool,
+    record_allowance_history: bool,
+    record_grant_history: bool,
+    shard_grant_history_len: usize,
+    validation_threads: usize,
+    validation_level: ValidationLevel,
+    rng_replay: Option<RngRecording>,
+    description: String,
+    metric_observers: Vec<(String, MetricObserver)>,
+    base_bandwidth_controller: Option<BaseBandwidthController>,
+    initial_state: Option<InitialState>,
+    grant_rng_source: GrantRngSource,
+    record_workload: bool,
+    workload_replay: Option<Arc<WorkloadTrace>>,
+}
+
+/// A function used to create new receipt senders
+type ReceiptSenderFactory = Box<dyn FnMut(&mut DefaultRng) -> Box<dyn ReceiptSender>>;
+
+impl SimulationBuilder {
+    /// Create a new simulation with this many shards
+    pub fn new(num_shards: usize) -> SimulationBuilder {
+        let shards = (0..num_shards).map(ShardUId::new).collect();
+        SimulationBuilder {
+            shards,
+            receipt_senders: BTreeMap::new(),
+            unstoppable_senders: BTreeMap::new(),
+            random_seed: 0,
+            default_sender_factory: None,
+            missing_block_probability: 0.0,
+            missing_chunk_generator: None,
+            downtime: BTreeMap::new(),
+            scheduler_params: SchedulerParams::default(),
+            config: SimulationConfig::default(),
+            incoming_processing_limit: usize::MAX,
+            incoming_outages: BTreeMap::new(),
+            maintenance_windows: BTreeMap::new(),
+            request_faults: BTreeMap::new(),
+            grant_overuses: BTreeMap::new(),
+            scheduler_faults: BTreeMap::new(),
+            stale_shard_layouts: BTreeMap::new(),
+            receipt_size_mutations: BTreeMap::new(),
+            backpressure: None,
+            queue_cap: None,
+            drain_policy: DrainPolicy::default(),
+            request_policy: Box::new(WholeQueue),
+            load_phase_starts: BTreeSet::new(),
+            sender_seeds: BTreeMap::new(),
+            record_rng: false,
+            record_allowance_history: false,
+            record_grant_history: false,
+            shard_grant_history_len: 0,
+            validation_threads: 1,
+            validation_level: ValidationLevel::default(),
+            rng_replay: None,
+            description: String::new(),
+            metric_observers: Vec::new(),
+            base_bandwidth_controller: None,
+            initial_state: None,
+            grant_rng_source: GrantRngSource::default(),
+            record_workload: false,
+            workload_replay: None,
+        }
+    }
+
+    /// Set a receipt sender between two shards.
+    /// For shard links that didn't have the sender set, builder will use the default sender factory to create one.
+    pub fn receipt_sender(
+        mut self,
+        from_shard: usize,
+        to_shard: usize,
+        sender: impl ReceiptSender + 'static,
+    ) -> Self {
+        let shard_link = ShardLink {
+            from: ShardUId::new(from_shard),
+            to: ShardUId::new(to_shard),
+        };
+
+        if self.receipt_senders.contains_key(&shard_link) {
+            panic!("There's already a receipt sender for {:?}", shard_link);
+        }
+
+        self.receipt_senders.insert(shard_link, Box::new(sender));
+        self
+    }
+
+    /// Set a receipt sender between two shards, with its own seed.
+    /// The sender generates the same receipts regardless of the simulation seed, see `sender_seeds`.
+    pub fn receipt_sender_with_seed(
+        self,
+        from_shard: usize,
+        to_shard: usize,
+        sender: impl ReceiptSender + 'static,
+        seed: u64,
+    ) -> Self {
+        self.receipt_sender(from_shard, to_shard, sender)
+            .sender_seeds([((from_shard, to_shard), seed)])
+    }
+
+    /// Override seeds of receipt senders on some links, given as `((from_shard, to_shard), seed)`.
+    /// Randomness of these senders is derived from their seed instead of the simulation seed,
+    /// so their behaviour stays fixed while everything else varies.
+    /// Also applies to senders created by the default sender factory.
+    pub fn sender_seeds(mut self, seeds: impl IntoIterator<Item = ((usize, usize), u64)>) -> Self {
+        for ((from_shard, to_shard), seed) in seeds {
+            let shard_link = ShardLink {
+                from: ShardUId::new(from_shard),
+                to: ShardUId::new(to_shard),
+            };
+            self.sender_seeds.insert(shard_link, seed);
+        }
+        self
+    }
+
+    /// Send receipts between two shards with a load that changes at the start of every phase.
+    /// Start heights of the phases are remembered, `PhaseStats::for_load_phases` calculates
+    /// the stats separately for each phase.
+    pub fn step_load(
+        mut self,
+        from_shard: usize,
+        to_shard: usize,
+        generator: impl ReceiptGenerator + Clone + 'static,
+        phases: &[LoadPhase],
+    ) -> Self {
+        let mut phases = phases.to_vec();
+        phases.sort_by_key(|phase| phase.start_height);
+        self.load_phase_starts
+            .extend(phases.iter().map(|phase| phase.start_height));
+        self.receipt_sender(
+            from_shard,
+            to_shard,
+            StepLoadReceiptSender { generator, phases },
+        )
+    }
+
+    /// Set a sender of unstoppable receipts between two shards.
+    /// Unstoppable receipts (e.g. refunds) are sent at the next height regardless of the bandwidth grants.
+    /// Use `SchedulerParams::unstoppable_reserve` to leave room for them.
+    pub fn unstoppable_receipt_sender(
+        mut self,
+        from_shard: usize,
+        to_shard: usize,
+        sender: impl ReceiptSender + 'static,
+    ) -> Self {
+        let shard_link = ShardLink {
+            from: ShardUId::new(from_shard),
+            to: ShardUId::new(to_shard),
+        };
+
+        if self.unstoppable_senders.contains_key(&shard_link) {
+            panic!(
+                "There's already an unstoppable receipt sender for {:?}",
+                shard_link
+            );
+        }
+
+        self.unstoppable_senders
+            .insert(shard_link, Box::new(sender));
+        self
+    }
+
+    /// Random seed used by the simulation.
+    /// Default sender factory doesn't use this seed.
+    pub fn random_seed(mut self, seed: u64) -> Self {
+        self.random_seed = seed;
+        self
+    }
+
+    /// Specify a function that will be used to create receipt senders for shard links that didn't have a sender set explicitly.
+    pub fn default_sender_factory(
+        mut self,
+        f: impl FnMut(&mut DefaultRng) -> Box<dyn ReceiptSender> + 'static,
+    ) -> Self {
+        if self.default_sender_factory.is_some() {
+            panic!("Default sender factory is already set!");
+        }
+        self.default_sender_factory = Some(Box::new(f));
+        self
+    }
+
+    /// Probability that the block at a height is missing, see `Simulation::missing_block_probability`
+    /// for what happens with the grants.
+    pub fn missing_block_probability(mut self, p: f64) -> Self {
+        self.missing_block_probability = p;
+        self
+    }
+
+    /// Decides at every height which chunks are missing. The generator has to be `Clone`,
+    /// so that the simulation can be forked, see `Simulation::fork`.
+    pub fn missing_chunk_generator(
+        mut self,
+        generator: impl FnMut(usize, ShardUId, &mut DefaultRng) -> bool + Clone + 'static,
+    ) -> Self {
+        self.missing_chunk_generator = Some(Box::new(generator));
+        self
+    }
+
+    /// Chunks are missing when their chunk producer is offline.
+    /// Replaces the missing chunk generator.
+    pub fn chunk_producers(mut self, producers: ChunkProducers) -> Self {
+        if self.missing_chunk_generator.is_some() {
+            panic!("Missing chunk generator is already set!");
+        }
+        self.missing_chunk_generator = Some(Box::new(producers.into_missing_chunk_generator()));
+        self
+    }
+
+    /// The shard is down at these heights, all of its chunks are missing.
+    /// Can be called many times to add more downtime windows. Works together with the missing
+    /// chunk generator - a chunk is missing when it's in a downtime window or the generator says so.
+    pub fn downtime(mut self, shard: usize, heights: Range<usize>) -> Self {
+        let shard_id = ShardUId::new(shard);
+        assert!(self.shards.contains(&shard_id), "No shard {}", shard);
+        self.downtime.entry(shard_id).or_default().push(heights);
+        self
+    }
+
+    /// Parameters used by the bandwidth scheduler on all shards.
+    pub fn scheduler_params(mut self, params: SchedulerParams) -> Self {
+        self.scheduler_params = params;
+        self
+    }
+
+    /// Protocol limits used by the simulation, the defaults are the limits of the real protocol.
+    /// Doesn't change the scheduler parameters, use `SchedulerParams::for_config` to scale the max
+    /// allowance with the shard bandwidth. Receipt generators have their own size ranges, they have to
+    /// generate receipts that fit in `config.max_receipt_size`.
+    pub fn config(mut self, config: SimulationConfig) -> Self {
+        config.validate();
+        self.config = config;
+        self
+    }
+
+    /// How many bytes of incoming receipts a chunk can process.
+    /// Receipts that can't be processed wait in the incoming backlog. By default there's no limit.
+    pub fn incoming_processing_limit(mut self, bytes_per_chunk: usize) -> Self {
+        self.incoming_processing_limit = bytes_per_chunk;
+        self
+    }
+
+    /// The shard can't process any incoming receipts at these heights, its incoming processing
+    /// limit is zero. Receipts sent to the shard pile up in its incoming backlog.
+    pub fn incoming_outage(mut self, shard: usize, heights: Range<usize>) -> Self {
+        let shard_id = ShardUId::new(shard);
+        assert!(self.shards.contains(&shard_id), "No shard {}", shard);
+        self.incoming_outages
+            .entry(shard_id)
+            .or_default()
+            .push(heights);
+        self
+    }
+
+    /// Throttle the receipt senders on all links: when the outgoing queue holds more than
+    /// `queue_threshold` bytes, the receipts generated at this height are shed instead of being queued.
+    pub fn backpressure(mut self, queue_threshold: usize) -> Self {
+        self.backpressure = Some(Backpressure { queue_threshold });
+        self
+    }
+
+    /// Limit the size of the outgoing queues on all links. When new receipts push a queue over
+    /// `max_bytes`, receipts are dropped according to the policy.
+    pub fn queue_cap(mut self, max_bytes: usize, policy: DropPolicy) -> Self {
+        self.queue_cap = Some(QueueCap { max_bytes, policy });
+        self
+    }
+
+    /// Order in which receipts of different priorities are sent from the outgoing queues.
+    /// By default higher priorities are always sent first.
+    pub fn drain_policy(mut self, drain_policy: DrainPolicy) -> Self {
+        self.drain_policy = drain_policy;
+        self
+    }
+
+    /// For which receipts in the outgoing queues the shards request bandwidth.
+    /// By default the requests cover the whole queue (`WholeQueue`).
+    pub fn request_policy(mut self, request_policy: impl RequestPolicy + 'static) -> Self {
+        self.request_policy = Box::new(request_policy);
+        self
+    }
+
+    /// The shard is in maintenance mode at these heights, the scheduler doesn't grant anything
+    /// to it and/or from it. Can be called many times to add more maintenance windows.
+    pub fn maintenance(
+        mut self,
+        shard: usize,
+        heights: Range<usize>,
+        maintenance: Maintenance,
+    ) -> Self {
+        let shard_id = ShardUId::new(shard);
+        assert!(self.shards.contains(&shard_id), "No shard {}", shard);
+        self.maintenance_windows
+            .entry(shard_id)
+            .or_default()
+            .push((heights, maintenance));
+        self
+    }
+
+    /// The shard sends malicious bandwidth requests which don't correspond to its outgoing queues.
+    pub fn malicious_requester(mut self, shard: usize, fault: RequestFault) -> Self {
+        self.request_faults.insert(ShardUId::new(shard), fault);
+        self
+    }
+
+    /// The shard is byzantine and sends more than it was granted.
+    pub fn byzantine_sender(mut self, shard: usize, overuse: GrantOveruse) -> Self {
+        self.grant_overuses.insert(ShardUId::new(shard), overuse);
+        self
+    }
+
+    /// The shard corrupts the state of its bandwidth scheduler.
+    pub fn scheduler_fault(mut self, shard: usize, fault: SchedulerFault) -> Self {
+        self.scheduler_faults.insert(ShardUId::new(shard), fault);
+        self
+    }
+
+    /// Receipts received by the shard are `growth_ratio` bigger than what the senders accounted for.
+    pub fn receipt_size_mutation(mut self, shard: usize, growth_ratio: f64) -> Self {
+        self.receipt_size_mutations
+            .insert(ShardUId::new(shard), ReceiptSizeMutation { growth_ratio });
+        self
+    }
+
+    /// The shard addresses its bandwidth requests to shards of the layout with this version,
+    /// which doesn't exist in the simulation.
+    pub fn stale_shard_layout(mut self, shard: usize, layout_version: u32) -> Self {
+        self.stale_shard_layouts
+            .insert(ShardUId::new(shard), StaleShardLayout { layout_version });
+        self
+    }
+
+    /// Record every random draw made during the run.
+    /// The recording can be taken out with `simulation.rng.take_recording()`.
+    pub fn record_rng(mut self) -> Self {
+        self.record_rng = true;
+        self
+    }
+
+    /// Replay random draws recorded in a previous run.
+    /// Each consumer of randomness gets back its own recorded stream, even when the order of draws
+    /// changed since the recording. Draws beyond the recording come from the seeded generator.
+    pub fn replay_rng(mut self, recording: RngRecording) -> Self {
+        self.rng_replay = Some(recording);
+        self
+    }
+
+    /// Record the receipts generated by the receipt senders at every height, see `WorkloadTrace::from_run`.
+    pub fn record_workload(mut self) -> Self {
+        self.record_workload = true;
+        self
+    }
+
+    /// Generate the receipts recorded in the trace instead of using the receipt senders. The senders set
+    /// in the builder and the default sender factory are ignored, every link generates exactly the receipts
+    /// that were generated at the same height in the recorded run. Unstoppable senders are still used.
+    pub fn replay_workload(mut self, trace: Arc<WorkloadTrace>) -> Self {
+        self.workload_replay = Some(trace);
+        self
+    }
+
+    /// Store a snapshot of every shard's allowances at every height.
+    /// Useful for seeing how the allowances evolved around a failure, but takes a lot of memory.
+    pub fn record_allowance_history(mut self) -> Self {
+        self.record_allowance_history = true;
+        self
+    }
+
+    /// Store the grants computed at every height, e.g. for rendering them with `GrantFrames`.
+    pub fn record_grant_history(mut self) -> Self {
+        self.record_grant_history = true;
+        self
+    }
+
+    /// Every shard keeps the grants from the last `heights` heights, see `Shard::grant_history`.
+    pub fn shard_grant_history(mut self, heights: usize) -> Self {
+        self.shard_grant_history_len = heights;
+        self
+    }
+
+    /// Experimental: adjust the base bandwidth between heights with the controller, starting from
+    /// `SchedulerParams::max_base_bandwidth`. The trajectory is in `simulation.base_bandwidth_trajectory`.
+    pub fn base_bandwidth_controller(mut self, controller: BaseBandwidthController) -> Self {
+        self.base_bandwidth_controller = Some(controller);
+        self
+    }
+
+    /// Where the bandwidth schedulers get their rng from, `GrantRngSource::BlockHash` by default.
+    pub fn grant_rng_source(mut self, source: GrantRngSource) -> Self {
+        self.grant_rng_source = source;
+        self
+    }
+
+    /// Start from this state instead of an empty genesis at height 0, e.g. to reproduce an incident
+    /// from the moment when the queues were already long.
+    pub fn initial_state(mut self, initial_state: InitialState) -> Self {
+        self.initial_state = Some(initial_state);
+        self
+    }
+
+    /// Measure a custom metric at every non-missing height. The values are aggregated in `TestStats`
+    /// next to the built-in metrics, see `CustomMetricStats`. The observer has to be `Clone`,
+    /// like all the parts of a simulation which can be forked.
+    pub fn metric_observer(
+        mut self,
+        name: &str,
+        observer: impl FnMut(&Block, &HeightMetrics) -> Option<f64> + Clone + 'static,
+    ) -> Self {
+        assert!(
+            self.metric_observers
+                .iter()
+                .all(|(existing, _)| existing != name),
+            "There's already a metric named {}",
+            name
+        );
+        self.metric_observers
+            .push((name.to_string(), Box::new(observer)));
+        self
+    }
+
+    /// Human-readable description of the run, e.g. "big vs small sender, 2 shards". It's printed with
+    /// the stats and saved in the manifest and the exports, so that results of big sweeps can still
+    /// be interpreted later. Doesn't affect the run, so it's not a part of `config_fingerprint`.
+    pub fn description(mut self, description: &str) -> Self {
+        assert!(
+            !description.contains('\n'),
+            "Description must be a single line: {:?}",
+            description
+        );
+        self.description = description.to_string();
+        self
+    }
+
+    /// Run the validation checks at every height on this many threads.
+    pub fn validation_threads(mut self, threads: usize) -> Self {
+        assert!(threads > 0, "Validation needs at least one thread!");
+        self.validation_threads = threads;
+        self
+    }
+
+    /// How much validation to do at every height, `ValidationLevel::Full` by default.
+    pub fn validation_level(mut self, level: ValidationLevel) -> Self {
+        self.validation_level = level;
+        self
+    }
+
+    /// Description of the configuration which affects the results of the run, e.g. for caching results.
+    /// Receipt senders and missing chunk generators are closures which can't be described, only
+    /// the links that have them are listed. The caller has to identify them in some other way.
+    pub fn config_fingerprint(&self) -> String {
+        format!(
+            "shards={:?} senders={:?} unstoppable={:?} default_senders={} seed={} \
+             missing_chunks={} downtime={:?} missing_blocks={} params={:?} config={:?} processing_limit={} \
+             outages={:?} maintenance={:?} request_faults={:?} overuses={:?} scheduler_faults={:?} stale_layouts={:?} \
+             size_mutations={:?} backpressure={:?} queue_cap={:?} drain={:?} request_policy={:?} phases={:?} sender_seeds={:?} replay={:?} \
+             base_bandwidth_controller={:?} initial_state={:?} grant_rng={:?} workload_replay={:?}",
+            self.shards,
+            self.receipt_senders.keys().collect::<Vec<_>>(),
+            self.unstoppable_senders.keys().collect::<Vec<_>>(),
+            self.default_sender_factory.is_some(),
+            self.random_seed,
+            self.missing_chunk_generator.is_some(),
+            self.downtime,
+            self.missing_block_probability,
+            self.scheduler_params,
+            self.config,
+            self.incoming_processing_limit,
+            self.incoming_outages,
+            self.maintenance_windows,
+            self.request_faults,
+            self.grant_overuses,
+            self.scheduler_faults,
+            self.stale_shard_layouts,
+            self.receipt_size_mutations,
+            self.backpressure,
+            self.queue_cap,
+            self.drain_policy,
+            self.request_policy,
+            self.load_phase_starts,
+            self.sender_seeds,
+            self.rng_replay,
+            self.base_bandwidth_controller,
+            self.initial_state,
+            self.grant_rng_source,
+            self.workload_replay,
+        )
+    }
+
+    /// Build the simulation
+    pub fn build(mut self) -> Simulation {
+        self.scheduler_params.validate(&self.config);
+        for shard_id in self.scheduler_params.shard_bandwidth.keys() {
+            assert!(
+                self.shards.contains(shard_id),
+                "Bandwidth limit for shard {:?} which isn't in the simulation",
+                shard_id
+            );
+        }
+        if let Some(trace) = &self.workload_replay {
+            self.default_sender_factory = None;
+            self.receipt_senders = trace
+                .links()
+                .map(|link| {
+                    let sender = TraceReceiptSender {
+                        trace: trace.clone(),
+                        link,
+                    };
+                    (link, Box::new(sender) as Box<dyn ReceiptSender>)
+                })
+                .collect();
+        }
+        if let Some(mut sender_factory) = self.default_sender_factory.take() {
+            let mut create_senders_rng = rng_from_seed(self.random_seed);
+            for from_shard in &self.shards {
+                for to_shard in &self.shards {
+                    let shard_link = ShardLink {
+                        from: *from_shard,
+                        to: *to_shard,
+                    };
+                    self.receipt_senders
+                        .entry(shard_link)
+                        .or_insert_with(|| sender_factory(&mut create_senders_rng));
+                }
+            }
+        }
+
+        if !self.downtime.is_empty() {
+            let downtime = std::mem::take(&mut self.downtime);
+            let mut generator = self.missing_chunk_generator.take();
+            self.missing_chunk_generator = Some(Box::new(move |height, shard_id, rng| {
+                // Always ask the generator, so that adding downtime doesn't change its random draws.
+                let generated_missing = generator
+                    .as_mut()
+                    .is_some_and(|generator| generator(height, shard_id, rng));
+                let is_down = downtime
+                    .get(&shard_id)
+                    .is_some_and(|windows| windows.iter().any(|heights| heights.contains(&height)));
+                generated_missing || is_down
+            }));
+        }
+
+        let config_description = self.config_fingerprint();
+        let mut simulation = Simulation::new(
+            ShardLayout::new(0, self.shards),
+            self.receipt_senders,
+            self.unstoppable_senders,
+            self.random_seed,
+            self.missing_block_probability,
+            self.missing_chunk_generator,
+            self.scheduler_params,
+            self.config,
+            self.incoming_processing_limit,
+        );
+        for (shard_id, fault) in self.request_faults {
+            simulation.shards.get_mut(&shard_id).unwrap().request_fault = Some(fault);
+        }
+        for (shard_id, outages) in self.incoming_outages {
+            let shard = simulation.shards.get_mut(&shard_id).unwrap();
+            for heights in outages {
+                shard.incoming_backlog.add_outage(heights);
+            }
+        }
+        simulation.maintenance_windows = self.maintenance_windows;
+        for (shard_id, overuse) in self.grant_overuses {
+            simulation.shards.get_mut(&shard_id).unwrap().grant_overuse = Some(overuse);
+        }
+        for (shard_id, fault) in self.scheduler_faults {
+            simulation
+                .shards
+                .get_mut(&shard_id)
+                .unwrap()
+                .scheduler_fault = Some(fault);
+        }
+        for (shard_id, stale_shard_layout) in self.stale_shard_layouts {
+            simulation
+                .shards
+                .get_mut(&shard_id)
+                .unwrap()
+                .stale_shard_layout = Some(stale_shard_layout);
+        }
+        for (shard_id, mutation) in self.receipt_size_mutations {
+            simulation
+                .shards
+                .get_mut(&shard_id)
+                .unwrap()
+                .receipt_size_mutation = Some(mutation);
+        }
+        for shard in simulation.shards.values_mut() {
+            shard.backpressure = self.backpressure;
+            shard.queue_cap = self.queue_cap;
+            shard.grant_rng_source = self.grant_rng_source;
+            shard.grant_history_len = self.shard_grant_history_len;
+            shard.record_workload = self.record_workload;
+            for outgoing_queue in shard.outgoing_queues.values_mut() {
+                outgoing_queue.set_drain_policy(self.drain_policy);
+                outgoing_queue.set_request_policy(self.request_policy.clone());
+            }
+        }
+        simulation.record_allowance_history = self.record_allowance_history;
+        simulation.record_grant_history = self.record_grant_history;
+        simulation.validation_threads = self.validation_threads;
+        simulation.validation_level = self.validation_level;
+        simulation.load_phase_starts = self.load_phase_starts;
+        simulation.config_description = config_description;
+        simulation.description = self.description;
+        simulation.metric_observers = self.metric_observers;
+        simulation.base_bandwidth_controller = self.base_bandwidth_controller;
+        for (shard_link, seed) in self.sender_seeds {
+            simulation
+                .shards
+                .get_mut(&shard_link.from)
+                .unwrap()
+                .sender_seeds
+                .insert(shard_link.to, seed);
+        }
+        if let Some(initial_state) = &self.initial_state {
+            initial_state.apply(&mut simulation);
+        }
+        if self.record_rng {
+            simulation.rng.start_recording();
+        }
+        if let Some(recording) = self.rng_replay {
+            simulation.rng.replay(recording);
+        }
+        simulation
+    }
+}
+
+#[test]
+fn builder_doesnt_crash() {
+    use super::receipt_sender::NoReceiptSender;
+
+    let _simulation = SimulationBuilder::new(2)
+        .random_seed(0)
+        .receipt_sender(0, 1, NoReceiptSender)
+        .default_sender_factory(|_rng| Box::new(NoReceiptSender))
+        .missing_block_probability(0.01)
+        .missing_chunk_generator(|_, _, _| false)
+        .scheduler_params(SchedulerParams::default())
+        .incoming_processing_limit(usize::MAX)
+        .build();
+}
@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use rand::distributions::{Distribution, WeightedIndex};
+
+use crate::chain::ShardUId;
+use crate::rng::{rng_from_seed, DefaultRng};
+
+/// Chunk producers assigned to shards at every height.
+/// When a producer is offline, all chunks assigned to it are missing. This creates correlated
+/// patterns of missing chunks - one producer can make chunks on many shards missing at once.
+#[derive(Clone, Debug)]
+pub struct ChunkProducers {
+    num_producers: usize,
+    assignment: ProducerAssignment,
+    /// Heights at which the producers are offline
+    offline: BTreeMap<usize, Vec<Range<usize>>>,
+}
+
+#[derive(Clone, Debug)]
+enum ProducerAssignment {
+    /// Producers take turns, at every height each shard gets the next producer.
+    RoundRobin,
+    /// Producer for every (height, shard) is sampled with probability proportional to its stake.
+    /// In reality the sampling would use a seed derived from the epoch, here it's derived from the height and shard.
+    StakeWeighted(WeightedIndex<u64>),
+}
+
+impl ChunkProducers {
+    pub fn round_robin(num_producers: usize) -> ChunkProducers {
+        assert!(
+            num_producers > 0,
+            "There must be at least one chunk producer"
+        );
+        ChunkProducers {
+            num_producers,
+            assignment: ProducerAssignment::RoundRobin,
+            offline: BTreeMap::new(),
+        }
+    }
+
+    /// `stakes[i]` is the stake of the i-th producer.
+    pub fn stake_weighted(stakes: Vec<u64>) -> ChunkProducers {
+        let weights = WeightedIndex::new(&stakes).expect("Invalid stakes");
+        ChunkProducers {
+            num_producers: stakes.len(),
+            assignment: ProducerAssignment::StakeWeighted(weights),
+            offline: BTreeMap::new(),
+        }
+    }
+
+    /// The producer is offline at these heights.
+    pub fn offline(mut self, producer: usize, heights: Range<usize>) -> Self {
+        assert!(producer < self.num_producers, "No producer {}", producer);
+        self.offline.entry(producer).or_default().push(heights);
+        self
+    }
+
+    /// Producer which produces the chunk on the shard at this height.
+    pub fn producer_for(&self, height: usize, shard_id: ShardUId) -> usize {
+        match &self.assignment {
+            ProducerAssignment::RoundRobin => {
+                (height + shard_id.shard_id as usize) % self.num_producers
+            }
+            ProducerAssignment::StakeWeighted(weights) => {
+                let seed = (height as u64) << 32 | shard_id.shard_id as u64;
+                weights.sample(&mut rng_from_seed(seed))
+            }
+        }
+    }
+
+    pub fn is_offline(&self, producer: usize, height: usize) -> bool {
+        self.offline
+            .get(&producer)
+            .is_some_and(|ranges| ranges.iter().any(|heights| heights.contains(&height)))
+    }
+
+    /// The chunk is missing when its producer is offline.
+    pub fn is_chunk_missing(&self, height: usize, shard_id: ShardUId) -> bool {
+        self.is_offline(self.producer_for(height, shard_id), height)
+    }
+
+    /// Generator of missing chunks that can be passed to the simulation.
+    pub fn into_missing_chunk_generator(
+        self,
+    ) -> impl FnMut(usize, ShardUId, &mut DefaultRng) -> bool + Clone + 'static {
+        move |height, shard_id, _rng| self.is_chunk_missing(height, shard_id)
+    }
+}
+
+#[test]
+fn test_stake_weighted_assignment() {
+    let producers = ChunkProducers::stake_weighted(vec![1, 3, 0]);
+    let mut produced = [0; 3];
+    for height in 0..1000 {
+        produced[producers.producer_for(height, ShardUId::new(0))] += 1;
+        // Assignment is deterministic
+        assert_eq!(
+            producers.producer_for(height, ShardUId::new(1)),
+            producers.producer_for(height, ShardUId::new(1))
+        );
+    }
+    assert_eq!(produced[2], 0);
+    assert!(produced[1] > 2 * produced[0]);
+}
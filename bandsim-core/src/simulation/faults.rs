@@ -0,0 +1,96 @@
+use rand::Rng;
+
+use crate::bandwidth_request::{BandwidthRequest, BandwidthRequestBitmap};
+use crate::chain::{ShardLink, ShardUId};
+use crate::rng::DefaultRng;
+
+/// A malicious shard emits bandwidth requests that don't correspond to its outgoing queues.
+/// Request contents are controlled by the chunk producer, the scheduler can't trust them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequestFault {
+    /// Request all possible values on every link
+    AllBits,
+    /// Request random values on every link
+    RandomBits,
+    /// Request only the maximum value on every link
+    OnlyMaxBit,
+}
+
+impl RequestFault {
+    /// Bandwidth requests sent by the malicious shard instead of the real ones.
+    pub fn make_bandwidth_requests(
+        &self,
+        shard_ids: &[ShardUId],
+        rng: &mut DefaultRng,
+    ) -> Vec<BandwidthRequest> {
+        let mut requests = Vec::new();
+        for to_shard in shard_ids {
+            let mut bitmap = BandwidthRequestBitmap::new();
+            for i in 0..bitmap.len() {
+                let bit = match self {
+                    RequestFault::AllBits => true,
+                    RequestFault::RandomBits => rng.gen_bool(0.5),
+                    RequestFault::OnlyMaxBit => i == bitmap.len() - 1,
+                };
+                bitmap.set_bit(i, bit);
+            }
+            if bitmap.is_all_false() {
+                continue;
+            }
+            requests.push(BandwidthRequest {
+                to_shard: *to_shard,
+                grant_options_bitmap: bitmap,
+            });
+        }
+        requests
+    }
+}
+
+/// A byzantine shard which sends more than it was granted on every link.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GrantOveruse {
+    /// How many bytes over the grant the shard is willing to send on a link.
+    pub extra_bytes: usize,
+}
+
+/// A shard which addresses its bandwidth requests using an outdated shard layout, e.g. right after
+/// a resharding, when the chunk producer hasn't switched to the new layout yet.
+/// All requests target shards with `layout_version`, which don't exist in the current layout.
+/// The scheduler drops such requests, they aren't mapped to the new shards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StaleShardLayout {
+    pub layout_version: u32,
+}
+
+impl StaleShardLayout {
+    /// Readdress the requests to the shards of the stale layout.
+    pub fn readdress_requests(&self, requests: &mut [BandwidthRequest]) {
+        for request in requests {
+            request.to_shard.version = self.layout_version;
+        }
+    }
+}
+
+/// Receipts which grow on the way, e.g. because metadata is added to them, so the receiving shard
+/// gets more bytes than the senders accounted for when using their grants.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReceiptSizeMutation {
+    /// By how much the incoming receipts grow, 0.01 means that they're 1% bigger when received.
+    pub growth_ratio: f64,
+}
+
+impl ReceiptSizeMutation {
+    /// Size of the incoming receipts when the senders accounted for `sent_size` bytes.
+    pub fn received_size(&self, sent_size: usize) -> usize {
+        (sent_size as f64 * (1.0 + self.growth_ratio)).round() as usize
+    }
+}
+
+/// A buggy or malicious shard whose scheduler state diverges from the other shards.
+/// At `height` the shard overwrites its allowance on `link` with `allowance`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SchedulerFault {
+    pub height: usize,
+    pub link: ShardLink,
+    pub allowance: usize,
+}
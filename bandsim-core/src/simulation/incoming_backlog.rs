@@ -0,0 +1,78 @@
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use crate::chain::CongestionInfo;
+
+/// Incoming receipts which were received by the shard, but not processed yet.
+/// Every chunk can process at most `processing_limit` bytes of incoming receipts.
+#[derive(Clone)]
+pub struct IncomingBacklog {
+    /// Size of receipts received at every height, the oldest ones first.
+    /// Partially processed receipts have their size reduced.
+    received: VecDeque<ReceivedReceipts>,
+    total_size: usize,
+    processing_limit: usize,
+    /// Heights at which the shard can't process any incoming receipts.
+    outages: Vec<Range<usize>>,
+}
+
+#[derive(Clone)]
+struct ReceivedReceipts {
+    height: usize,
+    size: usize,
+}
+
+impl IncomingBacklog {
+    pub fn new(processing_limit: usize) -> IncomingBacklog {
+        IncomingBacklog {
+            received: VecDeque::new(),
+            total_size: 0,
+            processing_limit,
+            outages: Vec::new(),
+        }
+    }
+
+    /// The processing limit is zero at these heights.
+    pub fn add_outage(&mut self, heights: Range<usize>) {
+        self.outages.push(heights);
+    }
+
+    fn processing_limit_at(&self, height: usize) -> usize {
+        if self.outages.iter().any(|heights| heights.contains(&height)) {
+            return 0;
+        }
+        self.processing_limit
+    }
+
+    /// Add receipts received at this height to the backlog and process as much as the limit allows.
+    /// Returns the congestion info after processing.
+    pub fn receive_and_process(&mut self, height: usize, received_size: usize) -> CongestionInfo {
+        if received_size > 0 {
+            self.received.push_back(ReceivedReceipts {
+                height,
+                size: received_size,
+            });
+            self.total_size += received_size;
+        }
+
+        let mut remaining_limit = self.processing_limit_at(height);
+        while let Some(oldest) = self.received.front_mut() {
+            let processed = std::cmp::min(oldest.size, remaining_limit);
+            oldest.size -= processed;
+            self.total_size -= processed;
+            remaining_limit -= processed;
+            if oldest.size > 0 {
+                break;
+            }
+            self.received.pop_front();
+        }
+
+        CongestionInfo {
+            incoming_backlog_size: self.total_size,
+            processing_delay: self
+                .received
+                .front()
+                .map_or(0, |oldest| height - oldest.height),
+        }
+    }
+}
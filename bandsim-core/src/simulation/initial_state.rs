@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+
+use crate::chain::{Receipt, ShardLink};
+
+use super::queue_snapshot::QueueSnapshot;
+use super::Simulation;
+
+/// State of the chain at the genesis of the simulation. By default the simulation starts from an empty
+/// genesis at height 0, reproducing an incident requires starting mid-flight, with the backlogs
+/// and allowances that the chain had at that moment.
+#[derive(Clone, Debug, Default)]
+pub struct InitialState {
+    /// Height of the genesis block, the first simulated height is `height + 1`.
+    pub height: usize,
+    /// Receipts waiting in the outgoing queue of every link, in the order in which they were queued.
+    pub outgoing_queues: BTreeMap<ShardLink, Vec<QueuedReceipt>>,
+    /// Allowances of the bandwidth scheduler. All shards start with the same allowances.
+    pub allowances: BTreeMap<ShardLink, usize>,
+}
+
+/// A receipt which was already waiting in an outgoing queue at the genesis.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueuedReceipt {
+    pub receipt: Receipt,
+    /// Height at which the receipt was added to the queue, at most the height of the genesis.
+    pub enqueued_height: usize,
+}
+
+impl InitialState {
+    /// Start with the queues from the snapshot, at the height at which it was taken.
+    /// The allowances aren't in the snapshot, they start empty.
+    pub fn from_queue_snapshot(snapshot: QueueSnapshot) -> InitialState {
+        InitialState {
+            height: snapshot.height,
+            outgoing_queues: snapshot.queues,
+            allowances: BTreeMap::new(),
+        }
+    }
+
+    /// Put the simulation into this state. Must be called before the simulation runs.
+    pub fn apply(&self, simulation: &mut Simulation) {
+        assert_eq!(
+            simulation.blocks.len(),
+            1,
+            "The initial state must be applied before running the simulation!"
+        );
+        simulation.start_height = self.height;
+        simulation.blocks[0].as_mut().unwrap().height = self.height;
+
+        for (shard_link, receipts) in &self.outgoing_queues {
+            let shard = simulation
+                .shards
+                .get_mut(&shard_link.from)
+                .unwrap_or_else(|| panic!("No shard for the queue on {:?}!", shard_link));
+            let outgoing_queue = shard
+                .outgoing_queues
+                .get_mut(&shard_link.to)
+                .unwrap_or_else(|| panic!("No outgoing queue on {:?}!", shard_link));
+            for queued in receipts {
+                assert!(
+                    queued.enqueued_height <= self.height,
+                    "Receipt on {:?} is enqueued after the genesis!",
+                    shard_link
+                );
+                outgoing_queue.set_current_height(queued.enqueued_height);
+                outgoing_queue.push(queued.receipt.clone());
+            }
+            outgoing_queue.set_current_height(self.height);
+            let backlog: usize = receipts.iter().map(|queued| queued.receipt.size).sum();
+            *simulation.initial_backlog.entry(*shard_link).or_default() += backlog;
+            // The receipts entered the queue before the simulation, count them as offered.
+            simulation
+                .queue_conservation
+                .links
+                .entry(*shard_link)
+                .or_default()
+                .offered += backlog;
+        }
+
+        for shard in simulation.shards.values_mut() {
+            for (shard_link, allowance) in &self.allowances {
+                shard
+                    .bandwidth_scheduler
+                    .set_allowance(*shard_link, *allowance);
+            }
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::bandwidth_scheduler::{AllowanceConcentration, AllowanceSaturation, GrantDenial};
+use crate::chain::{Receipt, ReceiptPriority, ReceiptTag, ShardLink, ShardUId};
+
+/// Measurements collected by the simulation at a single height.
+/// Blocks only contain the things that would be on chain, this contains everything else
+/// that is useful for calculating stats.
+#[derive(Clone)]
+pub struct HeightMetrics {
+    pub height: usize,
+    /// How many bytes were waiting in the outgoing queue on every link right before sending receipts.
+    /// Recorded for all shards, also the ones with a missing chunk (which don't send anything at this height).
+    pub queued_before_send: BTreeMap<ShardLink, usize>,
+    /// How many bytes of new receipts were generated by the receipt senders on every link with a receipt sender.
+    /// Only shards with a non-missing chunk generate new receipts.
+    pub offered: BTreeMap<ShardLink, usize>,
+    /// How many of the offered bytes were shed because of backpressure or queue caps, on links where something was shed.
+    pub shed: BTreeMap<ShardLink, usize>,
+    /// Receipts dropped from over-full outgoing queues at this height, by their age (number of heights spent in the queue).
+    pub dropped: BTreeMap<ShardLink, LatencyHistogram>,
+    /// Age (in heights) of the oldest receipt in the outgoing queue right before sending receipts.
+    /// Recorded for all shards, links with an empty outgoing queue don't have an entry here.
+    pub oldest_receipt_age: BTreeMap<ShardLink, usize>,
+    /// Latencies of receipts sent at this height.
+    /// Latency is the number of heights between adding the receipt to the outgoing queue and sending it.
+    pub sent_latencies: BTreeMap<ShardLink, LatencyHistogram>,
+    /// Latencies of tagged receipts sent at this height, on all links.
+    pub sent_latencies_by_tag: BTreeMap<ReceiptTag, LatencyHistogram>,
+    /// Latencies of receipts with each priority sent at this height, on all links.
+    pub sent_latencies_by_priority: BTreeMap<ReceiptPriority, LatencyHistogram>,
+    /// Allowances in the bandwidth scheduler of every shard, right after running the scheduler at this height.
+    /// Empty unless the simulation records allowance history, storing them at every height takes a lot of memory.
+    pub allowances: BTreeMap<ShardUId, BTreeMap<ShardLink, usize>>,
+    /// Grants computed at this height, taken from the first shard like `allowance_saturation`.
+    /// Empty unless the simulation records grant history.
+    pub grants: BTreeMap<ShardLink, usize>,
+    /// Links with allowance at the cap and at zero, right after running the scheduler at this height.
+    /// All shards have the same allowances, they're counted on the first shard.
+    pub allowance_saturation: AllowanceSaturation,
+    /// Share of the allowance held by the richest links, right after running the scheduler at this height.
+    /// Counted on the first shard, like `allowance_saturation`.
+    pub allowance_concentration: AllowanceConcentration,
+    /// Number of per-link values in the scheduler state after running the scheduler at this height,
+    /// counted on the first shard.
+    pub scheduler_state_size: usize,
+    /// Bandwidth increases denied by the scheduler at this height, taken from the first shard.
+    pub denials: Vec<GrantDenial>,
+    /// Total grants before they were scaled down to the global budget at this height,
+    /// `None` when the budget wasn't exceeded. Taken from the first shard.
+    pub over_budget: Option<usize>,
+    /// Height of the block from which the grants at this height were computed - the last non-missing
+    /// block before this height.
+    pub scheduler_input_height: usize,
+    /// Values of the custom metrics measured by the simulation's metric observers.
+    pub custom: BTreeMap<String, f64>,
+    /// Receipts generated by the receipt senders at this height, in the order in which they were generated,
+    /// on links where something was generated. Empty unless the simulation records the workload.
+    pub arrivals: BTreeMap<ShardLink, Vec<Receipt>>,
+    /// Shards which left a receipt in an outgoing queue because the chunk was full, although the grant
+    /// would allow to send it. Empty unless there's a chunk size limit, see `SchedulerParams::chunk_size_limit`.
+    pub chunk_size_bound: BTreeSet<ShardUId>,
+    /// Shards which left a receipt in an outgoing queue because it didn't fit into the grant of its link,
+    /// and not because the chunk was full.
+    pub bandwidth_bound: BTreeSet<ShardUId>,
+}
+
+impl HeightMetrics {
+    pub fn new(height: usize) -> HeightMetrics {
+        HeightMetrics {
+            height,
+            queued_before_send: BTreeMap::new(),
+            offered: BTreeMap::new(),
+            shed: BTreeMap::new(),
+            dropped: BTreeMap::new(),
+            oldest_receipt_age: BTreeMap::new(),
+            sent_latencies: BTreeMap::new(),
+            sent_latencies_by_tag: BTreeMap::new(),
+            sent_latencies_by_priority: BTreeMap::new(),
+            allowances: BTreeMap::new(),
+            grants: BTreeMap::new(),
+            allowance_saturation: AllowanceSaturation::default(),
+            allowance_concentration: AllowanceConcentration::default(),
+            scheduler_state_size: 0,
+            denials: Vec::new(),
+            over_budget: None,
+            scheduler_input_height: 0,
+            custom: BTreeMap::new(),
+            arrivals: BTreeMap::new(),
+            chunk_size_bound: BTreeSet::new(),
+            bandwidth_bound: BTreeSet::new(),
+        }
+    }
+}
+
+/// Number of receipts and bytes sent with each latency.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    pub buckets: BTreeMap<usize, LatencyBucket>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LatencyBucket {
+    pub receipts: usize,
+    pub bytes: usize,
+}
+
+impl LatencyHistogram {
+    pub fn add(&mut self, latency: usize, receipt_size: usize) {
+        let bucket = self.buckets.entry(latency).or_default();
+        bucket.receipts += 1;
+        bucket.bytes += receipt_size;
+    }
+
+    pub fn merge(&mut self, other: &LatencyHistogram) {
+        for (latency, other_bucket) in &other.buckets {
+            let bucket = self.buckets.entry(*latency).or_default();
+            bucket.receipts += other_bucket.receipts;
+            bucket.bytes += other_bucket.bytes;
+        }
+    }
+
+    pub fn total_receipts(&self) -> usize {
+        self.buckets.values().map(|bucket| bucket.receipts).sum()
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        self.buckets.values().map(|bucket| bucket.bytes).sum()
+    }
+
+    /// Smallest latency such that at least `fraction` (e.g. 0.99) of the receipts had this latency or a lower one.
+    /// Returns `None` when the histogram is empty.
+    pub fn percentile(&self, fraction: f64) -> Option<usize> {
+        let total_receipts = self.total_receipts();
+        if total_receipts == 0 {
+            return None;
+        }
+        let needed = ((total_receipts as f64 * fraction).ceil() as usize).max(1);
+        let mut receipts = 0;
+        for (latency, bucket) in &self.buckets {
+            receipts += bucket.receipts;
+            if receipts >= needed {
+                return Some(*latency);
+            }
+        }
+        self.buckets.keys().last().copied()
+    }
+
+    /// Average latency of a sent byte. Big receipts matter more than small ones.
+    pub fn mean_latency_by_bytes(&self) -> f64 {
+        let total_bytes = self.total_bytes();
+        if total_bytes == 0 {
+            return 0.0;
+        }
+        let weighted_sum: usize = self
+            .buckets
+            .iter()
+            .map(|(latency, bucket)| latency * bucket.bytes)
+            .sum();
+        weighted_sum as f64 / total_bytes as f64
+    }
+}
@@ -0,0 +1,1198 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use base_bandwidth_controller::{BaseBandwidthController, TuningPoint};
+use faults::{GrantOveruse, ReceiptSizeMutation, RequestFault, SchedulerFault, StaleShardLayout};
+use incoming_backlog::IncomingBacklog;
+use metrics::{HeightMetrics, LatencyHistogram};
+use outgoing_queue::{Backpressure, OutgoingQueue, QueueCap};
+use rand::Rng;
+use receipt_sender::ReceiptSender;
+
+use crate::bandwidth_scheduler::{BandwidthScheduler, Maintenance, SchedulerParams};
+use crate::chain::{Block, Chunk, CongestionInfo, ShardLink, ShardUId, SimulationConfig};
+use crate::rng::{rng_from_seed, DefaultRng, GrantRngSource, RngConsumer};
+use crate::shard_layout::ShardLayout;
+use crate::validation::{
+    find_grant_violations, find_scheduler_divergence, validate_block, validate_block_parallel,
+    validate_blocks, validate_grant_lifetimes, validate_incoming_receipts,
+    validate_receiver_quotas, validate_shard_schedulers, GrantViolation, QueueConservation,
+    SchedulerDivergence, ValidationLevel,
+};
+
+pub mod base_bandwidth_controller;
+pub mod builder;
+pub mod chunk_producers;
+pub mod faults;
+pub mod incoming_backlog;
+pub mod initial_state;
+pub mod metrics;
+pub mod outgoing_queue;
+pub mod queue_snapshot;
+pub mod receipt_sender;
+pub mod workload_trace;
+
+/// Simulates the blockchain.
+/// Generates blocks and chunks, uses bandwidth scheduler to schedule bandwidth, sends receipts between shards.
+#[derive(Clone)]
+pub struct Simulation {
+    pub shard_layout: Arc<ShardLayout>,
+    pub shards: BTreeMap<ShardUId, Shard>,
+    /// Blocks from the genesis, `blocks[i]` is at height `start_height + i`.
+    pub blocks: Vec<Option<Block>>,
+    /// Height of the genesis block, 0 unless the simulation starts from an `InitialState`.
+    pub start_height: usize,
+    /// Bytes which were already in the outgoing queues at the genesis, see `InitialState`.
+    pub initial_backlog: BTreeMap<ShardLink, usize>,
+    pub rng: DefaultRng,
+    /// Probability that the block at a height is missing. A missing block has no chunks, so nothing
+    /// is sent and the scheduler doesn't run at that height. Grants are valid only for the height at
+    /// which they were computed, they aren't carried over the missing heights: the next block computes
+    /// its grants from scratch from the last non-missing block, and the allowances grow only once.
+    /// Checked by `validate_grant_lifetimes`.
+    pub missing_block_probability: f64,
+    pub missing_chunk_generator: MissingChunkGenerator,
+    pub random_seed: u64,
+    pub scheduler_params: SchedulerParams,
+    /// Protocol limits, see `SimulationBuilder::config`.
+    pub config: SimulationConfig,
+    /// Configuration of the builder which created the simulation, see `SimulationBuilder::config_fingerprint`.
+    /// Empty when the simulation wasn't created by a builder.
+    pub config_description: String,
+    /// Human-readable description of the run, see `SimulationBuilder::description`. Empty when not set.
+    pub description: String,
+    /// Measurements from every non-missing block (except genesis).
+    pub metrics: Vec<HeightMetrics>,
+    /// Total time spent running the bandwidth scheduler on all shards.
+    pub scheduler_time: Duration,
+    /// Total time spent in validation checks.
+    pub validation_time: Duration,
+    /// Number of threads used for the validation checks at every height.
+    pub validation_threads: usize,
+    pub validation_level: ValidationLevel,
+    /// Bytes which entered and left the outgoing queues, checked with `ValidationLevel::Paranoid`.
+    pub queue_conservation: QueueConservation,
+    /// Chunks which sent more than they were granted. They were rejected and are missing in the blocks.
+    pub grant_violations: Vec<GrantViolation>,
+    /// The first height at which the scheduler state on some shard was different than on the other shards.
+    pub first_scheduler_divergence: Option<SchedulerDivergence>,
+    /// Bandwidth granted on every link, summed over all heights in `metrics`.
+    /// All shards compute the same grants, they're taken from the first shard.
+    pub total_granted: BTreeMap<ShardLink, usize>,
+    /// Store a snapshot of every shard's allowances at every height in `metrics`.
+    pub record_allowance_history: bool,
+    /// Store the grants computed at every height in `metrics`.
+    pub record_grant_history: bool,
+    /// Heights at which the load changes, `PhaseStats` are calculated separately between them.
+    pub load_phase_starts: BTreeSet<usize>,
+    /// Adjusts the base bandwidth between heights, see `BaseBandwidthController`.
+    pub base_bandwidth_controller: Option<BaseBandwidthController>,
+    /// Base bandwidth and the controller's observations at every non-missing height.
+    /// Empty without a base bandwidth controller.
+    pub base_bandwidth_trajectory: Vec<TuningPoint>,
+    /// Named custom metrics, measured at every non-missing height and stored in `HeightMetrics::custom`.
+    pub metric_observers: Vec<(String, MetricObserver)>,
+    /// Heights at which shards are in maintenance mode, applied to `SchedulerParams::maintenance`
+    /// of all shards before running the scheduler.
+    pub maintenance_windows: BTreeMap<ShardUId, Vec<(Range<usize>, Maintenance)>>,
+}
+
+/// Measures a custom metric at a height, given the final block and the built-in measurements.
+/// Returns `None` when there's nothing to measure at this height.
+pub type MetricObserver = Box<dyn MetricObserverFn>;
+
+/// A function which takes the block heightand shard id and decides whether the chunk should be missing.
+pub type MissingChunkGenerator = Box<dyn MissingChunkFn>;
+
+/// Function behind a `MetricObserver`. It's implemented for all closures which can be cloned,
+/// so that the observer can be cloned with the rest of the simulation, see `Simulation::fork`.
+pub trait MetricObserverFn: FnMut(&Block, &HeightMetrics) -> Option<f64> {
+    fn clone_box(&self) -> MetricObserver;
+}
+
+impl<F: FnMut(&Block, &HeightMetrics) -> Option<f64> + Clone + 'static> MetricObserverFn for F {
+    fn clone_box(&self) -> MetricObserver {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for MetricObserver {
+    fn clone(&self) -> Self {
+        (**self).clone_box()
+    }
+}
+
+/// Function behind a `MissingChunkGenerator`, implemented for all closures which can be cloned.
+pub trait MissingChunkFn: FnMut(usize, ShardUId, &mut DefaultRng) -> bool {
+    fn clone_box(&self) -> MissingChunkGenerator;
+}
+
+impl<F: FnMut(usize, ShardUId, &mut DefaultRng) -> bool + Clone + 'static> MissingChunkFn for F {
+    fn clone_box(&self) -> MissingChunkGenerator {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for MissingChunkGenerator {
+    fn clone(&self) -> Self {
+        (**self).clone_box()
+    }
+}
+
+/// This structs exists to ensure that the simulation actually runs before performing checks.
+/// Validatoin checks take a `SimulationRun` which ensures that the simulation was run
+/// before performing the checks.
+pub struct SimulationRun {
+    pub simulation: Simulation,
+    pub performance: RunPerformance,
+}
+
+/// Wall-clock performance of a simulation run.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct RunPerformance {
+    /// How long it took to run the simulation
+    pub total_time: Duration,
+    /// How many heights were simulated
+    pub heights: usize,
+    /// How much of the total time was spent in the bandwidth scheduler
+    pub scheduler_time: Duration,
+    /// How much of the total time was spent in validation checks
+    pub validation_time: Duration,
+}
+
+impl SimulationRun {
+    /// Allowance of the link in the scheduler of `shard` at every non-missing height.
+    /// Requires a simulation which records allowance history.
+    pub fn allowance_history(&self, shard: ShardUId, shard_link: ShardLink) -> Vec<(usize, usize)> {
+        assert!(
+            self.simulation.record_allowance_history,
+            "Allowance history wasn't recorded!"
+        );
+        self.simulation
+            .metrics
+            .iter()
+            .filter_map(|metrics| {
+                let allowance = metrics.allowances.get(&shard)?.get(&shard_link)?;
+                Some((metrics.height, *allowance))
+            })
+            .collect()
+    }
+
+    /// Traffic graph in the GraphViz DOT format, with an edge for every link that was granted or used
+    /// some bandwidth. Edge labels show the average granted and used bandwidth per height, edge width
+    /// is proportional to the used bandwidth. With `top_k` only the `top_k` most used links are included.
+    /// Render with e.g. `dot -Tsvg traffic.dot -o traffic.svg`.
+    pub fn to_dot(&self, top_k: Option<usize>) -> String {
+        let simulation = &self.simulation;
+        let mut used: BTreeMap<ShardLink, usize> = BTreeMap::new();
+        for block in simulation.blocks.iter().flatten() {
+            for (shard_id, chunk) in block
+                .chunks
+                .iter()
+                .filter_map(|(shard_id, chunk_opt)| Some((shard_id, chunk_opt.as_ref()?)))
+            {
+                let sent_sizes = chunk
+                    .prev_outgoing_receipts_size
+                    .iter()
+                    .chain(&chunk.prev_unstoppable_receipts_size);
+                for (to_shard, size) in sent_sizes {
+                    let shard_link = ShardLink {
+                        from: *shard_id,
+                        to: *to_shard,
+                    };
+                    *used.entry(shard_link).or_default() += size;
+                }
+            }
+        }
+
+        let num_heights = simulation.metrics.len().max(1) as f64;
+        let mut links: Vec<(ShardLink, f64, f64)> = used
+            .keys()
+            .chain(simulation.total_granted.keys())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|shard_link| {
+                let granted = simulation
+                    .total_granted
+                    .get(shard_link)
+                    .copied()
+                    .unwrap_or(0);
+                let link_used = used.get(shard_link).copied().unwrap_or(0);
+                (
+                    *shard_link,
+                    granted as f64 / num_heights,
+                    link_used as f64 / num_heights,
+                )
+            })
+            .filter(|(_link, granted, link_used)| *granted > 0.0 || *link_used > 0.0)
+            .collect();
+        if let Some(top_k) = top_k {
+            // Stable sort, links with the same usage stay in the link order
+            links.sort_by(|a, b| b.2.total_cmp(&a.2));
+            links.truncate(top_k);
+            links.sort_by_key(|(shard_link, _granted, _used)| *shard_link);
+        }
+
+        let megabytes = |bytes: f64| bytes / 1_000_000.0;
+        let mut res = String::from("digraph traffic {\n");
+        if !simulation.description.is_empty() {
+            res.push_str(&format!(
+                "    label={:?};\n    labelloc=t;\n",
+                simulation.description
+            ));
+        }
+        for shard_id in simulation.shards.keys() {
+            res.push_str(&format!("    \"{:?}\";\n", shard_id));
+        }
+        for (shard_link, granted, link_used) in links {
+            res.push_str(&format!(
+                "    \"{:?}\" -> \"{:?}\" [label=\"granted {:.2} MB, used {:.2} MB\", penwidth={:.2}];\n",
+                shard_link.from,
+                shard_link.to,
+                megabytes(granted),
+                megabytes(link_used),
+                1.0 + 9.0 * link_used / simulation.config.max_shard_bandwidth as f64
+            ));
+        }
+        res.push_str("}\n");
+        res
+    }
+
+    /// Time series with one row for every height (including genesis and missing blocks), e.g. for plotting
+    /// utilization and fairness over time. Columns: `height`, `block_missing` and for every shard
+    /// `shard_<id>_chunk_missing`, `shard_<id>_sent` (bytes sent by the shard's chunk),
+    /// `shard_<id>_received` (bytes sent to the shard at this height) and `shard_<id>_queued`
+    /// (bytes in the shard's outgoing queues before sending, empty when the block is missing).
+    pub fn to_timeseries_csv(&self) -> String {
+        let simulation = &self.simulation;
+        let metrics_by_height: BTreeMap<usize, &HeightMetrics> = simulation
+            .metrics
+            .iter()
+            .map(|metrics| (metrics.height, metrics))
+            .collect();
+
+        let mut res = String::from("height,block_missing");
+        for shard_id in simulation.shards.keys() {
+            let id = shard_id.shard_id;
+            res.push_str(&format!(
+                ",shard_{id}_chunk_missing,shard_{id}_sent,shard_{id}_received,shard_{id}_queued"
+            ));
+        }
+        res.push('\n');
+        for (index, block_opt) in simulation.blocks.iter().enumerate() {
+            let height = simulation.start_height + index;
+            let mut sent: BTreeMap<ShardUId, usize> = BTreeMap::new();
+            let mut received: BTreeMap<ShardUId, usize> = BTreeMap::new();
+            for (shard_id, chunk) in block_opt
+                .iter()
+                .flat_map(|block| &block.chunks)
+                .filter_map(|(shard_id, chunk_opt)| Some((shard_id, chunk_opt.as_ref()?)))
+            {
+                let sent_sizes = chunk
+                    .prev_outgoing_receipts_size
+                    .iter()
+                    .chain(&chunk.prev_unstoppable_receipts_size);
+                for (to_shard, size) in sent_sizes {
+                    *sent.entry(*shard_id).or_default() += size;
+                    *received.entry(*to_shard).or_default() += size;
+                }
+            }
+
+            res.push_str(&format!("{},{}", height, block_opt.is_none() as u8));
+            for shard_id in simulation.shards.keys() {
+                let chunk_missing = !block_opt
+                    .as_ref()
+                    .is_some_and(|block| matches!(block.chunks.get(shard_id), Some(Some(_))));
+                let queued = metrics_by_height
+                    .get(&height)
+                    .map(|metrics| {
+                        metrics
+                            .queued_before_send
+                            .iter()
+                            .filter(|(shard_link, _size)| shard_link.from == *shard_id)
+                            .map(|(_shard_link, size)| size)
+                            .sum::<usize>()
+                            .to_string()
+                    })
+                    .unwrap_or_default();
+                res.push_str(&format!(
+                    ",{},{},{},{}",
+                    chunk_missing as u8,
+                    sent.get(shard_id).unwrap_or(&0),
+                    received.get(shard_id).unwrap_or(&0),
+                    queued
+                ));
+            }
+            res.push('\n');
+        }
+        res
+    }
+
+    pub fn save_timeseries_csv(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_timeseries_csv())
+    }
+}
+
+impl RunPerformance {
+    pub fn heights_per_second(&self) -> f64 {
+        self.heights as f64 / self.total_time.as_secs_f64()
+    }
+
+    pub fn scheduler_time_share(&self) -> f64 {
+        self.scheduler_time.as_secs_f64() / self.total_time.as_secs_f64()
+    }
+
+    pub fn validation_time_share(&self) -> f64 {
+        self.validation_time.as_secs_f64() / self.total_time.as_secs_f64()
+    }
+}
+
+impl Simulation {
+    /// Create a new simulation.
+    /// It's usually more convenient to use `SimulationBuilder`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        shard_layout: ShardLayout,
+        mut receipt_senders: BTreeMap<ShardLink, Box<dyn ReceiptSender>>,
+        mut unstoppable_senders: BTreeMap<ShardLink, Box<dyn ReceiptSender>>,
+        random_seed: u64,
+        missing_block_probability: f64,
+        missing_generator: Option<MissingChunkGenerator>,
+        scheduler_params: SchedulerParams,
+        config: SimulationConfig,
+        incoming_processing_limit: usize,
+    ) -> Simulation {
+        let rng = rng_from_seed(random_seed);
+        let shard_layout = Arc::new(shard_layout);
+        let shard_ids = shard_layout.shard_ids();
+
+        let mut shards = BTreeMap::new();
+        for shard_id in shard_ids {
+            let mut shard_senders = BTreeMap::new();
+            let mut shard_unstoppable_senders = BTreeMap::new();
+            for to_shard in shard_ids {
+                let shard_link = ShardLink {
+                    from: *shard_id,
+                    to: *to_shard,
+                };
+                if let Some(link_sender) = receipt_senders.remove(&shard_link) {
+                    shard_senders.insert(*to_shard, link_sender);
+                }
+                if let Some(link_sender) = unstoppable_senders.remove(&shard_link) {
+                    shard_unstoppable_senders.insert(*to_shard, link_sender);
+                }
+            }
+            shards.insert(
+                *shard_id,
+                Shard::new(
+                    *shard_id,
+                    shard_ids,
+                    shard_senders,
+                    shard_unstoppable_senders,
+                    scheduler_params.clone(),
+                    config,
+                    incoming_processing_limit,
+                ),
+            );
+        }
+
+        let missing_chunk_generator =
+            missing_generator.unwrap_or_else(|| Box::new(|_height, _shard_id, _rng| false));
+
+        let res = Simulation {
+            shards,
+            blocks: vec![Some(Self::make_genesis_block(
+                &shard_layout,
+                &scheduler_params,
+                &config,
+            ))],
+            start_height: 0,
+            initial_backlog: BTreeMap::new(),
+            shard_layout,
+            rng,
+            missing_block_probability,
+            missing_chunk_generator,
+            random_seed,
+            scheduler_params,
+            config,
+            config_description: String::new(),
+            description: String::new(),
+            metrics: Vec::new(),
+            scheduler_time: Duration::ZERO,
+            validation_time: Duration::ZERO,
+            validation_threads: 1,
+            validation_level: ValidationLevel::default(),
+            queue_conservation: QueueConservation::default(),
+            grant_violations: Vec::new(),
+            first_scheduler_divergence: None,
+            total_granted: BTreeMap::new(),
+            record_allowance_history: false,
+            record_grant_history: false,
+            load_phase_starts: BTreeSet::new(),
+            base_bandwidth_controller: None,
+            base_bandwidth_trajectory: Vec::new(),
+            metric_observers: Vec::new(),
+            maintenance_windows: BTreeMap::new(),
+        };
+        // Automatically information about the simulation for every created simulation.
+        // Less repetition in tests.
+        res.print_info();
+        res
+    }
+
+    fn make_genesis_block(
+        shard_layout: &Arc<ShardLayout>,
+        scheduler_params: &SchedulerParams,
+        config: &SimulationConfig,
+    ) -> Block {
+        let mut genesis_block = Block {
+            height: 0,
+            shard_layout: shard_layout.clone(),
+            chunks: BTreeMap::new(),
+        };
+        for shard_id in shard_layout.shard_ids() {
+            let genesis_chunk = Chunk {
+                prev_incoming_receipts_size: 0,
+                prev_outgoing_receipts_size: BTreeMap::new(),
+                prev_unstoppable_receipts_size: BTreeMap::new(),
+                bandwidth_requests: Vec::new(),
+                congestion_info: CongestionInfo::default(),
+                receiver_quotas: BTreeMap::new(),
+            };
+            genesis_block.chunks.insert(*shard_id, Some(genesis_chunk));
+        }
+        validate_block(&genesis_block, &[], scheduler_params, config);
+        genesis_block
+    }
+
+    /// Height of the next block, one past the last (possibly missing) block.
+    pub fn end_height(&self) -> usize {
+        self.start_height + self.blocks.len()
+    }
+
+    /// Blocks at these heights, `None` for missing blocks.
+    pub fn blocks_at(&self, heights: Range<usize>) -> &[Option<Block>] {
+        &self.blocks[heights.start - self.start_height..heights.end - self.start_height]
+    }
+
+    /// Move the simulation one block forward
+    fn step(&mut self) {
+        self.rng.set_consumer(RngConsumer::MissingBlock);
+        let is_block_missing = self.rng.gen_bool(self.missing_block_probability);
+        if is_block_missing {
+            self.blocks.push(None);
+            return;
+        }
+
+        let mut new_block = Block {
+            height: self.end_height(),
+            shard_layout: self.shard_layout.clone(),
+            chunks: BTreeMap::new(),
+        };
+        let mut height_metrics = HeightMetrics::new(new_block.height);
+        height_metrics.scheduler_input_height = last_non_missing_block(&self.blocks).height;
+
+        self.apply_maintenance(new_block.height);
+        // All shards compute their grants first, the receivers need them to check the new chunks.
+        for (shard_uid, shard) in self.shards.iter_mut() {
+            self.scheduler_time += shard.next_height(&self.blocks, new_block.height);
+            if self.record_allowance_history {
+                height_metrics
+                    .allowances
+                    .insert(*shard_uid, shard.bandwidth_scheduler.allowances().clone());
+            }
+            shard.record_queue_metrics(new_block.height, &mut height_metrics);
+        }
+
+        let shard_uids: Vec<ShardUId> = self.shards.keys().copied().collect();
+        for shard_uid in shard_uids {
+            self.rng.set_consumer(RngConsumer::MissingChunk(shard_uid));
+            let is_chunk_missing =
+                (self.missing_chunk_generator)(new_block.height, shard_uid, &mut self.rng);
+            if is_chunk_missing {
+                new_block.chunks.insert(shard_uid, None);
+                continue;
+            }
+
+            // Receiving shards check that nobody sent them more than the grant they computed for the link.
+            // Such a chunk is invalid and gets rejected, just like it would be rejected in the real protocol.
+            // The shard doesn't apply a rejected chunk, its state stays the same as with a missing chunk.
+            let send_plan = self.shards[&shard_uid].plan_sending(new_block.height);
+            let grants_by_receiver = self
+                .shards
+                .iter()
+                .map(|(shard_id, shard)| (*shard_id, &shard.latest_grants))
+                .collect();
+            let grant_violations = find_grant_violations(
+                new_block.height,
+                shard_uid,
+                &send_plan.outgoing_receipt_sizes(),
+                &grants_by_receiver,
+            );
+            if !grant_violations.is_empty() {
+                self.grant_violations.extend(grant_violations);
+                new_block.chunks.insert(shard_uid, None);
+                continue;
+            }
+
+            let new_chunk = self
+                .shards
+                .get_mut(&shard_uid)
+                .unwrap()
+                .apply_and_produce_chunk(
+                    &self.blocks,
+                    new_block.height,
+                    send_plan,
+                    &mut self.rng,
+                    &mut height_metrics,
+                );
+            new_block.chunks.insert(shard_uid, Some(new_chunk));
+        }
+
+        if let Some(shard) = self.shards.values().next() {
+            height_metrics.allowance_saturation = shard.bandwidth_scheduler.allowance_saturation();
+            height_metrics.allowance_concentration =
+                shard.bandwidth_scheduler.allowance_concentration();
+            height_metrics.scheduler_state_size = shard.bandwidth_scheduler.persistent_state_size();
+            height_metrics.denials = shard.bandwidth_scheduler.denials().to_vec();
+            height_metrics.over_budget = shard.bandwidth_scheduler.over_budget();
+            for (shard_link, grant) in &shard.latest_grants {
+                *self.total_granted.entry(*shard_link).or_default() += grant;
+            }
+            if self.record_grant_history {
+                height_metrics.grants = shard.latest_grants.clone();
+            }
+        }
+
+        let validation_start = Instant::now();
+        if self.validation_level >= ValidationLevel::Basic {
+            // With basic validation only the first shard's scheduler is validated,
+            // the others are assumed to have the same state.
+            let validated_shards = if self.validation_level >= ValidationLevel::Full {
+                self.shards.len()
+            } else {
+                1
+            };
+            let schedulers: Vec<_> = self
+                .shards
+                .values()
+                .take(validated_shards)
+                .map(|shard| (&shard.bandwidth_scheduler, &shard.latest_grants))
+                .collect();
+            validate_shard_schedulers(
+                &schedulers,
+                &last_non_missing_block(&self.blocks).shard_layout,
+                self.validation_threads,
+            );
+            validate_receiver_quotas(schedulers[0].1, last_non_missing_block(&self.blocks));
+        }
+
+        // All shards must have the same scheduler state, report the first place where it's not true.
+        if self.validation_level >= ValidationLevel::Full
+            && self.first_scheduler_divergence.is_none()
+        {
+            self.first_scheduler_divergence =
+                find_scheduler_divergence(new_block.height, &self.shards);
+        }
+
+        if self.validation_level >= ValidationLevel::Paranoid {
+            self.queue_conservation.record(&height_metrics);
+            let queued = self
+                .shards
+                .values()
+                .flat_map(|shard| {
+                    shard.outgoing_queues.iter().map(|(to_shard, queue)| {
+                        let link = ShardLink {
+                            from: shard.id,
+                            to: *to_shard,
+                        };
+                        (link, queue.total_size())
+                    })
+                })
+                .collect();
+            self.queue_conservation.validate(new_block.height, &queued);
+        }
+        self.validation_time += validation_start.elapsed();
+
+        let validation_start = Instant::now();
+        if self.validation_level >= ValidationLevel::Basic {
+            validate_block_parallel(
+                &new_block,
+                &self.blocks,
+                &self.scheduler_params,
+                &self.config,
+                self.validation_threads,
+            );
+        }
+        if self.validation_level >= ValidationLevel::Paranoid {
+            validate_incoming_receipts(&new_block, &self.blocks);
+        }
+        self.validation_time += validation_start.elapsed();
+
+        if let Some(controller) = self.base_bandwidth_controller {
+            self.tune_base_bandwidth(&controller, &height_metrics);
+        }
+
+        for (name, observer) in &mut self.metric_observers {
+            if let Some(value) = observer(&new_block, &height_metrics) {
+                height_metrics.custom.insert(name.clone(), value);
+            }
+        }
+
+        self.blocks.push(Some(new_block));
+        self.metrics.push(height_metrics);
+    }
+
+    /// Put the shards which have a maintenance window at this height into maintenance mode on all schedulers.
+    fn apply_maintenance(&mut self, height: usize) {
+        if self.maintenance_windows.is_empty() {
+            return;
+        }
+        let maintenance: BTreeMap<ShardUId, Maintenance> = self
+            .maintenance_windows
+            .iter()
+            .filter_map(|(shard_id, windows)| {
+                let (_heights, maintenance) = windows
+                    .iter()
+                    .find(|(heights, _maintenance)| heights.contains(&height))?;
+                Some((*shard_id, *maintenance))
+            })
+            .collect();
+        for shard in self.shards.values_mut() {
+            if shard.bandwidth_scheduler.params().maintenance != maintenance {
+                let params = SchedulerParams {
+                    maintenance: maintenance.clone(),
+                    ..shard.bandwidth_scheduler.params().clone()
+                };
+                shard.bandwidth_scheduler.set_params(params);
+            }
+        }
+    }
+
+    /// Let the controller observe the height and set the base bandwidth for the next one on all shards.
+    fn tune_base_bandwidth(
+        &mut self,
+        controller: &BaseBandwidthController,
+        height_metrics: &HeightMetrics,
+    ) {
+        let Some(first_shard) = self.shards.values().next() else {
+            return;
+        };
+        let sent = height_metrics
+            .sent_latencies
+            .iter()
+            .map(|(shard_link, latencies)| (*shard_link, latencies.total_bytes()))
+            .collect();
+        let scheduler = &first_shard.bandwidth_scheduler;
+        let (point, next_base_bandwidth) = controller.observe(
+            height_metrics.height,
+            scheduler.params().max_base_bandwidth,
+            scheduler.get_base_bandwidth(self.shards.len()),
+            first_shard.latest_grants.keys().copied(),
+            &sent,
+            &height_metrics.queued_before_send,
+        );
+        self.base_bandwidth_trajectory.push(point);
+        for shard in self.shards.values_mut() {
+            let params = SchedulerParams {
+                max_base_bandwidth: next_base_bandwidth,
+                ..shard.bandwidth_scheduler.params().clone()
+            };
+            shard.bandwidth_scheduler.set_params(params);
+        }
+    }
+
+    /// Run the simulation for this many blocks.
+    pub fn run_for(mut self, steps: usize) -> SimulationRun {
+        let start_time = Instant::now();
+        let scheduler_time_before = self.scheduler_time;
+        let validation_time_before = self.validation_time;
+        for _ in 0..steps {
+            self.step();
+        }
+        if self.validation_level >= ValidationLevel::Basic {
+            let validation_start = Instant::now();
+            validate_blocks(&self);
+            validate_grant_lifetimes(&self);
+            self.validation_time += validation_start.elapsed();
+        }
+        let performance = RunPerformance {
+            total_time: start_time.elapsed(),
+            heights: steps,
+            scheduler_time: self.scheduler_time - scheduler_time_before,
+            validation_time: self.validation_time - validation_time_before,
+        };
+        SimulationRun {
+            simulation: self,
+            performance,
+        }
+    }
+
+    /// Deep copy of the whole simulation state, including the rng, the receipt senders and the queues.
+    /// After a common warm-up the copies can be continued in different ways (e.g. with other senders
+    /// or faults) and compared from an identical starting point. A copy continued without changes
+    /// produces exactly the same blocks as the original.
+    pub fn fork(&self) -> Simulation {
+        self.clone()
+    }
+
+    /// Replace the receipt sender on a link, e.g. in a forked simulation.
+    pub fn replace_receipt_sender(
+        &mut self,
+        from_shard: usize,
+        to_shard: usize,
+        sender: impl ReceiptSender + 'static,
+    ) {
+        let shard = self
+            .shards
+            .get_mut(&ShardUId::new(from_shard))
+            .unwrap_or_else(|| panic!("No shard {}", from_shard));
+        shard
+            .receipt_senders
+            .insert(ShardUId::new(to_shard), Box::new(sender));
+    }
+
+    pub fn print_info(&self) {
+        println!("Simulation");
+        println!("shards num: {}", self.shards.len());
+        println!("Receipt Senders:");
+        for (sid, shard) in &self.shards {
+            for (to_shard, sender) in &shard.receipt_senders {
+                let shard_link = ShardLink {
+                    from: *sid,
+                    to: *to_shard,
+                };
+                println!("{:?}: {:?}", shard_link, sender);
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Shard {
+    pub id: ShardUId,
+    pub bandwidth_scheduler: BandwidthScheduler,
+    pub latest_grants: BTreeMap<ShardLink, usize>,
+    /// Grants from the last `grant_history_len` heights at which the scheduler ran, oldest first.
+    pub recent_grants: VecDeque<(usize, BTreeMap<ShardLink, usize>)>,
+    /// How many heights are kept in `recent_grants`, 0 disables the history.
+    pub grant_history_len: usize,
+    pub outgoing_queues: BTreeMap<ShardUId, OutgoingQueue>,
+    pub receipt_senders: BTreeMap<ShardUId, Box<dyn ReceiptSender>>,
+    /// Receipt senders (by the receiving shard) which derive their rng stream from this seed
+    /// instead of the simulation seed.
+    pub sender_seeds: BTreeMap<ShardUId, u64>,
+    /// Queues of unstoppable receipts, which are sent right away, regardless of the grants.
+    pub unstoppable_queues: BTreeMap<ShardUId, OutgoingQueue>,
+    pub unstoppable_senders: BTreeMap<ShardUId, Box<dyn ReceiptSender>>,
+    pub incoming_backlog: IncomingBacklog,
+    /// When set, the shard sends malicious bandwidth requests instead of the real ones.
+    pub request_fault: Option<RequestFault>,
+    /// When set, the shard sends more than it was granted.
+    pub grant_overuse: Option<GrantOveruse>,
+    /// When set, the shard corrupts its scheduler state.
+    pub scheduler_fault: Option<SchedulerFault>,
+    /// When set, the shard addresses its bandwidth requests to shards from an old layout.
+    pub stale_shard_layout: Option<StaleShardLayout>,
+    /// When set, the receipts received by the shard are bigger than what their senders sent.
+    pub receipt_size_mutation: Option<ReceiptSizeMutation>,
+    /// When set, receipt senders are throttled when their outgoing queue is too long.
+    pub backpressure: Option<Backpressure>,
+    /// When set, receipts are dropped from outgoing queues which are over the cap.
+    pub queue_cap: Option<QueueCap>,
+    /// Where the shard's bandwidth scheduler gets its rng from, must be the same on all shards.
+    pub grant_rng_source: GrantRngSource,
+    /// Store the generated receipts in `HeightMetrics::arrivals`, see `WorkloadTrace`.
+    pub record_workload: bool,
+}
+
+fn last_non_missing_block(past_blocks: &[Option<Block>]) -> &Block {
+    for block_opt in past_blocks.iter().rev() {
+        if let Some(block) = block_opt {
+            return block;
+        }
+    }
+    panic!("All blocks are missing!");
+}
+
+impl Shard {
+    fn new(
+        id: ShardUId,
+        shard_ids: &[ShardUId],
+        mut receipt_senders_in: BTreeMap<ShardUId, Box<dyn ReceiptSender>>,
+        unstoppable_senders: BTreeMap<ShardUId, Box<dyn ReceiptSender>>,
+        scheduler_params: SchedulerParams,
+        config: SimulationConfig,
+        incoming_processing_limit: usize,
+    ) -> Shard {
+        let mut outgoing_queues = BTreeMap::new();
+        let mut unstoppable_queues = BTreeMap::new();
+        let mut receipt_senders = BTreeMap::new();
+        for shard_id in shard_ids {
+            let mut outgoing_queue = OutgoingQueue::new(*shard_id);
+            outgoing_queue.set_config(config);
+            outgoing_queues.insert(*shard_id, outgoing_queue.clone());
+            unstoppable_queues.insert(*shard_id, outgoing_queue);
+
+            if let Some(sender) = receipt_senders_in.remove(shard_id) {
+                receipt_senders.insert(*shard_id, sender);
+            }
+        }
+        assert!(receipt_senders_in.is_empty());
+
+        Shard {
+            id,
+            bandwidth_scheduler: BandwidthScheduler::new(scheduler_params, config),
+            latest_grants: BTreeMap::new(),
+            recent_grants: VecDeque::new(),
+            grant_history_len: 0,
+            outgoing_queues,
+            receipt_senders,
+            unstoppable_queues,
+            unstoppable_senders,
+            incoming_backlog: IncomingBacklog::new(incoming_processing_limit),
+            request_fault: None,
+            grant_overuse: None,
+            scheduler_fault: None,
+            stale_shard_layout: None,
+            receipt_size_mutation: None,
+            backpressure: None,
+            queue_cap: None,
+            grant_rng_source: GrantRngSource::default(),
+            record_workload: false,
+            sender_seeds: BTreeMap::new(),
+        }
+    }
+
+    /// Update the local state on the next height.
+    /// This happens on every height with a non-missing block even when the chunk on this shard is missing.
+    /// BandwidthScheduler has to be run on every height to keep its state on all shards in sync.
+    /// Returns how long it took to run the scheduler.
+    fn next_height(&mut self, past_blocks: &[Option<Block>], height: usize) -> Duration {
+        let last_block = last_non_missing_block(past_blocks);
+        let mut rng = self.grant_rng_source.rng_for_block(last_block);
+        let start_time = Instant::now();
+        self.latest_grants = self.bandwidth_scheduler.run(last_block, &mut rng);
+        let scheduler_time = start_time.elapsed();
+        if self.grant_history_len > 0 {
+            if self.recent_grants.len() == self.grant_history_len {
+                self.recent_grants.pop_front();
+            }
+            self.recent_grants
+                .push_back((height, self.latest_grants.clone()));
+        }
+        if let Some(fault) = self.scheduler_fault.filter(|fault| fault.height == height) {
+            self.bandwidth_scheduler
+                .set_allowance(fault.link, fault.allowance);
+        }
+        scheduler_time
+    }
+
+    /// Grants of the link at the heights kept in `recent_grants`, oldest first.
+    /// Heights at which the link wasn't granted anything have a grant of 0.
+    pub fn grant_history(&self, shard_link: ShardLink) -> Vec<(usize, usize)> {
+        self.recent_grants
+            .iter()
+            .map(|(height, grants)| (*height, grants.get(&shard_link).copied().unwrap_or(0)))
+            .collect()
+    }
+
+    /// Record the state of the outgoing queues at the beginning of the height, before sending any receipts.
+    fn record_queue_metrics(&self, height: usize, metrics: &mut HeightMetrics) {
+        for (to_shard, outgoing_queue) in &self.outgoing_queues {
+            let shard_link = ShardLink {
+                from: self.id,
+                to: *to_shard,
+            };
+            metrics
+                .queued_before_send
+                .insert(shard_link, outgoing_queue.total_size());
+            if let Some(oldest_height) = outgoing_queue.oldest_receipt_height() {
+                metrics
+                    .oldest_receipt_age
+                    .insert(shard_link, height - oldest_height);
+            }
+        }
+    }
+
+    /// Decide which receipts the new chunk will send, without changing any state.
+    /// The receivers check the plan against their grants before the chunk is applied.
+    fn plan_sending(&self, height: usize) -> SendPlan {
+        // Send all unstoppable receipts, they use up the granted bandwidth first.
+        let mut remaining_grants = self.latest_grants.clone();
+        let mut unstoppable_receipt_sizes: BTreeMap<ShardUId, usize> = BTreeMap::new();
+        for (to_shard, unstoppable_queue) in &self.unstoppable_queues {
+            let shard_link = ShardLink {
+                from: self.id,
+                to: *to_shard,
+            };
+            let unstoppable_size = unstoppable_queue.total_size();
+            if unstoppable_size == 0 {
+                continue;
+            }
+            if let Some(link_grant) = remaining_grants.get_mut(&shard_link) {
+                *link_grant = link_grant.saturating_sub(unstoppable_size);
+            }
+            unstoppable_receipt_sizes.insert(*to_shard, unstoppable_size);
+        }
+
+        // Space left in the chunk after the unstoppable receipts, they're sent even when they don't fit.
+        let mut chunk_space = match &self.bandwidth_scheduler.params().chunk_size_limit {
+            Some(chunk_size_limit) => chunk_size_limit
+                .receipts_space()
+                .saturating_sub(unstoppable_receipt_sizes.values().sum()),
+            None => usize::MAX,
+        };
+
+        // Send outgoing receipts using the granted bandwidth
+        // The links which send first can fill up the chunk, start from a different link at every height.
+        let mut outgoing_queues: Vec<_> = self.outgoing_queues.iter().collect();
+        let num_queues = outgoing_queues.len();
+        outgoing_queues.rotate_left(height % num_queues.max(1));
+        let mut plan = SendPlan {
+            unstoppable_receipt_sizes,
+            ..SendPlan::default()
+        };
+        for (to_shard, outgoing_queue) in outgoing_queues {
+            let shard_link = ShardLink {
+                from: self.id,
+                to: *to_shard,
+            };
+            let mut link_grant = remaining_grants.get(&shard_link).copied().unwrap_or(0);
+            if let Some(overuse) = &self.grant_overuse {
+                link_grant += overuse.extra_bytes;
+            }
+            let (mut num_receipts, mut link_outgoing_receipts_size) = (0, 0);
+            for receipt_size in outgoing_queue.iter_sizes() {
+                if receipt_size > link_grant {
+                    plan.grant_too_small = true;
+                    break;
+                }
+                if receipt_size > chunk_space {
+                    plan.chunk_full = true;
+                    break;
+                }
+                num_receipts += 1;
+                link_outgoing_receipts_size += receipt_size;
+                link_grant -= receipt_size;
+                chunk_space -= receipt_size;
+            }
+            plan.outgoing
+                .insert(*to_shard, (num_receipts, link_outgoing_receipts_size));
+        }
+        plan
+    }
+
+    /// Applies the last chunk on this shard and produces a new one which sends the receipts from `send_plan`.
+    /// Measurements about the outgoing queues are recorded in `metrics`.
+    fn apply_and_produce_chunk(
+        &mut self,
+        past_blocks: &[Option<Block>],
+        height: usize,
+        send_plan: SendPlan,
+        rng: &mut DefaultRng,
+        metrics: &mut HeightMetrics,
+    ) -> Chunk {
+        // Gather incoming receipts from previous heights
+        let mut incoming_receipts_size = 0;
+        for block_opt in past_blocks.iter().rev() {
+            let Some(block) = block_opt else {
+                continue;
+            };
+
+            let mut this_shard_non_missing = false;
+            for (shard_uid, chunk_opt) in &block.chunks {
+                if *shard_uid == self.id && chunk_opt.is_some() {
+                    this_shard_non_missing = true;
+                }
+
+                let Some(chunk) = chunk_opt else {
+                    continue;
+                };
+                let cur_incoming_receipts_size = chunk
+                    .prev_outgoing_receipts_size
+                    .get(&self.id)
+                    .unwrap_or(&0);
+                let cur_incoming_unstoppable_size = chunk
+                    .prev_unstoppable_receipts_size
+                    .get(&self.id)
+                    .unwrap_or(&0);
+                incoming_receipts_size +=
+                    cur_incoming_receipts_size + cur_incoming_unstoppable_size;
+            }
+            if this_shard_non_missing {
+                break;
+            }
+        }
+        if let Some(mutation) = &self.receipt_size_mutation {
+            incoming_receipts_size = mutation.received_size(incoming_receipts_size);
+        }
+
+        let congestion_info = self
+            .incoming_backlog
+            .receive_and_process(height, incoming_receipts_size);
+
+        // Send all unstoppable receipts
+        for unstoppable_queue in self.unstoppable_queues.values_mut() {
+            while unstoppable_queue.pop().is_some() {}
+        }
+
+        // Send the planned outgoing receipts
+        for (to_shard, (num_receipts, _size)) in &send_plan.outgoing {
+            let outgoing_queue = self.outgoing_queues.get_mut(to_shard).unwrap();
+            let shard_link = ShardLink {
+                from: self.id,
+                to: *to_shard,
+            };
+            for _ in 0..*num_receipts {
+                let enqueued_height = outgoing_queue.first_receipt_height().unwrap();
+                let receipt = outgoing_queue.pop().unwrap();
+                metrics
+                    .sent_latencies
+                    .entry(shard_link)
+                    .or_default()
+                    .add(height - enqueued_height, receipt.size);
+                metrics
+                    .sent_latencies_by_priority
+                    .entry(receipt.priority)
+                    .or_default()
+                    .add(height - enqueued_height, receipt.size);
+                if let Some(tag) = receipt.tag {
+                    metrics
+                        .sent_latencies_by_tag
+                        .entry(tag)
+                        .or_default()
+                        .add(height - enqueued_height, receipt.size);
+                }
+            }
+        }
+        // When the chunk is full, it doesn't matter that some links also ran out of their grants.
+        if send_plan.chunk_full {
+            metrics.chunk_size_bound.insert(self.id);
+        } else if send_plan.grant_too_small {
+            metrics.bandwidth_bound.insert(self.id);
+        }
+
+        // Generate new receipts
+        for (to_shard, receipt_sender) in self.receipt_senders.iter_mut() {
+            let outgoing_queue = self.outgoing_queues.get_mut(to_shard).unwrap();
+            outgoing_queue.set_current_height(height);
+
+            let shard_link = ShardLink {
+                from: self.id,
+                to: *to_shard,
+            };
+            let pushed_before = outgoing_queue.total_pushed();
+            let throttled = self
+                .backpressure
+                .is_some_and(|b| outgoing_queue.total_size() > b.queue_threshold);
+            let consumer = RngConsumer::ReceiptSender(shard_link);
+            match self.sender_seeds.get(to_shard) {
+                Some(seed) => rng.set_stream_with_seed(consumer, height, *seed),
+                None => rng.set_stream(consumer, height),
+            }
+            receipt_sender.send_receipts(outgoing_queue, rng);
+            metrics
+                .offered
+                .insert(shard_link, outgoing_queue.total_pushed() - pushed_before);
+            if self.record_workload {
+                let arrivals = outgoing_queue.pushed_after(pushed_before);
+                if !arrivals.is_empty() {
+                    metrics.arrivals.insert(shard_link, arrivals);
+                }
+            }
+            let mut shed = 0;
+            if throttled {
+                shed += outgoing_queue.remove_pushed_after(pushed_before);
+            }
+            if let Some(queue_cap) = &self.queue_cap {
+                let mut dropped = LatencyHistogram::default();
+                outgoing_queue.enforce_cap(queue_cap, |receipt, enqueued_height| {
+                    dropped.add(height - enqueued_height, receipt.size)
+                });
+                if dropped.total_receipts() > 0 {
+                    shed += dropped.total_bytes();
+                    metrics.dropped.insert(shard_link, dropped);
+                }
+            }
+            if shed > 0 {
+                metrics.shed.insert(shard_link, shed);
+            }
+        }
+
+        // Generate unstoppable receipts, they'll be sent at the next height
+        for (to_shard, unstoppable_sender) in self.unstoppable_senders.iter_mut() {
+            let unstoppable_queue = self.unstoppable_queues.get_mut(to_shard).unwrap();
+            let shard_link = ShardLink {
+                from: self.id,
+                to: *to_shard,
+            };
+            rng.set_stream(RngConsumer::UnstoppableSender(shard_link), height);
+            unstoppable_sender.send_receipts(unstoppable_queue, rng);
+        }
+
+        // Generate bandwidth requests
+        let last_block = last_non_missing_block(past_blocks);
+        let num_shards = last_block.shard_layout.num_shards();
+        let base_bandwidth = self.bandwidth_scheduler.get_base_bandwidth(num_shards);
+        let config = self.bandwidth_scheduler.config();
+        let mut bandwidth_requests = Vec::new();
+        for outgoing_queue in self.outgoing_queues.values() {
+            if let Some(bandwidth_request) =
+                outgoing_queue.make_bandwidth_request(base_bandwidth, config)
+            {
+                bandwidth_requests.push(bandwidth_request);
+            }
+        }
+        if let Some(request_fault) = &self.request_fault {
+            rng.set_consumer(RngConsumer::RequestFault(self.id));
+            bandwidth_requests =
+                request_fault.make_bandwidth_requests(last_block.shard_layout.shard_ids(), rng);
+        }
+        if let Some(stale_shard_layout) = &self.stale_shard_layout {
+            stale_shard_layout.readdress_requests(&mut bandwidth_requests);
+        }
+
+        // Advertise how much this shard is willing to receive from every sender
+        let receiver_quotas = match &self.bandwidth_scheduler.params().receiver_quotas {
+            Some(receiver_quotas) => {
+                receiver_quotas.quotas(&congestion_info, last_block.shard_layout.shard_ids())
+            }
+            None => BTreeMap::new(),
+        };
+
+        Chunk {
+            prev_incoming_receipts_size: incoming_receipts_size,
+            prev_outgoing_receipts_size: send_plan.outgoing_receipt_sizes(),
+            prev_unstoppable_receipts_size: send_plan.unstoppable_receipt_sizes,
+            bandwidth_requests,
+            congestion_info,
+            receiver_quotas,
+        }
+    }
+}
+
+/// Receipts that a shard sends in its new chunk, see `Shard::plan_sending`.
+#[derive(Clone, Debug, Default)]
+struct SendPlan {
+    /// Total size of the unstoppable receipts sent to every shard, their queues are always sent whole.
+    unstoppable_receipt_sizes: BTreeMap<ShardUId, usize>,
+    /// (number of receipts, their total size) taken from the outgoing queue to every shard.
+    outgoing: BTreeMap<ShardUId, (usize, usize)>,
+    /// Some receipt didn't fit into the chunk.
+    chunk_full: bool,
+    /// Some receipt didn't fit into the grant of its link.
+    grant_too_small: bool,
+}
+
+impl SendPlan {
+    fn outgoing_receipt_sizes(&self) -> BTreeMap<ShardUId, usize> {
+        self.outgoing
+            .iter()
+            .map(|(to_shard, (_num_receipts, size))| (*to_shard, *size))
+            .collect()
+    }
+}
@@ -0,0 +1,780 @@
+use std::collections::VecDeque;
+
+use crate::bandwidth_request::BandwidthRequest;
+use crate::chain::{Receipt, ReceiptPriority, ShardUId, SimulationConfig};
+
+/// Queue of receipts waiting to be sent to one shard.
+/// Receipts of every priority class wait in a separate sub-queue, the drain policy decides from which
+/// sub-queue the next receipt is taken. With receipts of a single class it's a simple FIFO.
+#[derive(Clone)]
+pub struct OutgoingQueue {
+    to_shard: ShardUId,
+    /// Sub-queue for every priority, indexed like `ReceiptPriority::ALL`.
+    sub_queues: [SubQueue; ReceiptPriority::ALL.len()],
+    drain_policy: DrainPolicy,
+    drain_state: DrainState,
+    request_policy: Box<dyn RequestPolicy>,
+    total_size: usize,
+    /// Total size of all receipts that were ever pushed to this queue.
+    total_pushed: usize,
+    /// Height at which new receipts are pushed to the queue.
+    current_height: usize,
+    /// Limits of the chain, every pushed receipt has to fit the receipt size limits.
+    config: SimulationConfig,
+}
+
+/// Order in which receipts of different priorities are sent.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DrainPolicy {
+    /// Receipts with a higher priority are always sent first.
+    #[default]
+    StrictPriority,
+    /// Deficit round robin between the priorities. Every priority gets a share of the sent bytes
+    /// proportional to its weight (as long as it has something to send), weights are indexed like
+    /// `ReceiptPriority::ALL` and must be positive.
+    Weighted([usize; ReceiptPriority::ALL.len()]),
+}
+
+/// Decides for which receipts in the queue the shard requests bandwidth.
+/// Every outgoing queue gets its own clone of the policy.
+pub trait RequestPolicy: std::fmt::Debug + CloneRequestPolicy {
+    fn make_bandwidth_request(
+        &self,
+        queue: &OutgoingQueue,
+        base_bandwidth: usize,
+        config: &SimulationConfig,
+    ) -> Option<BandwidthRequest>;
+}
+
+/// Implemented for every `RequestPolicy` which is `Clone`, allows to clone boxed policies.
+pub trait CloneRequestPolicy {
+    fn clone_box(&self) -> Box<dyn RequestPolicy>;
+}
+
+impl<T: RequestPolicy + Clone + 'static> CloneRequestPolicy for T {
+    fn clone_box(&self) -> Box<dyn RequestPolicy> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn RequestPolicy> {
+    fn clone(&self) -> Self {
+        (**self).clone_box()
+    }
+}
+
+/// Request enough bandwidth to send the whole queue (up to the max shard bandwidth).
+/// This is the default request policy.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WholeQueue;
+
+impl RequestPolicy for WholeQueue {
+    fn make_bandwidth_request(
+        &self,
+        queue: &OutgoingQueue,
+        base_bandwidth: usize,
+        config: &SimulationConfig,
+    ) -> Option<BandwidthRequest> {
+        queue.make_whole_queue_request(base_bandwidth, config)
+    }
+}
+
+/// Request only enough bandwidth to send the next receipt.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HeadReceipt;
+
+impl RequestPolicy for HeadReceipt {
+    fn make_bandwidth_request(
+        &self,
+        queue: &OutgoingQueue,
+        base_bandwidth: usize,
+        config: &SimulationConfig,
+    ) -> Option<BandwidthRequest> {
+        BandwidthRequest::from_receipt_sizes(
+            queue.to_shard,
+            queue.iter_sizes().take(1),
+            base_bandwidth,
+            config,
+        )
+    }
+}
+
+/// Request bandwidth for receipts which fit in `(1 - headroom) * max_shard_bandwidth` bytes,
+/// but always at least for the next one. `headroom` must be in [0, 1).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Headroom(pub f64);
+
+impl RequestPolicy for Headroom {
+    fn make_bandwidth_request(
+        &self,
+        queue: &OutgoingQueue,
+        base_bandwidth: usize,
+        config: &SimulationConfig,
+    ) -> Option<BandwidthRequest> {
+        let Headroom(headroom) = *self;
+        assert!(
+            (0.0..1.0).contains(&headroom),
+            "Request headroom must be in [0, 1)"
+        );
+        let limit = ((1.0 - headroom) * config.max_shard_bandwidth as f64) as usize;
+        let mut total_size = 0;
+        let receipt_sizes = queue.iter_sizes().take_while(|size| {
+            let is_first = total_size == 0;
+            total_size += size;
+            is_first || total_size <= limit
+        });
+        BandwidthRequest::from_receipt_sizes(queue.to_shard, receipt_sizes, base_bandwidth, config)
+    }
+}
+
+/// How many bytes a priority with weight 1 can send in one round of weighted draining.
+const WEIGHTED_DRAIN_QUANTUM: usize = 100_000;
+
+/// Position of the deficit round robin used by `DrainPolicy::Weighted`.
+#[derive(Clone, Copy, Debug, Default)]
+struct DrainState {
+    /// Index of the sub-queue whose turn it is
+    current: usize,
+    /// Whether the current sub-queue already got its quantum in this turn
+    turn_started: bool,
+    /// How many bytes every sub-queue can still send
+    deficits: [usize; ReceiptPriority::ALL.len()],
+}
+
+/// Receipts of one priority, in the order in which they were pushed.
+#[derive(Clone, Default)]
+struct SubQueue {
+    receipts: VecDeque<QueuedReceipt>,
+    total_size: usize,
+    /// Total size of all receipts that were ever pushed to this sub-queue.
+    total_pushed: usize,
+}
+
+/// Throttles the receipt sender of a link when its outgoing queue is too long, like a node which
+/// stops admitting new transactions when its outgoing buffers are full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Backpressure {
+    /// When the queue holds more than this many bytes before generating new receipts, all receipts
+    /// generated at this height are shed.
+    pub queue_threshold: usize,
+}
+
+/// Limit on the size of an outgoing queue. When new receipts push the queue over the limit,
+/// receipts are dropped according to the policy until the queue fits again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QueueCap {
+    pub max_bytes: usize,
+    pub policy: DropPolicy,
+}
+
+/// Which receipts are dropped when the outgoing queue is over its cap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DropPolicy {
+    /// New receipts which don't fit in the queue are rejected.
+    RejectNew,
+    /// The oldest receipts are dropped to make space for the new ones.
+    DropOldest,
+    /// The largest receipts are dropped, which frees the space by dropping as few receipts as possible.
+    /// Among receipts of the same size the newest one is dropped.
+    DropLargest,
+}
+
+/// A receipt waiting in the queue
+#[derive(Clone)]
+struct QueuedReceipt {
+    receipt: Receipt,
+    /// Height at which the receipt was added to the queue
+    enqueued_height: usize,
+    /// Value of `total_pushed` of the sub-queue after pushing this receipt.
+    /// Allows to calculate prefix sums of receipt sizes without walking the whole sub-queue.
+    pushed_until_this: usize,
+    /// Value of `total_pushed` of the whole queue after pushing this receipt.
+    /// Gives the order of receipts across sub-queues.
+    queue_pushed_until_this: usize,
+}
+
+impl SubQueue {
+    fn push(&mut self, receipt: Receipt, enqueued_height: usize, queue_pushed_until_this: usize) {
+        self.total_size += receipt.size;
+        self.total_pushed += receipt.size;
+        self.receipts.push_back(QueuedReceipt {
+            receipt,
+            enqueued_height,
+            pushed_until_this: self.total_pushed,
+            queue_pushed_until_this,
+        });
+    }
+
+    fn pop(&mut self) -> Option<QueuedReceipt> {
+        let res = self.receipts.pop_front();
+        res.as_ref()
+            .inspect(|queued| self.total_size -= queued.receipt.size);
+        res
+    }
+
+    /// Remove the receipt at `index` as if it was never pushed.
+    /// The prefix sums of all later receipts have to be updated.
+    fn remove(&mut self, index: usize) -> QueuedReceipt {
+        let removed = self.receipts.remove(index).unwrap();
+        let size = removed.receipt.size;
+        for later in self.receipts.range_mut(index..) {
+            later.pushed_until_this -= size;
+        }
+        self.total_size -= size;
+        self.total_pushed -= size;
+        removed
+    }
+
+    /// Smallest total size of the first k receipts in the sub-queue that is larger than `size`.
+    fn first_prefix_sum_above(&self, size: usize) -> Option<usize> {
+        let popped = self.total_pushed - self.total_size;
+        let prefix_sum = |r: &QueuedReceipt| r.pushed_until_this - popped;
+        let idx = self.receipts.partition_point(|r| prefix_sum(r) <= size);
+        self.receipts.get(idx).map(prefix_sum)
+    }
+}
+
+impl DrainPolicy {
+    /// Index of the sub-queue from which the next receipt is taken. `head_size` gives the size of
+    /// the next receipt in every sub-queue, `state` is advanced as if the receipt was taken.
+    fn next_sub_queue(
+        &self,
+        state: &mut DrainState,
+        head_size: impl Fn(usize) -> Option<usize>,
+    ) -> Option<usize> {
+        let num_sub_queues = ReceiptPriority::ALL.len();
+        if (0..num_sub_queues).all(|index| head_size(index).is_none()) {
+            return None;
+        }
+        let weights = match self {
+            DrainPolicy::StrictPriority => {
+                return (0..num_sub_queues).find(|index| head_size(*index).is_some())
+            }
+            DrainPolicy::Weighted(weights) => weights,
+        };
+        loop {
+            let current = state.current;
+            match head_size(current) {
+                None => state.deficits[current] = 0,
+                Some(size) => {
+                    if !state.turn_started {
+                        state.deficits[current] += weights[current] * WEIGHTED_DRAIN_QUANTUM;
+                        state.turn_started = true;
+                    }
+                    if state.deficits[current] >= size {
+                        state.deficits[current] -= size;
+                        return Some(current);
+                    }
+                }
+            }
+            state.current = (current + 1) % num_sub_queues;
+            state.turn_started = false;
+        }
+    }
+}
+
+impl OutgoingQueue {
+    pub fn new(to_shard: ShardUId) -> Self {
+        OutgoingQueue {
+            to_shard,
+            sub_queues: Default::default(),
+            drain_policy: DrainPolicy::default(),
+            drain_state: DrainState::default(),
+            request_policy: Box::new(WholeQueue),
+            total_size: 0,
+            total_pushed: 0,
+            current_height: 0,
+            config: SimulationConfig::default(),
+        }
+    }
+
+    /// Limits of the chain to which the queue belongs, the default config until set.
+    pub fn set_config(&mut self, config: SimulationConfig) {
+        self.config = config;
+    }
+
+    pub fn config(&self) -> &SimulationConfig {
+        &self.config
+    }
+
+    /// Shard to which the receipts from this queue are sent.
+    pub fn to_shard(&self) -> ShardUId {
+        self.to_shard
+    }
+
+    pub fn set_drain_policy(&mut self, drain_policy: DrainPolicy) {
+        if let DrainPolicy::Weighted(weights) = &drain_policy {
+            assert!(
+                weights.iter().all(|weight| *weight > 0),
+                "Drain weights must be positive"
+            );
+        }
+        self.drain_policy = drain_policy;
+        self.drain_state = DrainState::default();
+    }
+
+    pub fn set_request_policy(&mut self, request_policy: Box<dyn RequestPolicy>) {
+        self.request_policy = request_policy;
+    }
+
+    pub fn push(&mut self, receipt: Receipt) {
+        // A receipt larger than the max receipt size could never be granted and would stall the link.
+        assert!(
+            (self.config.min_receipt_size..=self.config.max_receipt_size).contains(&receipt.size),
+            "Receipt of size {} doesn't fit the receipt size limits {}..={}",
+            receipt.size,
+            self.config.min_receipt_size,
+            self.config.max_receipt_size
+        );
+        self.total_size += receipt.size;
+        self.total_pushed += receipt.size;
+        let sub_queue = &mut self.sub_queues[receipt.priority as usize];
+        sub_queue.push(receipt, self.current_height, self.total_pushed);
+    }
+
+    /// Sub-queue from which the next receipt will be taken, and the drain state after taking it.
+    fn next_sub_queue(&self) -> Option<(usize, DrainState)> {
+        if self.drain_policy == DrainPolicy::StrictPriority {
+            // Fast path, this is called for every sent receipt.
+            let index = self
+                .sub_queues
+                .iter()
+                .position(|sub_queue| !sub_queue.receipts.is_empty())?;
+            return Some((index, self.drain_state));
+        }
+        let mut state = self.drain_state;
+        let index = self.drain_policy.next_sub_queue(&mut state, |index| {
+            self.sub_queues[index]
+                .receipts
+                .front()
+                .map(|r| r.receipt.size)
+        })?;
+        Some((index, state))
+    }
+
+    fn next_receipt(&self) -> Option<&QueuedReceipt> {
+        let (index, _) = self.next_sub_queue()?;
+        self.sub_queues[index].receipts.front()
+    }
+
+    pub fn pop(&mut self) -> Option<Receipt> {
+        let (index, state) = self.next_sub_queue()?;
+        self.drain_state = state;
+        let queued = self.sub_queues[index].pop().unwrap();
+        self.total_size -= queued.receipt.size;
+        Some(queued.receipt)
+    }
+
+    /// Size of the receipt that will be returned by the next `pop`.
+    pub fn first_receipt_size(&self) -> Option<usize> {
+        self.next_receipt().map(|r| r.receipt.size)
+    }
+
+    /// Height at which the receipt that will be returned by the next `pop` was added to the queue.
+    pub fn first_receipt_height(&self) -> Option<usize> {
+        self.next_receipt().map(|r| r.enqueued_height)
+    }
+
+    /// Set the height at which the receipts pushed from now on are generated.
+    pub fn set_current_height(&mut self, height: usize) {
+        self.current_height = height;
+    }
+
+    /// Height at which receipts pushed to the queue are generated.
+    pub fn current_height(&self) -> usize {
+        self.current_height
+    }
+
+    /// Height at which the oldest receipt in the queue was added to the queue.
+    pub fn oldest_receipt_height(&self) -> Option<usize> {
+        self.sub_queues
+            .iter()
+            .filter_map(|sub_queue| sub_queue.receipts.front())
+            .map(|r| r.enqueued_height)
+            .min()
+    }
+
+    pub fn total_size(&self) -> usize {
+        self.total_size
+    }
+
+    /// Receipts in the queue with the heights at which they were added, in the order in which they were pushed.
+    pub fn receipts(&self) -> Vec<(&Receipt, usize)> {
+        let mut receipts: Vec<&QueuedReceipt> = self
+            .sub_queues
+            .iter()
+            .flat_map(|sub_queue| sub_queue.receipts.iter())
+            .collect();
+        receipts.sort_by_key(|queued| queued.queue_pushed_until_this);
+        receipts
+            .into_iter()
+            .map(|queued| (&queued.receipt, queued.enqueued_height))
+            .collect()
+    }
+
+    pub fn total_pushed(&self) -> usize {
+        self.total_pushed
+    }
+
+    /// Number of receipts in the queue.
+    pub fn total_receipts(&self) -> usize {
+        self.sub_queues
+            .iter()
+            .map(|sub_queue| sub_queue.receipts.len())
+            .sum()
+    }
+
+    /// Sizes of the receipts in the queue, in the order in which they will be popped (assuming
+    /// that nothing new is pushed).
+    pub fn iter_sizes(&self) -> impl Iterator<Item = usize> + '_ {
+        self.send_order().map(|r| r.receipt.size)
+    }
+
+    /// Number of receipts in every size bucket. `buckets` are the inclusive upper bounds of the
+    /// buckets in increasing order, receipts larger than the last bound are counted in an extra
+    /// bucket at the end, so the result has `buckets.len() + 1` entries.
+    pub fn size_histogram(&self, buckets: &[usize]) -> Vec<usize> {
+        assert!(
+            buckets.windows(2).all(|pair| pair[0] < pair[1]),
+            "Histogram buckets must be increasing"
+        );
+        let mut counts = vec![0; buckets.len() + 1];
+        for sub_queue in &self.sub_queues {
+            for queued in &sub_queue.receipts {
+                counts[buckets.partition_point(|bound| *bound < queued.receipt.size)] += 1;
+            }
+        }
+        counts
+    }
+
+    /// Total size of the receipts with this priority.
+    pub fn priority_size(&self, priority: ReceiptPriority) -> usize {
+        self.sub_queues[priority as usize].total_size
+    }
+
+    /// Receipts pushed after `total_pushed` was equal to the given value, in the order in which they were pushed.
+    pub fn pushed_after(&self, total_pushed: usize) -> Vec<Receipt> {
+        let mut pushed: Vec<&QueuedReceipt> = self
+            .sub_queues
+            .iter()
+            .flat_map(|sub_queue| {
+                sub_queue
+                    .receipts
+                    .iter()
+                    .rev()
+                    .take_while(|r| r.queue_pushed_until_this > total_pushed)
+            })
+            .collect();
+        pushed.sort_by_key(|r| r.queue_pushed_until_this);
+        pushed.into_iter().map(|r| r.receipt.clone()).collect()
+    }
+
+    /// Remove all receipts pushed after `total_pushed` was equal to the given value, as if they
+    /// were never pushed. Returns the total size of the removed receipts.
+    pub fn remove_pushed_after(&mut self, total_pushed: usize) -> usize {
+        let mut removed = 0;
+        for sub_queue in &mut self.sub_queues {
+            while sub_queue
+                .receipts
+                .back()
+                .is_some_and(|r| r.queue_pushed_until_this > total_pushed)
+            {
+                let index = sub_queue.receipts.len() - 1;
+                removed += sub_queue.remove(index).receipt.size;
+            }
+        }
+        self.total_size -= removed;
+        self.total_pushed -= removed;
+        removed
+    }
+
+    /// Drop receipts until the queue holds at most `cap.max_bytes` bytes.
+    /// `on_drop` is called with every dropped receipt and the height at which it was added to the queue.
+    pub fn enforce_cap(&mut self, cap: &QueueCap, mut on_drop: impl FnMut(Receipt, usize)) {
+        while self.total_size > cap.max_bytes {
+            let candidates =
+                self.sub_queues
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(sub_queue_index, sub_queue)| {
+                        sub_queue
+                            .receipts
+                            .iter()
+                            .enumerate()
+                            .map(move |(index, r)| (sub_queue_index, index, r))
+                    });
+            let order = |r: &QueuedReceipt| r.queue_pushed_until_this;
+            let (sub_queue_index, index, _) = match cap.policy {
+                DropPolicy::RejectNew => candidates.max_by_key(|(_, _, r)| order(r)),
+                DropPolicy::DropOldest => candidates.min_by_key(|(_, _, r)| order(r)),
+                DropPolicy::DropLargest => {
+                    candidates.max_by_key(|(_, _, r)| (r.receipt.size, order(r)))
+                }
+            }
+            .unwrap();
+            let dropped = self.remove(sub_queue_index, index);
+            on_drop(dropped.receipt, dropped.enqueued_height);
+        }
+    }
+
+    /// Remove a receipt as if it was never pushed.
+    fn remove(&mut self, sub_queue_index: usize, index: usize) -> QueuedReceipt {
+        let removed = self.sub_queues[sub_queue_index].remove(index);
+        let size = removed.receipt.size;
+        for sub_queue in &mut self.sub_queues {
+            for later in sub_queue.receipts.iter_mut() {
+                if later.queue_pushed_until_this > removed.queue_pushed_until_this {
+                    later.queue_pushed_until_this -= size;
+                }
+            }
+        }
+        self.total_size -= size;
+        self.total_pushed -= size;
+        removed
+    }
+
+    /// Bandwidth request for the receipts chosen by the request policy.
+    pub fn make_bandwidth_request(
+        &self,
+        base_bandwidth: usize,
+        config: &SimulationConfig,
+    ) -> Option<BandwidthRequest> {
+        self.request_policy
+            .make_bandwidth_request(self, base_bandwidth, config)
+    }
+
+    /// Bandwidth request for all receipts in the queue, see `WholeQueue`.
+    pub fn make_whole_queue_request(
+        &self,
+        base_bandwidth: usize,
+        config: &SimulationConfig,
+    ) -> Option<BandwidthRequest> {
+        let mut non_empty = self
+            .sub_queues
+            .iter()
+            .filter(|sub_queue| !sub_queue.receipts.is_empty());
+        if let (Some(sub_queue), None) = (non_empty.next(), non_empty.next()) {
+            // All receipts have the same priority, they're sent in the order in which they were pushed.
+            return BandwidthRequest::from_prefix_sums(
+                self.to_shard,
+                |size| sub_queue.first_prefix_sum_above(size),
+                base_bandwidth,
+                config,
+            );
+        }
+        BandwidthRequest::from_receipt_sizes(
+            self.to_shard,
+            self.send_order().map(|r| r.receipt.size),
+            base_bandwidth,
+            config,
+        )
+    }
+
+    /// Receipts in the order in which they will be popped, assuming that nothing new is pushed.
+    fn send_order(&self) -> impl Iterator<Item = &QueuedReceipt> {
+        let mut state = self.drain_state;
+        let mut positions = [0; ReceiptPriority::ALL.len()];
+        std::iter::from_fn(move || {
+            let index = self.drain_policy.next_sub_queue(&mut state, |index| {
+                self.sub_queues[index]
+                    .receipts
+                    .get(positions[index])
+                    .map(|r| r.receipt.size)
+            })?;
+            positions[index] += 1;
+            self.sub_queues[index].receipts.get(positions[index] - 1)
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sub_queues
+            .iter()
+            .all(|sub_queue| sub_queue.receipts.is_empty())
+    }
+}
+
+#[test]
+fn test_make_bandwidth_request_matches_receipt_sizes() {
+    use rand::Rng;
+
+    use crate::chain::{MAX_RECEIPT_SIZE, MIN_RECEIPT_SIZE};
+    use crate::rng::rng_from_seed;
+
+    let config = SimulationConfig::default();
+    let mut rng = rng_from_seed(0);
+    let mut queue = OutgoingQueue::new(ShardUId::new(1));
+    for _ in 0..2000 {
+        if rng.gen_bool(0.6) {
+            let size = if rng.gen_bool(0.1) {
+                rng.gen_range(MIN_RECEIPT_SIZE..=MAX_RECEIPT_SIZE)
+            } else {
+                rng.gen_range(MIN_RECEIPT_SIZE..50_000)
+            };
+            queue.push(Receipt {
+                size,
+                tag: None,
+                priority: ReceiptPriority::default(),
+            });
+        } else if rng.gen_bool(0.9) {
+            queue.pop();
+        } else {
+            // Dropping receipts from the middle of the queue must keep the prefix sums correct.
+            let policy = [
+                DropPolicy::RejectNew,
+                DropPolicy::DropOldest,
+                DropPolicy::DropLargest,
+            ][rng.gen_range(0..3)];
+            let max_bytes = rng.gen_range(0..=queue.total_size());
+            queue.enforce_cap(&QueueCap { max_bytes, policy }, |_, _| {});
+            assert!(queue.total_size() <= max_bytes);
+        }
+
+        for base_bandwidth in [0, 50_000, 100_000] {
+            let expected = BandwidthRequest::from_receipt_sizes(
+                queue.to_shard,
+                queue.send_order().map(|r| r.receipt.size),
+                base_bandwidth,
+                &config,
+            );
+            let actual = queue.make_bandwidth_request(base_bandwidth, &config);
+            assert_eq!(
+                actual.map(|r| r.grant_options_bitmap),
+                expected.map(|r| r.grant_options_bitmap)
+            );
+        }
+    }
+}
+
+#[test]
+#[should_panic(expected = "doesn't fit the receipt size limits")]
+fn test_push_checks_receipt_size_limits() {
+    let mut queue = OutgoingQueue::new(ShardUId::new(1));
+    queue.set_config(SimulationConfig {
+        max_receipt_size: 100_000,
+        ..SimulationConfig::default()
+    });
+    queue.push(Receipt {
+        size: 100_000,
+        tag: None,
+        priority: ReceiptPriority::default(),
+    });
+    queue.push(Receipt {
+        size: 100_001,
+        tag: None,
+        priority: ReceiptPriority::default(),
+    });
+}
+
+#[test]
+fn test_send_order_matches_pop_order() {
+    use rand::Rng;
+
+    use crate::chain::MIN_RECEIPT_SIZE;
+    use crate::rng::rng_from_seed;
+
+    let mut rng = rng_from_seed(0);
+    for drain_policy in [
+        DrainPolicy::StrictPriority,
+        DrainPolicy::Weighted([3, 2, 1]),
+    ] {
+        let mut queue = OutgoingQueue::new(ShardUId::new(1));
+        queue.set_drain_policy(drain_policy);
+        for _ in 0..50 {
+            for _ in 0..rng.gen_range(0..20) {
+                queue.push(Receipt {
+                    size: rng.gen_range(MIN_RECEIPT_SIZE..500_000),
+                    tag: None,
+                    priority: ReceiptPriority::ALL[rng.gen_range(0..3)],
+                });
+            }
+            let expected: Vec<(usize, ReceiptPriority)> = queue
+                .send_order()
+                .map(|r| (r.receipt.size, r.receipt.priority))
+                .collect();
+            let mut popped = Vec::new();
+            while let Some(receipt) = queue.pop() {
+                popped.push((receipt.size, receipt.priority));
+                // Pop only some of the receipts, the order must still match after pushing new ones.
+                if rng.gen_bool(0.05) {
+                    break;
+                }
+            }
+            assert_eq!(popped, expected[..popped.len()]);
+        }
+    }
+}
+
+#[test]
+fn test_request_policies() {
+    let config = SimulationConfig::default();
+    let mut queue = OutgoingQueue::new(ShardUId::new(1));
+    for _ in 0..10 {
+        queue.push(Receipt {
+            size: 1_000_000,
+            tag: None,
+            priority: ReceiptPriority::default(),
+        });
+    }
+    let request_for = |receipts: usize| {
+        BandwidthRequest::from_receipt_sizes(
+            queue.to_shard,
+            std::iter::repeat_n(1_000_000, receipts),
+            0,
+            &config,
+        )
+        .map(|r| r.grant_options_bitmap)
+    };
+    let policies: [(Box<dyn RequestPolicy>, _); 4] = [
+        (Box::new(WholeQueue), request_for(10)),
+        (Box::new(HeadReceipt), request_for(1)),
+        // 2.25 MB of the 4.5 MB are left as headroom, two receipts fit in the rest.
+        (Box::new(Headroom(0.5)), request_for(2)),
+        // The next receipt is requested even when it doesn't fit.
+        (Box::new(Headroom(0.9)), request_for(1)),
+    ];
+    for (policy, expected) in policies {
+        let description = format!("{:?}", policy);
+        queue.set_request_policy(policy);
+        let actual = queue
+            .make_bandwidth_request(0, &config)
+            .map(|r| r.grant_options_bitmap);
+        assert_eq!(actual, expected, "{}", description);
+    }
+}
+
+#[test]
+fn test_queue_size_accessors() {
+    let mut queue = OutgoingQueue::new(ShardUId::new(1));
+    queue.set_config(SimulationConfig {
+        min_receipt_size: 500,
+        ..SimulationConfig::default()
+    });
+    assert_eq!(queue.total_receipts(), 0);
+    assert_eq!(queue.iter_sizes().count(), 0);
+    assert_eq!(queue.size_histogram(&[1000]), vec![0, 0]);
+
+    for (size, priority) in [
+        (500, ReceiptPriority::Low),
+        (1000, ReceiptPriority::Normal),
+        (5000, ReceiptPriority::High),
+        (20_000, ReceiptPriority::Normal),
+    ] {
+        queue.push(Receipt {
+            size,
+            tag: None,
+            priority,
+        });
+    }
+    assert_eq!(queue.total_receipts(), 4);
+    // Strict priority, higher priorities are popped first.
+    assert_eq!(
+        queue.iter_sizes().collect::<Vec<_>>(),
+        vec![5000, 1000, 20_000, 500]
+    );
+    assert_eq!(queue.size_histogram(&[1000, 10_000]), vec![2, 1, 1]);
+    assert_eq!(queue.size_histogram(&[]), vec![4]);
+
+    queue.pop();
+    assert_eq!(queue.total_receipts(), 3);
+    assert_eq!(queue.iter_sizes().sum::<usize>(), queue.total_size());
+}
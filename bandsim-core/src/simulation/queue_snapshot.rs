@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::chain::{Receipt, ReceiptPriority, ShardLink, ShardUId};
+
+use super::initial_state::QueuedReceipt;
+use super::Simulation;
+
+/// Contents of the outgoing queues at some height. Can be saved to a file and loaded as the initial
+/// state of another simulation (see `InitialState::from_queue_snapshot`), or compared with a snapshot
+/// from another run. Unstoppable receipts aren't included.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueueSnapshot {
+    /// Height of the last block when the snapshot was taken.
+    pub height: usize,
+    /// Receipts in every non-empty queue, in the order in which they were queued.
+    pub queues: BTreeMap<ShardLink, Vec<QueuedReceipt>>,
+}
+
+impl QueueSnapshot {
+    /// Snapshot of the outgoing queues after the last block of the simulation.
+    pub fn capture(simulation: &Simulation) -> QueueSnapshot {
+        let mut queues = BTreeMap::new();
+        for (shard_id, shard) in &simulation.shards {
+            for (to_shard, outgoing_queue) in &shard.outgoing_queues {
+                if outgoing_queue.is_empty() {
+                    continue;
+                }
+                let shard_link = ShardLink {
+                    from: *shard_id,
+                    to: *to_shard,
+                };
+                let receipts = outgoing_queue
+                    .receipts()
+                    .into_iter()
+                    .map(|(receipt, enqueued_height)| QueuedReceipt {
+                        receipt: receipt.clone(),
+                        enqueued_height,
+                    })
+                    .collect();
+                queues.insert(shard_link, receipts);
+            }
+        }
+        QueueSnapshot {
+            height: simulation.end_height() - 1,
+            queues,
+        }
+    }
+
+    /// Total size of the queued receipts on every link.
+    pub fn queued_bytes(&self) -> BTreeMap<ShardLink, usize> {
+        self.queues
+            .iter()
+            .map(|(shard_link, receipts)| {
+                let bytes = receipts.iter().map(|queued| queued.receipt.size).sum();
+                (*shard_link, bytes)
+            })
+            .collect()
+    }
+
+    /// One line per receipt: `receipt`, the sending and the receiving shard id, size, height at which
+    /// it was queued, priority and tag (empty without a tag), separated with tabs.
+    pub fn to_text(&self) -> String {
+        let mut res = String::new();
+        res.push_str(&format!("height\t{}\n", self.height));
+        for (shard_link, receipts) in &self.queues {
+            for queued in receipts {
+                let receipt = &queued.receipt;
+                res.push_str(&format!(
+                    "receipt\t{}\t{}\t{}\t{}\t{:?}\t{}\n",
+                    shard_link.from.shard_id,
+                    shard_link.to.shard_id,
+                    receipt.size,
+                    queued.enqueued_height,
+                    receipt.priority,
+                    receipt.tag.as_deref().unwrap_or("")
+                ));
+            }
+        }
+        res
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_text())
+    }
+
+    /// Load a snapshot saved with `save`.
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<QueueSnapshot> {
+        let invalid = |line: &str| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid queue snapshot line: {}", line),
+            )
+        };
+        let mut snapshot = QueueSnapshot::default();
+        for line in std::fs::read_to_string(path)?.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            match fields[..] {
+                ["height", height] => {
+                    snapshot.height = height.parse().map_err(|_| invalid(line))?;
+                }
+                ["receipt", from, to, size, enqueued_height, priority, tag] => {
+                    let shard = |id: &str| -> std::io::Result<ShardUId> {
+                        Ok(ShardUId::new(id.parse().map_err(|_| invalid(line))?))
+                    };
+                    let shard_link = ShardLink {
+                        from: shard(from)?,
+                        to: shard(to)?,
+                    };
+                    let priority = ReceiptPriority::ALL
+                        .into_iter()
+                        .find(|p| format!("{:?}", p) == priority)
+                        .ok_or_else(|| invalid(line))?;
+                    let receipt = Receipt {
+                        size: size.parse().map_err(|_| invalid(line))?,
+                        tag: (!tag.is_empty()).then(|| tag.into()),
+                        priority,
+                    };
+                    snapshot
+                        .queues
+                        .entry(shard_link)
+                        .or_default()
+                        .push(QueuedReceipt {
+                            receipt,
+                            enqueued_height: enqueued_height.parse().map_err(|_| invalid(line))?,
+                        });
+                }
+                _ => return Err(invalid(line)),
+            }
+        }
+        Ok(snapshot)
+    }
+}
@@ -0,0 +1,563 @@
+use std::collections::BTreeMap;
+
+use rand::Rng;
+use rand_distr::{Distribution, Pareto, Weibull};
+
+use crate::chain::{Receipt, ReceiptPriority, ReceiptTag, ShardUId, SimulationConfig};
+use crate::rng::DefaultRng;
+
+use super::outgoing_queue::OutgoingQueue;
+
+/// Sends any number of receipts to the provided queue.
+/// Senders are cloned when a simulation is forked (see `Simulation::fork`), so every sender has to be `Clone`.
+/// This wasn't required before forking was added, older senders need a `#[derive(Clone)]`.
+pub trait ReceiptSender: std::fmt::Debug + CloneReceiptSender {
+    fn send_receipts(&mut self, outgoing_queue: &mut OutgoingQueue, rng: &mut DefaultRng);
+}
+
+/// Implemented for every `ReceiptSender` which is `Clone`, allows to clone boxed senders.
+pub trait CloneReceiptSender {
+    fn clone_box(&self) -> Box<dyn ReceiptSender>;
+}
+
+impl<T: ReceiptSender + Clone + 'static> CloneReceiptSender for T {
+    fn clone_box(&self) -> Box<dyn ReceiptSender> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn ReceiptSender> {
+    fn clone(&self) -> Self {
+        (**self).clone_box()
+    }
+}
+
+impl ReceiptSender for Box<dyn ReceiptSender> {
+    fn send_receipts(&mut self, outgoing_queue: &mut OutgoingQueue, rng: &mut DefaultRng) {
+        (**self).send_receipts(outgoing_queue, rng);
+    }
+}
+
+/// Generates a single receipt of some kind.
+/// The receipt has to fit the receipt size limits of the `config`.
+/// Like senders, generators are cloned when a simulation is forked and have to be `Clone`.
+pub trait ReceiptGenerator: std::fmt::Debug + CloneReceiptGenerator {
+    fn generate_receipt(&mut self, config: &SimulationConfig, rng: &mut DefaultRng) -> Receipt;
+
+    /// Generate a receipt that will be sent to `to_shard`. Receipt senders always use this method,
+    /// generators whose receipts depend on the destination override it.
+    fn generate_receipt_to(
+        &mut self,
+        _to_shard: ShardUId,
+        config: &SimulationConfig,
+        rng: &mut DefaultRng,
+    ) -> Receipt {
+        self.generate_receipt(config, rng)
+    }
+}
+
+/// Queue size up to which `FullSpeedReceiptSender` fills the queue, a bit more than two heights
+/// of the max shard bandwidth (10MB with the default limits).
+fn full_queue_size(config: &SimulationConfig) -> usize {
+    config.max_shard_bandwidth * 20 / 9
+}
+
+/// Implemented for every `ReceiptGenerator` which is `Clone`, allows to clone boxed generators.
+pub trait CloneReceiptGenerator {
+    fn clone_box(&self) -> Box<dyn ReceiptGenerator>;
+}
+
+impl<T: ReceiptGenerator + Clone + 'static> CloneReceiptGenerator for T {
+    fn clone_box(&self) -> Box<dyn ReceiptGenerator> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn ReceiptGenerator> {
+    fn clone(&self) -> Self {
+        (**self).clone_box()
+    }
+}
+
+/// Sends receipts of some kind as fast as possible.
+#[derive(Clone, Debug)]
+pub struct FullSpeedReceiptSender<RG: ReceiptGenerator>(pub RG);
+
+impl<RG: ReceiptGenerator + Clone + 'static> ReceiptSender for FullSpeedReceiptSender<RG> {
+    fn send_receipts(&mut self, outgoing_queue: &mut OutgoingQueue, rng: &mut DefaultRng) {
+        while outgoing_queue.total_size() < full_queue_size(outgoing_queue.config()) {
+            let receipt =
+                self.0
+                    .generate_receipt_to(outgoing_queue.to_shard(), outgoing_queue.config(), rng);
+            outgoing_queue.push(receipt);
+        }
+    }
+}
+
+/// Sends receipts of some kind at a constant rate - `bytes_per_height` bytes at every height.
+/// Unlike `FullSpeedReceiptSender` it doesn't look at the queue, so the queue grows without bounds
+/// when the rate is higher than what can be sent.
+#[derive(Clone, Debug)]
+pub struct ConstantRateReceiptSender<RG: ReceiptGenerator> {
+    pub generator: RG,
+    pub bytes_per_height: usize,
+}
+
+impl<RG: ReceiptGenerator + Clone + 'static> ReceiptSender for ConstantRateReceiptSender<RG> {
+    fn send_receipts(&mut self, outgoing_queue: &mut OutgoingQueue, rng: &mut DefaultRng) {
+        let mut sent = 0;
+        while sent < self.bytes_per_height {
+            let receipt = self.generator.generate_receipt_to(
+                outgoing_queue.to_shard(),
+                outgoing_queue.config(),
+                rng,
+            );
+            sent += receipt.size;
+            outgoing_queue.push(receipt);
+        }
+    }
+}
+
+/// A period of constant load, which starts at `start_height` and lasts until the start of the next phase.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoadPhase {
+    pub start_height: usize,
+    pub bytes_per_height: usize,
+}
+
+/// Sends receipts at a rate which jumps between levels at the given heights - a step function.
+/// Before the first phase nothing is sent.
+#[derive(Clone, Debug)]
+pub struct StepLoadReceiptSender<RG: ReceiptGenerator> {
+    pub generator: RG,
+    /// Phases sorted by the start height
+    pub phases: Vec<LoadPhase>,
+}
+
+impl<RG: ReceiptGenerator + Clone + 'static> ReceiptSender for StepLoadReceiptSender<RG> {
+    fn send_receipts(&mut self, outgoing_queue: &mut OutgoingQueue, rng: &mut DefaultRng) {
+        let height = outgoing_queue.current_height();
+        let Some(phase) = self
+            .phases
+            .iter()
+            .rev()
+            .find(|phase| phase.start_height <= height)
+        else {
+            return;
+        };
+        let mut sent = 0;
+        while sent < phase.bytes_per_height {
+            let receipt = self.generator.generate_receipt_to(
+                outgoing_queue.to_shard(),
+                outgoing_queue.config(),
+                rng,
+            );
+            sent += receipt.size;
+            outgoing_queue.push(receipt);
+        }
+    }
+}
+
+/// Sends receipts at a rate which increases linearly with the height, until it reaches `max_bytes_per_height`.
+/// At height `h` it sends `start_bytes_per_height + h * increase_per_height` bytes.
+/// Use `StepLoadReceiptSender` for a load that follows an arbitrary schedule.
+#[derive(Clone, Debug)]
+pub struct RampReceiptSender<RG: ReceiptGenerator> {
+    pub generator: RG,
+    pub start_bytes_per_height: usize,
+    pub increase_per_height: usize,
+    pub max_bytes_per_height: usize,
+}
+
+impl<RG: ReceiptGenerator + Clone + 'static> ReceiptSender for RampReceiptSender<RG> {
+    fn send_receipts(&mut self, outgoing_queue: &mut OutgoingQueue, rng: &mut DefaultRng) {
+        let height = outgoing_queue.current_height();
+        let bytes_per_height = self
+            .increase_per_height
+            .saturating_mul(height)
+            .saturating_add(self.start_bytes_per_height)
+            .min(self.max_bytes_per_height);
+        let mut sent = 0;
+        while sent < bytes_per_height {
+            let receipt = self.generator.generate_receipt_to(
+                outgoing_queue.to_shard(),
+                outgoing_queue.config(),
+                rng,
+            );
+            sent += receipt.size;
+            outgoing_queue.push(receipt);
+        }
+    }
+}
+
+/// Self-similar, heavy-tailed traffic - a superposition of many on/off sources with Pareto distributed
+/// period lengths. Unlike smooth traffic, it has bursts at all time scales, which leads to much longer
+/// queueing delays at the same average load.
+/// Every source is on half of the time on average, so the average load is `num_sources * bytes_per_source / 2`.
+#[derive(Clone, Debug)]
+pub struct ParetoOnOffReceiptSender<RG: ReceiptGenerator> {
+    generator: RG,
+    bytes_per_source: usize,
+    period_distribution: Pareto<f64>,
+    num_sources: usize,
+    /// Initialized at the first height, every source starts in a random state.
+    sources: Vec<OnOffSource>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct OnOffSource {
+    is_on: bool,
+    remaining_heights: usize,
+}
+
+impl<RG: ReceiptGenerator + Clone + 'static> ParetoOnOffReceiptSender<RG> {
+    /// `shape` of the Pareto distribution should be between 1 and 2 - the smaller it is, the heavier the tail.
+    /// The shortest on/off period lasts a single height.
+    pub fn new(
+        generator: RG,
+        num_sources: usize,
+        bytes_per_source: usize,
+        shape: f64,
+    ) -> ParetoOnOffReceiptSender<RG> {
+        ParetoOnOffReceiptSender {
+            generator,
+            bytes_per_source,
+            period_distribution: Pareto::new(1.0, shape).unwrap(),
+            num_sources,
+            sources: Vec::new(),
+        }
+    }
+}
+
+impl<RG: ReceiptGenerator + Clone + 'static> ReceiptSender for ParetoOnOffReceiptSender<RG> {
+    fn send_receipts(&mut self, outgoing_queue: &mut OutgoingQueue, rng: &mut DefaultRng) {
+        // Period lengths can be huge, cap them to avoid overflows.
+        let sample_period = |rng: &mut DefaultRng| -> usize {
+            self.period_distribution.sample(rng).min(1e9) as usize
+        };
+        if self.sources.is_empty() {
+            self.sources = (0..self.num_sources)
+                .map(|_| OnOffSource {
+                    is_on: rng.gen_bool(0.5),
+                    remaining_heights: sample_period(rng),
+                })
+                .collect();
+        }
+
+        let mut bytes_per_height = 0;
+        for source in &mut self.sources {
+            if source.remaining_heights == 0 {
+                source.is_on = !source.is_on;
+                source.remaining_heights = sample_period(rng);
+            }
+            source.remaining_heights -= 1;
+            if source.is_on {
+                bytes_per_height += self.bytes_per_source;
+            }
+        }
+
+        let mut sent = 0;
+        while sent < bytes_per_height {
+            let receipt = self.generator.generate_receipt_to(
+                outgoing_queue.to_shard(),
+                outgoing_queue.config(),
+                rng,
+            );
+            sent += receipt.size;
+            outgoing_queue.push(receipt);
+        }
+    }
+}
+
+/// Sends receipts using the `base` sender, and during `burst_heights` additionally using the `burst` sender.
+#[derive(Clone, Debug)]
+pub struct BurstReceiptSender<B: ReceiptSender, S: ReceiptSender> {
+    pub base: B,
+    pub burst: S,
+    pub burst_heights: std::ops::Range<usize>,
+}
+
+impl<B: ReceiptSender + Clone + 'static, S: ReceiptSender + Clone + 'static> ReceiptSender
+    for BurstReceiptSender<B, S>
+{
+    fn send_receipts(&mut self, outgoing_queue: &mut OutgoingQueue, rng: &mut DefaultRng) {
+        self.base.send_receipts(outgoing_queue, rng);
+        if self
+            .burst_heights
+            .contains(&outgoing_queue.current_height())
+        {
+            self.burst.send_receipts(outgoing_queue, rng);
+        }
+    }
+}
+
+/// Doesn't send any receipts
+#[derive(Clone, Debug)]
+pub struct NoReceiptSender;
+
+impl ReceiptSender for NoReceiptSender {
+    fn send_receipts(&mut self, _outgoing_queue: &mut OutgoingQueue, _rng: &mut DefaultRng) {
+        // Doesn't send any receipts
+    }
+}
+
+/// Always generates receipts of one size
+#[derive(Clone, Debug)]
+pub struct OneSizeReceiptGenerator {
+    pub size: usize,
+}
+
+impl ReceiptGenerator for OneSizeReceiptGenerator {
+    fn generate_receipt(&mut self, _config: &SimulationConfig, _rng: &mut DefaultRng) -> Receipt {
+        Receipt {
+            size: self.size,
+            tag: None,
+            priority: ReceiptPriority::default(),
+        }
+    }
+}
+
+/// Generates receipts of random sizes, receipt sizes are sampled from a uniform distribution
+#[derive(Clone, Debug)]
+pub struct RandomSizeReceiptGenerator {
+    pub size_range: std::ops::RangeInclusive<usize>,
+}
+
+impl ReceiptGenerator for RandomSizeReceiptGenerator {
+    fn generate_receipt(&mut self, _config: &SimulationConfig, rng: &mut DefaultRng) -> Receipt {
+        Receipt {
+            size: rng.gen_range(self.size_range.clone()),
+            tag: None,
+            priority: ReceiptPriority::default(),
+        }
+    }
+}
+
+/// Attaches a tag to all receipts generated by the inner generator.
+#[derive(Clone, Debug)]
+pub struct TaggedReceiptGenerator<RG: ReceiptGenerator> {
+    pub generator: RG,
+    pub tag: ReceiptTag,
+}
+
+impl<RG: ReceiptGenerator + Clone + 'static> TaggedReceiptGenerator<RG> {
+    pub fn new(generator: RG, tag: &str) -> TaggedReceiptGenerator<RG> {
+        TaggedReceiptGenerator {
+            generator,
+            tag: tag.into(),
+        }
+    }
+}
+
+impl<RG: ReceiptGenerator + Clone + 'static> ReceiptGenerator for TaggedReceiptGenerator<RG> {
+    fn generate_receipt(&mut self, config: &SimulationConfig, rng: &mut DefaultRng) -> Receipt {
+        Receipt {
+            tag: Some(self.tag.clone()),
+            ..self.generator.generate_receipt(config, rng)
+        }
+    }
+
+    fn generate_receipt_to(
+        &mut self,
+        to_shard: ShardUId,
+        config: &SimulationConfig,
+        rng: &mut DefaultRng,
+    ) -> Receipt {
+        Receipt {
+            tag: Some(self.tag.clone()),
+            ..self.generator.generate_receipt_to(to_shard, config, rng)
+        }
+    }
+}
+
+/// Gives all receipts generated by the inner generator the same priority.
+#[derive(Clone, Debug)]
+pub struct PriorityReceiptGenerator<RG: ReceiptGenerator> {
+    pub generator: RG,
+    pub priority: ReceiptPriority,
+}
+
+impl<RG: ReceiptGenerator + Clone + 'static> ReceiptGenerator for PriorityReceiptGenerator<RG> {
+    fn generate_receipt(&mut self, config: &SimulationConfig, rng: &mut DefaultRng) -> Receipt {
+        Receipt {
+            priority: self.priority,
+            ..self.generator.generate_receipt(config, rng)
+        }
+    }
+
+    fn generate_receipt_to(
+        &mut self,
+        to_shard: ShardUId,
+        config: &SimulationConfig,
+        rng: &mut DefaultRng,
+    ) -> Receipt {
+        Receipt {
+            priority: self.priority,
+            ..self.generator.generate_receipt_to(to_shard, config, rng)
+        }
+    }
+}
+
+/// Receipt sizes depend on the destination shard, e.g. big receipts only towards shard 0.
+/// Destinations without their own generator use the default generator.
+#[derive(Clone, Debug)]
+pub struct DestinationCorrelatedReceiptGenerator {
+    pub generators: BTreeMap<ShardUId, Box<dyn ReceiptGenerator>>,
+    pub default_generator: Box<dyn ReceiptGenerator>,
+}
+
+impl DestinationCorrelatedReceiptGenerator {
+    pub fn new(default_generator: impl ReceiptGenerator + 'static) -> Self {
+        DestinationCorrelatedReceiptGenerator {
+            generators: BTreeMap::new(),
+            default_generator: Box::new(default_generator),
+        }
+    }
+
+    /// Use this generator for receipts sent to `to_shard`.
+    pub fn destination(
+        mut self,
+        to_shard: usize,
+        generator: impl ReceiptGenerator + 'static,
+    ) -> Self {
+        self.generators
+            .insert(ShardUId::new(to_shard), Box::new(generator));
+        self
+    }
+}
+
+impl ReceiptGenerator for DestinationCorrelatedReceiptGenerator {
+    /// The destination is unknown, the receipt comes from the default generator.
+    fn generate_receipt(&mut self, config: &SimulationConfig, rng: &mut DefaultRng) -> Receipt {
+        self.default_generator.generate_receipt(config, rng)
+    }
+
+    fn generate_receipt_to(
+        &mut self,
+        to_shard: ShardUId,
+        config: &SimulationConfig,
+        rng: &mut DefaultRng,
+    ) -> Receipt {
+        self.generators
+            .get_mut(&to_shard)
+            .unwrap_or(&mut self.default_generator)
+            .generate_receipt_to(to_shard, config, rng)
+    }
+}
+
+/// Generates receipts of the "typical" size - mostly small, sometimes big.
+// TODO - Make sure that receipts generated by this match the real world.
+#[derive(Clone, Debug)]
+pub struct TypicalReceiptGenerator {
+    distribution: Weibull<f64>,
+}
+
+impl TypicalReceiptGenerator {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> TypicalReceiptGenerator {
+        TypicalReceiptGenerator {
+            distribution: Weibull::new(0.1, 1.0).unwrap(),
+        }
+    }
+}
+
+impl ReceiptGenerator for TypicalReceiptGenerator {
+    fn generate_receipt(&mut self, config: &SimulationConfig, rng: &mut DefaultRng) -> Receipt {
+        let (min_size, max_size) = (config.min_receipt_size, config.max_receipt_size);
+        let sample: f64 = self.distribution.sample(rng);
+        let receipt_size: usize = ((max_size - min_size) as f64 * sample) as usize;
+        if !(min_size..=max_size).contains(&receipt_size) {
+            return self.generate_receipt(config, rng);
+        }
+        Receipt {
+            size: receipt_size,
+            tag: None,
+            priority: ReceiptPriority::default(),
+        }
+    }
+}
+
+/// Visualise the histogram of receipt sizes generated by a receipt generator.
+#[cfg(test)]
+pub mod tests {
+    use crate::chain::{SimulationConfig, MAX_RECEIPT_SIZE};
+
+    use super::{ReceiptGenerator, TypicalReceiptGenerator};
+
+    fn show_generated_size_distribution(generator: &mut impl ReceiptGenerator) {
+        use crate::rng::rng_from_seed;
+
+        let samples = 100000;
+        let bucket_size = 80_000;
+
+        let mut next_bucket_start = 0;
+        let mut buckets = Vec::new();
+        loop {
+            let mut bucket_end = next_bucket_start + bucket_size;
+            if bucket_end > MAX_RECEIPT_SIZE {
+                bucket_end = MAX_RECEIPT_SIZE;
+            }
+            buckets.push((next_bucket_start..=bucket_end, 0));
+            next_bucket_start = bucket_end;
+            if next_bucket_start == MAX_RECEIPT_SIZE {
+                break;
+            }
+        }
+
+        let mut min = usize::MAX;
+        let mut max = 0;
+        let mut rng = rng_from_seed(0);
+        for _ in 0..samples {
+            let receipt = generator.generate_receipt(&SimulationConfig::default(), &mut rng);
+            if receipt.size > MAX_RECEIPT_SIZE {
+                panic!(
+                    "receipt size too large! {} > {}",
+                    receipt.size, MAX_RECEIPT_SIZE
+                );
+            }
+            for (range, count) in buckets.iter_mut() {
+                if range.contains(&receipt.size) {
+                    *count += 1;
+                }
+            }
+            if receipt.size < min {
+                min = receipt.size;
+            }
+            if receipt.size > max {
+                max = receipt.size;
+            }
+        }
+
+        let max_count = buckets.iter().map(|(_, count)| count).max().unwrap();
+        let max_histogram_bar_len = 140;
+
+        let aligned_number = |num: usize| {
+            let target_len = 8;
+            let mut num_str = num.to_string();
+            while num_str.len() < target_len {
+                num_str = " ".to_string() + &num_str;
+            }
+            num_str
+        };
+
+        println!("Generator: {:?}", generator);
+        println!("{} samples, min = {}, max = {}", samples, min, max);
+        for (range, count) in &buckets {
+            let bar_len =
+                ((*count as f64 / *max_count as f64) * max_histogram_bar_len as f64) as usize;
+            let mut bar: String = "#".repeat(bar_len);
+            if bar.is_empty() && *count > 0 {
+                bar.push('.');
+            }
+
+            println!("{}: {}", aligned_number(*range.end()), bar);
+        }
+    }
+
+    /// Run with `--nocapture` to see the histogram
+    /// cargo test --release show_typical_generator -- --nocapture
+    #[test]
+    fn show_typical_generator_size_distribution() {
+        show_generated_size_distribution(&mut TypicalReceiptGenerator::new());
+    }
+}
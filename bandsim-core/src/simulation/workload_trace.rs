@@ -0,0 +1,135 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use crate::chain::{Receipt, ShardLink, ShardUId};
+use crate::rng::DefaultRng;
+
+use super::builder::SimulationBuilder;
+use super::outgoing_queue::OutgoingQueue;
+use super::receipt_sender::ReceiptSender;
+use super::SimulationRun;
+
+/// Receipts generated on every link at every height of a recorded run.
+/// Senders like `FullSpeedReceiptSender` look at their outgoing queue, so under a different scheduler they
+/// generate different receipts, even with the same seed. Simulations which replay the same trace
+/// (see `SimulationBuilder::replay_workload`) get exactly the same receipts at the same heights,
+/// the differences between them come only from the scheduler and its parameters.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WorkloadTrace {
+    /// Shards of the recorded simulation.
+    pub shards: Vec<ShardUId>,
+    /// Generated receipts by link and height, only for the heights at which something was generated.
+    arrivals: BTreeMap<ShardLink, BTreeMap<usize, Vec<Receipt>>>,
+}
+
+impl WorkloadTrace {
+    /// Run the simulation for `steps` heights and record the receipts generated by its senders.
+    pub fn record(builder: SimulationBuilder, steps: usize) -> WorkloadTrace {
+        Self::from_run(&builder.record_workload().build().run_for(steps))
+    }
+
+    /// Collect the receipts generated in a run built with `SimulationBuilder::record_workload`.
+    pub fn from_run(simulation_run: &SimulationRun) -> WorkloadTrace {
+        let simulation = &simulation_run.simulation;
+        assert!(
+            simulation
+                .shards
+                .values()
+                .all(|shard| shard.record_workload),
+            "The run didn't record the workload, use `SimulationBuilder::record_workload`!"
+        );
+        let mut arrivals: BTreeMap<ShardLink, BTreeMap<usize, Vec<Receipt>>> = BTreeMap::new();
+        for height_metrics in &simulation.metrics {
+            for (link, receipts) in &height_metrics.arrivals {
+                arrivals
+                    .entry(*link)
+                    .or_default()
+                    .insert(height_metrics.height, receipts.clone());
+            }
+        }
+        WorkloadTrace {
+            shards: simulation.shards.keys().copied().collect(),
+            arrivals,
+        }
+    }
+
+    /// Receipts generated on the link at the height, in the order in which they were generated.
+    pub fn arrivals(&self, link: ShardLink, height: usize) -> &[Receipt] {
+        self.arrivals
+            .get(&link)
+            .and_then(|by_height| by_height.get(&height))
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    /// Total size of the receipts generated on the link at the height.
+    pub fn arrived_bytes(&self, link: ShardLink, height: usize) -> usize {
+        self.arrivals(link, height)
+            .iter()
+            .map(|receipt| receipt.size)
+            .sum()
+    }
+
+    /// Links on which something was generated.
+    pub fn links(&self) -> impl Iterator<Item = ShardLink> + '_ {
+        self.arrivals.keys().copied()
+    }
+
+    fn all_receipts(&self) -> impl Iterator<Item = &Receipt> + '_ {
+        self.arrivals
+            .values()
+            .flat_map(|by_height| by_height.values())
+            .flatten()
+    }
+
+    pub fn total_receipts(&self) -> usize {
+        self.all_receipts().count()
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        self.all_receipts().map(|receipt| receipt.size).sum()
+    }
+
+    /// Check that a run generated exactly the bytes from the trace on every link, at every height
+    /// at which the sending shard had a chunk. A shard with a missing chunk doesn't generate anything,
+    /// so the runs which replay a trace should have the same missing chunks and blocks as the recorded run.
+    pub fn check_replayed(&self, simulation_run: &SimulationRun) {
+        for height_metrics in &simulation_run.simulation.metrics {
+            for (link, offered) in &height_metrics.offered {
+                let expected = self.arrived_bytes(*link, height_metrics.height);
+                assert_eq!(
+                    *offered, expected,
+                    "{:?} generated {} bytes at height {}, the trace has {}!",
+                    link, offered, height_metrics.height, expected
+                );
+            }
+        }
+    }
+}
+
+/// Generates the receipts recorded on one link in a `WorkloadTrace`, regardless of the queue and the grants.
+#[derive(Clone)]
+pub struct TraceReceiptSender {
+    pub trace: Arc<WorkloadTrace>,
+    pub link: ShardLink,
+}
+
+impl ReceiptSender for TraceReceiptSender {
+    fn send_receipts(&mut self, outgoing_queue: &mut OutgoingQueue, _rng: &mut DefaultRng) {
+        let height = outgoing_queue.current_height();
+        for receipt in self.trace.arrivals(self.link, height) {
+            outgoing_queue.push(receipt.clone());
+        }
+    }
+}
+
+// The whole trace would be too long to print with the other senders.
+impl Debug for TraceReceiptSender {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TraceReceiptSender")
+            .field("link", &self.link)
+            .field("trace_receipts", &self.trace.total_receipts())
+            .finish()
+    }
+}
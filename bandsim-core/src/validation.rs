@@ -0,0 +1,697 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::bandwidth_scheduler::{BandwidthScheduler, SchedulerParams};
+use crate::chain::{Block, Chunk, ShardLink, ShardUId, SimulationConfig};
+use crate::shard_layout::ShardLayout;
+use crate::simulation::metrics::HeightMetrics;
+use crate::simulation::{Shard, Simulation, SimulationRun};
+
+/// How much validation the simulation does at every height.
+/// Each level includes all checks of the previous one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ValidationLevel {
+    /// No checks, for measuring the performance of the simulation itself.
+    Off,
+    /// Validate every block and the scheduler of the first shard.
+    Basic,
+    /// Validate the scheduler of every shard and check that all shards have the same scheduler state.
+    #[default]
+    Full,
+    /// Check that receiving shards see exactly the receipts that were sent to them, and that no bytes
+    /// appear or disappear in the outgoing queues.
+    Paranoid,
+}
+
+/// Validate that bandwidth grants generated by BandwidthScheduler are legal.
+/// Checks that every shard sends and receives at most its max outgoing and incoming bandwidth.
+pub fn validate_grants(
+    grants: &BTreeMap<ShardLink, usize>,
+    params: &SchedulerParams,
+    config: &SimulationConfig,
+) {
+    let mut total_outgoing: BTreeMap<ShardUId, usize> = BTreeMap::new();
+    let mut total_incoming: BTreeMap<ShardUId, usize> = BTreeMap::new();
+
+    for (link, grant) in grants {
+        *total_outgoing.entry(link.from).or_insert(0) += grant;
+        *total_incoming.entry(link.to).or_insert(0) += grant;
+    }
+
+    for (shard_id, outgoing) in total_outgoing {
+        if outgoing > params.max_outgoing_bandwidth(shard_id, config) {
+            panic!("Total outgoing for shard {:?} is {}", shard_id, outgoing);
+        }
+    }
+    for (shard_id, incoming) in total_incoming {
+        if incoming > params.max_incoming_bandwidth(shard_id, config) {
+            panic!("Total incoming for shard {:?} is {}", shard_id, incoming);
+        }
+    }
+}
+
+/// Validate that the scheduler has allowances and grants only on links between shards in the layout.
+/// Bandwidth requests to shards which don't exist (e.g. sent right after a resharding) must be dropped,
+/// they can't create phantom links in the scheduler state.
+pub fn validate_scheduler_links(
+    scheduler: &BandwidthScheduler,
+    grants: &BTreeMap<ShardLink, usize>,
+    shard_layout: &ShardLayout,
+) {
+    let in_layout =
+        |link: &ShardLink| shard_layout.contains(link.from) && shard_layout.contains(link.to);
+    if let Some(link) = scheduler.allowances().keys().find(|link| !in_layout(link)) {
+        panic!(
+            "Allowance on link {:?} which isn't in the shard layout!",
+            link
+        );
+    }
+    if let Some(link) = grants.keys().find(|link| !in_layout(link)) {
+        panic!("Grant on link {:?} which isn't in the shard layout!", link);
+    }
+}
+
+/// Validate that no link was granted more than the quota advertised by its receiver in the block
+/// that the scheduler processed, see `ReceiverQuotas`.
+pub fn validate_receiver_quotas(grants: &BTreeMap<ShardLink, usize>, block: &Block) {
+    for (receiver, chunk) in &block.chunks {
+        let Some(chunk) = chunk else {
+            continue;
+        };
+        for (sender, quota) in &chunk.receiver_quotas {
+            let link = ShardLink {
+                from: *sender,
+                to: *receiver,
+            };
+            let grant = grants.get(&link).copied().unwrap_or(0);
+            if grant > *quota {
+                panic!(
+                    "Grant {} on link {:?} exceeds the receiver quota {}",
+                    grant, link, quota
+                );
+            }
+        }
+    }
+}
+
+/// Validate that receipts sent in the block are legal.
+/// A shard should receive at most the max shard bandwidth at every height.
+/// The only exception is when the previous chunk was missing on a shard,
+/// then the shard can receive twice the max shard bandwidth, but it can't be
+/// more than that.
+pub fn validate_block(
+    block: &Block,
+    prev_blocks: &[Option<Block>],
+    params: &SchedulerParams,
+    config: &SimulationConfig,
+) {
+    validate_block_parallel(block, prev_blocks, params, config, 1);
+}
+
+/// Same as `validate_block`, but the chunks are validated on `threads` threads.
+pub fn validate_block_parallel(
+    block: &Block,
+    prev_blocks: &[Option<Block>],
+    params: &SchedulerParams,
+    config: &SimulationConfig,
+    threads: usize,
+) {
+    let prev_block = prev_blocks.iter().rev().flatten().next();
+
+    // There's exactly one (possibly missing) chunk for every shard in the layout
+    if !block.chunks.keys().eq(block.shard_layout.shard_ids()) {
+        panic!(
+            "Chunks in block at height {} don't match the shard layout! chunks: {:?}, layout: {:?}",
+            block.height,
+            block.chunks.keys().collect::<Vec<_>>(),
+            block.shard_layout
+        );
+    }
+
+    validate_unstoppable_receipts(block, params, config);
+
+    let chunks: Vec<(ShardUId, &Chunk)> = block
+        .chunks
+        .iter()
+        .filter_map(|(shard_id, chunk_opt)| Some((*shard_id, chunk_opt.as_ref()?)))
+        .collect();
+    check_in_parallel(&chunks, threads, |(shard_id, chunk)| {
+        let max_incoming_receipts = max_incoming_receipts(prev_block, *shard_id, params, config);
+        if chunk.prev_incoming_receipts_size > max_incoming_receipts {
+            panic!(
+                "TOO MANY INCOMING RECEIPTS! {} > {}",
+                chunk.prev_incoming_receipts_size, max_incoming_receipts
+            );
+        }
+
+        let total_outgoing_receipts: usize = chunk.prev_outgoing_receipts_size.values().sum();
+        let max_outgoing_receipts = params.max_outgoing_bandwidth(*shard_id, config);
+        if total_outgoing_receipts > max_outgoing_receipts {
+            panic!(
+                "TOO MANY OUTGOING RECEIPTS! {} > {}",
+                total_outgoing_receipts, max_outgoing_receipts
+            );
+        }
+
+        for bandwidth_request in &chunk.bandwidth_requests {
+            if bandwidth_request.grant_options_bitmap.is_all_false() {
+                panic!("Bandwidth request has no options!");
+            }
+        }
+
+        validate_congestion_info(block.height, *shard_id, chunk, prev_blocks);
+    });
+}
+
+/// How many bytes of receipts the shard's chunk can receive, the max incoming bandwidth of the shard, or twice as much
+/// when the shard's chunk in the previous block was missing.
+fn max_incoming_receipts(
+    prev_block: Option<&Block>,
+    shard_id: ShardUId,
+    params: &SchedulerParams,
+    config: &SimulationConfig,
+) -> usize {
+    let prev_chunk_missing = prev_block
+        .map(|b: &Block| !b.chunks.get(&shard_id).unwrap().is_some())
+        .unwrap_or(false);
+    let max_incoming_bandwidth = params.max_incoming_bandwidth(shard_id, config);
+    if prev_chunk_missing {
+        2 * max_incoming_bandwidth
+    } else {
+        max_incoming_bandwidth
+    }
+}
+
+/// A chunk which received more receipts than the incoming limit allows, see `validate_block`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IncomingLimitViolation {
+    pub height: usize,
+    pub shard: ShardUId,
+    pub received: usize,
+    pub limit: usize,
+}
+
+/// Find all chunks which received more than their incoming limit. Unlike `validate_block` it doesn't panic,
+/// it's meant for runs with faults that are expected to break the limit, with validation turned off.
+pub fn find_incoming_limit_violations(
+    simulation_run: &SimulationRun,
+) -> Vec<IncomingLimitViolation> {
+    let blocks = &simulation_run.simulation.blocks;
+    let mut violations = Vec::new();
+    for (index, block) in blocks.iter().enumerate() {
+        let Some(block) = block else {
+            continue;
+        };
+        let prev_block = blocks[..index].iter().rev().flatten().next();
+        for (shard_id, chunk) in &block.chunks {
+            let Some(chunk) = chunk else {
+                continue;
+            };
+            let limit = max_incoming_receipts(
+                prev_block,
+                *shard_id,
+                &simulation_run.simulation.scheduler_params,
+                &simulation_run.simulation.config,
+            );
+            if chunk.prev_incoming_receipts_size > limit {
+                violations.push(IncomingLimitViolation {
+                    height: block.height,
+                    shard: *shard_id,
+                    received: chunk.prev_incoming_receipts_size,
+                    limit,
+                });
+            }
+        }
+    }
+    violations
+}
+
+/// Run the checks of every shard's scheduler (`validate_grants` and `validate_scheduler_links`),
+/// split between `threads` threads. Every shard runs its own scheduler, with many shards these checks
+/// take a big part of the simulation time.
+pub fn validate_shard_schedulers(
+    schedulers: &[(&BandwidthScheduler, &BTreeMap<ShardLink, usize>)],
+    shard_layout: &ShardLayout,
+    threads: usize,
+) {
+    check_in_parallel(schedulers, threads, |(scheduler, grants)| {
+        validate_grants(grants, scheduler.params(), scheduler.config());
+        validate_scheduler_links(scheduler, grants, shard_layout);
+    });
+}
+
+/// Check that every chunk reports as incoming exactly the receipts which were sent to its shard
+/// since the shard's previous chunk, as seen in the blocks.
+pub fn validate_incoming_receipts(block: &Block, prev_blocks: &[Option<Block>]) {
+    for (shard_id, chunk) in block
+        .chunks
+        .iter()
+        .filter_map(|(shard_id, chunk_opt)| Some((shard_id, chunk_opt.as_ref()?)))
+    {
+        let mut sent_to_shard = 0;
+        for prev_block in prev_blocks.iter().rev().flatten() {
+            for prev_chunk in prev_block.chunks.values().flatten() {
+                sent_to_shard += prev_chunk
+                    .prev_outgoing_receipts_size
+                    .get(shard_id)
+                    .unwrap_or(&0);
+                sent_to_shard += prev_chunk
+                    .prev_unstoppable_receipts_size
+                    .get(shard_id)
+                    .unwrap_or(&0);
+            }
+            if matches!(prev_block.chunks.get(shard_id), Some(Some(_))) {
+                break;
+            }
+        }
+        if chunk.prev_incoming_receipts_size != sent_to_shard {
+            panic!(
+                "Shard {:?} at height {} received {} bytes, but {} bytes were sent to it!",
+                shard_id, block.height, chunk.prev_incoming_receipts_size, sent_to_shard
+            );
+        }
+    }
+}
+
+/// The observed missing block ratio can differ from the configured probability by at most this many
+/// standard deviations of the binomial distribution.
+pub const MISSING_BLOCK_RATIO_TOLERANCE: f64 = 5.0;
+
+/// The normal approximation of the binomial distribution is good only when both the expected number of
+/// missing blocks and of present blocks are at least this large. Shorter runs don't check the ratio.
+pub const MIN_EXPECTED_BLOCKS_FOR_RATIO_CHECK: f64 = 10.0;
+
+/// Validate the whole chain after the run.
+/// The genesis block is present, every block is at the height given by its position (so the heights
+/// of non-missing blocks are increasing and nothing is skipped), and the fraction of missing blocks
+/// matches `missing_block_probability` within `MISSING_BLOCK_RATIO_TOLERANCE` when the run is long enough
+/// for the check to be meaningful, see `MIN_EXPECTED_BLOCKS_FOR_RATIO_CHECK`.
+pub fn validate_blocks(simulation: &Simulation) {
+    match simulation.blocks.first() {
+        Some(Some(genesis)) if genesis.height == simulation.start_height => {}
+        Some(Some(genesis)) => panic!(
+            "Genesis block is at height {}, expected {}!",
+            genesis.height, simulation.start_height
+        ),
+        _ => panic!("Genesis block is missing!"),
+    }
+
+    let mut prev_height = None;
+    for (i, block) in simulation.blocks.iter().enumerate() {
+        let Some(block) = block else {
+            continue;
+        };
+        if block.height != simulation.start_height + i {
+            panic!(
+                "Block number {} is at height {}, expected {}!",
+                i,
+                block.height,
+                simulation.start_height + i
+            );
+        }
+        if prev_height.is_some_and(|prev_height| block.height <= prev_height) {
+            panic!(
+                "Block at height {} comes after a block at height {}!",
+                block.height,
+                prev_height.unwrap()
+            );
+        }
+        prev_height = Some(block.height);
+    }
+
+    let stats = MissingBlockStats::new(simulation);
+    let p = simulation.missing_block_probability;
+    let n = stats.heights as f64;
+    // With probability 0 or 1 the outcome is deterministic, there's no deviation to allow.
+    let is_random = p > 0.0 && p < 1.0;
+    if is_random && (n * p).min(n * (1.0 - p)) < MIN_EXPECTED_BLOCKS_FOR_RATIO_CHECK {
+        return;
+    }
+    let max_deviation = MISSING_BLOCK_RATIO_TOLERANCE * (p * (1.0 - p) / n.max(1.0)).sqrt();
+    if (stats.missing_blocks_ratio - p).abs() > max_deviation {
+        panic!(
+            "{} of {} blocks are missing ({:.4}), but the missing block probability is {}!",
+            stats.missing_blocks, stats.heights, stats.missing_blocks_ratio, p
+        );
+    }
+}
+
+/// Validate that grants live for a single height, see `Simulation::missing_block_probability`.
+/// The scheduler runs only at heights with a block, and every non-missing block is the input of the
+/// scheduler exactly once - at the next non-missing height, however many missing blocks are in between.
+/// A block that was used twice would mean that grants (and allowances) accumulate over missing heights.
+pub fn validate_grant_lifetimes(simulation: &Simulation) {
+    let runs = simulation
+        .shards
+        .values()
+        .next()
+        .map(|shard| shard.bandwidth_scheduler.runs())
+        .unwrap_or(0);
+    if runs != simulation.metrics.len() {
+        panic!(
+            "The scheduler ran {} times, but there are {} non-missing blocks after the genesis!",
+            runs,
+            simulation.metrics.len()
+        );
+    }
+
+    let mut prev_height = simulation.start_height;
+    for height_metrics in &simulation.metrics {
+        if height_metrics.scheduler_input_height != prev_height {
+            panic!(
+                "Grants at height {} were computed from the block at height {}, expected {}!",
+                height_metrics.height, height_metrics.scheduler_input_height, prev_height
+            );
+        }
+        prev_height = height_metrics.height;
+    }
+}
+
+/// Missing blocks after the genesis. Chunks can't be produced at a height without a block, so a long
+/// run of missing blocks stalls all shards at once.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MissingBlockStats {
+    /// Number of heights after the genesis.
+    pub heights: usize,
+    pub missing_blocks: usize,
+    pub missing_blocks_ratio: f64,
+    /// The most consecutive heights with a missing block.
+    pub longest_missing_streak: usize,
+}
+
+impl MissingBlockStats {
+    pub fn new(simulation: &Simulation) -> MissingBlockStats {
+        let mut missing_blocks = 0;
+        let mut streak = 0;
+        let mut longest_missing_streak = 0;
+        let heights = simulation.blocks.len().saturating_sub(1);
+        for block_opt in simulation.blocks.iter().skip(1) {
+            if block_opt.is_some() {
+                streak = 0;
+                continue;
+            }
+            missing_blocks += 1;
+            streak += 1;
+            longest_missing_streak = longest_missing_streak.max(streak);
+        }
+        MissingBlockStats {
+            heights,
+            missing_blocks,
+            missing_blocks_ratio: missing_blocks as f64 / heights.max(1) as f64,
+            longest_missing_streak,
+        }
+    }
+}
+
+/// Bytes which entered and left the outgoing queue of a link since the start of the run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LinkFlow {
+    pub offered: usize,
+    pub shed: usize,
+    pub sent: usize,
+}
+
+/// Keeps track of the bytes which entered and left the outgoing queues, to check that nothing
+/// appears or disappears in the queues: offered = shed + sent + queued.
+#[derive(Clone, Debug, Default)]
+pub struct QueueConservation {
+    pub links: BTreeMap<ShardLink, LinkFlow>,
+}
+
+impl QueueConservation {
+    pub fn record(&mut self, metrics: &HeightMetrics) {
+        for (link, offered) in &metrics.offered {
+            self.links.entry(*link).or_default().offered += offered;
+        }
+        for (link, shed) in &metrics.shed {
+            self.links.entry(*link).or_default().shed += shed;
+        }
+        for (link, sent) in &metrics.sent_latencies {
+            self.links.entry(*link).or_default().sent += sent.total_bytes();
+        }
+    }
+
+    /// `queued` is the number of bytes currently waiting in the outgoing queue of every link.
+    pub fn validate(&self, height: usize, queued: &BTreeMap<ShardLink, usize>) {
+        for (link, flow) in &self.links {
+            let queued_on_link = queued.get(link).copied().unwrap_or(0);
+            if flow.offered != flow.shed + flow.sent + queued_on_link {
+                panic!(
+                    "Bytes in the outgoing queue of {:?} aren't conserved at height {}! {:?}, queued: {}",
+                    link, height, flow, queued_on_link
+                );
+            }
+        }
+    }
+}
+
+/// Run `check` on every item, the items are split evenly between `threads` threads.
+/// A check which panics on a worker thread panics with the same message on the calling thread.
+fn check_in_parallel<T: Sync>(items: &[T], threads: usize, check: impl Fn(&T) + Sync) {
+    if threads <= 1 || items.len() <= 1 {
+        items.iter().for_each(check);
+        return;
+    }
+    let check = &check;
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = items
+            .chunks(items.len().div_ceil(threads))
+            .map(|chunk| scope.spawn(move || chunk.iter().for_each(check)))
+            .collect();
+        for worker in workers {
+            if let Err(panic) = worker.join() {
+                std::panic::resume_unwind(panic);
+            }
+        }
+    });
+}
+
+/// Part of the scheduler state that differs between shards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SchedulerStateKind {
+    Allowance,
+    Deficit,
+    Grant,
+}
+
+/// A place where the scheduler state on one shard is different than on the majority of shards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SchedulerDivergence {
+    pub height: usize,
+    /// The shard which has a different state than the majority.
+    pub shard: ShardUId,
+    pub link: ShardLink,
+    pub kind: SchedulerStateKind,
+    /// Value on the majority of shards
+    pub expected: usize,
+    /// Value on the divergent shard
+    pub actual: usize,
+}
+
+/// Bandwidth scheduler state must be the same on all shards.
+/// Finds the first link on which some shard disagrees with the majority, allowances and deficits are checked before grants.
+pub fn find_scheduler_divergence(
+    height: usize,
+    shards: &BTreeMap<ShardUId, Shard>,
+) -> Option<SchedulerDivergence> {
+    let allowances = shards
+        .iter()
+        .map(|(id, shard)| (*id, shard.bandwidth_scheduler.allowances()))
+        .collect();
+    let deficits = shards
+        .iter()
+        .map(|(id, shard)| (*id, shard.bandwidth_scheduler.deficits()))
+        .collect();
+    let grants = shards
+        .iter()
+        .map(|(id, shard)| (*id, &shard.latest_grants))
+        .collect();
+
+    [
+        (SchedulerStateKind::Allowance, allowances),
+        (SchedulerStateKind::Deficit, deficits),
+        (SchedulerStateKind::Grant, grants),
+    ]
+    .into_iter()
+    .find_map(|(kind, state_by_shard)| find_state_divergence(height, kind, &state_by_shard))
+}
+
+fn find_state_divergence(
+    height: usize,
+    kind: SchedulerStateKind,
+    state_by_shard: &BTreeMap<ShardUId, &BTreeMap<ShardLink, usize>>,
+) -> Option<SchedulerDivergence> {
+    // Fast path - usually all states are equal
+    let mut states = state_by_shard.values();
+    let first = states.next()?;
+    if states.all(|state| state == first) {
+        return None;
+    }
+
+    let all_links: BTreeSet<ShardLink> = state_by_shard
+        .values()
+        .flat_map(|state| state.keys().copied())
+        .collect();
+    for link in all_links {
+        let link_values: BTreeMap<ShardUId, usize> = state_by_shard
+            .iter()
+            .map(|(id, state)| (*id, state.get(&link).copied().unwrap_or(0)))
+            .collect();
+        let mut counts: BTreeMap<usize, usize> = BTreeMap::new();
+        for value in link_values.values() {
+            *counts.entry(*value).or_default() += 1;
+        }
+        if counts.len() == 1 {
+            continue;
+        }
+        let (expected, _count) = counts.iter().max_by_key(|(_value, count)| **count).unwrap();
+        let (shard, actual) = link_values
+            .iter()
+            .find(|(_shard, value)| *value != expected)
+            .unwrap();
+        return Some(SchedulerDivergence {
+            height,
+            shard: *shard,
+            link,
+            kind,
+            expected: *expected,
+            actual: *actual,
+        });
+    }
+    None
+}
+
+/// A chunk which sent more receipts on a link than the link was granted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GrantViolation {
+    pub height: usize,
+    pub link: ShardLink,
+    pub granted: usize,
+    pub sent: usize,
+}
+
+/// Find links on which a chunk of `from_shard` would send more than it was granted, as seen by the receiving shards.
+/// `outgoing_receipts_size` is what the chunk sends to every shard, `grants_by_receiver` contains the grants
+/// calculated by every shard's scheduler for this height.
+/// Unstoppable receipts are sent regardless of the grants, they're not counted here.
+pub fn find_grant_violations(
+    height: usize,
+    from_shard: ShardUId,
+    outgoing_receipts_size: &BTreeMap<ShardUId, usize>,
+    grants_by_receiver: &BTreeMap<ShardUId, &BTreeMap<ShardLink, usize>>,
+) -> Vec<GrantViolation> {
+    let mut violations = Vec::new();
+    for (to_shard, sent) in outgoing_receipts_size {
+        let link = ShardLink {
+            from: from_shard,
+            to: *to_shard,
+        };
+        let granted = grants_by_receiver[to_shard]
+            .get(&link)
+            .copied()
+            .unwrap_or(0);
+        if *sent > granted {
+            violations.push(GrantViolation {
+                height,
+                link,
+                granted,
+                sent: *sent,
+            });
+        }
+    }
+    violations
+}
+
+/// Unstoppable receipts are sent regardless of the grants.
+/// Check that they never cause a shard to send or receive more than its max bandwidth in a single block.
+fn validate_unstoppable_receipts(
+    block: &Block,
+    params: &SchedulerParams,
+    config: &SimulationConfig,
+) {
+    // (all receipts, unstoppable receipts) received by every shard
+    let mut incoming: BTreeMap<ShardUId, (usize, usize)> = BTreeMap::new();
+    for (shard_id, chunk) in block
+        .chunks
+        .iter()
+        .filter_map(|(shard_id, chunk_opt)| Some((shard_id, chunk_opt.as_ref()?)))
+    {
+        let unstoppable: usize = chunk.prev_unstoppable_receipts_size.values().sum();
+        let outgoing = chunk.prev_outgoing_receipts_size.values().sum::<usize>() + unstoppable;
+        let max_outgoing = params.max_outgoing_bandwidth(*shard_id, config);
+        if unstoppable > 0 && outgoing > max_outgoing {
+            panic!(
+                "Unstoppable receipts made shard {:?} send too much! {} > {} ({} unstoppable)",
+                shard_id, outgoing, max_outgoing, unstoppable
+            );
+        }
+
+        for (to_shard, size) in &chunk.prev_outgoing_receipts_size {
+            incoming.entry(*to_shard).or_default().0 += size;
+        }
+        for (to_shard, size) in &chunk.prev_unstoppable_receipts_size {
+            let shard_incoming = incoming.entry(*to_shard).or_default();
+            shard_incoming.0 += size;
+            shard_incoming.1 += size;
+        }
+    }
+
+    for (shard_id, (total, unstoppable)) in incoming {
+        let max_incoming = params.max_incoming_bandwidth(shard_id, config);
+        if unstoppable > 0 && total > max_incoming {
+            panic!(
+                "Unstoppable receipts made shard {:?} receive too much! {} > {} ({} unstoppable)",
+                shard_id, total, max_incoming, unstoppable
+            );
+        }
+    }
+}
+
+/// Congestion info has to follow from the congestion info in the previous chunk on this shard.
+/// The backlog can grow only by the size of received receipts and the oldest receipt
+/// can't wait longer than the time that passed since the previous chunk.
+fn validate_congestion_info(
+    height: usize,
+    shard_id: ShardUId,
+    chunk: &Chunk,
+    prev_blocks: &[Option<Block>],
+) {
+    let info = &chunk.congestion_info;
+    // Receipts received at this height can be waiting with zero delay, but there can't be a delay without a backlog.
+    if info.incoming_backlog_size == 0 && info.processing_delay != 0 {
+        panic!(
+            "Inconsistent congestion info on shard {:?} at height {}: {:?}",
+            shard_id, height, info
+        );
+    }
+
+    let prev_chunk = prev_blocks
+        .iter()
+        .flatten()
+        .rev()
+        .find_map(|block| Some((block.height, block.chunks.get(&shard_id)?.as_ref()?)));
+    let Some((prev_height, prev_chunk)) = prev_chunk else {
+        return;
+    };
+    let prev_info = &prev_chunk.congestion_info;
+
+    let max_backlog = prev_info.incoming_backlog_size + chunk.prev_incoming_receipts_size;
+    if info.incoming_backlog_size > max_backlog {
+        panic!(
+            "Incoming backlog on shard {:?} grew more than the received receipts! {} > {}",
+            shard_id, info.incoming_backlog_size, max_backlog
+        );
+    }
+
+    // When the backlog was empty, only the receipts received at this height can be waiting
+    let max_delay = if prev_info.incoming_backlog_size == 0 {
+        0
+    } else {
+        prev_info.processing_delay + (height - prev_height)
+    };
+    if info.processing_delay > max_delay {
+        panic!(
+            "Processing delay on shard {:?} grew too fast! {} > {}",
+            shard_id, info.processing_delay, max_delay
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! Criterion benchmarks of the performance-sensitive parts of the simulator:
+//! running the scheduler, making bandwidth requests, the outgoing queue and distributing the remaining bandwidth.
+//! Run them with `cargo bench --bench hot_paths`.
+//! To check a change for regressions, save a baseline before the change and compare with it afterwards:
+//! `cargo bench --bench hot_paths -- --save-baseline before`, then `cargo bench --bench hot_paths -- --baseline before`.
+
+use std::collections::BTreeMap;
+use std::hint::black_box;
+use std::sync::Arc;
+
+use bandsim_harness::bandwidth_request::{BandwidthRequest, BandwidthRequestBitmap};
+use bandsim_harness::bandwidth_scheduler::distribute_remaining::distribute_remaining_bandwidth;
+use bandsim_harness::chain::{
+    Block, Chunk, CongestionInfo, Receipt, ReceiptPriority, ShardUId, SimulationConfig,
+    MAX_SHARD_BANDWIDTH, MIN_RECEIPT_SIZE,
+};
+use bandsim_harness::rng::rng_from_seed;
+use bandsim_harness::shard_layout::ShardLayout;
+use bandsim_harness::simulation::outgoing_queue::OutgoingQueue;
+use bandsim_harness::{BandwidthScheduler, SchedulerParams};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+/// A block in which every shard requests every possible bandwidth option on every link.
+/// This is the worst case for the scheduler - it has to process the maximum number of options.
+fn dense_requests_block(num_shards: usize) -> Block {
+    let shards: Vec<ShardUId> = (0..num_shards).map(ShardUId::new).collect();
+    let mut all_options = BandwidthRequestBitmap::new();
+    for i in 0..all_options.len() {
+        all_options.set_bit(i, true);
+    }
+
+    let mut chunks = BTreeMap::new();
+    for shard in &shards {
+        let bandwidth_requests = shards
+            .iter()
+            .map(|to_shard| BandwidthRequest {
+                to_shard: *to_shard,
+                grant_options_bitmap: all_options.clone(),
+            })
+            .collect();
+        let chunk = Chunk {
+            prev_incoming_receipts_size: 0,
+            prev_outgoing_receipts_size: BTreeMap::new(),
+            prev_unstoppable_receipts_size: BTreeMap::new(),
+            bandwidth_requests,
+            congestion_info: CongestionInfo::default(),
+            receiver_quotas: BTreeMap::new(),
+        };
+        chunks.insert(*shard, Some(chunk));
+    }
+    Block {
+        height: 1,
+        shard_layout: Arc::new(ShardLayout::new(0, shards)),
+        chunks,
+    }
+}
+
+/// Outgoing queue with `num_receipts` small receipts, all with the same priority unless `mixed_priorities` is set.
+fn filled_queue(num_receipts: usize, mixed_priorities: bool) -> OutgoingQueue {
+    let mut queue = OutgoingQueue::new(ShardUId::new(0));
+    for i in 0..num_receipts {
+        let priority = if mixed_priorities {
+            ReceiptPriority::ALL[i % ReceiptPriority::ALL.len()]
+        } else {
+            ReceiptPriority::Normal
+        };
+        queue.push(Receipt {
+            size: MIN_RECEIPT_SIZE,
+            tag: None,
+            priority,
+        });
+    }
+    queue
+}
+
+/// Spare bandwidth of every shard, uneven so that the algorithm has some work to do.
+fn spare_bandwidth(num_shards: usize) -> BTreeMap<ShardUId, usize> {
+    (0..num_shards)
+        .map(|i| (ShardUId::new(i), MAX_SHARD_BANDWIDTH / (i + 1)))
+        .collect()
+}
+
+fn scheduler(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scheduler_dense_requests");
+    for num_shards in [4, 16, 32] {
+        let block = dense_requests_block(num_shards);
+        let mut scheduler =
+            BandwidthScheduler::new(SchedulerParams::default(), SimulationConfig::default());
+        let mut rng = rng_from_seed(0);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(num_shards),
+            &block,
+            |b, block| b.iter(|| black_box(scheduler.run(block, &mut rng))),
+        );
+    }
+    group.finish();
+}
+
+fn bandwidth_request(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bandwidth_request");
+    let config = SimulationConfig::default();
+    for num_receipts in [100, 10_000] {
+        for (name, mixed_priorities) in [("one_priority", false), ("mixed_priorities", true)] {
+            let queue = filled_queue(num_receipts, mixed_priorities);
+            group.bench_function(BenchmarkId::new(name, num_receipts), |b| {
+                b.iter(|| black_box(queue.make_bandwidth_request(100_000, &config)))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn outgoing_queue(c: &mut Criterion) {
+    let mut group = c.benchmark_group("outgoing_queue_push_pop_10000");
+    for (name, mixed_priorities) in [("one_priority", false), ("mixed_priorities", true)] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut queue = filled_queue(10_000, mixed_priorities);
+                while let Some(receipt) = queue.pop() {
+                    black_box(receipt);
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn distribute_remaining(c: &mut Criterion) {
+    let mut group = c.benchmark_group("distribute_remaining");
+    for num_shards in [4, 16, 64] {
+        let left = spare_bandwidth(num_shards);
+        let right = spare_bandwidth(num_shards);
+        group.bench_function(BenchmarkId::from_parameter(num_shards), |b| {
+            b.iter(|| black_box(distribute_remaining_bandwidth(&left, &right)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    scheduler,
+    bandwidth_request,
+    outgoing_queue,
+    distribute_remaining
+);
+criterion_main!(benches);
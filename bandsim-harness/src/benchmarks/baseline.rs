@@ -0,0 +1,200 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::BenchResult;
+
+/// When this environment variable is set, benchmarks overwrite the stored baselines with the new results
+/// instead of comparing against them.
+pub const SAVE_BASELINES_VAR: &str = "BANDSIM_SAVE_BASELINES";
+
+/// Results of a benchmark suite that were committed to the repository, used as a reference point for
+/// performance work. Stored in `src/benchmarks/baselines/<suite>.tsv`, one benchmark per line.
+/// The numbers depend on the machine, compare against baselines taken on the same machine.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Baselines {
+    pub benchmarks: BTreeMap<String, Baseline>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Baseline {
+    pub median: Duration,
+    pub allocations: usize,
+}
+
+/// Result of a benchmark compared to its baseline.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BaselineComparison {
+    pub name: String,
+    /// `None` when the benchmark has no stored baseline.
+    pub baseline: Option<Baseline>,
+    pub median: Duration,
+    pub allocations: usize,
+}
+
+impl BaselineComparison {
+    /// New median divided by the baseline median, above 1.0 means that the benchmark got slower.
+    pub fn median_ratio(&self) -> Option<f64> {
+        let baseline = self.baseline?;
+        Some(self.median.as_secs_f64() / baseline.median.as_secs_f64().max(f64::MIN_POSITIVE))
+    }
+
+    /// The benchmark got slower by more than `tolerance` (e.g. 0.1 = 10%), or started making more allocations.
+    pub fn is_regression(&self, tolerance: f64) -> bool {
+        let Some(baseline) = self.baseline else {
+            return false;
+        };
+        self.median_ratio().unwrap() > 1.0 + tolerance || self.allocations > baseline.allocations
+    }
+}
+
+impl Baselines {
+    pub fn from_results(results: &[BenchResult]) -> Baselines {
+        let benchmarks = results
+            .iter()
+            .map(|result| {
+                let baseline = Baseline {
+                    median: result.median,
+                    allocations: result.allocations,
+                };
+                (result.name.clone(), baseline)
+            })
+            .collect();
+        Baselines { benchmarks }
+    }
+
+    /// Path of the baseline file of a benchmark suite.
+    pub fn path(suite: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/benchmarks/baselines")
+            .join(format!("{suite}.tsv"))
+    }
+
+    /// Save the baselines to a file, one benchmark per line: name, median in nanoseconds and allocations.
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut contents = String::new();
+        for (name, baseline) in &self.benchmarks {
+            contents.push_str(&format!(
+                "{}\t{}\t{}\n",
+                name,
+                baseline.median.as_nanos(),
+                baseline.allocations
+            ));
+        }
+        std::fs::write(path, contents)
+    }
+
+    /// Load baselines saved with `save`.
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Baselines> {
+        let invalid = |line: &str| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid baseline line: {}", line),
+            )
+        };
+        let mut benchmarks = BTreeMap::new();
+        for line in std::fs::read_to_string(path)?.lines() {
+            let mut fields = line.split('\t');
+            let (Some(name), Some(median), Some(allocations), None) =
+                (fields.next(), fields.next(), fields.next(), fields.next())
+            else {
+                return Err(invalid(line));
+            };
+            let baseline = Baseline {
+                median: Duration::from_nanos(median.parse().map_err(|_| invalid(line))?),
+                allocations: allocations.parse().map_err(|_| invalid(line))?,
+            };
+            benchmarks.insert(name.to_string(), baseline);
+        }
+        Ok(Baselines { benchmarks })
+    }
+
+    pub fn compare(&self, results: &[BenchResult]) -> Vec<BaselineComparison> {
+        results
+            .iter()
+            .map(|result| BaselineComparison {
+                name: result.name.clone(),
+                baseline: self.benchmarks.get(&result.name).copied(),
+                median: result.median,
+                allocations: result.allocations,
+            })
+            .collect()
+    }
+}
+
+/// Compare the results of a benchmark suite with its stored baselines and print the differences.
+/// With `BANDSIM_SAVE_BASELINES=1` the results are saved as the new baselines instead.
+pub fn compare_with_baselines(suite: &str, results: &[BenchResult]) {
+    let path = Baselines::path(suite);
+    if std::env::var_os(SAVE_BASELINES_VAR).is_some() {
+        Baselines::from_results(results).save(&path).unwrap();
+        println!("Saved baselines to {}", path.display());
+        return;
+    }
+    let baselines = match Baselines::load(&path) {
+        Ok(baselines) => baselines,
+        Err(err) => {
+            println!(
+                "No baselines for {suite} ({err}), run with {SAVE_BASELINES_VAR}=1 to save them"
+            );
+            return;
+        }
+    };
+    let name_width = results.iter().map(|r| r.name.len()).max().unwrap_or(0);
+    println!(
+        "{:<w$} | {:>12} | {:>12} | {:>8} | allocations",
+        "benchmark",
+        "baseline",
+        "median",
+        "change",
+        w = name_width
+    );
+    for comparison in baselines.compare(results) {
+        let Some(baseline) = comparison.baseline else {
+            println!("{:<w$} | no baseline", comparison.name, w = name_width);
+            continue;
+        };
+        println!(
+            "{:<w$} | {:>12.2?} | {:>12.2?} | {:>+7.1}% | {} -> {}{}",
+            comparison.name,
+            baseline.median,
+            comparison.median,
+            (comparison.median_ratio().unwrap() - 1.0) * 100.0,
+            baseline.allocations,
+            comparison.allocations,
+            if comparison.is_regression(0.1) {
+                "  REGRESSION"
+            } else {
+                ""
+            },
+            w = name_width
+        );
+    }
+}
+
+#[test]
+fn baselines_roundtrip() {
+    let results = vec![BenchResult {
+        name: "scheduler run, 4 shards".to_string(),
+        iterations: 10,
+        median: Duration::from_micros(150),
+        mean: Duration::from_micros(170),
+        min: Duration::from_micros(140),
+        allocations: 12,
+    }];
+    let baselines = Baselines::from_results(&results);
+    let path = std::env::temp_dir().join(format!(
+        "bandsim_baselines_roundtrip_{}.tsv",
+        std::process::id()
+    ));
+    baselines.save(&path).unwrap();
+    assert_eq!(Baselines::load(&path).unwrap(), baselines);
+    std::fs::remove_file(&path).unwrap();
+
+    let mut slower = results.clone();
+    slower[0].median = Duration::from_micros(300);
+    let comparison = &baselines.compare(&slower)[0];
+    assert_eq!(comparison.median_ratio(), Some(2.0));
+    assert!(comparison.is_regression(0.1));
+    assert!(!baselines.compare(&results)[0].is_regression(0.1));
+}
@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+
+use crate::bandwidth_scheduler::distribute_remaining::distribute_remaining_bandwidth;
+use crate::chain::{
+    Receipt, ReceiptPriority, ShardUId, SimulationConfig, MAX_SHARD_BANDWIDTH, MIN_RECEIPT_SIZE,
+};
+use crate::simulation::outgoing_queue::OutgoingQueue;
+
+use super::baseline::compare_with_baselines;
+use super::{bench, print_results};
+
+fn receipt(size: usize, priority: ReceiptPriority) -> Receipt {
+    Receipt {
+        size,
+        tag: None,
+        priority,
+    }
+}
+
+/// Outgoing queue with `num_receipts` small receipts, all with the same priority unless `mixed_priorities` is set.
+fn filled_queue(num_receipts: usize, mixed_priorities: bool) -> OutgoingQueue {
+    let mut queue = OutgoingQueue::new(ShardUId::new(0));
+    for i in 0..num_receipts {
+        let priority = if mixed_priorities {
+            ReceiptPriority::ALL[i % ReceiptPriority::ALL.len()]
+        } else {
+            ReceiptPriority::Normal
+        };
+        queue.push(receipt(MIN_RECEIPT_SIZE, priority));
+    }
+    queue
+}
+
+/// Spare bandwidth of every shard, uneven so that the algorithm has some work to do.
+fn spare_bandwidth(num_shards: usize) -> BTreeMap<ShardUId, usize> {
+    (0..num_shards)
+        .map(|i| (ShardUId::new(i), MAX_SHARD_BANDWIDTH / (i + 1)))
+        .collect()
+}
+
+/// Measure how long it takes to make a bandwidth request from an outgoing queue with many receipts.
+/// cargo test --release bench_bandwidth_request_construction -- --ignored --nocapture
+#[ignore]
+#[test]
+fn bench_bandwidth_request_construction() {
+    let mut results = Vec::new();
+    for num_receipts in [100, 10_000] {
+        let queue = filled_queue(num_receipts, false);
+        results.push(bench(
+            &format!("bandwidth request, one priority, {num_receipts} receipts"),
+            1000,
+            || {
+                std::hint::black_box(
+                    queue.make_bandwidth_request(100_000, &SimulationConfig::default()),
+                );
+            },
+        ));
+        let queue = filled_queue(num_receipts, true);
+        results.push(bench(
+            &format!("bandwidth request, mixed priorities, {num_receipts} receipts"),
+            1000,
+            || {
+                std::hint::black_box(
+                    queue.make_bandwidth_request(100_000, &SimulationConfig::default()),
+                );
+            },
+        ));
+    }
+    print_results(&results);
+    compare_with_baselines("bandwidth_request", &results);
+}
+
+/// Measure how long it takes to push and pop receipts from an outgoing queue.
+/// cargo test --release bench_outgoing_queue -- --ignored --nocapture
+#[ignore]
+#[test]
+fn bench_outgoing_queue() {
+    let mut results = Vec::new();
+    for mixed_priorities in [false, true] {
+        let name = if mixed_priorities {
+            "mixed priorities"
+        } else {
+            "one priority"
+        };
+        results.push(bench(
+            &format!("outgoing queue, push and pop 10000 receipts, {name}"),
+            100,
+            || {
+                let mut queue = filled_queue(10_000, mixed_priorities);
+                while let Some(receipt) = queue.pop() {
+                    std::hint::black_box(receipt);
+                }
+            },
+        ));
+    }
+    print_results(&results);
+    compare_with_baselines("outgoing_queue", &results);
+}
+
+/// Measure how long it takes to distribute the remaining bandwidth between all links.
+/// cargo test --release bench_distribute_remaining -- --ignored --nocapture
+#[ignore]
+#[test]
+fn bench_distribute_remaining() {
+    let mut results = Vec::new();
+    for num_shards in [4, 16, 64] {
+        let left = spare_bandwidth(num_shards);
+        let right = spare_bandwidth(num_shards);
+        results.push(bench(
+            &format!("distribute remaining, {num_shards} shards"),
+            1000,
+            || {
+                std::hint::black_box(distribute_remaining_bandwidth(&left, &right));
+            },
+        ));
+    }
+    print_results(&results);
+    compare_with_baselines("distribute_remaining", &results);
+}
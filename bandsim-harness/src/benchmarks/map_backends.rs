@@ -0,0 +1,186 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::BuildHasherDefault;
+
+use rand::seq::SliceRandom;
+
+use crate::chain::{ShardLink, ShardUId, MAX_SHARD_BANDWIDTH};
+use crate::rng::{rng_from_seed, DefaultRng};
+
+use super::{bench, print_results};
+
+/// A map from ShardLink to a number, like the allowances and grants in BandwidthScheduler.
+trait LinkMap {
+    fn new(shards: &[ShardUId]) -> Self;
+    fn get(&self, link: &ShardLink) -> usize;
+    fn set(&mut self, link: &ShardLink, value: usize);
+}
+
+impl LinkMap for BTreeMap<ShardLink, usize> {
+    fn new(_shards: &[ShardUId]) -> Self {
+        BTreeMap::new()
+    }
+
+    fn get(&self, link: &ShardLink) -> usize {
+        BTreeMap::get(self, link).copied().unwrap_or(0)
+    }
+
+    fn set(&mut self, link: &ShardLink, value: usize) {
+        self.insert(*link, value);
+    }
+}
+
+/// HashMap with a deterministic hasher - all shards must iterate over the map in the same order.
+type DeterministicHashMap = HashMap<ShardLink, usize, BuildHasherDefault<DefaultHasher>>;
+
+impl LinkMap for DeterministicHashMap {
+    fn new(_shards: &[ShardUId]) -> Self {
+        DeterministicHashMap::default()
+    }
+
+    fn get(&self, link: &ShardLink) -> usize {
+        HashMap::get(self, link).copied().unwrap_or(0)
+    }
+
+    fn set(&mut self, link: &ShardLink, value: usize) {
+        self.insert(*link, value);
+    }
+}
+
+/// Flat vector indexed by `from * num_shards + to`. Assumes that shard ids are 0..num_shards.
+struct VecLinkMap {
+    num_shards: usize,
+    values: Vec<usize>,
+}
+
+impl VecLinkMap {
+    fn index(&self, link: &ShardLink) -> usize {
+        link.from.shard_id as usize * self.num_shards + link.to.shard_id as usize
+    }
+}
+
+impl LinkMap for VecLinkMap {
+    fn new(shards: &[ShardUId]) -> Self {
+        VecLinkMap {
+            num_shards: shards.len(),
+            values: vec![0; shards.len() * shards.len()],
+        }
+    }
+
+    fn get(&self, link: &ShardLink) -> usize {
+        self.values[self.index(link)]
+    }
+
+    fn set(&mut self, link: &ShardLink, value: usize) {
+        let index = self.index(link);
+        self.values[index] = value;
+    }
+}
+
+/// Access the maps the same way BandwidthScheduler does during `heights` heights:
+/// - Add allowance on all links
+/// - Grant base bandwidth on all links
+/// - Grant more bandwidth on random links that requested it, decreasing their allowance
+///
+/// Returns a checksum of all the grants, which allows to check that all backends behave the same.
+fn scheduler_like_workload<M: LinkMap>(
+    num_shards: usize,
+    heights: usize,
+    rng: &mut DefaultRng,
+) -> usize {
+    let shards: Vec<ShardUId> = (0..num_shards).map(ShardUId::new).collect();
+    let mut links = Vec::new();
+    for from in &shards {
+        for to in &shards {
+            links.push(ShardLink {
+                from: *from,
+                to: *to,
+            });
+        }
+    }
+
+    let mut checksum: usize = 0;
+    let mut allowances = M::new(&shards);
+    for _ in 0..heights {
+        let mut grants = M::new(&shards);
+        for link in &links {
+            let allowance = allowances.get(link) + MAX_SHARD_BANDWIDTH / num_shards;
+            allowances.set(link, allowance.min(MAX_SHARD_BANDWIDTH));
+        }
+        for link in &links {
+            grants.set(link, grants.get(link) + 1000);
+        }
+        for _ in 0..(links.len() * 4) {
+            let link = links.choose(rng).unwrap();
+            let allowance = allowances.get(link);
+            allowances.set(link, allowance.saturating_sub(50_000));
+            grants.set(link, grants.get(link) + 50_000);
+        }
+        for link in &links {
+            checksum = checksum.wrapping_mul(31).wrapping_add(grants.get(link));
+        }
+    }
+    checksum
+}
+
+#[test]
+fn map_backends_are_equivalent() {
+    for num_shards in [1, 2, 7] {
+        let btree = scheduler_like_workload::<BTreeMap<ShardLink, usize>>(
+            num_shards,
+            10,
+            &mut rng_from_seed(0),
+        );
+        let hash =
+            scheduler_like_workload::<DeterministicHashMap>(num_shards, 10, &mut rng_from_seed(0));
+        let vec = scheduler_like_workload::<VecLinkMap>(num_shards, 10, &mut rng_from_seed(0));
+        assert_eq!(btree, hash);
+        assert_eq!(btree, vec);
+    }
+}
+
+/// Compare the performance of different map backends for allowances and grants.
+/// cargo test --release bench_map_backends -- --ignored --nocapture
+#[ignore]
+#[test]
+fn bench_map_backends() {
+    let mut results = Vec::new();
+    for num_shards in [4, 16, 64] {
+        let heights = 10;
+        let iterations = 100;
+        results.push(bench(
+            &format!("BTreeMap, {num_shards} shards"),
+            iterations,
+            || {
+                std::hint::black_box(scheduler_like_workload::<BTreeMap<ShardLink, usize>>(
+                    num_shards,
+                    heights,
+                    &mut rng_from_seed(0),
+                ));
+            },
+        ));
+        results.push(bench(
+            &format!("HashMap, {num_shards} shards"),
+            iterations,
+            || {
+                std::hint::black_box(scheduler_like_workload::<DeterministicHashMap>(
+                    num_shards,
+                    heights,
+                    &mut rng_from_seed(0),
+                ));
+            },
+        ));
+        results.push(bench(
+            &format!("Vec, {num_shards} shards"),
+            iterations,
+            || {
+                std::hint::black_box(scheduler_like_workload::<VecLinkMap>(
+                    num_shards,
+                    heights,
+                    &mut rng_from_seed(0),
+                ));
+            },
+        ));
+    }
+    print_results(&results);
+}
@@ -0,0 +1,97 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+pub mod baseline;
+pub mod hot_paths;
+pub mod map_backends;
+pub mod scheduler;
+
+/// Timing of a piece of code that was run many times.
+#[derive(Clone, Debug)]
+pub struct BenchResult {
+    pub name: String,
+    pub iterations: usize,
+    pub median: Duration,
+    pub mean: Duration,
+    pub min: Duration,
+    /// Average number of heap allocations made during a single iteration
+    pub allocations: usize,
+}
+
+/// Allocator which counts the allocations made on every thread, which allows benchmarks to measure
+/// how many allocations the benchmarked code makes, even when other tests run in parallel.
+struct CountingAllocator;
+
+thread_local! {
+    static THREAD_ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = THREAD_ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL_ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Number of allocations made on the current thread so far.
+fn thread_allocations() -> usize {
+    THREAD_ALLOCATIONS.with(|count| count.get())
+}
+
+/// Run `f` `iterations` times and measure how long it takes.
+pub fn bench(name: &str, iterations: usize, mut f: impl FnMut()) -> BenchResult {
+    assert!(iterations > 0);
+
+    let mut timings = Vec::with_capacity(iterations);
+    let mut allocations = 0;
+    for _ in 0..iterations {
+        let allocations_before = thread_allocations();
+        let start_time = Instant::now();
+        f();
+        timings.push(start_time.elapsed());
+        allocations += thread_allocations() - allocations_before;
+    }
+    timings.sort();
+
+    BenchResult {
+        name: name.to_string(),
+        iterations,
+        median: timings[timings.len() / 2],
+        mean: timings.iter().sum::<Duration>() / iterations as u32,
+        min: timings[0],
+        allocations: allocations / iterations,
+    }
+}
+
+pub fn print_results(results: &[BenchResult]) {
+    let name_width = results.iter().map(|r| r.name.len()).max().unwrap_or(0);
+    println!(
+        "{:<w$} | {:>12} | {:>12} | {:>12} | {:>11} | iterations",
+        "benchmark",
+        "median",
+        "mean",
+        "min",
+        "allocations",
+        w = name_width
+    );
+    for result in results {
+        println!(
+            "{:<w$} | {:>12.2?} | {:>12.2?} | {:>12.2?} | {:>11} | {}",
+            result.name,
+            result.median,
+            result.mean,
+            result.min,
+            result.allocations,
+            result.iterations,
+            w = name_width
+        );
+    }
+}
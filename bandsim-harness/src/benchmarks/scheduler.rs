@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::bandwidth_request::{BandwidthRequest, BandwidthRequestBitmap};
+use crate::bandwidth_scheduler::input::SchedulerInput;
+use crate::bandwidth_scheduler::{BandwidthScheduler, SchedulerParams};
+use crate::chain::{Block, Chunk, CongestionInfo, ShardUId, SimulationConfig};
+use crate::rng::rng_from_seed;
+use crate::shard_layout::ShardLayout;
+
+use super::baseline::compare_with_baselines;
+use super::{bench, print_results};
+
+/// A block in which every shard requests every possible bandwidth option on every link.
+/// This is the worst case for the scheduler - it has to process the maximum number of options.
+pub fn dense_requests_block(num_shards: usize) -> Block {
+    let shards: Vec<ShardUId> = (0..num_shards).map(ShardUId::new).collect();
+    let mut all_options = BandwidthRequestBitmap::new();
+    for i in 0..all_options.len() {
+        all_options.set_bit(i, true);
+    }
+
+    let mut chunks = BTreeMap::new();
+    for shard in &shards {
+        let bandwidth_requests = shards
+            .iter()
+            .map(|to_shard| BandwidthRequest {
+                to_shard: *to_shard,
+                grant_options_bitmap: all_options.clone(),
+            })
+            .collect();
+        let chunk = Chunk {
+            prev_incoming_receipts_size: 0,
+            prev_outgoing_receipts_size: BTreeMap::new(),
+            prev_unstoppable_receipts_size: BTreeMap::new(),
+            bandwidth_requests,
+            congestion_info: CongestionInfo::default(),
+            receiver_quotas: BTreeMap::new(),
+        };
+        chunks.insert(*shard, Some(chunk));
+    }
+    Block {
+        height: 1,
+        shard_layout: Arc::new(ShardLayout::new(0, shards)),
+        chunks,
+    }
+}
+
+#[test]
+fn dense_requests_dont_crash_scheduler() {
+    let block = dense_requests_block(8);
+    let mut scheduler =
+        BandwidthScheduler::new(SchedulerParams::default(), SimulationConfig::default());
+    for height in 0..10 {
+        scheduler.run(&block, &mut rng_from_seed(height));
+    }
+}
+
+/// `SchedulerInput` built by hand gives the same grants as the block it describes.
+#[test]
+fn hand_built_input_matches_block() {
+    let block = dense_requests_block(3);
+    let shards: Vec<ShardUId> = (0..3).map(ShardUId::new).collect();
+    let mut all_options = BandwidthRequestBitmap::new();
+    for i in 0..all_options.len() {
+        all_options.set_bit(i, true);
+    }
+    let shard_requests: Vec<BandwidthRequest> = shards
+        .iter()
+        .map(|to_shard| BandwidthRequest {
+            to_shard: *to_shard,
+            grant_options_bitmap: all_options.clone(),
+        })
+        .collect();
+    let input = SchedulerInput {
+        shards: &shards,
+        chunk_present: shards.iter().map(|shard| (*shard, true)).collect(),
+        requests: shards
+            .iter()
+            .map(|shard| (*shard, shard_requests.as_slice()))
+            .collect(),
+    };
+
+    let mut block_scheduler =
+        BandwidthScheduler::new(SchedulerParams::default(), SimulationConfig::default());
+    let mut input_scheduler =
+        BandwidthScheduler::new(SchedulerParams::default(), SimulationConfig::default());
+    for height in 0..5 {
+        assert_eq!(
+            block_scheduler.run(&block, &mut rng_from_seed(height)),
+            input_scheduler.run(&input, &mut rng_from_seed(height))
+        );
+    }
+    assert_eq!(block_scheduler.allowances(), input_scheduler.allowances());
+}
+
+/// Measure how long it takes to run the scheduler when all links request all options.
+/// cargo test --release bench_scheduler_dense_requests -- --ignored --nocapture
+#[ignore]
+#[test]
+fn bench_scheduler_dense_requests() {
+    let mut results = Vec::new();
+    for num_shards in [4, 16, 32, 64] {
+        let block = dense_requests_block(num_shards);
+        let mut scheduler =
+            BandwidthScheduler::new(SchedulerParams::default(), SimulationConfig::default());
+        let mut height = 0;
+        results.push(bench(
+            &format!("scheduler run, dense requests, {num_shards} shards"),
+            50,
+            || {
+                height += 1;
+                std::hint::black_box(scheduler.run(&block, &mut rng_from_seed(height)));
+            },
+        ));
+    }
+    print_results(&results);
+    compare_with_baselines("scheduler", &results);
+}
+
+/// Number of shards for which the scheduler's time budget is checked.
+const BUDGET_NUM_SHARDS: usize = 16;
+/// The scheduler runs during block processing, it must finish within this time even in the worst case.
+const SCHEDULER_TIME_BUDGET: Duration = Duration::from_millis(2);
+
+/// Check that the median time of running the scheduler with dense requests is within the budget.
+/// Timings are meaningless in debug mode, run it in release mode:
+/// cargo test --release scheduler_within_time_budget -- --ignored --nocapture
+#[ignore]
+#[test]
+fn scheduler_within_time_budget() {
+    let block = dense_requests_block(BUDGET_NUM_SHARDS);
+    let mut scheduler =
+        BandwidthScheduler::new(SchedulerParams::default(), SimulationConfig::default());
+    let mut height = 0;
+    let result = bench(
+        &format!("scheduler run, dense requests, {BUDGET_NUM_SHARDS} shards"),
+        200,
+        || {
+            height += 1;
+            std::hint::black_box(scheduler.run(&block, &mut rng_from_seed(height)));
+        },
+    );
+    print_results(std::slice::from_ref(&result));
+    assert!(
+        result.median <= SCHEDULER_TIME_BUDGET,
+        "Scheduler took {:?} (median), budget is {:?}",
+        result.median,
+        SCHEDULER_TIME_BUDGET
+    );
+}
@@ -0,0 +1,169 @@
+use std::ops::Range;
+
+use crate::simulation::builder::SimulationBuilder;
+use crate::validation::TestStats;
+
+/// A function which creates the scenario for the given seed.
+type ScenarioFn = Box<dyn Fn(u64) -> SimulationBuilder + Sync>;
+
+/// Runs the same scenario with many seeds and summarizes how utilization and fairness vary between them.
+/// Replaces writing a separate test for every seed, a single unlucky seed shows up as the min or max.
+pub struct BatchRunner {
+    scenario_name: String,
+    make_scenario: ScenarioFn,
+    seeds: Range<u64>,
+    steps: usize,
+    threads: usize,
+}
+
+/// Stats of every run in the batch and their summary.
+pub struct BatchResult {
+    pub scenario_name: String,
+    /// Stats of the run with every seed, sorted by seed.
+    pub runs: Vec<(u64, TestStats)>,
+    /// Summary of `bandwidth_utilization.utilization` of all runs.
+    pub utilization: Summary,
+    /// Summary of the max sent/min sent ratio of all runs.
+    pub fairness: Summary,
+}
+
+/// Mean, extremes and (population) standard deviation of a metric over all runs in a batch.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Summary {
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+    pub stddev: f64,
+}
+
+impl Summary {
+    pub fn new(values: &[f64]) -> Summary {
+        assert!(!values.is_empty(), "Can't summarize an empty batch!");
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let variance = values
+            .iter()
+            .map(|value| (value - mean).powi(2))
+            .sum::<f64>()
+            / values.len() as f64;
+        Summary {
+            mean,
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            stddev: variance.sqrt(),
+        }
+    }
+}
+
+impl BatchRunner {
+    /// Run the scenario with many seeds. `make_scenario` creates the scenario for a single seed.
+    pub fn new(
+        scenario_name: &str,
+        make_scenario: impl Fn(u64) -> SimulationBuilder + Sync + 'static,
+    ) -> Self {
+        BatchRunner {
+            scenario_name: scenario_name.to_string(),
+            make_scenario: Box::new(make_scenario),
+            seeds: 0..10,
+            steps: 1000,
+            threads: 1,
+        }
+    }
+
+    /// Seeds to run the scenario with.
+    pub fn seeds(mut self, seeds: Range<u64>) -> Self {
+        assert!(!seeds.is_empty(), "Batch without any seeds!");
+        self.seeds = seeds;
+        self
+    }
+
+    /// For how many blocks every simulation should run.
+    pub fn steps(mut self, steps: usize) -> Self {
+        self.steps = steps;
+        self
+    }
+
+    /// Run the simulations on this many threads.
+    pub fn threads(mut self, threads: usize) -> Self {
+        assert!(threads > 0, "Batch runner needs at least one thread!");
+        self.threads = threads;
+        self
+    }
+
+    pub fn run(&self) -> BatchResult {
+        let seeds: Vec<u64> = self.seeds.clone().collect();
+        let mut runs: Vec<(u64, TestStats)> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.threads)
+                .map(|thread_idx| {
+                    let seeds = &seeds;
+                    scope.spawn(move || {
+                        seeds
+                            .iter()
+                            .copied()
+                            .skip(thread_idx)
+                            .step_by(self.threads)
+                            .map(|seed| (seed, self.run_seed(seed)))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        });
+        runs.sort_by_key(|(seed, _stats)| *seed);
+
+        let utilization: Vec<f64> = runs
+            .iter()
+            .map(|(_seed, stats)| stats.bandwidth_utilization.utilization)
+            .collect();
+        let fairness: Vec<f64> = runs
+            .iter()
+            .map(|(_seed, stats)| stats.max_min_ratio.ratio)
+            .collect();
+        BatchResult {
+            scenario_name: self.scenario_name.clone(),
+            runs,
+            utilization: Summary::new(&utilization),
+            fairness: Summary::new(&fairness),
+        }
+    }
+
+    fn run_seed(&self, seed: u64) -> TestStats {
+        println!(
+            "===================== Batch {} seed = {} =====================",
+            self.scenario_name, seed
+        );
+        let simulation_run = (self.make_scenario)(seed).build().run_for(self.steps);
+        TestStats::new(&simulation_run)
+    }
+}
+
+impl BatchResult {
+    /// The seed with the worst fairness (the biggest max sent/min sent ratio).
+    pub fn least_fair_seed(&self) -> u64 {
+        self.runs
+            .iter()
+            .max_by(|(_, a), (_, b)| a.max_min_ratio.ratio.total_cmp(&b.max_min_ratio.ratio))
+            .map(|(seed, _stats)| *seed)
+            .unwrap()
+    }
+
+    pub fn print_summary(&self) {
+        println!("Batch {} ({} seeds):", self.scenario_name, self.runs.len());
+        for (name, summary) in [
+            ("utilization", &self.utilization),
+            ("fairness", &self.fairness),
+        ] {
+            println!(
+                "  {:>11}: mean = {:.2}%, min = {:.2}%, max = {:.2}%, stddev = {:.2}%",
+                name,
+                summary.mean * 100.0,
+                summary.min * 100.0,
+                summary.max * 100.0,
+                summary.stddev * 100.0
+            );
+        }
+        println!("  least fair seed: {}", self.least_fair_seed());
+    }
+}
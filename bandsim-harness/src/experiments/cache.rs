@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::simulation::builder::SimulationBuilder;
+
+/// On-disk cache of metric values measured in finished runs, so that sweeps which reuse the same
+/// configuration don't simulate it again.
+/// Entries are keyed by a hash of the scenario name, the builder configuration, the number of steps,
+/// the measured metrics and the code version. The code version is derived from the test binary, any
+/// change in the code invalidates all entries.
+pub struct ResultCache {
+    dir: PathBuf,
+}
+
+/// Identifies a run whose results can be cached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CacheKey(pub u64);
+
+impl ResultCache {
+    /// Cache stored in `dir`, one file per entry. The directory is created when the first entry is stored.
+    pub fn new(dir: impl Into<PathBuf>) -> ResultCache {
+        ResultCache { dir: dir.into() }
+    }
+
+    /// Key of a run of the scenario created by `builder`.
+    /// Receipt senders can't be described by the builder, `scenario` has to identify them, e.g. the name of
+    /// the scenario and the value of the swept parameter.
+    pub fn key(
+        scenario: &str,
+        builder: &SimulationBuilder,
+        steps: usize,
+        metric_names: &[String],
+    ) -> CacheKey {
+        let description = format!(
+            "{}\n{}\n{}\n{:?}\n{}",
+            code_version(),
+            scenario,
+            builder.config_fingerprint(),
+            metric_names,
+            steps
+        );
+        CacheKey(fnv1a_hash(description.as_bytes()))
+    }
+
+    fn path(&self, key: CacheKey) -> PathBuf {
+        self.dir.join(format!("{:016x}.tsv", key.0))
+    }
+
+    /// Metric values stored under the key, `None` when the run isn't in the cache.
+    pub fn get(&self, key: CacheKey) -> Option<BTreeMap<String, f64>> {
+        let contents = std::fs::read_to_string(self.path(key)).ok()?;
+        let mut metrics = BTreeMap::new();
+        for line in contents.lines() {
+            // A damaged entry is treated like a missing one, the run will be simulated again.
+            let (name, value) = line.split_once('\t')?;
+            metrics.insert(name.to_string(), value.parse().ok()?);
+        }
+        Some(metrics)
+    }
+
+    pub fn insert(&self, key: CacheKey, metrics: &BTreeMap<String, f64>) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let mut contents = String::new();
+        for (name, value) in metrics {
+            // Display of f64 is the shortest representation which parses back to the same value
+            contents.push_str(&format!("{}\t{}\n", name, value));
+        }
+        std::fs::write(self.path(key), contents)
+    }
+}
+
+/// Version of the code that produced the results. The test binary is rebuilt whenever the code changes,
+/// so its size and modification time identify the version.
+fn code_version() -> String {
+    let exe_metadata = std::env::current_exe().and_then(std::fs::metadata);
+    let exe_version = match exe_metadata {
+        Ok(metadata) => format!("{} {:?}", metadata.len(), metadata.modified().ok()),
+        Err(_) => "unknown".to_string(),
+    };
+    format!("{} {}", env!("CARGO_PKG_VERSION"), exe_version)
+}
+
+/// FNV-1a, unlike `DefaultHasher` it's guaranteed to stay the same between Rust versions.
+fn fnv1a_hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
@@ -0,0 +1,234 @@
+use std::ops::Range;
+
+use crate::bandwidth_scheduler::SchedulerParams;
+use crate::simulation::builder::SimulationBuilder;
+use crate::validation::TestStats;
+
+/// A function which creates the workload for the given random seed.
+type WorkloadFactory = Box<dyn Fn(u64) -> SimulationBuilder>;
+
+/// A function which extracts a single number from the stats of a finished run.
+type MetricFn = Box<dyn Fn(&TestStats) -> f64>;
+
+struct Metric {
+    name: String,
+    higher_is_better: bool,
+    f: MetricFn,
+}
+
+/// Runs the same workload under the old and new scheduler configuration, with many random seeds,
+/// and compares the metrics. Used to evaluate protocol changes before proposing them.
+pub struct UpgradeComparison {
+    make_workload: WorkloadFactory,
+    old_params: SchedulerParams,
+    new_params: SchedulerParams,
+    seeds: Range<u64>,
+    steps: usize,
+    metrics: Vec<Metric>,
+    regression_tolerance: f64,
+}
+
+/// Comparison of the old and new configuration, one entry per metric.
+#[derive(Clone, Debug)]
+pub struct ComparisonReport {
+    pub old_params: SchedulerParams,
+    pub new_params: SchedulerParams,
+    pub num_seeds: usize,
+    pub metrics: Vec<MetricComparison>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MetricComparison {
+    pub name: String,
+    pub higher_is_better: bool,
+    /// Mean over all seeds with the old configuration
+    pub old_mean: f64,
+    /// Mean over all seeds with the new configuration
+    pub new_mean: f64,
+    /// For how many seeds the new configuration was worse than the old one
+    pub worse_seeds: usize,
+    /// The new configuration is worse by more than the regression tolerance
+    pub is_regression: bool,
+}
+
+impl MetricComparison {
+    pub fn delta(&self) -> f64 {
+        self.new_mean - self.old_mean
+    }
+
+    /// Change relative to the old value, 0 when the old value is 0.
+    pub fn relative_delta(&self) -> f64 {
+        if self.old_mean == 0.0 {
+            return 0.0;
+        }
+        self.delta() / self.old_mean.abs()
+    }
+}
+
+impl UpgradeComparison {
+    /// Compare `old_params` with `new_params` on the workload created by `make_workload`.
+    pub fn new(
+        old_params: SchedulerParams,
+        new_params: SchedulerParams,
+        make_workload: impl Fn(u64) -> SimulationBuilder + 'static,
+    ) -> Self {
+        UpgradeComparison {
+            make_workload: Box::new(make_workload),
+            old_params,
+            new_params,
+            seeds: 0..10,
+            steps: 1000,
+            metrics: Vec::new(),
+            regression_tolerance: 0.01,
+        }
+    }
+
+    /// Add a metric that will be compared.
+    pub fn metric(
+        mut self,
+        name: &str,
+        higher_is_better: bool,
+        f: impl Fn(&TestStats) -> f64 + 'static,
+    ) -> Self {
+        self.metrics.push(Metric {
+            name: name.to_string(),
+            higher_is_better,
+            f: Box::new(f),
+        });
+        self
+    }
+
+    /// Random seeds with which the workload is run.
+    pub fn seeds(mut self, seeds: Range<u64>) -> Self {
+        self.seeds = seeds;
+        self
+    }
+
+    /// For how many blocks every simulation should run.
+    pub fn steps(mut self, steps: usize) -> Self {
+        self.steps = steps;
+        self
+    }
+
+    /// A metric is a regression when the new mean is worse than the old one by more than this fraction.
+    pub fn regression_tolerance(mut self, tolerance: f64) -> Self {
+        self.regression_tolerance = tolerance;
+        self
+    }
+
+    /// Value of every metric for every seed.
+    fn measure(&self, params: &SchedulerParams) -> Vec<Vec<f64>> {
+        let mut values = vec![Vec::new(); self.metrics.len()];
+        for seed in self.seeds.clone() {
+            println!(
+                "===================== Comparison seed {}, {:?} =====================",
+                seed, params
+            );
+            let simulation_run = (self.make_workload)(seed)
+                .random_seed(seed)
+                .scheduler_params(params.clone())
+                .build()
+                .run_for(self.steps);
+            let stats = TestStats::new(&simulation_run);
+            for (metric, metric_values) in self.metrics.iter().zip(values.iter_mut()) {
+                metric_values.push((metric.f)(&stats));
+            }
+        }
+        values
+    }
+
+    pub fn run(self) -> ComparisonReport {
+        assert!(!self.metrics.is_empty(), "Comparison without any metrics!");
+        assert!(!self.seeds.is_empty(), "Comparison without any seeds!");
+
+        let old_values = self.measure(&self.old_params);
+        let new_values = self.measure(&self.new_params);
+        let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len() as f64;
+
+        let mut metrics = Vec::new();
+        for ((metric, old), new) in self.metrics.iter().zip(&old_values).zip(&new_values) {
+            let is_worse = |old: f64, new: f64| {
+                if metric.higher_is_better {
+                    new < old
+                } else {
+                    new > old
+                }
+            };
+            let old_mean = mean(old);
+            let new_mean = mean(new);
+            let tolerance = old_mean.abs() * self.regression_tolerance;
+            let is_regression = if metric.higher_is_better {
+                new_mean < old_mean - tolerance
+            } else {
+                new_mean > old_mean + tolerance
+            };
+            metrics.push(MetricComparison {
+                name: metric.name.clone(),
+                higher_is_better: metric.higher_is_better,
+                old_mean,
+                new_mean,
+                worse_seeds: old
+                    .iter()
+                    .zip(new)
+                    .filter(|(o, n)| is_worse(**o, **n))
+                    .count(),
+                is_regression,
+            });
+        }
+
+        ComparisonReport {
+            old_params: self.old_params,
+            new_params: self.new_params,
+            num_seeds: (self.seeds.end - self.seeds.start) as usize,
+            metrics,
+        }
+    }
+}
+
+impl ComparisonReport {
+    pub fn regressions(&self) -> impl Iterator<Item = &MetricComparison> {
+        self.metrics.iter().filter(|metric| metric.is_regression)
+    }
+
+    pub fn has_regressions(&self) -> bool {
+        self.regressions().next().is_some()
+    }
+
+    /// Report in markdown, ready to be attached to a protocol change proposal.
+    pub fn to_markdown(&self) -> String {
+        let mut res = String::new();
+        res.push_str("## Scheduler upgrade comparison\n\n");
+        res.push_str(&format!("- old: `{:?}`\n", self.old_params));
+        res.push_str(&format!("- new: `{:?}`\n", self.new_params));
+        res.push_str(&format!("- seeds: {}\n\n", self.num_seeds));
+        res.push_str("| metric | old | new | delta | worse seeds | |\n");
+        res.push_str("|---|---|---|---|---|---|\n");
+        for metric in &self.metrics {
+            res.push_str(&format!(
+                "| {} ({}) | {:.4} | {:.4} | {:+.4} ({:+.2}%) | {}/{} | {} |\n",
+                metric.name,
+                if metric.higher_is_better {
+                    "higher is better"
+                } else {
+                    "lower is better"
+                },
+                metric.old_mean,
+                metric.new_mean,
+                metric.delta(),
+                metric.relative_delta() * 100.0,
+                metric.worse_seeds,
+                self.num_seeds,
+                if metric.is_regression {
+                    "**REGRESSION**"
+                } else {
+                    ""
+                }
+            ));
+        }
+        res
+    }
+
+    pub fn print(&self) {
+        println!("{}", self.to_markdown());
+    }
+}
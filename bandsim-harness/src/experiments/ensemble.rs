@@ -0,0 +1,109 @@
+use std::sync::Arc;
+
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::workload_trace::WorkloadTrace;
+use crate::validation::TestStats;
+
+/// A function which creates the simulation that all variants start from.
+type BaseFn = Box<dyn Fn() -> SimulationBuilder>;
+
+/// A function which changes the base simulation, e.g. sets other scheduler parameters.
+type VariantFn = Box<dyn Fn(SimulationBuilder) -> SimulationBuilder>;
+
+/// Runs several variants of a simulation on exactly the same workload. The workload trace is recorded
+/// from the base simulation and replayed in every variant, so the variants get the same receipts at the same
+/// heights even when their senders would react to the grants. Sharing the seed isn't enough for that.
+pub struct Ensemble {
+    make_base: BaseFn,
+    variants: Vec<(String, VariantFn)>,
+    steps: usize,
+}
+
+/// The shared trace and the stats of every variant, in the order in which the variants were added.
+pub struct EnsembleResult {
+    pub trace: Arc<WorkloadTrace>,
+    pub runs: Vec<(String, TestStats)>,
+}
+
+impl Ensemble {
+    /// `make_base` creates the simulation from which the workload is recorded. The variants are applied
+    /// to a new base simulation, so they have the same seed, missing chunks and blocks as the recorded run.
+    pub fn new(make_base: impl Fn() -> SimulationBuilder + 'static) -> Self {
+        Ensemble {
+            make_base: Box::new(make_base),
+            variants: Vec::new(),
+            steps: 1000,
+        }
+    }
+
+    /// Add a variant, `change` gets the base simulation and returns the simulation to compare.
+    pub fn variant(
+        mut self,
+        name: &str,
+        change: impl Fn(SimulationBuilder) -> SimulationBuilder + 'static,
+    ) -> Self {
+        assert!(
+            self.variants.iter().all(|(existing, _)| existing != name),
+            "There's already a variant named {}",
+            name
+        );
+        self.variants.push((name.to_string(), Box::new(change)));
+        self
+    }
+
+    /// For how many blocks every simulation should run.
+    pub fn steps(mut self, steps: usize) -> Self {
+        self.steps = steps;
+        self
+    }
+
+    pub fn run(&self) -> EnsembleResult {
+        assert!(!self.variants.is_empty(), "Ensemble without any variants!");
+        let trace = Arc::new(WorkloadTrace::record((self.make_base)(), self.steps));
+        let runs = self
+            .variants
+            .iter()
+            .map(|(name, change)| {
+                println!(
+                    "===================== Ensemble variant {} =====================",
+                    name
+                );
+                let simulation_run = change((self.make_base)())
+                    .replay_workload(trace.clone())
+                    .build()
+                    .run_for(self.steps);
+                trace.check_replayed(&simulation_run);
+                (name.clone(), TestStats::new(&simulation_run))
+            })
+            .collect();
+        EnsembleResult { trace, runs }
+    }
+}
+
+impl EnsembleResult {
+    pub fn stats(&self, name: &str) -> &TestStats {
+        self.runs
+            .iter()
+            .find(|(variant, _stats)| variant == name)
+            .map(|(_variant, stats)| stats)
+            .unwrap_or_else(|| panic!("No variant named {}", name))
+    }
+
+    pub fn print_summary(&self) {
+        println!(
+            "Ensemble of {} variants on a trace of {} receipts ({} bytes):",
+            self.runs.len(),
+            self.trace.total_receipts(),
+            self.trace.total_bytes()
+        );
+        for (name, stats) in &self.runs {
+            println!(
+                "  {}: utilization = {:.2}%, fairness = {:.2}%, max receipt age = {}",
+                name,
+                stats.bandwidth_utilization.utilization * 100.0,
+                stats.max_min_ratio.ratio * 100.0,
+                stats.max_receipt_age.age
+            );
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::panic::AssertUnwindSafe;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::bandwidth_scheduler::{
+    AllowanceIncrement, AllowancePolicy, ChunkSizeLimit, Maintenance, ReceiverQuotas,
+    SchedulerAlgorithm, SchedulerParams, ShardBandwidth,
+};
+use crate::chain::{ShardUId, SimulationConfig, MAX_RECEIPT_SIZE, MAX_SHARD_BANDWIDTH};
+use crate::rng::{rng_from_seed, DefaultRng};
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{
+    ConstantRateReceiptSender, FullSpeedReceiptSender, NoReceiptSender, OneSizeReceiptGenerator,
+    RandomSizeReceiptGenerator, ReceiptSender, TypicalReceiptGenerator,
+};
+use crate::simulation::SimulationRun;
+use crate::validation::ValidationLevel;
+
+use super::seed_hunter::panic_message;
+
+/// Receipt senders that the fuzzer puts on the links.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FuzzSender {
+    Idle,
+    SmallReceipts,
+    MaxSizeReceipts,
+    RandomSizeReceipts,
+    TypicalReceipts,
+    /// Sends 100kB receipts at a constant rate, regardless of the queue.
+    ConstantRate {
+        bytes_per_height: usize,
+    },
+}
+
+impl FuzzSender {
+    fn generate(rng: &mut DefaultRng) -> FuzzSender {
+        match rng.gen_range(0..6) {
+            0 => FuzzSender::Idle,
+            1 => FuzzSender::SmallReceipts,
+            2 => FuzzSender::MaxSizeReceipts,
+            3 => FuzzSender::RandomSizeReceipts,
+            4 => FuzzSender::TypicalReceipts,
+            _ => FuzzSender::ConstantRate {
+                bytes_per_height: rng.gen_range(0..=MAX_SHARD_BANDWIDTH),
+            },
+        }
+    }
+
+    fn make_sender(&self, config: &SimulationConfig) -> Box<dyn ReceiptSender> {
+        match *self {
+            FuzzSender::Idle => Box::new(NoReceiptSender),
+            FuzzSender::SmallReceipts => {
+                Box::new(FullSpeedReceiptSender(OneSizeReceiptGenerator {
+                    size: config.min_receipt_size,
+                }))
+            }
+            FuzzSender::MaxSizeReceipts => {
+                Box::new(FullSpeedReceiptSender(OneSizeReceiptGenerator {
+                    size: config.max_receipt_size,
+                }))
+            }
+            FuzzSender::RandomSizeReceipts => {
+                Box::new(FullSpeedReceiptSender(RandomSizeReceiptGenerator {
+                    size_range: config.min_receipt_size..=config.max_receipt_size,
+                }))
+            }
+            FuzzSender::TypicalReceipts => {
+                Box::new(FullSpeedReceiptSender(TypicalReceiptGenerator::new()))
+            }
+            FuzzSender::ConstantRate { bytes_per_height } => Box::new(ConstantRateReceiptSender {
+                generator: OneSizeReceiptGenerator { size: 100_000 },
+                bytes_per_height,
+            }),
+        }
+    }
+}
+
+/// A random but valid simulation configuration. It's plain data, so a failing case can be printed
+/// and rebuilt from the output. Faults which are meant to break the protocol (malicious requesters,
+/// byzantine senders, scheduler faults) are never generated, every case has to pass the invariants.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FuzzCase {
+    pub seed: u64,
+    pub num_shards: usize,
+    pub senders: BTreeMap<(usize, usize), FuzzSender>,
+    pub missing_chunk_probability: f64,
+    pub missing_block_probability: f64,
+    pub incoming_processing_limit: Option<usize>,
+    pub backpressure: Option<usize>,
+    /// (shard, heights, maintenance)
+    pub maintenance: Option<(usize, Range<usize>, Maintenance)>,
+    pub config: SimulationConfig,
+    pub scheduler_params: SchedulerParams,
+}
+
+impl FuzzCase {
+    /// Generate the case for the seed, the same seed always gives the same case.
+    pub fn generate(seed: u64) -> FuzzCase {
+        let mut rng = rng_from_seed(seed);
+        let num_shards = rng.gen_range(1..=6);
+        let mut senders = BTreeMap::new();
+        for from in 0..num_shards {
+            for to in 0..num_shards {
+                senders.insert((from, to), FuzzSender::generate(&mut rng));
+            }
+        }
+        // Every optional feature is turned on in some of the cases.
+        let sometimes = |rng: &mut DefaultRng| rng.gen_bool(0.3);
+
+        let missing_chunk_probability = if sometimes(&mut rng) {
+            rng.gen_range(0.0..0.3)
+        } else {
+            0.0
+        };
+        let missing_block_probability = if sometimes(&mut rng) {
+            rng.gen_range(0.0..0.2)
+        } else {
+            0.0
+        };
+        let incoming_processing_limit = sometimes(&mut rng)
+            .then(|| rng.gen_range(MAX_SHARD_BANDWIDTH / 8..=2 * MAX_SHARD_BANDWIDTH));
+        let backpressure = sometimes(&mut rng)
+            .then(|| rng.gen_range(MAX_SHARD_BANDWIDTH..=20 * MAX_SHARD_BANDWIDTH));
+        let maintenance = sometimes(&mut rng).then(|| {
+            let start = rng.gen_range(0..100);
+            let kind = *[
+                Maintenance::NoIncoming,
+                Maintenance::NoOutgoing,
+                Maintenance::NoTraffic,
+            ]
+            .choose(&mut rng)
+            .unwrap();
+            (
+                rng.gen_range(0..num_shards),
+                start..start + rng.gen_range(1..50),
+                kind,
+            )
+        });
+
+        // Every receipt has to fit into the capacities and the chunk.
+        let capacity =
+            |rng: &mut DefaultRng| rng.gen_range(MAX_RECEIPT_SIZE..=2 * MAX_SHARD_BANDWIDTH);
+        let config = SimulationConfig {
+            max_shard_incoming_bandwidth: sometimes(&mut rng).then(|| capacity(&mut rng)),
+            ..SimulationConfig::default()
+        };
+        let algorithm = match rng.gen_range(0..5) {
+            0 => SchedulerAlgorithm::Allowance,
+            1 => SchedulerAlgorithm::RandomPriority,
+            2 => SchedulerAlgorithm::RoundRobin,
+            3 => SchedulerAlgorithm::DeficitRoundRobin {
+                quantum: rng.gen_range(10_000..=MAX_SHARD_BANDWIDTH),
+            },
+            _ => SchedulerAlgorithm::MaxFlow,
+        };
+        let mut allowance = AllowancePolicy::for_config(&config);
+        if sometimes(&mut rng) {
+            allowance.max_allowance =
+                rng.gen_range(MAX_SHARD_BANDWIDTH / 10..=4 * MAX_SHARD_BANDWIDTH);
+        }
+        if sometimes(&mut rng) {
+            allowance.per_height_increment =
+                AllowanceIncrement::Fixed(rng.gen_range(1_000..=MAX_SHARD_BANDWIDTH));
+        }
+        let global_budget = sometimes(&mut rng)
+            .then(|| rng.gen_range(MAX_SHARD_BANDWIDTH..=num_shards * MAX_SHARD_BANDWIDTH));
+        let receiver_quotas = sometimes(&mut rng).then(|| ReceiverQuotas {
+            max_backlog: rng.gen_range(MAX_SHARD_BANDWIDTH..=10 * MAX_SHARD_BANDWIDTH),
+        });
+        let chunk_size_limit = sometimes(&mut rng).then(|| {
+            let transactions_size = rng.gen_range(0..=MAX_SHARD_BANDWIDTH / 4);
+            ChunkSizeLimit {
+                max_chunk_size: MAX_RECEIPT_SIZE
+                    + transactions_size
+                    + rng.gen_range(0..=MAX_SHARD_BANDWIDTH),
+                transactions_size,
+            }
+        });
+        let mut shard_bandwidth = BTreeMap::new();
+        for shard in 0..num_shards {
+            if sometimes(&mut rng) {
+                let bandwidth = ShardBandwidth {
+                    outgoing: capacity(&mut rng),
+                    incoming: capacity(&mut rng),
+                };
+                shard_bandwidth.insert(ShardUId::new(shard), bandwidth);
+            }
+        }
+        let scheduler_params = SchedulerParams {
+            allowance,
+            algorithm,
+            global_budget,
+            receiver_quotas,
+            chunk_size_limit,
+            shard_bandwidth,
+            ..SchedulerParams::for_config(&config)
+        };
+
+        FuzzCase {
+            seed,
+            num_shards,
+            senders,
+            missing_chunk_probability,
+            missing_block_probability,
+            incoming_processing_limit,
+            backpressure,
+            maintenance,
+            config,
+            scheduler_params,
+        }
+    }
+
+    /// The simulation described by the case, with all validation turned on.
+    pub fn builder(&self) -> SimulationBuilder {
+        let missing_chunk_probability = self.missing_chunk_probability;
+        let mut builder = SimulationBuilder::new(self.num_shards)
+            .random_seed(self.seed)
+            .validation_level(ValidationLevel::Paranoid)
+            .config(self.config)
+            .scheduler_params(self.scheduler_params.clone())
+            .missing_block_probability(self.missing_block_probability)
+            .missing_chunk_generator(move |_height, _shard_id, rng| {
+                rng.gen_bool(missing_chunk_probability)
+            });
+        for ((from, to), sender) in &self.senders {
+            builder = builder.receipt_sender(*from, *to, sender.make_sender(&self.config));
+        }
+        if let Some(limit) = self.incoming_processing_limit {
+            builder = builder.incoming_processing_limit(limit);
+        }
+        if let Some(queue_threshold) = self.backpressure {
+            builder = builder.backpressure(queue_threshold);
+        }
+        if let Some((shard, heights, maintenance)) = &self.maintenance {
+            builder = builder.maintenance(*shard, heights.clone(), *maintenance);
+        }
+        builder
+    }
+}
+
+/// Invariants which hold in every valid configuration, on top of the checks that the simulation
+/// does at every height with `ValidationLevel::Paranoid`. Unlike `TestStats` it doesn't look at
+/// fairness or utilization, a configuration can be valid and still perform badly.
+pub fn check_invariants(simulation_run: &SimulationRun) {
+    let simulation = &simulation_run.simulation;
+    assert!(
+        simulation.grant_violations.is_empty(),
+        "Honest senders exceeded their grants: {:?}",
+        simulation.grant_violations
+    );
+    assert!(
+        simulation.first_scheduler_divergence.is_none(),
+        "Scheduler state diverged: {:?}",
+        simulation.first_scheduler_divergence
+    );
+}
+
+/// A case which broke an invariant.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FuzzFailure {
+    pub case: FuzzCase,
+    pub message: String,
+}
+
+/// Run the cases generated from the seeds for `steps` heights each, returns the ones which broke an invariant.
+pub fn fuzz(seeds: Range<u64>, steps: usize) -> Vec<FuzzFailure> {
+    let mut failures = Vec::new();
+    for seed in seeds {
+        let case = FuzzCase::generate(seed);
+        println!(
+            "===================== Fuzz case seed = {} =====================",
+            seed
+        );
+        println!("{:?}", case);
+        let run_result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            check_invariants(&case.builder().build().run_for(steps));
+        }));
+        if let Err(payload) = run_result {
+            failures.push(FuzzFailure {
+                case,
+                message: panic_message(payload.as_ref()),
+            });
+        }
+    }
+    failures
+}
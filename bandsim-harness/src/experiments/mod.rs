@@ -0,0 +1,8 @@
+pub mod batch;
+pub mod cache;
+pub mod comparison;
+pub mod ensemble;
+pub mod fuzzer;
+pub mod partition;
+pub mod seed_hunter;
+pub mod sensitivity;
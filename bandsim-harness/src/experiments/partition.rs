@@ -0,0 +1,86 @@
+use std::ops::Range;
+
+use crate::chain::{ShardUId, SimulationConfig};
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{ConstantRateReceiptSender, OneSizeReceiptGenerator};
+use crate::validation::PartitionRecovery;
+
+/// Partition and heal: the upper half of the shards can't process any incoming receipts during
+/// `partition_heights`, then they recover. Receipts sent to them pile up in the incoming backlogs,
+/// which drain after the partition ends.
+/// Every shard receives `load` of its bandwidth and can process the full max shard bandwidth per
+/// chunk, so the backlog drains at `(1 - load) * max_shard_bandwidth` per height.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PartitionAndHeal {
+    pub num_shards: usize,
+    pub partition_heights: Range<usize>,
+    /// Fraction of the shard bandwidth sent to every shard at every height.
+    pub load: f64,
+    /// Limits of the simulated chain.
+    pub config: SimulationConfig,
+}
+
+impl PartitionAndHeal {
+    pub fn new(num_shards: usize, partition_heights: Range<usize>) -> PartitionAndHeal {
+        assert!(num_shards >= 2, "Partition needs at least two shards");
+        PartitionAndHeal {
+            num_shards,
+            partition_heights,
+            load: 0.5,
+            config: SimulationConfig::default(),
+        }
+    }
+
+    pub fn load(mut self, load: f64) -> Self {
+        assert!(
+            load > 0.0 && load < 1.0,
+            "The backlog can't drain with load {}",
+            load
+        );
+        self.load = load;
+        self
+    }
+
+    pub fn config(mut self, config: SimulationConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Shards which can't process incoming receipts during the partition.
+    pub fn partitioned_shards(&self) -> Vec<ShardUId> {
+        (self.num_shards / 2..self.num_shards)
+            .map(ShardUId::new)
+            .collect()
+    }
+
+    pub fn builder(&self) -> SimulationBuilder {
+        let max_shard_bandwidth = self.config.max_shard_bandwidth;
+        let bytes_per_link = (max_shard_bandwidth as f64 * self.load) as usize / self.num_shards;
+        let mut builder = SimulationBuilder::new(self.num_shards)
+            .config(self.config)
+            .default_sender_factory(move |_rng| {
+                Box::new(ConstantRateReceiptSender {
+                    generator: OneSizeReceiptGenerator { size: 10_000 },
+                    bytes_per_height: bytes_per_link,
+                })
+            })
+            .incoming_processing_limit(max_shard_bandwidth);
+        for shard_id in self.partitioned_shards() {
+            builder =
+                builder.incoming_outage(shard_id.shard_id as usize, self.partition_heights.clone());
+        }
+        builder
+    }
+
+    /// Run the scenario for `steps` heights and measure the recovery.
+    pub fn run(&self, steps: usize) -> PartitionRecovery {
+        let simulation_run = self.builder().build().run_for(steps);
+        let recovery = PartitionRecovery::new(
+            &simulation_run,
+            self.partitioned_shards(),
+            self.partition_heights.clone(),
+        );
+        println!("{:#?}", recovery);
+        recovery
+    }
+}
@@ -0,0 +1,326 @@
+use std::any::Any;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+
+use crate::simulation::builder::SimulationBuilder;
+use crate::validation::TestStats;
+
+/// A function which creates the scenario for the given seed.
+type ScenarioFn = Box<dyn Fn(u64) -> SimulationBuilder + Sync>;
+
+/// A function which extracts a single number from the stats of a finished run.
+type MetricFn = Box<dyn Fn(&TestStats) -> f64 + Sync>;
+
+/// Searches the seed space for runs of a scenario which violate a metric threshold or panic.
+/// `find_minimal_counterexample` looks only for panics, the hunter also finds soft regressions,
+/// e.g. seeds where the utilization drops below some level.
+/// Every hit can be saved as a repro file, together with the rng recording of the failing run.
+/// Large searches can be split between many processes or machines with `partition`, all of them
+/// writing repro files to a shared directory, which is then summarized with `merge_results`.
+pub struct SeedHunter {
+    scenario_name: String,
+    make_scenario: ScenarioFn,
+    metric_name: String,
+    metric: MetricFn,
+    threshold: Threshold,
+    seeds: Range<u64>,
+    steps: usize,
+    threads: usize,
+    partition: (usize, usize),
+    repro_dir: Option<PathBuf>,
+}
+
+/// Values of the metric which are considered fine.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Threshold {
+    AtLeast(f64),
+    AtMost(f64),
+}
+
+impl Threshold {
+    pub fn is_violated_by(&self, value: f64) -> bool {
+        match self {
+            Threshold::AtLeast(min) => value < *min,
+            Threshold::AtMost(max) => value > *max,
+        }
+    }
+}
+
+/// A seed for which the scenario misbehaved.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SeedHit {
+    pub seed: u64,
+    pub failure: SeedFailure,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SeedFailure {
+    /// The run finished, but the metric was outside of the threshold.
+    Violation { value: f64 },
+    /// The run panicked, e.g. because of a failed validation.
+    Panic { message: String },
+}
+
+impl SeedHunter {
+    /// Hunt for failing seeds of the scenario. `make_scenario` creates the scenario for a single seed.
+    pub fn new(
+        scenario_name: &str,
+        make_scenario: impl Fn(u64) -> SimulationBuilder + Sync + 'static,
+    ) -> Self {
+        SeedHunter {
+            scenario_name: scenario_name.to_string(),
+            make_scenario: Box::new(make_scenario),
+            metric_name: String::new(),
+            metric: Box::new(|_stats| 0.0),
+            threshold: Threshold::AtLeast(f64::NEG_INFINITY),
+            seeds: 0..100,
+            steps: 1000,
+            threads: 1,
+            partition: (0, 1),
+            repro_dir: None,
+        }
+    }
+
+    /// Report seeds where the metric is outside of the threshold.
+    /// Without a metric only panics are reported.
+    pub fn threshold(
+        mut self,
+        metric_name: &str,
+        metric: impl Fn(&TestStats) -> f64 + Sync + 'static,
+        threshold: Threshold,
+    ) -> Self {
+        self.metric_name = metric_name.to_string();
+        self.metric = Box::new(metric);
+        self.threshold = threshold;
+        self
+    }
+
+    /// Seeds to search through.
+    pub fn seeds(mut self, seeds: Range<u64>) -> Self {
+        self.seeds = seeds;
+        self
+    }
+
+    /// For how many blocks every simulation should run.
+    pub fn steps(mut self, steps: usize) -> Self {
+        self.steps = steps;
+        self
+    }
+
+    /// Run the simulations on this many threads.
+    pub fn threads(mut self, threads: usize) -> Self {
+        assert!(threads > 0, "Seed hunter needs at least one thread!");
+        self.threads = threads;
+        self
+    }
+
+    /// Check only the seeds which belong to partition `index` out of `count`.
+    /// Hunters running with the same seed range and all partitions together cover the whole range.
+    pub fn partition(mut self, index: usize, count: usize) -> Self {
+        assert!(
+            index < count,
+            "Partition {} out of {} doesn't exist!",
+            index,
+            count
+        );
+        self.partition = (index, count);
+        self
+    }
+
+    /// Save a repro file for every hit in this directory.
+    /// The directory can be shared between hunters running in different processes.
+    pub fn repro_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.repro_dir = Some(dir.into());
+        self
+    }
+
+    /// Run the search, returns the hits sorted by seed.
+    pub fn run(&self) -> Vec<SeedHit> {
+        if let Some(dir) = &self.repro_dir {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+
+        let (partition_index, partition_count) = self.partition;
+        let seeds: Vec<u64> = self
+            .seeds
+            .clone()
+            .skip(partition_index)
+            .step_by(partition_count)
+            .collect();
+        let mut hits: Vec<SeedHit> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.threads)
+                .map(|thread_idx| {
+                    let seeds = &seeds;
+                    scope.spawn(move || {
+                        seeds
+                            .iter()
+                            .copied()
+                            .skip(thread_idx)
+                            .step_by(self.threads)
+                            .filter_map(|seed| self.check_seed(seed))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        });
+        hits.sort_by_key(|hit| hit.seed);
+        hits
+    }
+
+    fn check_seed(&self, seed: u64) -> Option<SeedHit> {
+        println!(
+            "===================== Hunt {} seed = {} =====================",
+            self.scenario_name, seed
+        );
+        let run_result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut builder = (self.make_scenario)(seed);
+            if self.repro_dir.is_some() {
+                builder = builder.record_rng();
+            }
+            let mut simulation_run = builder.build().run_for(self.steps);
+            let value = (self.metric)(&TestStats::new(&simulation_run));
+            (value, simulation_run.simulation.rng.take_recording())
+        }));
+
+        let hit = match run_result {
+            Ok((value, _)) if !self.threshold.is_violated_by(value) => return None,
+            Ok((value, recording)) => {
+                if let (Some(dir), Some(recording)) = (&self.repro_dir, recording) {
+                    recording
+                        .save(self.repro_path(dir, seed).with_extension("rng"))
+                        .unwrap();
+                }
+                SeedHit {
+                    seed,
+                    failure: SeedFailure::Violation { value },
+                }
+            }
+            Err(payload) => SeedHit {
+                seed,
+                failure: SeedFailure::Panic {
+                    message: panic_message(payload.as_ref()),
+                },
+            },
+        };
+        if let Some(dir) = &self.repro_dir {
+            std::fs::write(self.repro_path(dir, seed), self.repro_contents(&hit)).unwrap();
+        }
+        Some(hit)
+    }
+
+    fn repro_path(&self, dir: &Path, seed: u64) -> PathBuf {
+        dir.join(format!("{}_seed_{}.repro", self.scenario_name, seed))
+    }
+
+    fn repro_contents(&self, hit: &SeedHit) -> String {
+        let mut contents = format!(
+            "scenario = {}\nseed = {}\nsteps = {}\n",
+            self.scenario_name, hit.seed, self.steps
+        );
+        match &hit.failure {
+            SeedFailure::Violation { value } => contents.push_str(&format!(
+                "metric = {}\nthreshold = {:?}\nvalue = {}\n",
+                self.metric_name, self.threshold, value
+            )),
+            SeedFailure::Panic { message } => {
+                contents.push_str(&format!("panic = {}\n", message.replace('\n', " ")))
+            }
+        }
+        contents
+    }
+}
+
+/// Failures found by one or more hunters which are considered equivalent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FailureGroup {
+    pub scenario: String,
+    /// Description of the failure with all numbers removed, e.g.
+    /// "panic: Unstoppable receipts made shard shard_N send too much! N > N (N unstoppable)"
+    pub signature: String,
+    /// All seeds which failed this way, sorted and without duplicates.
+    pub seeds: Vec<u64>,
+}
+
+/// Read all repro files written to a shared results directory and group equivalent failures.
+/// Panics are equivalent when their messages differ only in numbers, threshold violations are
+/// equivalent when they violate the same threshold of the same metric.
+/// A seed reported by several hunters with overlapping ranges is counted once.
+pub fn merge_results(dir: impl AsRef<Path>) -> std::io::Result<Vec<FailureGroup>> {
+    let mut groups: BTreeMap<(String, String), BTreeSet<u64>> = BTreeMap::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_none_or(|extension| extension != "repro")
+        {
+            continue;
+        }
+        let contents = std::fs::read_to_string(&path)?;
+        let fields: BTreeMap<&str, &str> = contents
+            .lines()
+            .filter_map(|line| line.split_once(" = "))
+            .collect();
+        let invalid = || {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid repro file: {}", path.display()),
+            )
+        };
+        let scenario = fields.get("scenario").ok_or_else(invalid)?.to_string();
+        let seed = fields
+            .get("seed")
+            .and_then(|seed| seed.parse().ok())
+            .ok_or_else(invalid)?;
+        let signature = match (fields.get("panic"), fields.get("metric")) {
+            (Some(message), _) => format!("panic: {}", without_numbers(message)),
+            (None, Some(metric)) => format!(
+                "violation: {} {}",
+                metric,
+                fields.get("threshold").ok_or_else(invalid)?
+            ),
+            (None, None) => return Err(invalid()),
+        };
+        groups
+            .entry((scenario, signature))
+            .or_default()
+            .insert(seed);
+    }
+    Ok(groups
+        .into_iter()
+        .map(|((scenario, signature), seeds)| FailureGroup {
+            scenario,
+            signature,
+            seeds: seeds.into_iter().collect(),
+        })
+        .collect())
+}
+
+/// Replace every number in the text with "N".
+fn without_numbers(text: &str) -> String {
+    let mut result = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            if !result.ends_with('N') {
+                result.push('N');
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// Message of a caught panic, panics with a formatted message carry a `String`, the others a `&str`.
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<String>()
+        .cloned()
+        .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
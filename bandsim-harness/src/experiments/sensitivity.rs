@@ -0,0 +1,240 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+use crate::simulation::builder::SimulationBuilder;
+use crate::validation::TestStats;
+
+use super::cache::ResultCache;
+
+/// A function which creates a simulation builder configured with the given parameter value.
+type BuilderFactory<T> = Box<dyn Fn(&T) -> SimulationBuilder>;
+
+/// A function which extracts a single number from the stats of a finished run.
+type MetricFn = Box<dyn Fn(&TestStats) -> f64>;
+
+/// Runs the same scenario for every value of some parameter and measures how the chosen metrics change.
+/// The parameter can be anything that can be applied to a `SimulationBuilder` - a `SchedulerParams` field,
+/// number of shards, missing chunk probability, etc.
+pub struct SensitivityAnalysis<T> {
+    /// Name of the scenario created by `make_builder`, it identifies the receipt senders in the cache.
+    scenario: String,
+    parameter_name: String,
+    values: Vec<T>,
+    make_builder: BuilderFactory<T>,
+    metrics: Vec<(String, MetricFn)>,
+    steps: usize,
+    cache: Option<ResultCache>,
+}
+
+/// Results of a sensitivity analysis, one row per parameter value.
+#[derive(Clone, Debug)]
+pub struct SweepResult {
+    pub parameter_name: String,
+    pub metric_names: Vec<String>,
+    pub rows: Vec<SweepRow>,
+    /// Number of rows whose metrics were taken from the cache instead of running the simulation.
+    pub cached_rows: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct SweepRow {
+    pub parameter_value: String,
+    /// Value of every metric, in the same order as `SweepResult::metric_names`
+    pub metrics: Vec<f64>,
+}
+
+impl<T: Debug> SensitivityAnalysis<T> {
+    /// Sweep over `values` of the parameter. `make_builder` creates the scenario for a single parameter value.
+    /// Cached results are shared only by sweeps of the same `scenario`, two sweeps with different receipt senders
+    /// must have different scenario names.
+    pub fn new(
+        scenario: &str,
+        parameter_name: &str,
+        values: impl IntoIterator<Item = T>,
+        make_builder: impl Fn(&T) -> SimulationBuilder + 'static,
+    ) -> Self {
+        SensitivityAnalysis {
+            scenario: scenario.to_string(),
+            parameter_name: parameter_name.to_string(),
+            values: values.into_iter().collect(),
+            make_builder: Box::new(make_builder),
+            metrics: Vec::new(),
+            steps: 1000,
+            cache: None,
+        }
+    }
+
+    /// Add a metric that will be measured for every parameter value.
+    pub fn metric(mut self, name: &str, f: impl Fn(&TestStats) -> f64 + 'static) -> Self {
+        self.metrics.push((name.to_string(), Box::new(f)));
+        self
+    }
+
+    /// For how many blocks every simulation should run.
+    pub fn steps(mut self, steps: usize) -> Self {
+        self.steps = steps;
+        self
+    }
+
+    /// Reuse metrics of runs with the same configuration from the cache, and store the new ones there.
+    /// Metrics are identified by their names, the cache must be cleared when a metric function changes.
+    pub fn cache(mut self, cache: ResultCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn run(self) -> SweepResult {
+        assert!(
+            !self.metrics.is_empty(),
+            "Sensitivity analysis without any metrics!"
+        );
+
+        let metric_names: Vec<String> =
+            self.metrics.iter().map(|(name, _f)| name.clone()).collect();
+        let mut rows = Vec::new();
+        let mut cached_rows = 0;
+        for value in &self.values {
+            let scenario = format!("{}: {} = {:?}", self.scenario, self.parameter_name, value);
+            println!(
+                "===================== Sweep {} =====================",
+                scenario
+            );
+            let builder = (self.make_builder)(value);
+            let cache_key = self
+                .cache
+                .as_ref()
+                .map(|_| ResultCache::key(&scenario, &builder, self.steps, &metric_names));
+            let cached = self
+                .cache
+                .as_ref()
+                .zip(cache_key)
+                .and_then(|(cache, key)| cache.get(key))
+                .and_then(|cached| {
+                    metric_names
+                        .iter()
+                        .map(|name| cached.get(name).copied())
+                        .collect::<Option<Vec<f64>>>()
+                });
+            let metrics: Vec<f64> = match cached {
+                Some(metrics) => {
+                    println!("Using cached results");
+                    cached_rows += 1;
+                    metrics
+                }
+                None => {
+                    let simulation_run = builder.build().run_for(self.steps);
+                    let stats = TestStats::new(&simulation_run);
+                    let metrics: Vec<f64> =
+                        self.metrics.iter().map(|(_name, f)| f(&stats)).collect();
+                    if let (Some(cache), Some(key)) = (&self.cache, cache_key) {
+                        let named: BTreeMap<String, f64> = metric_names
+                            .iter()
+                            .cloned()
+                            .zip(metrics.iter().copied())
+                            .collect();
+                        cache.insert(key, &named).unwrap();
+                    }
+                    metrics
+                }
+            };
+            rows.push(SweepRow {
+                parameter_value: format!("{:?}", value),
+                metrics,
+            });
+        }
+
+        SweepResult {
+            parameter_name: self.parameter_name,
+            metric_names,
+            rows,
+            cached_rows,
+        }
+    }
+}
+
+impl SweepResult {
+    /// Values of the metric for every parameter value.
+    pub fn metric_values(&self, metric_name: &str) -> Vec<f64> {
+        let metric_idx = self
+            .metric_names
+            .iter()
+            .position(|name| name == metric_name)
+            .unwrap_or_else(|| panic!("No metric named {}", metric_name));
+        self.rows
+            .iter()
+            .map(|row| row.metrics[metric_idx])
+            .collect()
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut res = self.parameter_name.clone();
+        for name in &self.metric_names {
+            res.push(',');
+            res.push_str(name);
+        }
+        res.push('\n');
+        for row in &self.rows {
+            res.push_str(&row.parameter_value);
+            for value in &row.metrics {
+                res.push_str(&format!(",{}", value));
+            }
+            res.push('\n');
+        }
+        res
+    }
+
+    pub fn print_table(&self) {
+        let column_width = self
+            .metric_names
+            .iter()
+            .chain(std::iter::once(&self.parameter_name))
+            .chain(self.rows.iter().map(|row| &row.parameter_value))
+            .map(|s| s.len())
+            .max()
+            .unwrap_or(0)
+            .max(12);
+
+        let mut header = format!("{:>w$}", self.parameter_name, w = column_width);
+        for name in &self.metric_names {
+            header.push_str(&format!(" | {:>w$}", name, w = column_width));
+        }
+        println!("{}", header);
+        println!("{}", "-".repeat(header.len()));
+        for row in &self.rows {
+            let mut line = format!("{:>w$}", row.parameter_value, w = column_width);
+            for value in &row.metrics {
+                line.push_str(&format!(" | {:>w$.4}", value, w = column_width));
+            }
+            println!("{}", line);
+        }
+    }
+
+    /// Show the metric as a horizontal bar plot, one bar per parameter value.
+    pub fn print_plot(&self, metric_name: &str) {
+        let values = self.metric_values(metric_name);
+        let max_value = values.iter().copied().fold(0.0, f64::max);
+        let max_bar_len = 100;
+        let label_width = self
+            .rows
+            .iter()
+            .map(|row| row.parameter_value.len())
+            .max()
+            .unwrap_or(0);
+
+        println!("{} vs {}", metric_name, self.parameter_name);
+        for (row, value) in self.rows.iter().zip(values) {
+            let bar_len = if max_value > 0.0 {
+                ((value / max_value) * max_bar_len as f64) as usize
+            } else {
+                0
+            };
+            println!(
+                "{:>w$}: {} {:.4}",
+                row.parameter_value,
+                "#".repeat(bar_len),
+                value,
+                w = label_width
+            );
+        }
+    }
+}
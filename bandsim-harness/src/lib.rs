@@ -0,0 +1,39 @@
+// I don't like the .flatten() function, it's unintuitive
+#![allow(clippy::manual_flatten)]
+// The distribute_remaining tests sum the limits with .iter().map(|(_shard, limit)| limit), keep them as they are
+#![allow(clippy::iter_kv_map)]
+
+//! Test harness of the bandwidth scheduler simulator: stats of finished runs and assertions on them,
+//! scenario files, workload presets, experiments, the tests and the benchmarks.
+//! The modules of `bandsim-core` are re-exported, so that everything can be used from one place.
+//!
+//! ```
+//! use bandsim_harness::{FullSpeedReceiptSender, SimulationBuilder, TestStats, TypicalReceiptGenerator};
+//!
+//! let simulation_run = SimulationBuilder::new(2)
+//!     .receipt_sender(0, 1, FullSpeedReceiptSender(TypicalReceiptGenerator::new()))
+//!     .build()
+//!     .run_for(50);
+//! let stats = TestStats::new(&simulation_run);
+//! assert!(stats.bandwidth_utilization.utilization > 0.5);
+//! ```
+
+pub use bandsim_core::*;
+
+/// Micro-benchmarks of the performance-sensitive parts of the simulator which also count allocations.
+/// They are `#[ignore]`d tests, run them in release mode to get meaningful numbers:
+/// cargo test --release bench_ -- --ignored --nocapture
+/// Results are compared with the baselines stored in `benchmarks/baselines/`, set BANDSIM_SAVE_BASELINES=1 to update them.
+/// The criterion benchmarks of the same code paths are in `benches/hot_paths.rs`.
+#[cfg(test)]
+pub mod benchmarks;
+pub mod experiments;
+pub mod manifest;
+pub mod scenario;
+pub mod scenarios;
+#[cfg(test)]
+pub mod tests;
+pub mod validation;
+
+pub use scenario::{Scenario, ScenarioSender};
+pub use validation::{StatsThresholds, StatsViolation, TestStats};
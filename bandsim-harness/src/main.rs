@@ -0,0 +1,196 @@
+//! Command line interface for running a single simulation and printing its stats.
+//!
+//! Example: `cargo run --release -- run --shards 6 --steps 1000 --seed 3 --sender typical`
+
+use std::process::ExitCode;
+
+use bandsim_harness::scenarios::{self, PRESET_NAMES};
+use bandsim_harness::{
+    Scenario, ScenarioSender, SimulationConfig, StatsThresholds, StatsViolation, TestStats,
+};
+
+const USAGE: &str = "Usage:
+  bandsim run [OPTIONS]       Run a simulation with the same sender on every link
+  bandsim scenario <FILE>     Run a scenario file and check its thresholds
+  bandsim preset <NAME> [OPTIONS]
+                              Run a named workload: typical, hotspot, all_to_all_max or bursty,
+                              accepts --shards, --steps, --seed and --assert
+
+Options:
+  --shards <N>                     Number of shards (default: 6)
+  --steps <N>                      Number of heights to simulate (default: 1000)
+  --seed <N>                       Random seed of the simulation (default: 0)
+  --sender <KIND>                  Receipt sender used on every link (default: typical)
+                                   one of: typical, small, big, random, fixed:<bytes>, none
+  --missing-block-probability <P>  Probability that a block is missing (default: 0)
+  --assert                         Exit with an error when the stats don't pass the default thresholds";
+
+struct RunArgs {
+    scenario: Scenario,
+    assert: bool,
+}
+
+fn parse_run_args(mut args: impl Iterator<Item = String>) -> Result<RunArgs, String> {
+    let mut run_args = RunArgs {
+        scenario: Scenario {
+            shards: 6,
+            default_sender: ScenarioSender::Typical,
+            ..Scenario::default()
+        },
+        assert: false,
+    };
+    let scenario = &mut run_args.scenario;
+    while let Some(arg) = args.next() {
+        if arg == "--assert" {
+            run_args.assert = true;
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value for {arg}"))?;
+        let invalid = || format!("Invalid value for {arg}: {value}");
+        match arg.as_str() {
+            "--shards" => scenario.shards = value.parse().map_err(|_| invalid())?,
+            "--steps" => scenario.steps = value.parse().map_err(|_| invalid())?,
+            "--seed" => scenario.seed = value.parse().map_err(|_| invalid())?,
+            "--sender" => scenario.default_sender = ScenarioSender::parse(&value)?,
+            "--missing-block-probability" => {
+                scenario.missing_block_probability = value.parse().map_err(|_| invalid())?
+            }
+            _ => return Err(format!("Unknown option: {arg}")),
+        }
+    }
+    if scenario.shards == 0 {
+        return Err("--shards must be at least 1".to_string());
+    }
+    if !(0.0..1.0).contains(&scenario.missing_block_probability) {
+        return Err("--missing-block-probability must be in [0, 1)".to_string());
+    }
+    scenario.validate()?;
+    Ok(run_args)
+}
+
+struct PresetArgs {
+    name: String,
+    shards: usize,
+    steps: usize,
+    seed: u64,
+    assert: bool,
+}
+
+fn parse_preset_args(mut args: impl Iterator<Item = String>) -> Result<PresetArgs, String> {
+    let name = args.next().ok_or("Missing preset name")?;
+    if !PRESET_NAMES.contains(&name.as_str()) {
+        return Err(format!("Unknown preset: {name}"));
+    }
+    let mut preset_args = PresetArgs {
+        name,
+        shards: 6,
+        steps: 1000,
+        seed: 0,
+        assert: false,
+    };
+    while let Some(arg) = args.next() {
+        if arg == "--assert" {
+            preset_args.assert = true;
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value for {arg}"))?;
+        let invalid = || format!("Invalid value for {arg}: {value}");
+        match arg.as_str() {
+            "--shards" => preset_args.shards = value.parse().map_err(|_| invalid())?,
+            "--steps" => preset_args.steps = value.parse().map_err(|_| invalid())?,
+            "--seed" => preset_args.seed = value.parse().map_err(|_| invalid())?,
+            _ => return Err(format!("Unknown option: {arg}")),
+        }
+    }
+    if preset_args.shards == 0 {
+        return Err("--shards must be at least 1".to_string());
+    }
+    Ok(preset_args)
+}
+
+/// Print all violated thresholds, the exit code tells whether the stats passed.
+fn report(result: Result<(), Vec<StatsViolation>>) -> ExitCode {
+    match result {
+        Ok(()) => {
+            println!("PASSED");
+            ExitCode::SUCCESS
+        }
+        Err(violations) => {
+            println!("FAILED, {} thresholds violated:", violations.len());
+            for violation in violations {
+                println!("  {violation}");
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run_preset(args: PresetArgs) -> ExitCode {
+    let simulation_run = scenarios::preset(&args.name, args.shards, SimulationConfig::default())
+        .unwrap()
+        .random_seed(args.seed)
+        .build()
+        .run_for(args.steps);
+    let stats = TestStats::new(&simulation_run);
+    if !args.assert {
+        return ExitCode::SUCCESS;
+    }
+    report(stats.check(StatsThresholds::default()))
+}
+
+fn run(args: RunArgs) -> ExitCode {
+    let simulation_run = args.scenario.run();
+    let stats = TestStats::new(&simulation_run);
+    if !args.assert {
+        return ExitCode::SUCCESS;
+    }
+    report(stats.check(args.scenario.thresholds))
+}
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        Some("run") => match parse_run_args(args) {
+            Ok(run_args) => run(run_args),
+            Err(err) => {
+                eprintln!("{err}\n\n{USAGE}");
+                ExitCode::FAILURE
+            }
+        },
+        Some("scenario") => {
+            let Some(path) = args.next() else {
+                eprintln!("Missing scenario file\n\n{USAGE}");
+                return ExitCode::FAILURE;
+            };
+            match Scenario::load(&path) {
+                Ok(scenario) => {
+                    let stats = TestStats::new(&scenario.run());
+                    report(stats.check(scenario.thresholds))
+                }
+                Err(err) => {
+                    eprintln!("{err}");
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        Some("preset") => match parse_preset_args(args) {
+            Ok(preset_args) => run_preset(preset_args),
+            Err(err) => {
+                eprintln!("{err}\n\n{USAGE}");
+                ExitCode::FAILURE
+            }
+        },
+        Some("help" | "--help" | "-h") => {
+            println!("{USAGE}");
+            ExitCode::SUCCESS
+        }
+        _ => {
+            eprintln!("{USAGE}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::chain::ShardLink;
+use crate::simulation::SimulationRun;
+use crate::validation::TestStats;
+
+/// Everything needed to reproduce a run and check that the reproduction gives the same results:
+/// the configuration of the simulation, the crate version and the headline stats (including custom metrics).
+/// Saved as a text file with one `key<TAB>value` entry per line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunManifest {
+    pub crate_version: String,
+    /// See `SimulationBuilder::description`.
+    pub description: String,
+    pub random_seed: u64,
+    /// Debug representation of `SchedulerParams`.
+    pub scheduler_params: String,
+    /// See `SimulationBuilder::config_fingerprint`.
+    pub config: String,
+    /// Debug representation of the receipt sender on every link.
+    pub receipt_senders: BTreeMap<String, String>,
+    /// Number of heights in the run, including genesis and missing blocks.
+    pub heights: usize,
+    pub stats: BTreeMap<String, f64>,
+}
+
+impl RunManifest {
+    pub fn new(simulation_run: &SimulationRun, stats: &TestStats) -> RunManifest {
+        let simulation = &simulation_run.simulation;
+        let mut receipt_senders = BTreeMap::new();
+        for (shard_id, shard) in &simulation.shards {
+            for (to_shard, sender) in &shard.receipt_senders {
+                let shard_link = ShardLink {
+                    from: *shard_id,
+                    to: *to_shard,
+                };
+                receipt_senders.insert(format!("{:?}", shard_link), format!("{:?}", sender));
+            }
+        }
+        let headline_stats = [
+            ("max_min_ratio", stats.max_min_ratio.ratio),
+            (
+                "bandwidth_utilization",
+                stats.bandwidth_utilization.utilization,
+            ),
+            ("optimality_ratio", stats.optimality_gap.ratio),
+            ("max_receipt_age", stats.max_receipt_age.age as f64),
+            ("shed_ratio", stats.shed_ratio),
+            ("missing_chunks_ratio", stats.missing_chunks_ratio),
+            ("is_unstable", stats.is_unstable as u8 as f64),
+        ];
+        let mut stats_by_name: BTreeMap<String, f64> = headline_stats
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect();
+        for (name, custom_stats) in &stats.custom_metrics {
+            stats_by_name.insert(format!("{}.mean", name), custom_stats.mean);
+            stats_by_name.insert(format!("{}.max", name), custom_stats.max);
+        }
+        RunManifest {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            description: simulation.description.clone(),
+            random_seed: simulation.random_seed,
+            scheduler_params: format!("{:?}", simulation.scheduler_params),
+            config: simulation.config_description.clone(),
+            receipt_senders,
+            heights: simulation.blocks.len(),
+            stats: stats_by_name,
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut res = String::new();
+        res.push_str(&format!("crate_version\t{}\n", self.crate_version));
+        res.push_str(&format!("description\t{}\n", self.description));
+        res.push_str(&format!("random_seed\t{}\n", self.random_seed));
+        res.push_str(&format!("scheduler_params\t{}\n", self.scheduler_params));
+        res.push_str(&format!("config\t{}\n", self.config));
+        res.push_str(&format!("heights\t{}\n", self.heights));
+        for (link, sender) in &self.receipt_senders {
+            res.push_str(&format!("sender {}\t{}\n", link, sender));
+        }
+        for (name, value) in &self.stats {
+            // Display of f64 is the shortest representation which parses back to the same value
+            res.push_str(&format!("stat {}\t{}\n", name, value));
+        }
+        res
+    }
+
+    /// Write the manifest to a file, e.g. next to the results of an experiment.
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_text())
+    }
+
+    /// Load a manifest saved with `save`.
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<RunManifest> {
+        let invalid = |line: &str| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid manifest line: {}", line),
+            )
+        };
+        let mut manifest = RunManifest {
+            crate_version: String::new(),
+            description: String::new(),
+            random_seed: 0,
+            scheduler_params: String::new(),
+            config: String::new(),
+            receipt_senders: BTreeMap::new(),
+            heights: 0,
+            stats: BTreeMap::new(),
+        };
+        for line in std::fs::read_to_string(path)?.lines() {
+            let (key, value) = line.split_once('\t').ok_or_else(|| invalid(line))?;
+            match key {
+                "crate_version" => manifest.crate_version = value.to_string(),
+                "description" => manifest.description = value.to_string(),
+                "random_seed" => manifest.random_seed = value.parse().map_err(|_| invalid(line))?,
+                "scheduler_params" => manifest.scheduler_params = value.to_string(),
+                "config" => manifest.config = value.to_string(),
+                "heights" => manifest.heights = value.parse().map_err(|_| invalid(line))?,
+                _ => {
+                    if let Some(link) = key.strip_prefix("sender ") {
+                        manifest
+                            .receipt_senders
+                            .insert(link.to_string(), value.to_string());
+                    } else if let Some(name) = key.strip_prefix("stat ") {
+                        let value = value.parse().map_err(|_| invalid(line))?;
+                        manifest.stats.insert(name.to_string(), value);
+                    } else {
+                        return Err(invalid(line));
+                    }
+                }
+            }
+        }
+        Ok(manifest)
+    }
+}
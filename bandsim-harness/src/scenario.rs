@@ -0,0 +1,329 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use rand::Rng;
+
+use crate::chain::SimulationConfig;
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{
+    FullSpeedReceiptSender, NoReceiptSender, OneSizeReceiptGenerator, RandomSizeReceiptGenerator,
+    ReceiptSender, TypicalReceiptGenerator,
+};
+use crate::simulation::{Simulation, SimulationRun};
+use crate::validation::{StatsThresholds, TestStats};
+
+/// A simulation described in a file, so that it can be shared and reproduced without writing code.
+/// The file uses a small subset of TOML: `key = value` lines grouped into `[sections]`,
+/// values are numbers, booleans or strings in double quotes. Lines starting with `#` are comments.
+///
+/// ```toml
+/// description = "big vs small sender, 2 shards"
+/// shards = 2
+/// steps = 1000
+/// seed = 0
+/// missing_block_probability = 0.0
+/// missing_chunk_probability = 0.0
+///
+/// [senders]
+/// default = "none"
+/// "0 -> 0" = "big"
+/// "0 -> 1" = "small"
+///
+/// [config]
+/// max_shard_bandwidth = 4500000
+/// min_receipt_size = 1000
+/// max_receipt_size = 4000000
+///
+/// [thresholds]
+/// max_min_ratio = 1.25
+/// min_bandwidth_utilization = 0.9
+/// max_receipt_age = 20
+/// ```
+///
+/// Config values and thresholds which aren't specified keep their values from `SimulationConfig::default()`
+/// and `StatsThresholds::default()`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scenario {
+    /// See `SimulationBuilder::description`.
+    pub description: String,
+    pub shards: usize,
+    pub steps: usize,
+    pub seed: u64,
+    pub missing_block_probability: f64,
+    pub missing_chunk_probability: f64,
+    /// Sender used on links which don't have their own sender in `senders`.
+    pub default_sender: ScenarioSender,
+    /// Receipt senders by (from shard, to shard).
+    pub senders: BTreeMap<(usize, usize), ScenarioSender>,
+    /// Bandwidth and receipt size limits, the senders generate receipts within these limits.
+    pub config: SimulationConfig,
+    pub thresholds: StatsThresholds,
+}
+
+/// Receipt senders which can be used in scenario files, all of them send at full speed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScenarioSender {
+    /// `typical` - `TypicalReceiptGenerator`, mostly small receipts.
+    Typical,
+    /// `small` - receipts of the min receipt size.
+    Small,
+    /// `big` - receipts of the max receipt size.
+    Big,
+    /// `random` - receipt sizes sampled uniformly between the min and max receipt size.
+    Random,
+    /// `fixed:<bytes>` - receipts of the given size, it has to be within the receipt size limits.
+    Fixed(usize),
+    /// `none` - doesn't send anything.
+    None,
+}
+
+impl ScenarioSender {
+    pub fn parse(name: &str) -> Result<ScenarioSender, String> {
+        match name {
+            "typical" => Ok(ScenarioSender::Typical),
+            "small" => Ok(ScenarioSender::Small),
+            "big" => Ok(ScenarioSender::Big),
+            "random" => Ok(ScenarioSender::Random),
+            "none" => Ok(ScenarioSender::None),
+            _ => {
+                let size = name
+                    .strip_prefix("fixed:")
+                    .and_then(|size| size.parse().ok())
+                    .ok_or_else(|| format!("Unknown sender: {}", name))?;
+                Ok(ScenarioSender::Fixed(size))
+            }
+        }
+    }
+
+    pub fn make_sender(self, config: &SimulationConfig) -> Box<dyn ReceiptSender> {
+        let one_size = |size| -> Box<dyn ReceiptSender> {
+            Box::new(FullSpeedReceiptSender(OneSizeReceiptGenerator { size }))
+        };
+        match self {
+            ScenarioSender::Typical => {
+                Box::new(FullSpeedReceiptSender(TypicalReceiptGenerator::new()))
+            }
+            ScenarioSender::Small => one_size(config.min_receipt_size),
+            ScenarioSender::Big => one_size(config.max_receipt_size),
+            ScenarioSender::Random => {
+                Box::new(FullSpeedReceiptSender(RandomSizeReceiptGenerator {
+                    size_range: config.min_receipt_size..=config.max_receipt_size,
+                }))
+            }
+            ScenarioSender::Fixed(size) => one_size(size),
+            ScenarioSender::None => Box::new(NoReceiptSender),
+        }
+    }
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Scenario {
+            description: String::new(),
+            shards: 1,
+            steps: 1000,
+            seed: 0,
+            missing_block_probability: 0.0,
+            missing_chunk_probability: 0.0,
+            default_sender: ScenarioSender::None,
+            senders: BTreeMap::new(),
+            config: SimulationConfig::default(),
+            thresholds: StatsThresholds::default(),
+        }
+    }
+}
+
+impl Scenario {
+    /// Parse a scenario from the contents of a scenario file.
+    pub fn parse(text: &str) -> Result<Scenario, String> {
+        let mut scenario = Scenario::default();
+        let mut section = String::new();
+        for (line_index, raw_line) in text.lines().enumerate() {
+            let line = raw_line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |reason: &str| format!("Line {}: {}: {}", line_index + 1, reason, line);
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name.trim().to_string();
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid("expected key = value"))?;
+            let key = unquote(key.trim());
+            let value = unquote(value.trim());
+            let number = |value: &str| -> Result<f64, String> {
+                value.parse().map_err(|_| invalid("expected a number"))
+            };
+            let integer = |value: &str| -> Result<usize, String> {
+                value.parse().map_err(|_| invalid("expected an integer"))
+            };
+            match (section.as_str(), key) {
+                ("", "description") => scenario.description = value.to_string(),
+                ("", "shards") => scenario.shards = integer(value)?,
+                ("", "steps") => scenario.steps = integer(value)?,
+                ("", "seed") => scenario.seed = integer(value)? as u64,
+                ("", "missing_block_probability") => {
+                    scenario.missing_block_probability = number(value)?
+                }
+                ("", "missing_chunk_probability") => {
+                    scenario.missing_chunk_probability = number(value)?
+                }
+                ("senders", "default") => {
+                    scenario.default_sender =
+                        ScenarioSender::parse(value).map_err(|err| invalid(&err))?
+                }
+                ("senders", link) => {
+                    let (from, to) = link
+                        .split_once("->")
+                        .ok_or_else(|| invalid("expected a link like \"0 -> 1\""))?;
+                    let shard_link = (integer(from.trim())?, integer(to.trim())?);
+                    let sender = ScenarioSender::parse(value).map_err(|err| invalid(&err))?;
+                    if scenario.senders.insert(shard_link, sender).is_some() {
+                        return Err(invalid("duplicate sender"));
+                    }
+                }
+                ("config", "max_shard_bandwidth") => {
+                    scenario.config.max_shard_bandwidth = integer(value)?
+                }
+                ("config", "max_shard_incoming_bandwidth") => {
+                    scenario.config.max_shard_incoming_bandwidth = Some(integer(value)?)
+                }
+                ("config", "min_receipt_size") => {
+                    scenario.config.min_receipt_size = integer(value)?
+                }
+                ("config", "max_receipt_size") => {
+                    scenario.config.max_receipt_size = integer(value)?
+                }
+                ("thresholds", "max_min_ratio") => {
+                    scenario.thresholds.max_min_ratio = number(value)?
+                }
+                ("thresholds", "min_bandwidth_utilization") => {
+                    scenario.thresholds.min_bandwidth_utilization = number(value)?
+                }
+                ("thresholds", "min_optimality_ratio") => {
+                    scenario.thresholds.min_optimality_ratio = number(value)?
+                }
+                ("thresholds", "max_receipt_age") => {
+                    scenario.thresholds.max_receipt_age = Some(integer(value)?)
+                }
+                ("thresholds", "allow_unstable") => {
+                    scenario.thresholds.allow_unstable = value
+                        .parse()
+                        .map_err(|_| invalid("expected true or false"))?
+                }
+                ("thresholds", "max_shed_ratio") => {
+                    scenario.thresholds.max_shed_ratio = number(value)?
+                }
+                _ => return Err(invalid("unknown key")),
+            }
+        }
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    /// Load a scenario file.
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Scenario> {
+        let path = path.as_ref();
+        Scenario::parse(&std::fs::read_to_string(path)?).map_err(|err| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid scenario {}: {}", path.display(), err),
+            )
+        })
+    }
+
+    /// Check that the scenario describes a simulation which can be run.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.shards == 0 {
+            return Err("A scenario needs at least one shard".to_string());
+        }
+        for probability in [
+            self.missing_block_probability,
+            self.missing_chunk_probability,
+        ] {
+            if !(0.0..1.0).contains(&probability) {
+                return Err(format!("Probability {} is not in [0, 1)", probability));
+            }
+        }
+        if self.config.min_receipt_size == 0
+            || self.config.min_receipt_size > self.config.max_receipt_size
+        {
+            return Err(format!(
+                "Invalid receipt size limits {}..={}",
+                self.config.min_receipt_size, self.config.max_receipt_size
+            ));
+        }
+        for ((from, to), sender) in &self.senders {
+            if *from >= self.shards || *to >= self.shards {
+                return Err(format!(
+                    "Sender {} -> {} is outside of the shards",
+                    from, to
+                ));
+            }
+            self.validate_sender(*sender)?;
+        }
+        self.validate_sender(self.default_sender)
+    }
+
+    fn validate_sender(&self, sender: ScenarioSender) -> Result<(), String> {
+        let size_limits = self.config.min_receipt_size..=self.config.max_receipt_size;
+        match sender {
+            ScenarioSender::Fixed(size) if !size_limits.contains(&size) => Err(format!(
+                "Sender fixed:{} is outside of the receipt size limits {}..={}",
+                size,
+                size_limits.start(),
+                size_limits.end()
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// A builder for the simulation described by the scenario.
+    /// Can be used to add things which can't be described in a scenario file.
+    pub fn builder(&self) -> SimulationBuilder {
+        let mut builder = SimulationBuilder::new(self.shards)
+            .config(self.config)
+            .random_seed(self.seed)
+            .missing_block_probability(self.missing_block_probability)
+            .description(&self.description);
+        for ((from, to), sender) in &self.senders {
+            builder = builder.receipt_sender(*from, *to, sender.make_sender(&self.config));
+        }
+        if self.default_sender != ScenarioSender::None {
+            let default_sender = self.default_sender;
+            let config = self.config;
+            builder =
+                builder.default_sender_factory(move |_rng| default_sender.make_sender(&config));
+        }
+        if self.missing_chunk_probability > 0.0 {
+            let probability = self.missing_chunk_probability;
+            builder =
+                builder.missing_chunk_generator(move |_height, _id, rng| rng.gen_bool(probability));
+        }
+        builder
+    }
+
+    pub fn build(&self) -> Simulation {
+        self.builder().build()
+    }
+
+    pub fn run(&self) -> SimulationRun {
+        self.build().run_for(self.steps)
+    }
+
+    /// Run the scenario and check the stats against its thresholds.
+    pub fn run_and_assert(&self) -> TestStats {
+        let simulation_run = self.run();
+        let stats = TestStats::new(&simulation_run);
+        stats.assert_with(self.thresholds.clone());
+        stats
+    }
+}
+
+fn unquote(s: &str) -> &str {
+    s.strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(s)
+}
@@ -0,0 +1,98 @@
+//! Named workloads which can be reused between tests, experiments and the command line.
+//! Every preset returns a `SimulationBuilder` with the receipt senders (and the rest of the workload) set up
+//! for the limits in the given config, the caller can still change the seed, add faults, etc. before building
+//! the simulation.
+//! Unlike scenario files (see `Scenario`), presets can use any receipt sender.
+
+use rand::Rng;
+
+use crate::chain::SimulationConfig;
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{
+    ConstantRateReceiptSender, FullSpeedReceiptSender, OneSizeReceiptGenerator,
+    ParetoOnOffReceiptSender, TypicalReceiptGenerator,
+};
+
+/// Names of all presets, in the order in which they're listed in the docs.
+pub const PRESET_NAMES: [&str; 4] = ["typical", "hotspot", "all_to_all_max", "bursty"];
+
+/// Builder of the preset with this name, `None` when there's no such preset.
+pub fn preset(
+    name: &str,
+    num_shards: usize,
+    config: SimulationConfig,
+) -> Option<SimulationBuilder> {
+    let builder = match name {
+        "typical" => typical(num_shards, config),
+        "hotspot" => hotspot(num_shards, config),
+        "all_to_all_max" => all_to_all_max(num_shards, config),
+        "bursty" => bursty(num_shards, config),
+        _ => return None,
+    };
+    Some(builder)
+}
+
+/// Typical receipts at full speed on all links, with a bit of missing chunks and blocks.
+pub fn typical(num_shards: usize, config: SimulationConfig) -> SimulationBuilder {
+    SimulationBuilder::new(num_shards)
+        .config(config)
+        .description(&format!("typical preset, {} shards", num_shards))
+        .default_sender_factory(|_rng| {
+            Box::new(FullSpeedReceiptSender(TypicalReceiptGenerator::new()))
+        })
+        .missing_block_probability(0.05)
+        .missing_chunk_generator(|_, _, rng| rng.gen_bool(0.05))
+}
+
+/// All shards send typical receipts at full speed to shard 0, the other links carry a trickle
+/// of small receipts. Shard 0 is the bottleneck, the senders compete for its incoming bandwidth.
+pub fn hotspot(num_shards: usize, config: SimulationConfig) -> SimulationBuilder {
+    let mut builder = SimulationBuilder::new(num_shards)
+        .config(config)
+        .description(&format!("hotspot preset, {} shards", num_shards));
+    for from_shard in 0..num_shards {
+        builder = builder.receipt_sender(
+            from_shard,
+            0,
+            FullSpeedReceiptSender(TypicalReceiptGenerator::new()),
+        );
+    }
+    builder.default_sender_factory(move |_rng| {
+        Box::new(ConstantRateReceiptSender {
+            generator: OneSizeReceiptGenerator {
+                size: config.min_receipt_size,
+            },
+            bytes_per_height: 100_000,
+        })
+    })
+}
+
+/// Max size receipts at full speed on all links, the worst case for the scheduler.
+pub fn all_to_all_max(num_shards: usize, config: SimulationConfig) -> SimulationBuilder {
+    SimulationBuilder::new(num_shards)
+        .config(config)
+        .description(&format!("all_to_all_max preset, {} shards", num_shards))
+        .default_sender_factory(move |_rng| {
+            Box::new(FullSpeedReceiptSender(OneSizeReceiptGenerator {
+                size: config.max_receipt_size,
+            }))
+        })
+}
+
+/// Heavy-tailed traffic of small receipts on all links, see `ParetoOnOffReceiptSender`.
+/// The average load is about 70% of what the shards can send, but it comes in bursts at all time scales.
+pub fn bursty(num_shards: usize, config: SimulationConfig) -> SimulationBuilder {
+    let num_sources = 4;
+    let average_load = config.max_shard_bandwidth * 7 / 10 / num_shards;
+    SimulationBuilder::new(num_shards)
+        .config(config)
+        .description(&format!("bursty preset, {} shards", num_shards))
+        .default_sender_factory(move |_rng| {
+            Box::new(ParetoOnOffReceiptSender::new(
+                OneSizeReceiptGenerator { size: 10_000 },
+                num_sources,
+                average_load * 2 / num_sources,
+                1.2,
+            ))
+        })
+}
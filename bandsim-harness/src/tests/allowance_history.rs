@@ -0,0 +1,98 @@
+use crate::bandwidth_scheduler::SchedulerParams;
+use crate::chain::ShardUId;
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{FullSpeedReceiptSender, OneSizeReceiptGenerator};
+use crate::validation::TestStats;
+
+use super::link;
+
+/// 0 -> 1 - sends as much as possible, all other links are idle.
+/// The busy link keeps using up its allowance, the idle links stay at the maximum.
+#[test]
+fn allowance_history_shows_busy_link() {
+    let simulation_run = SimulationBuilder::new(2)
+        .receipt_sender(
+            0,
+            1,
+            FullSpeedReceiptSender(OneSizeReceiptGenerator { size: 100_000 }),
+        )
+        .record_allowance_history()
+        .build()
+        .run_for(100);
+
+    let max_allowance = SchedulerParams::default().allowance.max_allowance;
+    let busy_history = simulation_run.allowance_history(ShardUId::new(0), link(0, 1));
+    let idle_history = simulation_run.allowance_history(ShardUId::new(0), link(1, 0));
+    assert_eq!(busy_history.len(), 100);
+    assert_eq!(idle_history.len(), 100);
+    assert_eq!(idle_history.last().unwrap().1, max_allowance);
+    assert!(busy_history.last().unwrap().1 < max_allowance);
+
+    // All shards have the same scheduler state
+    assert_eq!(
+        simulation_run.allowance_history(ShardUId::new(1), link(0, 1)),
+        busy_history
+    );
+}
+
+/// The history isn't recorded by default.
+#[test]
+fn no_allowance_history_by_default() {
+    let simulation_run = SimulationBuilder::new(2).build().run_for(10);
+    assert!(simulation_run
+        .simulation
+        .metrics
+        .iter()
+        .all(|metrics| metrics.allowances.is_empty()));
+}
+
+/// 0 -> 1 - sends as much as possible, all other links are idle.
+/// The idle links are at the allowance cap most of the time, the busy link keeps spending its allowance.
+#[test]
+fn allowance_saturation_ratios() {
+    let simulation_run = SimulationBuilder::new(2)
+        .receipt_sender(
+            0,
+            1,
+            FullSpeedReceiptSender(OneSizeReceiptGenerator { size: 100_000 }),
+        )
+        .build()
+        .run_for(100);
+    let stats = TestStats::new(&simulation_run);
+    assert!(stats.allowance_at_max_ratio > 0.7);
+    assert!(stats.allowance_at_max_ratio <= 0.75);
+    assert!(stats.allowance_at_zero_ratio <= 0.25);
+
+    let saturation = simulation_run
+        .simulation
+        .metrics
+        .last()
+        .unwrap()
+        .allowance_saturation;
+    assert_eq!(saturation.links, 4);
+    assert_eq!(saturation.at_max, 3);
+}
+
+/// Same scenario as above. Three idle links share the cap, no link can hold much more than
+/// a third of the total allowance.
+#[test]
+fn allowance_concentration_with_idle_links() {
+    let simulation_run = SimulationBuilder::new(2)
+        .receipt_sender(
+            0,
+            1,
+            FullSpeedReceiptSender(OneSizeReceiptGenerator { size: 100_000 }),
+        )
+        .build()
+        .run_for(100);
+    let stats = TestStats::new(&simulation_run);
+    let concentration = &stats.allowance_concentration;
+    assert!(concentration.mean_top_decile_share >= 0.25);
+    assert!(concentration.max_top_decile_share <= 0.34);
+    // No drift towards the busy link
+    assert!(
+        (concentration.second_half_mean_top_decile_share - concentration.mean_top_decile_share)
+            .abs()
+            < 0.01
+    );
+}
@@ -0,0 +1,72 @@
+use crate::bandwidth_scheduler::{AllowanceIncrement, AllowancePolicy, SchedulerParams};
+use crate::chain::{ShardUId, MAX_SHARD_BANDWIDTH};
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{FullSpeedReceiptSender, OneSizeReceiptGenerator};
+use crate::simulation::SimulationRun;
+use crate::validation::TotalSent;
+
+use super::link;
+
+/// 0 -> 1 - sends as much as possible, all other links are idle.
+fn run_with_allowance(allowance: AllowancePolicy) -> SimulationRun {
+    SimulationBuilder::new(2)
+        .receipt_sender(
+            0,
+            1,
+            FullSpeedReceiptSender(OneSizeReceiptGenerator { size: 100_000 }),
+        )
+        .scheduler_params(SchedulerParams {
+            allowance,
+            ..SchedulerParams::default()
+        })
+        .record_allowance_history()
+        .build()
+        .run_for(100)
+}
+
+/// The idle links stop at the configured cap instead of the max shard bandwidth.
+#[test]
+fn lower_max_allowance() {
+    let max_allowance = MAX_SHARD_BANDWIDTH / 10;
+    let simulation_run = run_with_allowance(AllowancePolicy {
+        max_allowance,
+        ..AllowancePolicy::default()
+    });
+    let idle_history = simulation_run.allowance_history(ShardUId::new(0), link(1, 0));
+    assert_eq!(idle_history.last().unwrap().1, max_allowance);
+    assert!(idle_history
+        .iter()
+        .all(|(_, allowance)| *allowance <= max_allowance));
+}
+
+/// With a small fixed increment the idle links need many heights to reach the cap.
+#[test]
+fn fixed_increment_slows_down_convergence() {
+    let heights_to_cap = |per_height_increment| {
+        let simulation_run = run_with_allowance(AllowancePolicy {
+            per_height_increment,
+            ..AllowancePolicy::default()
+        });
+        simulation_run
+            .allowance_history(ShardUId::new(0), link(1, 0))
+            .iter()
+            .position(|(_, allowance)| *allowance == MAX_SHARD_BANDWIDTH)
+    };
+    // The fair share is half of the max shard bandwidth, the cap is reached after two heights.
+    let fair_share = heights_to_cap(AllowanceIncrement::FairShare).unwrap();
+    assert!(fair_share <= 2);
+    let fixed = heights_to_cap(AllowanceIncrement::Fixed(MAX_SHARD_BANDWIDTH / 50)).unwrap();
+    assert!(fixed >= 40);
+}
+
+/// Without any increment the links never get allowance, but they're still granted the base bandwidth.
+#[test]
+fn zero_increment() {
+    let simulation_run = run_with_allowance(AllowancePolicy {
+        per_height_increment: AllowanceIncrement::Fixed(0),
+        ..AllowancePolicy::default()
+    });
+    let busy_history = simulation_run.allowance_history(ShardUId::new(0), link(0, 1));
+    assert!(busy_history.iter().all(|(_, allowance)| *allowance == 0));
+    assert!(TotalSent::new(&simulation_run).sent(link(0, 1)) > 0);
+}
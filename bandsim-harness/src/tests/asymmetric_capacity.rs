@@ -0,0 +1,101 @@
+use std::collections::BTreeMap;
+
+use crate::bandwidth_scheduler::{SchedulerParams, ShardBandwidth};
+use crate::chain::{ShardUId, SimulationConfig, MAX_SHARD_BANDWIDTH};
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{FullSpeedReceiptSender, OneSizeReceiptGenerator};
+use crate::simulation::SimulationRun;
+use crate::validation::TestStats;
+
+use super::DEFAULT_TEST_LENGTH;
+
+/// Receipts are at most 100kB, so that they fit into the smaller capacities.
+fn small_receipts_config() -> SimulationConfig {
+    SimulationConfig {
+        max_receipt_size: 100_000,
+        ..SimulationConfig::default()
+    }
+}
+
+/// Four shards sending small receipts at full speed on all links.
+fn run_full_speed(config: SimulationConfig, params: SchedulerParams) -> SimulationRun {
+    SimulationBuilder::new(4)
+        .config(config)
+        .scheduler_params(params)
+        .default_sender_factory(|_rng| {
+            Box::new(FullSpeedReceiptSender(OneSizeReceiptGenerator {
+                size: 10_000,
+            }))
+        })
+        .build()
+        .run_for(DEFAULT_TEST_LENGTH)
+}
+
+/// The shards can receive only half of what they can send. Receiving is the bottleneck,
+/// the incoming capacity is saturated and half of the outgoing capacity stays unused.
+#[test]
+fn incoming_capacity_smaller() {
+    let config = SimulationConfig {
+        max_shard_incoming_bandwidth: Some(MAX_SHARD_BANDWIDTH / 2),
+        ..small_receipts_config()
+    };
+    let simulation_run = run_full_speed(config, SchedulerParams::for_config(&config));
+    let stats = TestStats::new(&simulation_run);
+    let utilization = &stats.capacity_utilization;
+    assert!(utilization.mean_incoming > 0.9, "{:?}", utilization);
+    assert!(utilization.mean_outgoing < 0.55, "{:?}", utilization);
+    stats.basic_assert();
+}
+
+/// With the same capacity in both directions both of them are used equally.
+#[test]
+fn symmetric_capacity() {
+    let config = small_receipts_config();
+    let simulation_run = run_full_speed(config, SchedulerParams::for_config(&config));
+    let utilization = TestStats::new(&simulation_run).capacity_utilization;
+    assert!(utilization.mean_outgoing > 0.9, "{:?}", utilization);
+    assert!((utilization.mean_outgoing - utilization.mean_incoming).abs() < 1e-9);
+}
+
+/// Shard 0 can send as much as the others, but receive only a quarter of that.
+#[test]
+fn one_shard_with_asymmetric_link() {
+    let config = small_receipts_config();
+    let params = SchedulerParams {
+        shard_bandwidth: BTreeMap::from([(
+            ShardUId::new(0),
+            ShardBandwidth {
+                outgoing: MAX_SHARD_BANDWIDTH,
+                incoming: MAX_SHARD_BANDWIDTH / 4,
+            },
+        )]),
+        ..SchedulerParams::for_config(&config)
+    };
+    let simulation_run = run_full_speed(config, params);
+    let (mut max_sent, mut max_received) = (0, 0);
+    for block in simulation_run.simulation.blocks.iter().flatten() {
+        let Some(chunk) = &block.chunks[&ShardUId::new(0)] else {
+            continue;
+        };
+        max_sent = max_sent.max(chunk.prev_outgoing_receipts_size.values().sum::<usize>());
+        max_received = max_received.max(chunk.prev_incoming_receipts_size);
+    }
+    assert!(max_received <= MAX_SHARD_BANDWIDTH / 4, "{}", max_received);
+    assert!(max_sent > MAX_SHARD_BANDWIDTH / 2, "{}", max_sent);
+
+    let utilization = TestStats::new(&simulation_run).capacity_utilization;
+    assert!(
+        utilization.incoming[&ShardUId::new(0)] > 0.9,
+        "{:?}",
+        utilization
+    );
+}
+
+#[test]
+#[should_panic(expected = "doesn't fit the max receipt size")]
+fn incoming_capacity_smaller_than_receipt() {
+    SimulationBuilder::new(2).config(SimulationConfig {
+        max_shard_incoming_bandwidth: Some(1_000),
+        ..SimulationConfig::default()
+    });
+}
@@ -0,0 +1,96 @@
+use crate::chain::{Receipt, ReceiptPriority, ShardUId, MAX_SHARD_BANDWIDTH};
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::outgoing_queue::OutgoingQueue;
+use crate::simulation::receipt_sender::{ConstantRateReceiptSender, OneSizeReceiptGenerator};
+use crate::validation::{StatsThresholds, TestStats};
+
+const QUEUE_THRESHOLD: usize = 10_000_000;
+const RECEIPT_SIZE: usize = 100_000;
+const BYTES_PER_HEIGHT: usize = MAX_SHARD_BANDWIDTH * 3 / 2;
+
+fn overloaded_link(backpressure: bool) -> SimulationBuilder {
+    let builder = SimulationBuilder::new(2).receipt_sender(
+        0,
+        1,
+        ConstantRateReceiptSender {
+            generator: OneSizeReceiptGenerator { size: RECEIPT_SIZE },
+            bytes_per_height: BYTES_PER_HEIGHT,
+        },
+    );
+    if backpressure {
+        builder.backpressure(QUEUE_THRESHOLD)
+    } else {
+        builder
+    }
+}
+
+/// 0 -> 1 - sends more than the link can handle.
+/// Without backpressure the queue grows forever, with backpressure the sender is throttled
+/// and the queue stays around the threshold. The excess load is shed.
+#[test]
+fn backpressure_bounds_queue() {
+    let unbounded_run = overloaded_link(false).build().run_for(300);
+    assert!(TestStats::new(&unbounded_run).is_unstable);
+
+    let simulation_run = overloaded_link(true).build().run_for(300);
+    let stats = TestStats::new(&simulation_run);
+    assert!(!stats.is_unstable);
+    stats.assert_no_queue_growth(0.1);
+
+    let metrics = &simulation_run.simulation.metrics;
+    let max_queued = metrics
+        .iter()
+        .flat_map(|m| m.queued_before_send.values())
+        .max()
+        .unwrap();
+    // Receipts generated at a single height can go over the threshold.
+    assert!(
+        *max_queued <= QUEUE_THRESHOLD + BYTES_PER_HEIGHT + RECEIPT_SIZE,
+        "{}",
+        max_queued
+    );
+
+    // The link can't send more than MAX_SHARD_BANDWIDTH, so about a third of the offered load is shed.
+    let shed: usize = metrics.iter().flat_map(|m| m.shed.values()).sum();
+    assert_eq!(stats.shed_bytes, shed);
+    assert!(
+        stats.shed_ratio > 0.25 && stats.shed_ratio < 0.4,
+        "{}",
+        stats.shed_ratio
+    );
+    stats.assert_with(StatsThresholds {
+        max_shed_ratio: 0.4,
+        ..StatsThresholds::default()
+    });
+}
+
+/// Runs which shed load fail the basic assertions, their utilization isn't comparable with runs that don't.
+#[test]
+#[should_panic = "of the offered load"]
+fn shed_load_fails_basic_assert() {
+    let simulation_run = overloaded_link(true).build().run_for(300);
+    TestStats::new(&simulation_run).basic_assert();
+}
+
+#[test]
+fn remove_pushed_after() {
+    let mut queue = OutgoingQueue::new(ShardUId::new(1));
+    queue.push(Receipt {
+        size: 1000,
+        tag: None,
+        priority: ReceiptPriority::default(),
+    });
+    let pushed_before = queue.total_pushed();
+    for size in [2000, 3000] {
+        queue.push(Receipt {
+            size,
+            tag: None,
+            priority: ReceiptPriority::default(),
+        });
+    }
+    assert_eq!(queue.remove_pushed_after(pushed_before), 5000);
+    assert_eq!(queue.total_size(), 1000);
+    assert_eq!(queue.total_pushed(), 1000);
+    assert_eq!(queue.pop().unwrap().size, 1000);
+    assert!(queue.is_empty());
+}
@@ -0,0 +1,52 @@
+use crate::simulation::base_bandwidth_controller::{
+    print_trajectory, BaseBandwidthController, TuningPoint,
+};
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{ConstantRateReceiptSender, OneSizeReceiptGenerator};
+
+use super::full_speed_typical_sender;
+
+fn run_with_controller(builder: SimulationBuilder) -> Vec<TuningPoint> {
+    let simulation_run = builder
+        .base_bandwidth_controller(BaseBandwidthController::default())
+        .build()
+        .run_for(500);
+    let trajectory = simulation_run.simulation.base_bandwidth_trajectory;
+    print_trajectory(&trajectory, 50);
+    trajectory
+}
+
+fn mean_base_bandwidth(points: &[TuningPoint]) -> f64 {
+    points.iter().map(|p| p.base_bandwidth as f64).sum::<f64>() / points.len() as f64
+}
+
+/// All links send a few small receipts. Most of the base bandwidth is wasted,
+/// the controller lowers it.
+#[test]
+fn light_traffic_lowers_base_bandwidth() {
+    let trajectory =
+        run_with_controller(SimulationBuilder::new(4).default_sender_factory(|_rng| {
+            Box::new(ConstantRateReceiptSender {
+                generator: OneSizeReceiptGenerator { size: 5_000 },
+                bytes_per_height: 10_000,
+            })
+        }));
+    assert_eq!(trajectory[0].base_bandwidth, 100_000);
+    let second_half = &trajectory[trajectory.len() / 2..];
+    assert!(mean_base_bandwidth(second_half) < 50_000.0);
+}
+
+/// All links send at full speed, none of them can send everything.
+/// The controller keeps raising the base bandwidth up to its limit, it doesn't help.
+#[test]
+fn full_load_raises_base_bandwidth() {
+    let trajectory = run_with_controller(
+        SimulationBuilder::new(4).default_sender_factory(full_speed_typical_sender),
+    );
+    let last = trajectory.last().unwrap();
+    assert_eq!(
+        last.base_bandwidth,
+        BaseBandwidthController::default().max_base_bandwidth
+    );
+    assert!(last.starvation_ratio > 0.5);
+}
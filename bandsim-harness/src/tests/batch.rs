@@ -0,0 +1,40 @@
+use crate::chain::SimulationConfig;
+use crate::experiments::batch::{BatchRunner, Summary};
+use crate::scenarios;
+
+fn typical_batch() -> BatchRunner {
+    BatchRunner::new("typical", |seed| {
+        scenarios::typical(4, SimulationConfig::default()).random_seed(seed)
+    })
+    .seeds(0..6)
+    .steps(300)
+}
+
+#[test]
+fn batch_summary() {
+    let result = typical_batch().threads(3).run();
+    result.print_summary();
+
+    let seeds: Vec<u64> = result.runs.iter().map(|(seed, _stats)| *seed).collect();
+    assert_eq!(seeds, (0..6).collect::<Vec<_>>());
+    for summary in [result.utilization, result.fairness] {
+        assert!(summary.min <= summary.mean && summary.mean <= summary.max);
+        assert!(summary.stddev > 0.0);
+    }
+    assert!(result.utilization.min > 0.5);
+    assert!(result.fairness.max < 2.15);
+
+    // Every seed is run separately, the number of threads doesn't change the results.
+    let single_thread = typical_batch().run();
+    assert_eq!(single_thread.utilization, result.utilization);
+    assert_eq!(single_thread.fairness, result.fairness);
+}
+
+#[test]
+fn summary_of_values() {
+    let summary = Summary::new(&[1.0, 2.0, 3.0, 4.0]);
+    assert_eq!(summary.mean, 2.5);
+    assert_eq!(summary.min, 1.0);
+    assert_eq!(summary.max, 4.0);
+    assert!((summary.stddev - 1.25_f64.sqrt()).abs() < 1e-12);
+}
@@ -1,7 +1,7 @@
-use crate::bandsim::chain::{MAX_RECEIPT_SIZE, MIN_RECEIPT_SIZE};
-use crate::bandsim::simulation::builder::SimulationBuilder;
-use crate::bandsim::simulation::receipt_sender::{FullSpeedReceiptSender, OneSizeReceiptGenerator};
-use crate::bandsim::validation::TestStats;
+use crate::chain::{MAX_RECEIPT_SIZE, MIN_RECEIPT_SIZE};
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{FullSpeedReceiptSender, OneSizeReceiptGenerator};
+use crate::validation::{StatsThresholds, TestStats};
 
 use super::DEFAULT_TEST_LENGTH;
 
@@ -29,9 +29,13 @@ fn big_vs_small_sender() {
         .run_for(DEFAULT_TEST_LENGTH);
 
     let stats = TestStats::new(&simulation_run);
-    stats.basic_assert();
-    assert!(stats.max_min_ratio.ratio <= 1.25);
-    assert!(stats.bandwidth_utilization.utilization > 0.90);
+    stats.assert_with(StatsThresholds {
+        max_min_ratio: 1.25,
+        min_bandwidth_utilization: 0.90,
+        max_receipt_age: Some(20),
+        ..StatsThresholds::default()
+    });
+    stats.assert_no_queue_growth(0.1);
 }
 
 /// 0 -> 0 - full speed big receipts
@@ -46,9 +50,13 @@ fn big_vs_small_receiver() {
         .run_for(DEFAULT_TEST_LENGTH);
 
     let stats = TestStats::new(&simulation_run);
-    stats.basic_assert();
-    assert!(stats.max_min_ratio.ratio <= 1.25);
-    assert!(stats.bandwidth_utilization.utilization > 0.90);
+    stats.assert_with(StatsThresholds {
+        max_min_ratio: 1.25,
+        min_bandwidth_utilization: 0.90,
+        max_receipt_age: Some(20),
+        ..StatsThresholds::default()
+    });
+    stats.assert_no_queue_growth(0.1);
 }
 
 /// 0 -> 0 - full speed big receipts
@@ -70,9 +78,12 @@ fn big_vs_many_small_sender() {
         .run_for(DEFAULT_TEST_LENGTH);
 
     let stats = TestStats::new(&simulation_run);
-    stats.basic_assert();
-    assert!(stats.max_min_ratio.ratio <= 1.15);
-    assert!(stats.bandwidth_utilization.utilization > 0.90);
+    stats.assert_with(StatsThresholds {
+        max_min_ratio: 1.15,
+        min_optimality_ratio: 0.95,
+        ..StatsThresholds::default()
+    });
+    stats.assert_no_queue_growth(0.1);
 }
 
 /// 0 -> 0 - full speed big receipts
@@ -93,7 +104,10 @@ fn big_vs_many_small_receiver() {
         .run_for(DEFAULT_TEST_LENGTH);
 
     let stats = TestStats::new(&simulation_run);
-    stats.basic_assert();
-    assert!(stats.max_min_ratio.ratio <= 1.15);
-    assert!(stats.bandwidth_utilization.utilization > 0.90);
+    stats.assert_with(StatsThresholds {
+        max_min_ratio: 1.15,
+        min_optimality_ratio: 0.95,
+        ..StatsThresholds::default()
+    });
+    stats.assert_no_queue_growth(0.1);
 }
@@ -0,0 +1,39 @@
+use crate::chain::MAX_SHARD_BANDWIDTH;
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::BurstReceiptSender;
+use crate::validation::BurstRecovery;
+
+use super::constant_rate_sender;
+
+/// 0 -> 1 - sends a quarter of the link capacity, and at heights 100..110 a burst of `burst_rate` bytes per height on top.
+/// 2 -> 1 - sends a quarter of the link capacity.
+fn burst_recovery(burst_rate: usize) -> BurstRecovery {
+    let burst_heights = 100..110;
+    let simulation_run = SimulationBuilder::new(3)
+        .receipt_sender(
+            0,
+            1,
+            BurstReceiptSender {
+                base: constant_rate_sender(100_000, MAX_SHARD_BANDWIDTH / 4),
+                burst: constant_rate_sender(100_000, burst_rate),
+                burst_heights: burst_heights.clone(),
+            },
+        )
+        .receipt_sender(2, 1, constant_rate_sender(100_000, MAX_SHARD_BANDWIDTH / 4))
+        .build()
+        .run_for(300);
+    let recovery = BurstRecovery::new(&simulation_run, burst_heights);
+    println!("{:#?}", recovery);
+    recovery
+}
+
+/// The queues go back to normal after the burst, a bigger burst takes longer to drain.
+#[test]
+fn queues_recover_after_burst() {
+    let small_burst = burst_recovery(MAX_SHARD_BANDWIDTH).recovery_time().unwrap();
+    let big_burst = burst_recovery(MAX_SHARD_BANDWIDTH * 3)
+        .recovery_time()
+        .unwrap();
+    assert!(small_burst < big_burst);
+    assert!(big_burst < 100);
+}
@@ -0,0 +1,99 @@
+use crate::bandwidth_scheduler::{ChunkSizeLimit, SchedulerParams};
+use crate::chain::MAX_SHARD_BANDWIDTH;
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{FullSpeedReceiptSender, OneSizeReceiptGenerator};
+use crate::simulation::SimulationRun;
+use crate::validation::{StatsThresholds, TestStats};
+
+use super::DEFAULT_TEST_LENGTH;
+
+fn run_with_chunk_size_limit(chunk_size_limit: Option<ChunkSizeLimit>) -> SimulationRun {
+    SimulationBuilder::new(4)
+        .default_sender_factory(|_rng| {
+            Box::new(FullSpeedReceiptSender(OneSizeReceiptGenerator {
+                size: 50_000,
+            }))
+        })
+        .scheduler_params(SchedulerParams {
+            chunk_size_limit,
+            ..SchedulerParams::default()
+        })
+        .build()
+        .run_for(DEFAULT_TEST_LENGTH)
+}
+
+/// Most bytes of receipts sent in a single chunk.
+fn max_sent_per_chunk(simulation_run: &SimulationRun) -> usize {
+    simulation_run
+        .simulation
+        .blocks
+        .iter()
+        .flatten()
+        .flat_map(|block| block.chunks.values().flatten())
+        .map(|chunk| chunk.prev_outgoing_receipts_size.values().sum::<usize>())
+        .max()
+        .unwrap()
+}
+
+/// The grants allow to send the max shard bandwidth, but only a quarter of it fits into a chunk
+/// next to the transactions. The chunk size binds almost always, the grants almost never.
+#[test]
+fn chunk_size_binds() {
+    let limit = ChunkSizeLimit {
+        max_chunk_size: MAX_SHARD_BANDWIDTH / 2,
+        transactions_size: MAX_SHARD_BANDWIDTH / 4,
+    };
+    let simulation_run = run_with_chunk_size_limit(Some(limit));
+    let max_sent = max_sent_per_chunk(&simulation_run);
+    assert!(max_sent <= limit.receipts_space(), "{}", max_sent);
+
+    let stats = TestStats::new(&simulation_run);
+    let chunk_size_stats = stats.chunk_size.as_ref().unwrap();
+    assert!(chunk_size_stats.chunk_size_bound_ratio > 0.9);
+    assert!(chunk_size_stats.bandwidth_bound_ratio < 0.1);
+    assert!(chunk_size_stats.mean_space_usage > 0.9);
+    // Throughput is measured against what fits into the chunks, the receipts fill almost all of the space.
+    stats.assert_with(StatsThresholds {
+        min_bandwidth_utilization: 0.9,
+        min_optimality_ratio: 0.9,
+        ..StatsThresholds::default()
+    });
+}
+
+/// A limit that is never hit doesn't change anything, the grants are what limits the chunks.
+#[test]
+fn chunk_size_not_reached() {
+    let without_limit = run_with_chunk_size_limit(None);
+    let with_limit = run_with_chunk_size_limit(Some(ChunkSizeLimit {
+        max_chunk_size: 2 * MAX_SHARD_BANDWIDTH,
+        transactions_size: MAX_SHARD_BANDWIDTH / 2,
+    }));
+    assert_eq!(
+        max_sent_per_chunk(&without_limit),
+        max_sent_per_chunk(&with_limit)
+    );
+    assert_eq!(
+        without_limit.simulation.total_granted,
+        with_limit.simulation.total_granted
+    );
+
+    let stats = TestStats::new(&with_limit);
+    let chunk_size_stats = stats.chunk_size.as_ref().unwrap();
+    assert_eq!(chunk_size_stats.chunk_size_bound_ratio, 0.0);
+    assert!(chunk_size_stats.bandwidth_bound_ratio > 0.9);
+    stats.basic_assert();
+    assert!(TestStats::new(&without_limit).chunk_size.is_none());
+}
+
+/// Transactions take space away from the receipts.
+#[test]
+fn transactions_reduce_receipt_space() {
+    let make_limit = |transactions_size| ChunkSizeLimit {
+        max_chunk_size: MAX_SHARD_BANDWIDTH,
+        transactions_size,
+    };
+    let no_transactions = run_with_chunk_size_limit(Some(make_limit(0)));
+    let with_transactions = run_with_chunk_size_limit(Some(make_limit(MAX_SHARD_BANDWIDTH / 2)));
+    assert!(max_sent_per_chunk(&with_transactions) <= MAX_SHARD_BANDWIDTH / 2);
+    assert!(max_sent_per_chunk(&no_transactions) > MAX_SHARD_BANDWIDTH / 2);
+}
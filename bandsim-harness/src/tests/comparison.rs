@@ -0,0 +1,47 @@
+use crate::bandwidth_scheduler::SchedulerParams;
+use crate::experiments::comparison::UpgradeComparison;
+use crate::simulation::builder::SimulationBuilder;
+
+use super::full_speed_typical_sender;
+
+fn typical_workload(_seed: u64) -> SimulationBuilder {
+    SimulationBuilder::new(3).default_sender_factory(full_speed_typical_sender)
+}
+
+fn compare_with(new_params: SchedulerParams) -> UpgradeComparison {
+    UpgradeComparison::new(SchedulerParams::default(), new_params, typical_workload)
+        .metric("fairness", false, |stats| stats.max_min_ratio.ratio)
+        .metric("utilization", true, |stats| {
+            stats.bandwidth_utilization.utilization
+        })
+        .seeds(0..3)
+        .steps(150)
+}
+
+/// Comparing a configuration with itself shows no differences.
+#[test]
+fn same_configuration_has_no_regressions() {
+    let report = compare_with(SchedulerParams::default()).run();
+    report.print();
+    assert!(!report.has_regressions());
+    for metric in &report.metrics {
+        assert_eq!(metric.delta(), 0.0);
+        assert_eq!(metric.worse_seeds, 0);
+    }
+    // Header, params, table header and one row per metric
+    assert_eq!(report.to_markdown().lines().count(), 6 + 2 + 2);
+}
+
+/// Reserving a lot of bandwidth for unstoppable receipts that are never sent lowers the utilization.
+#[test]
+fn reserved_bandwidth_is_a_regression() {
+    let report = compare_with(SchedulerParams {
+        unstoppable_reserve: 500_000,
+        ..SchedulerParams::default()
+    })
+    .run();
+    report.print();
+    let regressions: Vec<&str> = report.regressions().map(|m| m.name.as_str()).collect();
+    assert!(regressions.contains(&"utilization"));
+    assert!(report.to_markdown().contains("**REGRESSION**"));
+}
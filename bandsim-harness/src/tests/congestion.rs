@@ -0,0 +1,57 @@
+use crate::chain::{CongestionInfo, ShardUId, MAX_SHARD_BANDWIDTH};
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::SimulationRun;
+
+use super::constant_rate_sender;
+
+/// Congestion info in the latest non-missing chunk on the shard.
+fn latest_congestion_info(simulation_run: &SimulationRun, shard: usize) -> CongestionInfo {
+    simulation_run
+        .simulation
+        .blocks
+        .iter()
+        .flatten()
+        .rev()
+        .find_map(|block| block.chunks.get(&ShardUId::new(shard))?.as_ref())
+        .unwrap()
+        .congestion_info
+        .clone()
+}
+
+/// 0 -> 1 - sends half of the link capacity, but shard 1 can process only a quarter.
+/// Chunks on shard 1 are sometimes missing.
+/// The incoming backlog on shard 1 keeps growing, shard 0 doesn't receive anything.
+#[test]
+fn slow_processing_causes_incoming_backlog() {
+    let simulation_run = SimulationBuilder::new(2)
+        .receipt_sender(0, 1, constant_rate_sender(100_000, MAX_SHARD_BANDWIDTH / 2))
+        .incoming_processing_limit(MAX_SHARD_BANDWIDTH / 4)
+        .missing_chunk_generator(|height, shard_id, _rng| {
+            shard_id == ShardUId::new(1) && height % 5 == 0
+        })
+        .build()
+        .run_for(200);
+
+    let shard1_info = latest_congestion_info(&simulation_run, 1);
+    assert!(shard1_info.incoming_backlog_size > 100 * MAX_SHARD_BANDWIDTH / 8);
+    assert!(shard1_info.processing_delay > 50);
+    assert_eq!(
+        latest_congestion_info(&simulation_run, 0),
+        CongestionInfo::default()
+    );
+}
+
+/// Without a processing limit all incoming receipts are processed right away.
+#[test]
+fn no_incoming_backlog_without_processing_limit() {
+    let simulation_run = SimulationBuilder::new(2)
+        .receipt_sender(0, 1, constant_rate_sender(100_000, MAX_SHARD_BANDWIDTH / 2))
+        .build()
+        .run_for(100);
+
+    for block in simulation_run.simulation.blocks.iter().flatten() {
+        for chunk in block.chunks.values().flatten() {
+            assert_eq!(chunk.congestion_info, CongestionInfo::default());
+        }
+    }
+}
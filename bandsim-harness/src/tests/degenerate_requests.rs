@@ -0,0 +1,144 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::bandwidth_request::{BandwidthRequest, BandwidthRequestBitmap};
+use crate::bandwidth_scheduler::{BandwidthScheduler, SchedulerParams};
+use crate::chain::{
+    Block, Chunk, CongestionInfo, ShardLink, ShardUId, SimulationConfig, MAX_RECEIPT_SIZE,
+    MAX_SHARD_BANDWIDTH,
+};
+use crate::rng::rng_from_seed;
+use crate::shard_layout::ShardLayout;
+use crate::validation::validate_grants;
+
+// Pathological, but well-formed request sets. The current receipt senders never produce them,
+// but a malicious shard (or all of them) can.
+
+/// A block where every shard sends the same request on every link.
+fn uniform_requests_block(num_shards: usize, bitmap: &BandwidthRequestBitmap) -> Block {
+    let shards: Vec<ShardUId> = (0..num_shards).map(ShardUId::new).collect();
+    let mut chunks = BTreeMap::new();
+    for shard in &shards {
+        let bandwidth_requests = shards
+            .iter()
+            .map(|to_shard| BandwidthRequest {
+                to_shard: *to_shard,
+                grant_options_bitmap: bitmap.clone(),
+            })
+            .collect();
+        let chunk = Chunk {
+            prev_incoming_receipts_size: 0,
+            prev_outgoing_receipts_size: BTreeMap::new(),
+            prev_unstoppable_receipts_size: BTreeMap::new(),
+            bandwidth_requests,
+            congestion_info: CongestionInfo::default(),
+            receiver_quotas: BTreeMap::new(),
+        };
+        chunks.insert(*shard, Some(chunk));
+    }
+    Block {
+        height: 1,
+        shard_layout: Arc::new(ShardLayout::new(0, shards)),
+        chunks,
+    }
+}
+
+fn bitmap_with_bits(bits: impl IntoIterator<Item = usize>) -> BandwidthRequestBitmap {
+    let mut bitmap = BandwidthRequestBitmap::new();
+    for bit in bits {
+        bitmap.set_bit(bit, true);
+    }
+    bitmap
+}
+
+/// Run the scheduler on the block for a few heights and check that the grants are sane:
+/// * limits of every shard are respected
+/// * every link gets at least the base bandwidth
+/// * all links get a similar grant - the requests are symmetric, so the allocation should be too.
+///   The grants are compared over all heights, at a single height one link can win the shuffle.
+/// * the bandwidth isn't wasted - every shard can send almost all of its bandwidth.
+fn check_symmetric_allocation(block: &Block, heights: u64) {
+    let mut scheduler =
+        BandwidthScheduler::new(SchedulerParams::default(), SimulationConfig::default());
+    let base_bandwidth = scheduler.get_base_bandwidth(block.shard_layout.num_shards());
+    let mut total_grants: BTreeMap<ShardLink, usize> = BTreeMap::new();
+    for height in 0..heights {
+        let grants = scheduler.run(block, &mut rng_from_seed(height));
+        validate_grants(
+            &grants,
+            &SchedulerParams::default(),
+            &SimulationConfig::default(),
+        );
+        for link in block.shard_layout.all_links() {
+            let grant = grants.get(&link).copied().unwrap_or(0);
+            assert!(
+                grant >= base_bandwidth,
+                "Link {:?} got {} < base bandwidth {}",
+                link,
+                grant,
+                base_bandwidth
+            );
+            *total_grants.entry(link).or_insert(0) += grant;
+        }
+    }
+
+    let min_grant = *total_grants.values().min().unwrap();
+    let max_grant = *total_grants.values().max().unwrap();
+    assert!(
+        max_grant <= min_grant * 2 + MAX_RECEIPT_SIZE,
+        "Unfair allocation, min: {}, max: {}",
+        min_grant,
+        max_grant
+    );
+
+    let mut total_outgoing: BTreeMap<ShardUId, usize> = BTreeMap::new();
+    for (link, grant) in &total_grants {
+        *total_outgoing.entry(link.from).or_insert(0) += grant;
+    }
+    for (shard, outgoing) in total_outgoing {
+        let utilization = outgoing as f64 / (heights as usize * MAX_SHARD_BANDWIDTH) as f64;
+        assert!(
+            utilization > 0.9,
+            "Shard {:?} can use only {:.2} of its bandwidth",
+            shard,
+            utilization
+        );
+    }
+}
+
+#[test]
+fn all_shards_request_only_max() {
+    let only_max = bitmap_with_bits([BandwidthRequestBitmap::new().len() - 1]);
+    for num_shards in [1, 2, 3, 7, 16, 50] {
+        check_symmetric_allocation(&uniform_requests_block(num_shards, &only_max), 5);
+    }
+}
+
+#[test]
+fn all_shards_request_every_value() {
+    let all_values = bitmap_with_bits(0..BandwidthRequestBitmap::new().len());
+    for num_shards in [1, 2, 3, 7, 16, 50] {
+        check_symmetric_allocation(&uniform_requests_block(num_shards, &all_values), 5);
+    }
+}
+
+/// Every link asks for one byte more than the base bandwidth, which means the smallest option.
+#[test]
+fn thousands_of_shards_request_one_byte_over_base() {
+    let num_shards = 1000;
+    let base_bandwidth =
+        BandwidthScheduler::new(SchedulerParams::default(), SimulationConfig::default())
+            .get_base_bandwidth(num_shards);
+    let request = BandwidthRequest::from_receipt_sizes(
+        ShardUId::new(0),
+        std::iter::once(base_bandwidth + 1),
+        base_bandwidth,
+        &SimulationConfig::default(),
+    )
+    .unwrap();
+    assert_eq!(request.grant_options_bitmap, bitmap_with_bits([0]));
+    check_symmetric_allocation(
+        &uniform_requests_block(num_shards, &request.grant_options_bitmap),
+        1,
+    );
+}
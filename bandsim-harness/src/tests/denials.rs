@@ -0,0 +1,47 @@
+use crate::bandwidth_scheduler::DenialReason;
+use crate::chain::ShardUId;
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{FullSpeedReceiptSender, TypicalReceiptGenerator};
+use crate::validation::TestStats;
+
+fn full_speed_links(links: &[(usize, usize)]) -> TestStats {
+    let mut builder = SimulationBuilder::new(3);
+    for (from, to) in links {
+        builder = builder.receipt_sender(
+            *from,
+            *to,
+            FullSpeedReceiptSender(TypicalReceiptGenerator::new()),
+        );
+    }
+    TestStats::new(&builder.build().run_for(200))
+}
+
+/// 0 -> 2 and 1 -> 2 - both send at full speed, shard 2 can't receive everything.
+/// The denials point at the receiving shard.
+#[test]
+fn fan_in_is_receiver_limited() {
+    let denial_stats = full_speed_links(&[(0, 2), (1, 2)]).denial_stats;
+    assert!(denial_stats.total > 0);
+    assert!(denial_stats.share(DenialReason::ReceiverLimit) > 0.5);
+    let (shard_id, reason, share) = denial_stats.bottleneck().unwrap();
+    assert_eq!(
+        (shard_id, reason),
+        (ShardUId::new(2), DenialReason::ReceiverLimit)
+    );
+    assert!(share > 0.5);
+}
+
+/// 0 -> 1 and 0 -> 2 - shard 0 sends to both at full speed, it can't send everything.
+/// The denials point at the sending shard.
+#[test]
+fn fan_out_is_sender_limited() {
+    let denial_stats = full_speed_links(&[(0, 1), (0, 2)]).denial_stats;
+    assert!(denial_stats.total > 0);
+    assert!(denial_stats.share(DenialReason::SenderLimit) > 0.5);
+    let (shard_id, reason, share) = denial_stats.bottleneck().unwrap();
+    assert_eq!(
+        (shard_id, reason),
+        (ShardUId::new(0), DenialReason::SenderLimit)
+    );
+    assert!(share > 0.5);
+}
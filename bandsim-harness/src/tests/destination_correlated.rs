@@ -0,0 +1,44 @@
+use std::collections::BTreeMap;
+
+use crate::chain::ShardLink;
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::metrics::LatencyHistogram;
+use crate::simulation::receipt_sender::{
+    DestinationCorrelatedReceiptGenerator, FullSpeedReceiptSender, OneSizeReceiptGenerator,
+    RandomSizeReceiptGenerator, ReceiptSender,
+};
+use crate::validation::TestStats;
+
+/// All shards send to all shards at full speed. Receipts sent to shard 0 are big, all other receipts are small.
+/// Shard 0 receives big receipts from every shard, so its incoming limit is contended by big receipts only.
+#[test]
+fn big_receipts_only_to_shard_0() {
+    let simulation_run = SimulationBuilder::new(4)
+        .default_sender_factory(|_rng| -> Box<dyn ReceiptSender> {
+            Box::new(FullSpeedReceiptSender(
+                DestinationCorrelatedReceiptGenerator::new(RandomSizeReceiptGenerator {
+                    size_range: 1_000..=10_000,
+                })
+                .destination(0, OneSizeReceiptGenerator { size: 2_000_000 }),
+            ))
+        })
+        .build()
+        .run_for(300);
+
+    let mut sent: BTreeMap<ShardLink, LatencyHistogram> = BTreeMap::new();
+    for height_metrics in &simulation_run.simulation.metrics {
+        for (link, histogram) in &height_metrics.sent_latencies {
+            sent.entry(*link).or_default().merge(histogram);
+        }
+    }
+    for (link, histogram) in &sent {
+        let average_size = histogram.total_bytes() / histogram.total_receipts();
+        if link.to.shard_id == 0 {
+            assert_eq!(average_size, 2_000_000);
+        } else {
+            assert!(average_size <= 10_000, "{:?}: {}", link, average_size);
+        }
+    }
+
+    TestStats::new(&simulation_run).basic_assert();
+}
@@ -4,9 +4,9 @@ use std::time::Duration;
 use rand::seq::SliceRandom;
 use rand::Rng;
 
-use crate::bandsim::bandwidth_scheduler::distribute_remaining::distribute_remaining_bandwidth;
-use crate::bandsim::chain::{ShardLink, ShardUId, MAX_SHARD_BANDWIDTH};
-use crate::bandsim::rng::{rng_from_seed, DefaultRng};
+use crate::bandwidth_scheduler::distribute_remaining::distribute_remaining_bandwidth;
+use crate::chain::{ShardLink, ShardUId, MAX_SHARD_BANDWIDTH};
+use crate::rng::{rng_from_seed, DefaultRng};
 
 fn generate_shards(rng: &mut DefaultRng) -> Vec<ShardUId> {
     let num_shards: usize = rng.gen_range(1..10);
@@ -316,3 +316,66 @@ fn test_three_100() {
     };
     test_case.run();
 }
+
+/// Shard 1 can't send anything (a zero row).
+#[test]
+fn test_zero_outgoing_shard() {
+    let test_case = TestCase {
+        left: limits_from_data(&[(0, 100), (1, 0), (2, 100)]),
+        right: limits_from_data(&[(0, 100), (1, 100), (2, 100)]),
+        workload_type: "custom_zero_row",
+    };
+    test_case.run();
+}
+
+/// Shard 1 can't receive anything (a zero column).
+#[test]
+fn test_zero_incoming_shard() {
+    let test_case = TestCase {
+        left: limits_from_data(&[(0, 100), (1, 100), (2, 100)]),
+        right: limits_from_data(&[(0, 100), (1, 0), (2, 100)]),
+        workload_type: "custom_zero_column",
+    };
+    test_case.run();
+    let grants = distribute_remaining_bandwidth(&test_case.left, &test_case.right);
+    assert!(grants
+        .iter()
+        .all(|(link, grant)| link.to.shard_id != 1 || *grant == 0));
+}
+
+#[test]
+fn test_all_zero() {
+    let test_case = TestCase {
+        left: limits_from_data(&[(0, 0), (1, 0)]),
+        right: limits_from_data(&[(0, 0), (1, 0)]),
+        workload_type: "custom_all_zero",
+    };
+    test_case.run();
+}
+
+/// Shards in maintenance mode have no spare bandwidth on one or both sides.
+#[test]
+fn test_distributing_remaining_maintenance() {
+    let mut rng = rng_from_seed(0);
+    for _ in 0..10_000 {
+        let shards = generate_shards(&mut rng);
+        let b1 = generate_shard_bandwidth(&mut rng) * shards.len();
+        let b2 = generate_shard_bandwidth(&mut rng) * shards.len();
+        let mut left = generate_limits(&shards, b1, &mut rng);
+        let mut right = generate_limits(&shards, b2, &mut rng);
+        for shard in &shards {
+            if rng.gen_bool(0.3) {
+                left.insert(*shard, 0);
+            }
+            if rng.gen_bool(0.3) {
+                right.insert(*shard, 0);
+            }
+        }
+        let test_case = TestCase {
+            left,
+            right,
+            workload_type: "maintenance",
+        };
+        test_case.run();
+    }
+}
@@ -0,0 +1,84 @@
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{
+    ConstantRateReceiptSender, FullSpeedReceiptSender, OneSizeReceiptGenerator,
+    TypicalReceiptGenerator,
+};
+use crate::simulation::SimulationRun;
+
+/// 0 -> 1 - sends at full speed
+/// 1 -> 2 - sends 100 kB per height
+fn two_busy_links() -> SimulationRun {
+    SimulationBuilder::new(3)
+        .receipt_sender(0, 1, FullSpeedReceiptSender(TypicalReceiptGenerator::new()))
+        .receipt_sender(
+            1,
+            2,
+            ConstantRateReceiptSender {
+                generator: OneSizeReceiptGenerator { size: 10_000 },
+                bytes_per_height: 100_000,
+            },
+        )
+        .build()
+        .run_for(200)
+}
+
+fn edges(dot: &str) -> Vec<&str> {
+    dot.lines().filter(|line| line.contains("->")).collect()
+}
+
+#[test]
+fn dot_export_shows_used_links() {
+    let dot = two_busy_links().to_dot(None);
+    println!("{}", dot);
+    assert!(dot.starts_with("digraph traffic {\n"));
+    assert!(dot.ends_with("}\n"));
+    for shard in 0..3 {
+        assert!(dot.contains(&format!("    \"shard_{}\";\n", shard)));
+    }
+
+    // Links without a sender are granted bandwidth too, they show up with zero usage.
+    let edges = edges(&dot);
+    let busy_edge = edges
+        .iter()
+        .find(|edge| edge.contains("\"shard_0\" -> \"shard_1\""))
+        .unwrap();
+    assert!(!busy_edge.contains("used 0.00 MB"));
+    let light_edge = edges
+        .iter()
+        .find(|edge| edge.contains("\"shard_1\" -> \"shard_2\""))
+        .unwrap();
+    assert!(light_edge.contains("used 0.10 MB"));
+    let idle_edge = edges
+        .iter()
+        .find(|edge| edge.contains("\"shard_2\" -> \"shard_0\""))
+        .unwrap();
+    assert!(idle_edge.contains("used 0.00 MB"));
+    assert!(idle_edge.contains("penwidth=1.00"));
+}
+
+#[test]
+fn dot_export_top_k() {
+    let dot = two_busy_links().to_dot(Some(2));
+    let edges = edges(&dot);
+    assert_eq!(edges.len(), 2);
+    assert!(edges[0].contains("\"shard_0\" -> \"shard_1\""));
+    assert!(edges[1].contains("\"shard_1\" -> \"shard_2\""));
+}
+
+#[test]
+fn dot_export_description() {
+    let dot = two_busy_links().to_dot(None);
+    assert!(!dot.contains("labelloc"));
+
+    let simulation_run = SimulationBuilder::new(2)
+        .description("two shards, typical receipts")
+        .receipt_sender(0, 1, FullSpeedReceiptSender(TypicalReceiptGenerator::new()))
+        .build()
+        .run_for(20);
+    assert_eq!(
+        simulation_run.simulation.description,
+        "two shards, typical receipts"
+    );
+    let dot = simulation_run.to_dot(None);
+    assert!(dot.contains("    label=\"two shards, typical receipts\";\n    labelloc=t;\n"));
+}
@@ -0,0 +1,62 @@
+use crate::chain::{MAX_SHARD_BANDWIDTH, MIN_RECEIPT_SIZE};
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::outgoing_queue::DropPolicy;
+use crate::simulation::receipt_sender::{ConstantRateReceiptSender, RandomSizeReceiptGenerator};
+use crate::validation::TestStats;
+
+const QUEUE_CAP: usize = 10_000_000;
+
+/// 0 -> 1 - sends receipts of random sizes, 1.5x more than the link can handle.
+/// The queue is capped, the excess load is dropped according to the policy.
+fn overloaded_link_stats(policy: DropPolicy) -> TestStats {
+    let simulation_run = SimulationBuilder::new(2)
+        .receipt_sender(
+            0,
+            1,
+            ConstantRateReceiptSender {
+                generator: RandomSizeReceiptGenerator {
+                    size_range: MIN_RECEIPT_SIZE..=1_000_000,
+                },
+                bytes_per_height: MAX_SHARD_BANDWIDTH * 3 / 2,
+            },
+        )
+        .queue_cap(QUEUE_CAP, policy)
+        .build()
+        .run_for(300);
+
+    let max_queued = simulation_run
+        .simulation
+        .metrics
+        .iter()
+        .flat_map(|m| m.queued_before_send.values())
+        .max()
+        .unwrap();
+    assert!(*max_queued <= QUEUE_CAP);
+
+    let stats = TestStats::new(&simulation_run);
+    assert!(!stats.is_unstable);
+    assert_eq!(stats.drop_stats.policy, Some(policy));
+    assert_eq!(stats.drop_stats.dropped_bytes, stats.shed_bytes);
+    assert!(
+        stats.shed_ratio > 0.25 && stats.shed_ratio < 0.5,
+        "{}",
+        stats.shed_ratio
+    );
+    stats
+}
+
+#[test]
+fn drop_policies_in_overload() {
+    let reject_new = overloaded_link_stats(DropPolicy::RejectNew);
+    let drop_oldest = overloaded_link_stats(DropPolicy::DropOldest);
+    let drop_largest = overloaded_link_stats(DropPolicy::DropLargest);
+
+    // Rejected receipts never wait in the queue
+    assert_eq!(reject_new.drop_stats.max_dropped_age, 0);
+    // Dropping the oldest receipts wastes their waiting time, but keeps the queue fresh.
+    assert!(drop_oldest.drop_stats.mean_dropped_age > 0.0);
+    assert!(drop_oldest.max_receipt_age.age < reject_new.max_receipt_age.age);
+    // Dropping the largest receipts affects the least receipts.
+    assert!(drop_largest.drop_stats.dropped_receipts < reject_new.drop_stats.dropped_receipts);
+    assert!(drop_largest.drop_stats.dropped_receipts < drop_oldest.drop_stats.dropped_receipts);
+}
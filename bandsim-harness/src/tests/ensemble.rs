@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use rand::Rng;
+
+use crate::bandwidth_scheduler::{SchedulerAlgorithm, SchedulerParams};
+use crate::experiments::ensemble::Ensemble;
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::workload_trace::WorkloadTrace;
+use crate::validation::OfferedLoad;
+
+use super::full_speed_typical_sender;
+
+/// Full speed senders react to the grants - they fill up the queue whenever it gets short.
+fn reactive_workload() -> SimulationBuilder {
+    SimulationBuilder::new(3)
+        .random_seed(7)
+        .default_sender_factory(full_speed_typical_sender)
+        .missing_chunk_generator(|_height, _shard_id, rng| rng.gen_bool(0.1))
+}
+
+fn with_algorithm(
+    algorithm: SchedulerAlgorithm,
+) -> impl Fn(SimulationBuilder) -> SimulationBuilder {
+    move |builder| {
+        builder.scheduler_params(SchedulerParams {
+            algorithm,
+            ..SchedulerParams::default()
+        })
+    }
+}
+
+/// The same seed isn't enough, the senders generate different receipts under different schedulers.
+#[test]
+fn shared_seed_gives_different_workloads() {
+    let offered = |algorithm| {
+        let simulation_run = with_algorithm(algorithm)(reactive_workload())
+            .build()
+            .run_for(300);
+        OfferedLoad::new(&simulation_run)
+    };
+    assert_ne!(
+        offered(SchedulerAlgorithm::Allowance),
+        offered(SchedulerAlgorithm::RoundRobin)
+    );
+}
+
+/// All variants get exactly the recorded workload, only the delivered load differs.
+#[test]
+fn ensemble_shares_workload() {
+    let result = Ensemble::new(reactive_workload)
+        .variant("allowance", with_algorithm(SchedulerAlgorithm::Allowance))
+        .variant(
+            "round robin",
+            with_algorithm(SchedulerAlgorithm::RoundRobin),
+        )
+        .variant(
+            "deficit round robin",
+            with_algorithm(SchedulerAlgorithm::DeficitRoundRobin { quantum: 100_000 }),
+        )
+        .steps(300)
+        .run();
+    result.print_summary();
+    assert!(result.trace.total_receipts() > 0);
+
+    let offered = |name: &str| {
+        let links = &result.stats(name).offered_load.links;
+        links
+            .iter()
+            .map(|(link, load)| (*link, load.offered))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(offered("allowance"), offered("round robin"));
+    assert_eq!(offered("allowance"), offered("deficit round robin"));
+    assert_eq!(
+        result.stats("allowance").offered_load.total_offered(),
+        result.trace.total_bytes()
+    );
+    assert_ne!(
+        result.stats("allowance").total_sent,
+        result.stats("round robin").total_sent
+    );
+}
+
+/// Replaying a trace gives the same run every time, the recorded run's chain is reproduced too.
+#[test]
+fn replay_is_deterministic() {
+    let recorded_run = reactive_workload().record_workload().build().run_for(200);
+    let trace = Arc::new(WorkloadTrace::from_run(&recorded_run));
+    let replay = || {
+        reactive_workload()
+            .replay_workload(trace.clone())
+            .build()
+            .run_for(200)
+    };
+    let (first, second) = (replay(), replay());
+    trace.check_replayed(&first);
+    assert_eq!(OfferedLoad::new(&first), OfferedLoad::new(&recorded_run));
+    assert_eq!(OfferedLoad::new(&first), OfferedLoad::new(&second));
+}
+
+#[test]
+#[should_panic(expected = "The run didn't record the workload")]
+fn trace_needs_recording() {
+    let simulation_run = reactive_workload().build().run_for(10);
+    WorkloadTrace::from_run(&simulation_run);
+}
@@ -0,0 +1,52 @@
+use crate::chain::{ShardLink, ShardUId};
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::NoReceiptSender;
+use crate::simulation::Simulation;
+
+use super::full_speed_typical_sender;
+
+fn warmed_up_simulation() -> Simulation {
+    SimulationBuilder::new(3)
+        .default_sender_factory(full_speed_typical_sender)
+        .missing_block_probability(0.1)
+        .build()
+        .run_for(100)
+        .simulation
+}
+
+/// Forks which are continued the same way end up in the same state as the original.
+#[test]
+fn fork_continues_identically() {
+    let warmed_up = warmed_up_simulation();
+    let fork_a = warmed_up.fork().run_for(200).simulation;
+    let fork_b = warmed_up.fork().run_for(200).simulation;
+    let original = warmed_up.run_for(200).simulation;
+    for fork in [&fork_a, &fork_b] {
+        assert_eq!(fork.blocks.len(), original.blocks.len());
+        assert_eq!(fork.total_granted, original.total_granted);
+        for (fork_block, original_block) in fork.blocks.iter().zip(&original.blocks) {
+            assert_eq!(fork_block.is_some(), original_block.is_some());
+        }
+    }
+}
+
+/// A what-if continuation: the sender on 0 -> 1 stops after the warm-up.
+#[test]
+fn fork_with_different_sender() {
+    let warmed_up = warmed_up_simulation();
+    let shard_link = ShardLink {
+        from: ShardUId::new(0),
+        to: ShardUId::new(1),
+    };
+    let mut stopped = warmed_up.fork();
+    stopped.replace_receipt_sender(0, 1, NoReceiptSender);
+    let stopped = stopped.run_for(200).simulation;
+    let continued = warmed_up.run_for(200).simulation;
+
+    assert_ne!(stopped.total_granted, continued.total_granted);
+    let queue = |simulation: &Simulation| {
+        simulation.shards[&shard_link.from].outgoing_queues[&shard_link.to].total_size()
+    };
+    assert!(queue(&continued) > 0);
+    assert_eq!(queue(&stopped), 0);
+}
@@ -0,0 +1,28 @@
+use std::collections::BTreeSet;
+use std::mem::discriminant;
+
+use crate::experiments::fuzzer::{fuzz, FuzzCase};
+
+#[test]
+fn fuzz_configurations() {
+    let failures = fuzz(0..20, 200);
+    for failure in &failures {
+        println!("Seed {} failed: {}", failure.case.seed, failure.message);
+    }
+    assert!(failures.is_empty(), "{} fuzz cases failed", failures.len());
+}
+
+/// The same seed gives the same case and different seeds cover every scheduler algorithm.
+#[test]
+fn fuzz_cases_are_reproducible() {
+    let mut algorithms = BTreeSet::new();
+    for seed in 0..50 {
+        let case = FuzzCase::generate(seed);
+        assert_eq!(case, FuzzCase::generate(seed));
+        algorithms.insert(format!(
+            "{:?}",
+            discriminant(&case.scheduler_params.algorithm)
+        ));
+    }
+    assert_eq!(algorithms.len(), 5);
+}
@@ -0,0 +1,85 @@
+use crate::bandwidth_scheduler::SchedulerParams;
+use crate::chain::MAX_RECEIPT_SIZE;
+use crate::chain::MAX_SHARD_BANDWIDTH;
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{FullSpeedReceiptSender, OneSizeReceiptGenerator};
+use crate::simulation::SimulationRun;
+use crate::validation::{GlobalBudgetStats, StatsThresholds, TestStats};
+
+use super::DEFAULT_TEST_LENGTH;
+
+const BUDGET: usize = MAX_SHARD_BANDWIDTH * 3 / 2;
+
+fn run_with_budget(
+    num_shards: usize,
+    receipt_size: usize,
+    global_budget: Option<usize>,
+) -> SimulationRun {
+    SimulationBuilder::new(num_shards)
+        .default_sender_factory(move |_rng| {
+            Box::new(FullSpeedReceiptSender(OneSizeReceiptGenerator {
+                size: receipt_size,
+            }))
+        })
+        .scheduler_params(SchedulerParams {
+            global_budget,
+            ..SchedulerParams::default()
+        })
+        .record_grant_history()
+        .build()
+        .run_for(DEFAULT_TEST_LENGTH)
+}
+
+/// The grants never exceed the budget, so neither do the receipts sent with them.
+#[test]
+fn global_budget_limits_traffic() {
+    let simulation_run = run_with_budget(4, 50_000, Some(BUDGET));
+    for height_metrics in &simulation_run.simulation.metrics {
+        let granted: usize = height_metrics.grants.values().sum();
+        assert!(granted <= BUDGET, "{} > {}", granted, BUDGET);
+    }
+
+    let stats = TestStats::new(&simulation_run);
+    let budget_stats = stats.global_budget.as_ref().unwrap();
+    assert!(budget_stats.max_sent_per_block <= BUDGET);
+    // Full speed senders on 4 shards want much more than the budget
+    assert!(budget_stats.capped_ratio > 0.9);
+    assert!(budget_stats.mean_budget_usage > 0.7);
+    let capped_fairness = budget_stats.capped_fairness.unwrap();
+    assert!(capped_fairness.ratio <= 2.15, "{:?}", capped_fairness);
+
+    // Utilization is measured against the per-shard limits, the budget keeps it low.
+    stats.assert_with(StatsThresholds {
+        min_bandwidth_utilization: 0.0,
+        min_optimality_ratio: 0.0,
+        allow_unstable: true,
+        ..StatsThresholds::default()
+    });
+}
+
+/// A budget that is never hit doesn't change anything.
+#[test]
+fn global_budget_not_reached() {
+    let without_budget = run_with_budget(2, 50_000, None);
+    let with_budget = run_with_budget(2, 50_000, Some(4 * MAX_SHARD_BANDWIDTH));
+    assert_eq!(
+        without_budget.simulation.total_granted,
+        with_budget.simulation.total_granted
+    );
+
+    let stats = TestStats::new(&with_budget);
+    let budget_stats = stats.global_budget.as_ref().unwrap();
+    assert_eq!(budget_stats.capped_heights, 0);
+    assert_eq!(budget_stats.capped_fairness, None);
+    stats.basic_assert();
+    assert!(TestStats::new(&without_budget).global_budget.is_none());
+}
+
+/// Scaling keeps the shares of the links, but a link can't send a receipt larger than its scaled grant.
+/// With max size receipts on 16 links every scaled grant is too small and nothing gets sent.
+#[test]
+fn global_budget_starves_big_receipts() {
+    let simulation_run = run_with_budget(4, MAX_RECEIPT_SIZE, Some(BUDGET));
+    let budget_stats = GlobalBudgetStats::new(&simulation_run).unwrap();
+    assert_eq!(budget_stats.max_sent_per_block, 0, "{:?}", budget_stats);
+}
@@ -0,0 +1,47 @@
+use crate::chain::{ShardLink, ShardUId};
+use crate::simulation::builder::SimulationBuilder;
+
+use super::full_speed_typical_sender;
+
+/// Every shard keeps the grants from the last few heights, they match the recorded grants.
+#[test]
+fn shard_grant_history_matches_recorded_grants() {
+    let history_len = 5;
+    let simulation_run = SimulationBuilder::new(3)
+        .default_sender_factory(full_speed_typical_sender)
+        .missing_block_probability(0.2)
+        .shard_grant_history(history_len)
+        .record_grant_history()
+        .build()
+        .run_for(50);
+    let simulation = &simulation_run.simulation;
+
+    let shard_link = ShardLink {
+        from: ShardUId::new(0),
+        to: ShardUId::new(1),
+    };
+    let expected: Vec<(usize, usize)> = simulation.metrics
+        [simulation.metrics.len() - history_len..]
+        .iter()
+        .map(|metrics| {
+            let grant = metrics.grants.get(&shard_link).copied().unwrap_or(0);
+            (metrics.height, grant)
+        })
+        .collect();
+    assert!(expected.iter().any(|(_height, grant)| *grant > 0));
+    for shard in simulation.shards.values() {
+        assert_eq!(shard.recent_grants.len(), history_len);
+        assert_eq!(shard.grant_history(shard_link), expected);
+    }
+}
+
+#[test]
+fn shard_grant_history_is_disabled_by_default() {
+    let simulation_run = SimulationBuilder::new(2)
+        .default_sender_factory(full_speed_typical_sender)
+        .build()
+        .run_for(20);
+    for shard in simulation_run.simulation.shards.values() {
+        assert!(shard.recent_grants.is_empty());
+    }
+}
@@ -0,0 +1,35 @@
+use crate::chain::{ShardLink, ShardUId};
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{FullSpeedReceiptSender, TypicalReceiptGenerator};
+use crate::validation::LinkUtilization;
+
+/// 0 -> 1 - sends at full speed, nothing else is sent.
+/// The only busy link is the only dark cell in the heatmap.
+#[test]
+fn heatmap_of_single_busy_link() {
+    let simulation_run = SimulationBuilder::new(4)
+        .receipt_sender(0, 1, FullSpeedReceiptSender(TypicalReceiptGenerator::new()))
+        .build()
+        .run_for(200);
+
+    let link_utilization = LinkUtilization::new(&simulation_run, 100..200);
+    let busy_link = ShardLink {
+        from: ShardUId::new(0),
+        to: ShardUId::new(1),
+    };
+    assert_eq!(link_utilization.utilization.len(), 16);
+    // Receipts don't fill the grants perfectly, a full speed link uses ~85% of the bandwidth
+    assert!(link_utilization.utilization[&busy_link] > 0.8);
+    for (link, utilization) in &link_utilization.utilization {
+        if *link != busy_link {
+            assert_eq!(*utilization, 0.0);
+        }
+    }
+
+    let grid = link_utilization.to_text_grid();
+    println!("{}", grid);
+    let rows: Vec<&str> = grid.lines().collect();
+    assert_eq!(rows[1], "   0 1 2 3");
+    assert_eq!(rows[2], "0   %%    ");
+    assert_eq!(rows[3], "1         ");
+}
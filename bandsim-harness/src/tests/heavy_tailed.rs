@@ -0,0 +1,44 @@
+use crate::chain::MAX_SHARD_BANDWIDTH;
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{
+    ConstantRateReceiptSender, OneSizeReceiptGenerator, ParetoOnOffReceiptSender, ReceiptSender,
+};
+use crate::validation::{MaxReceiptAge, OfferedLoad};
+
+/// 0 -> 1 - sends about 70% of the link capacity using the given sender.
+fn run_with_sender(sender: impl ReceiptSender + 'static) -> (MaxReceiptAge, f64) {
+    let simulation_run = SimulationBuilder::new(2)
+        .receipt_sender(0, 1, sender)
+        .build()
+        .run_for(500);
+    let offered = OfferedLoad::new(&simulation_run)
+        .links
+        .values()
+        .map(|link| link.offered)
+        .sum::<usize>() as f64
+        / 500.0;
+    (MaxReceiptAge::new(&simulation_run), offered)
+}
+
+/// Heavy-tailed traffic with the same average load causes much longer delays than smooth traffic.
+#[test]
+fn heavy_tailed_traffic_has_longer_delays() {
+    let average_load = MAX_SHARD_BANDWIDTH * 7 / 10;
+    let (smooth_age, _) = run_with_sender(ConstantRateReceiptSender {
+        generator: OneSizeReceiptGenerator { size: 10_000 },
+        bytes_per_height: average_load,
+    });
+    let num_sources = 4;
+    let (bursty_age, bursty_offered) = run_with_sender(ParetoOnOffReceiptSender::new(
+        OneSizeReceiptGenerator { size: 10_000 },
+        num_sources,
+        average_load * 2 / num_sources,
+        1.2,
+    ));
+    println!("smooth: {:?}", smooth_age);
+    println!("bursty: {:?}, offered: {}", bursty_age, bursty_offered);
+
+    assert!(bursty_offered > average_load as f64 * 0.5);
+    assert!(bursty_offered < average_load as f64 * 1.5);
+    assert!(bursty_age.age > smooth_age.age * 3);
+}
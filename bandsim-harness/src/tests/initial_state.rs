@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+
+use crate::chain::{Receipt, ReceiptPriority, ShardUId};
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::initial_state::{InitialState, QueuedReceipt};
+use crate::simulation::queue_snapshot::QueueSnapshot;
+use crate::simulation::receipt_sender::{FullSpeedReceiptSender, TypicalReceiptGenerator};
+use crate::validation::{MaxReceiptAge, PhaseStats, TestStats, TotalSent, ValidationLevel};
+
+use super::link;
+
+/// 10 MB of receipts queued on 0 -> 1 at height 990, the simulation starts at height 1000.
+/// Every tenth receipt is a tagged high priority one.
+fn incident_state() -> InitialState {
+    let receipts = (0..100)
+        .map(|i| QueuedReceipt {
+            receipt: Receipt {
+                size: 100_000,
+                tag: (i % 10 == 0).then(|| "incident".into()),
+                priority: if i % 10 == 0 {
+                    ReceiptPriority::High
+                } else {
+                    ReceiptPriority::Normal
+                },
+            },
+            enqueued_height: 990,
+        })
+        .collect();
+    InitialState {
+        height: 1000,
+        outgoing_queues: BTreeMap::from([(link(0, 1), receipts)]),
+        allowances: BTreeMap::from([(link(0, 1), 1_000_000), (link(2, 1), 3_000_000)]),
+    }
+}
+
+#[test]
+fn start_from_initial_state() {
+    let simulation = SimulationBuilder::new(3)
+        .initial_state(incident_state())
+        .validation_level(ValidationLevel::Paranoid)
+        .build();
+    for shard in simulation.shards.values() {
+        assert_eq!(
+            shard.bandwidth_scheduler.allowances()[&link(2, 1)],
+            3_000_000
+        );
+    }
+    let outgoing_queue = &simulation.shards[&ShardUId::new(0)].outgoing_queues[&ShardUId::new(1)];
+    assert_eq!(outgoing_queue.total_size(), 10_000_000);
+
+    let simulation_run = simulation.run_for(20);
+    let simulation = &simulation_run.simulation;
+    assert_eq!(simulation.blocks[0].as_ref().unwrap().height, 1000);
+    assert_eq!(simulation.metrics[0].height, 1001);
+    assert_eq!(simulation.end_height(), 1021);
+
+    // The backlog is sent in a few heights, the receipts waited since height 990.
+    assert_eq!(TotalSent::new(&simulation_run).sent(link(0, 1)), 10_000_000);
+    assert!(simulation.shards[&ShardUId::new(0)].outgoing_queues[&ShardUId::new(1)].is_empty());
+    assert!(MaxReceiptAge::new(&simulation_run).age > 10);
+
+    // Stats work with heights that don't start at 0.
+    TestStats::new(&simulation_run);
+    let phases = PhaseStats::for_load_phases(&simulation_run);
+    assert_eq!(phases[0].heights, 1000..1021);
+}
+
+#[test]
+fn queue_snapshot_round_trip() {
+    let simulation_run = SimulationBuilder::new(3)
+        .receipt_sender(2, 1, FullSpeedReceiptSender(TypicalReceiptGenerator::new()))
+        .initial_state(incident_state())
+        .build()
+        .run_for(2);
+    let snapshot = QueueSnapshot::capture(&simulation_run.simulation);
+    assert_eq!(snapshot.height, 1002);
+    assert!(snapshot.queued_bytes()[&link(0, 1)] > 0);
+    assert!(snapshot.queued_bytes()[&link(2, 1)] > 0);
+
+    let path =
+        std::env::temp_dir().join(format!("bandsim_queue_snapshot_{}.tsv", std::process::id()));
+    snapshot.save(&path).unwrap();
+    let loaded = QueueSnapshot::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded, snapshot);
+
+    // A simulation started from the snapshot has the same queues.
+    let simulation = SimulationBuilder::new(3)
+        .initial_state(InitialState::from_queue_snapshot(loaded))
+        .build();
+    assert_eq!(QueueSnapshot::capture(&simulation), snapshot);
+}
@@ -0,0 +1,83 @@
+use crate::chain::{ShardLink, ShardUId, MAX_SHARD_BANDWIDTH};
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::metrics::LatencyHistogram;
+use crate::simulation::SimulationRun;
+use crate::validation::{TestStats, TotalSent};
+
+use super::constant_rate_sender;
+
+/// 100x longer than the usual tests, slow drift in the allowances could be hidden in shorter runs.
+const LONG_RUN_LENGTH: usize = 100_000;
+
+/// Demand on every link other than the heavy one.
+const LIGHT_DEMAND: usize = MAX_SHARD_BANDWIDTH / 8;
+
+/// Latencies of all receipts sent on the link during the run.
+fn link_latencies(simulation_run: &SimulationRun, link: ShardLink) -> LatencyHistogram {
+    let mut latencies = LatencyHistogram::default();
+    for height_metrics in &simulation_run.simulation.metrics {
+        if let Some(histogram) = height_metrics.sent_latencies.get(&link) {
+            latencies.merge(histogram);
+        }
+    }
+    latencies
+}
+
+/// 0 -> 1 - wants to send 10x more than every other link, more than the link can handle.
+/// All other links between the 3 shards - constant, light load.
+/// Backpressure keeps the heavy link's queue bounded during the long run.
+///
+/// The light links share the sending shard 0 and the receiving shard 1 with the heavy link,
+/// together they want 1.5x of the bandwidth of these shards. The documented bounds are:
+/// - The heavy link gets what's left after the light links, between 60% and 80% of the shard bandwidth,
+///   both in the first and in the second half of the run (no drift).
+/// - Light links deliver at least 99% of their load and 99% of their receipts are sent within 3 heights.
+///
+/// Takes a few minutes in debug mode, run it in release mode:
+/// cargo test --release permanent_asymmetric_demand -- --ignored --nocapture
+#[ignore]
+#[test]
+fn permanent_asymmetric_demand() {
+    let mut builder = SimulationBuilder::new(3).backpressure(MAX_SHARD_BANDWIDTH * 2);
+    for from in 0..3 {
+        for to in 0..3 {
+            let demand = if (from, to) == (0, 1) {
+                LIGHT_DEMAND * 10
+            } else {
+                LIGHT_DEMAND
+            };
+            builder = builder.receipt_sender(from, to, constant_rate_sender(50_000, demand));
+        }
+    }
+    let simulation_run = builder.build().run_for(LONG_RUN_LENGTH);
+    let stats = TestStats::new(&simulation_run);
+
+    let heavy_link = ShardLink {
+        from: ShardUId::new(0),
+        to: ShardUId::new(1),
+    };
+    let half = LONG_RUN_LENGTH / 2;
+    for heights in [0..half, half..LONG_RUN_LENGTH] {
+        let total_sent = TotalSent::for_heights(&simulation_run, heights.clone());
+        let share =
+            total_sent.sent(heavy_link) as f64 / (heights.len() * MAX_SHARD_BANDWIDTH) as f64;
+        println!("heavy link share in {:?}: {:.4}", heights, share);
+        assert!(share > 0.6 && share < 0.8, "{:?}: {}", heights, share);
+    }
+
+    for (link, load) in &stats.offered_load.links {
+        if link.is(0, 1) {
+            continue;
+        }
+        let delivered_ratio = load.delivered as f64 / load.offered as f64;
+        let p99 = link_latencies(&simulation_run, *link)
+            .percentile(0.99)
+            .unwrap();
+        println!(
+            "{:?}: delivered {:.4}, p99 latency {}",
+            link, delivered_ratio, p99
+        );
+        assert!(delivered_ratio >= 0.99, "{:?}: {}", link, delivered_ratio);
+        assert!(p99 <= 3, "{:?}: {}", link, p99);
+    }
+}
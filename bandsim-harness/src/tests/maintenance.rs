@@ -0,0 +1,84 @@
+use crate::bandwidth_scheduler::Maintenance;
+use crate::chain::{ShardLink, ShardUId};
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::SimulationRun;
+use crate::validation::TotalSent;
+
+use super::{full_speed_typical_sender, DEFAULT_TEST_LENGTH};
+
+fn run_with_maintenance(shard: usize, maintenance: Maintenance) -> SimulationRun {
+    SimulationBuilder::new(4)
+        .default_sender_factory(full_speed_typical_sender)
+        .maintenance(shard, 200..400, maintenance)
+        .maintenance(shard, 600..650, Maintenance::NoTraffic)
+        .record_grant_history()
+        .build()
+        .run_for(DEFAULT_TEST_LENGTH)
+}
+
+/// Bytes sent on links from and to the shard at heights in the window.
+fn sent_in_window(
+    simulation_run: &SimulationRun,
+    shard: usize,
+    from: usize,
+    to: usize,
+) -> (usize, usize) {
+    let total_sent = TotalSent::for_heights(simulation_run, from..to);
+    let shard = ShardUId::new(shard);
+    let mut outgoing = 0;
+    let mut incoming = 0;
+    for other in (0..4).map(ShardUId::new) {
+        outgoing += total_sent.sent(ShardLink {
+            from: shard,
+            to: other,
+        });
+        incoming += total_sent.sent(ShardLink {
+            from: other,
+            to: shard,
+        });
+    }
+    (outgoing, incoming)
+}
+
+/// Nothing is granted in the direction under maintenance, the other direction keeps working.
+/// Base bandwidth and the remaining bandwidth are distributed without the shard.
+#[test]
+fn maintenance_blocks_traffic() {
+    for (maintenance, blocks_outgoing, blocks_incoming) in [
+        (Maintenance::NoOutgoing, true, false),
+        (Maintenance::NoIncoming, false, true),
+        (Maintenance::NoTraffic, true, true),
+    ] {
+        let simulation_run = run_with_maintenance(1, maintenance);
+        let shard = ShardUId::new(1);
+        for metrics in &simulation_run.simulation.metrics {
+            let in_window = (200..400).contains(&metrics.height);
+            let outgoing_granted: usize = metrics
+                .grants
+                .iter()
+                .filter(|(link, _grant)| link.from == shard)
+                .map(|(_link, grant)| grant)
+                .sum();
+            let incoming_granted: usize = metrics
+                .grants
+                .iter()
+                .filter(|(link, _grant)| link.to == shard)
+                .map(|(_link, grant)| grant)
+                .sum();
+            if in_window {
+                assert_eq!(outgoing_granted == 0, blocks_outgoing, "{:?}", maintenance);
+                assert_eq!(incoming_granted == 0, blocks_incoming, "{:?}", maintenance);
+            }
+            if (600..650).contains(&metrics.height) {
+                assert_eq!(outgoing_granted + incoming_granted, 0);
+            }
+        }
+
+        let (outgoing, incoming) = sent_in_window(&simulation_run, 1, 200, 400);
+        assert_eq!(outgoing == 0, blocks_outgoing);
+        assert_eq!(incoming == 0, blocks_incoming);
+        // The shard works normally after the maintenance.
+        let (outgoing, incoming) = sent_in_window(&simulation_run, 1, 400, 600);
+        assert!(outgoing > 0 && incoming > 0);
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::bandwidth_request::{BandwidthRequest, BandwidthRequestBitmap};
+use crate::bandwidth_scheduler::{BandwidthScheduler, MalformedRequest, SchedulerParams};
+use crate::chain::{Block, Chunk, CongestionInfo, ShardUId, SimulationConfig};
+use crate::rng::rng_from_seed;
+use crate::shard_layout::ShardLayout;
+use crate::validation::validate_grants;
+
+use super::link;
+
+fn request(to_shard: usize, bits: &[usize]) -> BandwidthRequest {
+    let mut grant_options_bitmap = BandwidthRequestBitmap::new();
+    for bit in bits {
+        grant_options_bitmap.set_bit(*bit, true);
+    }
+    BandwidthRequest {
+        to_shard: ShardUId::new(to_shard),
+        grant_options_bitmap,
+    }
+}
+
+/// A block with 3 shards, where shard 0 has the given requests and the other shards request nothing.
+fn block_with_requests(bandwidth_requests: Vec<BandwidthRequest>) -> Block {
+    let shards: Vec<ShardUId> = (0..3).map(ShardUId::new).collect();
+    let mut chunks = BTreeMap::new();
+    for shard in &shards {
+        let chunk = Chunk {
+            prev_incoming_receipts_size: 0,
+            prev_outgoing_receipts_size: BTreeMap::new(),
+            prev_unstoppable_receipts_size: BTreeMap::new(),
+            bandwidth_requests: if shard.shard_id == 0 {
+                bandwidth_requests.clone()
+            } else {
+                Vec::new()
+            },
+            congestion_info: CongestionInfo::default(),
+            receiver_quotas: BTreeMap::new(),
+        };
+        chunks.insert(*shard, Some(chunk));
+    }
+    Block {
+        height: 1,
+        shard_layout: Arc::new(ShardLayout::new(0, shards)),
+        chunks,
+    }
+}
+
+/// Duplicated requests, requests to unknown shards and empty requests are skipped.
+/// The grants are the same as if the chunk contained only the well-formed requests.
+#[test]
+fn malformed_requests_are_ignored() {
+    let malformed_block = block_with_requests(vec![
+        request(1, &[3, 10]),
+        request(1, &[39]),
+        request(7, &[39]),
+        request(2, &[]),
+        request(2, &[5]),
+    ]);
+    let clean_block = block_with_requests(vec![request(1, &[3, 10]), request(2, &[5])]);
+
+    let mut scheduler =
+        BandwidthScheduler::new(SchedulerParams::default(), SimulationConfig::default());
+    let grants = scheduler.run(&malformed_block, &mut rng_from_seed(0));
+    assert_eq!(
+        scheduler.malformed_requests(),
+        &[
+            MalformedRequest::Duplicate(link(0, 1)),
+            MalformedRequest::UnknownShard(link(0, 7)),
+            MalformedRequest::EmptyBitmap(link(0, 2)),
+        ]
+    );
+    validate_grants(
+        &grants,
+        &SchedulerParams::default(),
+        &SimulationConfig::default(),
+    );
+    assert!(grants.keys().all(|link| link.to.shard_id < 3));
+
+    let mut clean_scheduler =
+        BandwidthScheduler::new(SchedulerParams::default(), SimulationConfig::default());
+    let clean_grants = clean_scheduler.run(&clean_block, &mut rng_from_seed(0));
+    assert!(clean_scheduler.malformed_requests().is_empty());
+    assert_eq!(grants, clean_grants);
+    assert_eq!(scheduler.allowances(), clean_scheduler.allowances());
+}
+
+/// The list of malformed requests describes only the last processed block.
+#[test]
+fn malformed_requests_are_reset() {
+    let mut scheduler =
+        BandwidthScheduler::new(SchedulerParams::default(), SimulationConfig::default());
+    scheduler.run(
+        &block_with_requests(vec![request(5, &[0])]),
+        &mut rng_from_seed(0),
+    );
+    assert_eq!(
+        scheduler.malformed_requests(),
+        &[MalformedRequest::UnknownShard(link(0, 5))]
+    );
+    scheduler.run(
+        &block_with_requests(vec![request(1, &[0])]),
+        &mut rng_from_seed(0),
+    );
+    assert!(scheduler.malformed_requests().is_empty());
+}
@@ -0,0 +1,142 @@
+use crate::chain::{ShardLink, ShardUId};
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::faults::{GrantOveruse, RequestFault, SchedulerFault};
+use crate::simulation::receipt_sender::{FullSpeedReceiptSender, TypicalReceiptGenerator};
+use crate::validation::{SchedulerStateKind, TestStats, TotalSent};
+
+/// 1 -> 3, 2 -> 3, 1 -> 2 - send typical receipts as fast as possible.
+/// Shard 0 doesn't have anything to send, its requests compete for the bandwidth of shards 2 and 3.
+fn contended_scenario() -> SimulationBuilder {
+    SimulationBuilder::new(4)
+        .receipt_sender(1, 3, FullSpeedReceiptSender(TypicalReceiptGenerator::new()))
+        .receipt_sender(2, 3, FullSpeedReceiptSender(TypicalReceiptGenerator::new()))
+        .receipt_sender(1, 2, FullSpeedReceiptSender(TypicalReceiptGenerator::new()))
+}
+
+/// Total amount of bytes sent by the shards other than shard 0.
+fn sent_by_honest_shards(builder: SimulationBuilder) -> usize {
+    let simulation_run = builder.build().run_for(300);
+    let total_sent = TotalSent::new(&simulation_run);
+    simulation_run
+        .simulation
+        .shard_layout
+        .all_links()
+        .filter(|link: &ShardLink| link.from.shard_id != 0)
+        .map(|link| total_sent.sent(link))
+        .sum()
+}
+
+/// How much the honest shards send when shard 0 is malicious, compared to when it's honest.
+fn honest_throughput_ratio(fault: RequestFault) -> f64 {
+    let baseline = sent_by_honest_shards(contended_scenario());
+    let with_fault = sent_by_honest_shards(contended_scenario().malicious_requester(0, fault));
+    with_fault as f64 / baseline as f64
+}
+
+/// A malicious shard can take at most its fair share of the bandwidth, so the damage to honest shards is bounded.
+const MIN_HONEST_THROUGHPUT_RATIO: f64 = 0.75;
+
+#[test]
+fn malicious_requester_all_bits() {
+    let ratio = honest_throughput_ratio(RequestFault::AllBits);
+    assert!(ratio > MIN_HONEST_THROUGHPUT_RATIO, "ratio: {}", ratio);
+}
+
+#[test]
+fn malicious_requester_random_bits() {
+    let ratio = honest_throughput_ratio(RequestFault::RandomBits);
+    assert!(ratio > MIN_HONEST_THROUGHPUT_RATIO, "ratio: {}", ratio);
+}
+
+#[test]
+fn malicious_requester_only_max_bit() {
+    let ratio = honest_throughput_ratio(RequestFault::OnlyMaxBit);
+    assert!(ratio > MIN_HONEST_THROUGHPUT_RATIO, "ratio: {}", ratio);
+}
+
+/// Shard 0 sends more than it was granted on the contended link 0 -> 3.
+/// The receiving shard must notice it and reject the chunks instead of silently accepting the receipts.
+#[test]
+fn byzantine_sender_is_detected() {
+    let simulation = contended_scenario()
+        .receipt_sender(0, 3, FullSpeedReceiptSender(TypicalReceiptGenerator::new()))
+        .byzantine_sender(
+            0,
+            GrantOveruse {
+                extra_bytes: 500_000,
+            },
+        )
+        .build()
+        .run_for(100)
+        .simulation;
+
+    assert!(!simulation.grant_violations.is_empty());
+    for violation in &simulation.grant_violations {
+        assert!(violation.link.is(0, 3));
+        assert!(violation.sent > violation.granted);
+        let block = simulation.blocks[violation.height].as_ref().unwrap();
+        assert!(block.chunks[&violation.link.from].is_none());
+    }
+}
+
+/// A rejected chunk isn't applied, the receipts it would send stay in the queue.
+/// The offered and delivered bytes still add up, so the stats can be computed.
+#[test]
+fn stats_with_byzantine_sender() {
+    let simulation_run = contended_scenario()
+        .receipt_sender(0, 3, FullSpeedReceiptSender(TypicalReceiptGenerator::new()))
+        .byzantine_sender(
+            0,
+            GrantOveruse {
+                extra_bytes: 500_000,
+            },
+        )
+        .build()
+        .run_for(300);
+    assert!(!simulation_run.simulation.grant_violations.is_empty());
+    let stats = TestStats::new(&simulation_run);
+    // The honest links keep sending while the chunks of shard 0 are rejected.
+    assert!(stats.bandwidth_utilization.utilization > 0.0);
+}
+
+/// Honest shards never send more than they were granted and their scheduler states never diverge.
+#[test]
+fn honest_shards_are_consistent() {
+    let simulation = contended_scenario()
+        .receipt_sender(0, 3, FullSpeedReceiptSender(TypicalReceiptGenerator::new()))
+        .build()
+        .run_for(100)
+        .simulation;
+    assert!(simulation.grant_violations.is_empty());
+    assert!(simulation.first_scheduler_divergence.is_none());
+}
+
+/// Shard 2 corrupts the allowance of link 0 -> 1 at height 50.
+/// The consistency check should point at exactly this height, shard and link.
+#[test]
+fn scheduler_divergence_is_pinpointed() {
+    let link = ShardLink {
+        from: ShardUId::new(0),
+        to: ShardUId::new(1),
+    };
+    let simulation = contended_scenario()
+        .scheduler_fault(
+            2,
+            SchedulerFault {
+                height: 50,
+                link,
+                allowance: 0,
+            },
+        )
+        .build()
+        .run_for(100)
+        .simulation;
+
+    let divergence = simulation.first_scheduler_divergence.unwrap();
+    assert_eq!(divergence.height, 50);
+    assert_eq!(divergence.shard, ShardUId::new(2));
+    assert_eq!(divergence.link, link);
+    assert_eq!(divergence.kind, SchedulerStateKind::Allowance);
+    assert_eq!(divergence.actual, 0);
+    assert!(divergence.expected > 0);
+}
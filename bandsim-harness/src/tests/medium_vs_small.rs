@@ -1,8 +1,8 @@
-use crate::bandsim::chain::{MAX_SHARD_BANDWIDTH, MIN_RECEIPT_SIZE};
-use crate::bandsim::simulation::builder::SimulationBuilder;
-use crate::bandsim::simulation::receipt_sender::{FullSpeedReceiptSender, OneSizeReceiptGenerator};
-use crate::bandsim::tests::DEFAULT_TEST_LENGTH;
-use crate::bandsim::validation::TestStats;
+use crate::chain::{MAX_SHARD_BANDWIDTH, MIN_RECEIPT_SIZE};
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{FullSpeedReceiptSender, OneSizeReceiptGenerator};
+use crate::tests::DEFAULT_TEST_LENGTH;
+use crate::validation::{StatsThresholds, TestStats};
 
 /// 0 -> 0 - full speed receipts slightly larger than half of max bandwidth
 /// 0 -> 1 - full speed small receipts
@@ -28,9 +28,12 @@ fn medium_vs_small_sender() {
         .run_for(DEFAULT_TEST_LENGTH);
 
     let stats = TestStats::new(&simulation_run);
-    stats.basic_assert();
-    assert!(stats.max_min_ratio.ratio <= 1.10);
-    assert!(stats.bandwidth_utilization.utilization > 0.95);
+    stats.assert_with(StatsThresholds {
+        max_min_ratio: 1.10,
+        min_bandwidth_utilization: 0.95,
+        ..StatsThresholds::default()
+    });
+    stats.assert_no_queue_growth(0.1);
 }
 
 /// 0 -> 0 - full speed receipts slightly larger than half of max bandwidth
@@ -57,9 +60,12 @@ fn medium_vs_small_receiver() {
         .run_for(DEFAULT_TEST_LENGTH);
 
     let stats = TestStats::new(&simulation_run);
-    stats.basic_assert();
-    assert!(stats.max_min_ratio.ratio <= 1.10);
-    assert!(stats.bandwidth_utilization.utilization > 0.95);
+    stats.assert_with(StatsThresholds {
+        max_min_ratio: 1.10,
+        min_bandwidth_utilization: 0.95,
+        ..StatsThresholds::default()
+    });
+    stats.assert_no_queue_growth(0.1);
 }
 
 /// 0 -> 0 - receips slightly larger than half of max bandwidth
@@ -78,6 +84,11 @@ fn one_medium() {
         .run_for(DEFAULT_TEST_LENGTH);
 
     let stats = TestStats::new(&simulation_run);
-    stats.basic_assert();
+    stats.assert_with(StatsThresholds {
+        // The optimum assumes that receipts can be split, which isn't possible with one receipt per height.
+        min_optimality_ratio: 0.45,
+        ..StatsThresholds::default()
+    });
+    stats.assert_no_queue_growth(0.1);
     assert!(stats.bandwidth_utilization.utilization <= 0.60);
 }
@@ -0,0 +1,135 @@
+use crate::bandwidth_scheduler::{SchedulerAlgorithm, SchedulerParams};
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{FullSpeedReceiptSender, OneSizeReceiptGenerator};
+use crate::simulation::SimulationRun;
+use crate::validation::{validate_blocks, validate_grant_lifetimes, MissingBlockStats, TestStats};
+
+use super::{full_speed_typical_sender, DEFAULT_TEST_LENGTH};
+
+fn run_with_missing_blocks(probability: f64) -> SimulationRun {
+    SimulationBuilder::new(4)
+        .default_sender_factory(full_speed_typical_sender)
+        .missing_block_probability(probability)
+        .build()
+        .run_for(DEFAULT_TEST_LENGTH)
+}
+
+/// Run a simulation where 20% of blocks are missing.
+#[test]
+fn twenty_percent_missing_blocks() {
+    let simulation_run = run_with_missing_blocks(0.2);
+    let stats = TestStats::new(&simulation_run);
+    stats.basic_assert();
+    let missing_blocks = stats.missing_blocks;
+    assert_eq!(missing_blocks.heights, DEFAULT_TEST_LENGTH);
+    assert!(missing_blocks.missing_blocks_ratio > 0.17);
+    assert!(missing_blocks.missing_blocks_ratio < 0.23);
+    assert!(missing_blocks.longest_missing_streak >= 2);
+}
+
+#[test]
+fn no_missing_blocks() {
+    let stats = TestStats::new(&run_with_missing_blocks(0.0));
+    assert_eq!(stats.missing_blocks.missing_blocks, 0);
+    assert_eq!(stats.missing_blocks.longest_missing_streak, 0);
+}
+
+#[test]
+#[should_panic(expected = "Genesis block is missing")]
+fn missing_genesis_is_detected() {
+    let mut simulation = run_with_missing_blocks(0.2).simulation;
+    simulation.blocks[0] = None;
+    validate_blocks(&simulation);
+}
+
+/// Removing a block shifts all later blocks to a lower position than their height.
+#[test]
+#[should_panic(expected = "Block number")]
+fn skipped_height_is_detected() {
+    let mut simulation = run_with_missing_blocks(0.2).simulation;
+    let last_present = simulation.blocks.iter().rposition(Option::is_some).unwrap();
+    simulation.blocks.remove(last_present - 1);
+    validate_blocks(&simulation);
+}
+
+#[test]
+#[should_panic(expected = "but the missing block probability is 0.5")]
+fn unexpected_missing_block_ratio_is_detected() {
+    let mut simulation = run_with_missing_blocks(0.2).simulation;
+    simulation.missing_block_probability = 0.5;
+    validate_blocks(&simulation);
+}
+
+/// With a rare missing block in a short run a single missing block is far above the expected ratio,
+/// but it's a valid outcome. The ratio isn't checked when too few missing blocks are expected.
+#[test]
+fn rare_missing_blocks_in_short_runs() {
+    for seed in 0..300 {
+        SimulationBuilder::new(2)
+            .random_seed(seed)
+            .missing_block_probability(0.001)
+            .build()
+            .run_for(20);
+    }
+}
+
+/// Round robin doesn't use the randomness derived from the block, so the scheduler sees
+/// the same inputs in every non-missing block regardless of the missing heights in between.
+fn run_round_robin(missing_block_probability: f64, steps: usize) -> SimulationRun {
+    SimulationBuilder::new(4)
+        .default_sender_factory(|_rng| {
+            Box::new(FullSpeedReceiptSender(OneSizeReceiptGenerator {
+                size: 100_000,
+            }))
+        })
+        .scheduler_params(SchedulerParams {
+            algorithm: SchedulerAlgorithm::RoundRobin,
+            ..SchedulerParams::default()
+        })
+        .missing_block_probability(missing_block_probability)
+        .record_grant_history()
+        .build()
+        .run_for(steps)
+}
+
+/// Grants are void at missing heights, the next block computes them from scratch.
+/// The non-missing blocks of a run with many back-to-back missing blocks get exactly the grants
+/// of a run without missing blocks - nothing is carried over and nothing accumulates.
+#[test]
+fn grants_are_not_carried_over_missing_blocks() {
+    let with_missing = run_round_robin(0.5, 400);
+    let missing_blocks = MissingBlockStats::new(&with_missing.simulation);
+    assert!(missing_blocks.longest_missing_streak >= 3);
+    let without_missing = run_round_robin(0.0, with_missing.simulation.metrics.len());
+
+    let metrics = &with_missing.simulation.metrics;
+    let expected_metrics = &without_missing.simulation.metrics;
+    assert_eq!(metrics.len(), expected_metrics.len());
+    let mut after_missing = 0;
+    for (height_metrics, expected) in metrics.iter().zip(expected_metrics) {
+        assert!(!height_metrics.grants.is_empty());
+        assert_eq!(height_metrics.grants, expected.grants);
+        assert!(height_metrics.scheduler_input_height < height_metrics.height);
+        if height_metrics.scheduler_input_height + 1 < height_metrics.height {
+            after_missing += 1;
+        }
+    }
+    assert!(after_missing > 50);
+}
+
+#[test]
+#[should_panic(expected = "were computed from the block at height")]
+fn reused_scheduler_input_is_detected() {
+    let mut simulation = run_with_missing_blocks(0.2).simulation;
+    let prev_input = simulation.metrics[9].scheduler_input_height;
+    simulation.metrics[10].scheduler_input_height = prev_input;
+    validate_grant_lifetimes(&simulation);
+}
+
+#[test]
+#[should_panic(expected = "The scheduler ran")]
+fn scheduler_run_without_block_is_detected() {
+    let mut simulation = run_with_missing_blocks(0.2).simulation;
+    simulation.metrics.pop();
+    validate_grant_lifetimes(&simulation);
+}
@@ -0,0 +1,158 @@
+use std::collections::BTreeSet;
+
+use rand::Rng;
+
+use crate::chain::ShardUId;
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::chunk_producers::ChunkProducers;
+use crate::simulation::SimulationRun;
+use crate::validation::{StatsThresholds, TestStats};
+
+use super::{full_speed_typical_sender, DEFAULT_TEST_LENGTH};
+
+/// Run a simulation where 10% of chunks are missing.
+/// TypicalSender sends mostly small receipts.
+#[test]
+fn ten_percent_missing_chunks() {
+    let simulation_run = SimulationBuilder::new(6)
+        .default_sender_factory(full_speed_typical_sender)
+        .missing_chunk_generator(|_height, _id, rng| rng.gen_bool(0.1))
+        .build()
+        .run_for(DEFAULT_TEST_LENGTH);
+    let stats = TestStats::new(&simulation_run);
+    stats.basic_assert();
+    assert!(stats.missing_chunks_ratio > 0.08);
+    assert!(stats.missing_chunks_ratio < 0.12);
+}
+
+/// Run a simulation where 10% of chunks on shard 0 are missing.
+/// TypicalSender sends mostly small receipts.
+#[test]
+fn ten_percent_missing_chunks_on_shard0() {
+    let simulation_run = SimulationBuilder::new(6)
+        .default_sender_factory(full_speed_typical_sender)
+        .missing_chunk_generator(|_height, id, rng| {
+            if id != ShardUId::new(0) {
+                return false;
+            }
+            rng.gen_bool(0.1)
+        })
+        .build()
+        .run_for(DEFAULT_TEST_LENGTH);
+    let stats = TestStats::new(&simulation_run);
+    stats.basic_assert();
+    assert!(stats.missing_chunks_ratio > 0.01);
+    assert!(stats.missing_chunks_ratio < 0.02);
+}
+
+/// 10 chunks aren't missing, then 10 chunks are missing, then 10 are missing, etc on shard 0
+/// TypicalSender sends mostly small receipts.
+#[test]
+fn ten_missing_ten_not_missing_on_shard0() {
+    let simulation_run = SimulationBuilder::new(6)
+        .default_sender_factory(full_speed_typical_sender)
+        .missing_chunk_generator(|height, id, _rng| {
+            if id != ShardUId::new(0) {
+                return false;
+            }
+            (height / 10) % 2 == 1
+        })
+        .build()
+        .run_for(DEFAULT_TEST_LENGTH);
+    let stats = TestStats::new(&simulation_run);
+
+    stats.assert_with(StatsThresholds {
+        // Standard fairness check fails because shard0 processes half the receipts that other shards do. This is expected.
+        max_min_ratio: 3.0,
+        // Raw utilization is low because shard0 can't send or receive half of the time, compare with the optimum instead.
+        min_bandwidth_utilization: 0.0,
+        min_optimality_ratio: 0.82,
+        ..StatsThresholds::default()
+    });
+
+    assert!(stats.missing_chunks_ratio > 0.05);
+    assert!(stats.missing_chunks_ratio < 0.10);
+}
+
+/// 4 shards, 4 chunk producers assigned round robin, producer 0 is offline for 50 heights.
+/// While it's offline there's a missing chunk at every height and the missing chunk moves between shards.
+#[test]
+fn offline_chunk_producer() {
+    let simulation_run = SimulationBuilder::new(4)
+        .default_sender_factory(full_speed_typical_sender)
+        .chunk_producers(ChunkProducers::round_robin(4).offline(0, 100..150))
+        .build()
+        .run_for(200);
+    let stats = TestStats::new(&simulation_run);
+    stats.basic_assert();
+
+    for block in simulation_run.simulation.blocks.iter().flatten() {
+        let missing: Vec<ShardUId> = block
+            .chunks
+            .iter()
+            .filter(|(_shard_id, chunk)| chunk.is_none())
+            .map(|(shard_id, _chunk)| *shard_id)
+            .collect();
+        if (100..150).contains(&block.height) {
+            let expected_shard = ShardUId::new((4 - block.height % 4) % 4);
+            assert_eq!(missing, vec![expected_shard]);
+        } else {
+            assert!(missing.is_empty());
+        }
+    }
+}
+
+/// Shard 2 is down for 50 heights, on top of 5% of chunks missing at random on all shards.
+/// The downtime makes exactly its chunks missing and doesn't change the randomly missing chunks.
+#[test]
+fn shard_downtime_window() {
+    let run = |downtime: bool| {
+        let mut builder = SimulationBuilder::new(4)
+            .default_sender_factory(full_speed_typical_sender)
+            .missing_chunk_generator(|_height, _id, rng| rng.gen_bool(0.05));
+        if downtime {
+            builder = builder.downtime(2, 100..150);
+        }
+        builder.build().run_for(200)
+    };
+    let missing_chunks = |simulation_run: &SimulationRun| -> BTreeSet<(usize, ShardUId)> {
+        simulation_run
+            .simulation
+            .blocks
+            .iter()
+            .flatten()
+            .flat_map(|block| {
+                block
+                    .chunks
+                    .iter()
+                    .filter(|(_shard_id, chunk)| chunk.is_none())
+                    .map(|(shard_id, _chunk)| (block.height, *shard_id))
+            })
+            .collect()
+    };
+
+    let simulation_run = run(true);
+    TestStats::new(&simulation_run).assert_with(StatsThresholds {
+        // Shard 2 can't send or receive for a quarter of the run.
+        max_min_ratio: 3.0,
+        min_bandwidth_utilization: 0.0,
+        ..StatsThresholds::default()
+    });
+
+    let with_downtime = missing_chunks(&simulation_run);
+    let without_downtime = missing_chunks(&run(false));
+    let down: BTreeSet<(usize, ShardUId)> = simulation_run
+        .simulation
+        .blocks
+        .iter()
+        .flatten()
+        .filter(|block| (100..150).contains(&block.height))
+        .map(|block| (block.height, ShardUId::new(2)))
+        .collect();
+    assert!(!down.is_empty());
+    let expected: BTreeSet<(usize, ShardUId)> = without_downtime.union(&down).copied().collect();
+    assert_eq!(with_downtime, expected);
+
+    // Same run again gives the same missing chunks.
+    assert_eq!(missing_chunks(&run(true)), with_downtime);
+}
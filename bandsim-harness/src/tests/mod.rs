@@ -0,0 +1,112 @@
+pub mod allowance_history;
+pub mod allowance_policy;
+pub mod asymmetric_capacity;
+pub mod backpressure;
+pub mod base_bandwidth_tuning;
+pub mod batch;
+pub mod big_vs_small;
+pub mod burst;
+pub mod chunk_size_limit;
+pub mod comparison;
+pub mod congestion;
+pub mod degenerate_requests;
+pub mod denials;
+pub mod destination_correlated;
+pub mod distribute_remaining;
+pub mod dot_export;
+pub mod drop_policy;
+pub mod ensemble;
+pub mod fork;
+pub mod fuzzer;
+pub mod global_budget;
+pub mod grant_history;
+pub mod heatmap;
+pub mod heavy_tailed;
+pub mod initial_state;
+pub mod long_run;
+pub mod maintenance;
+pub mod malformed_requests;
+pub mod malicious;
+pub mod medium_vs_small;
+pub mod missing_blocks;
+pub mod missing_chunks;
+pub mod overhead;
+pub mod parallel_validation;
+pub mod partition;
+pub mod presets;
+pub mod priority;
+pub mod ramp;
+pub mod randomized;
+pub mod receipt_size_mutation;
+pub mod receiver_quotas;
+pub mod recovery;
+pub mod replay;
+pub mod request_policy;
+pub mod rng_streams;
+pub mod scenario_files;
+pub mod scheduler_algorithms;
+pub mod seed_hunter;
+pub mod sensitivity;
+pub mod shard_bandwidth;
+pub mod simulation_config;
+pub mod stability;
+pub mod stale_layout;
+pub mod stats_check;
+pub mod step_load;
+pub mod symmetry;
+pub mod tags;
+pub mod timeseries_csv;
+pub mod typical;
+pub mod unstoppable;
+pub mod validation_levels;
+
+use crate::chain::{ShardLink, ShardUId};
+use crate::rng::DefaultRng;
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{
+    ConstantRateReceiptSender, FullSpeedReceiptSender, OneSizeReceiptGenerator, ReceiptSender,
+    TypicalReceiptGenerator,
+};
+
+pub const DEFAULT_TEST_LENGTH: usize = 1000;
+
+pub fn link(from: usize, to: usize) -> ShardLink {
+    ShardLink {
+        from: ShardUId::new(from),
+        to: ShardUId::new(to),
+    }
+}
+
+/// Sends typical receipts as fast as possible, can be passed to `SimulationBuilder::default_sender_factory`.
+pub fn full_speed_typical_sender(_rng: &mut DefaultRng) -> Box<dyn ReceiptSender> {
+    Box::new(FullSpeedReceiptSender(TypicalReceiptGenerator::new()))
+}
+
+/// Sends `bytes_per_height` bytes at every height, in receipts of `receipt_size` bytes.
+pub fn constant_rate_sender(
+    receipt_size: usize,
+    bytes_per_height: usize,
+) -> ConstantRateReceiptSender<OneSizeReceiptGenerator> {
+    ConstantRateReceiptSender {
+        generator: OneSizeReceiptGenerator { size: receipt_size },
+        bytes_per_height,
+    }
+}
+
+/// All links send typical receipts at full speed.
+pub fn all_links_busy(num_shards: usize) -> SimulationBuilder {
+    SimulationBuilder::new(num_shards).default_sender_factory(full_speed_typical_sender)
+}
+
+/// Every shard sends typical receipts at full speed to shard 0, which can't receive everything.
+pub fn hot_receiver(num_shards: usize) -> SimulationBuilder {
+    let mut builder = SimulationBuilder::new(num_shards);
+    for from in 0..num_shards {
+        builder = builder.receipt_sender(
+            from,
+            0,
+            FullSpeedReceiptSender(TypicalReceiptGenerator::new()),
+        );
+    }
+    builder
+}
@@ -0,0 +1,59 @@
+use crate::bandwidth_request::{
+    AggregatedBandwidthRequests, BANDWIDTH_REQUESTS_HEADER_SIZE, BANDWIDTH_REQUEST_SERIALIZED_SIZE,
+};
+use crate::chain::CongestionInfo;
+use crate::validation::{RequestOverhead, TestStats};
+
+use super::all_links_busy;
+
+fn all_links_busy_stats(num_shards: usize) -> TestStats {
+    let simulation_run = all_links_busy(num_shards).build().run_for(50);
+    TestStats::new(&simulation_run)
+}
+
+fn all_links_busy_overhead(num_shards: usize) -> RequestOverhead {
+    all_links_busy_stats(num_shards).request_overhead
+}
+
+/// When all links are busy, every chunk has a request to every shard.
+/// The overhead per block grows quadratically with the number of shards.
+#[test]
+fn request_overhead_grows_with_shards() {
+    let small = all_links_busy_overhead(2);
+    let big = all_links_busy_overhead(8);
+    let max_chunk_size = |num_shards: usize| {
+        BANDWIDTH_REQUESTS_HEADER_SIZE + num_shards * BANDWIDTH_REQUEST_SERIALIZED_SIZE
+    };
+    assert_eq!(small.per_chunk_max, max_chunk_size(2));
+    assert_eq!(big.per_chunk_max, max_chunk_size(8));
+    assert_eq!(big.per_block_max, 8 * max_chunk_size(8));
+    assert!(big.per_block_avg > small.per_block_avg * 10.0);
+}
+
+/// Aggregated requests don't repeat the header and the shard ids, with many shards they take less space.
+#[test]
+fn aggregated_requests_are_smaller() {
+    let overhead = all_links_busy_overhead(8);
+    assert_eq!(
+        overhead.aggregated_per_block_avg,
+        (8 * AggregatedBandwidthRequests::serialized_size(8, 8)) as f64
+    );
+    assert!(overhead.aggregated_per_block_avg < overhead.per_block_avg * 0.8);
+}
+
+/// In a busy network the metadata is tiny compared to the receipts.
+#[test]
+fn protocol_overhead_is_small() {
+    let stats = all_links_busy_stats(4);
+    let overhead = stats.protocol_overhead;
+    assert_eq!(
+        overhead.requests_per_block,
+        stats.request_overhead.per_block_avg
+    );
+    assert_eq!(
+        overhead.congestion_info_per_block,
+        (4 * CongestionInfo::SERIALIZED_SIZE) as f64
+    );
+    assert!(overhead.payload_per_block > 0.0);
+    assert!(overhead.overhead_ratio < 0.001);
+}
@@ -0,0 +1,66 @@
+use crate::bandwidth_scheduler::{BandwidthScheduler, SchedulerParams};
+use crate::chain::{ShardLink, ShardUId, SimulationConfig};
+use crate::shard_layout::ShardLayout;
+use crate::simulation::faults::SchedulerFault;
+use crate::validation::{validate_shard_schedulers, TotalSent, ValidationLevel};
+
+use super::all_links_busy;
+
+/// Validating on many threads doesn't change anything in the simulation.
+#[test]
+fn parallel_validation_gives_same_results() {
+    let single_thread = all_links_busy(8).build().run_for(100);
+    let many_threads = all_links_busy(8).validation_threads(4).build().run_for(100);
+    assert_eq!(
+        TotalSent::new(&single_thread),
+        TotalSent::new(&many_threads)
+    );
+    assert!(many_threads.performance.validation_time > std::time::Duration::ZERO);
+}
+
+/// A check failing on a worker thread fails with its own message.
+#[test]
+#[should_panic = "isn't in the shard layout"]
+fn parallel_validation_reports_failures() {
+    let healthy = BandwidthScheduler::new(SchedulerParams::default(), SimulationConfig::default());
+    let mut phantom =
+        BandwidthScheduler::new(SchedulerParams::default(), SimulationConfig::default());
+    let phantom_link = ShardLink {
+        from: ShardUId::new(0),
+        to: ShardUId::new(5),
+    };
+    phantom.set_allowance(phantom_link, 1000);
+    let grants = Default::default();
+    validate_shard_schedulers(
+        &[
+            (&healthy, &grants),
+            (&healthy, &grants),
+            (&healthy, &grants),
+            (&phantom, &grants),
+        ],
+        &ShardLayout::with_num_shards(4),
+        4,
+    );
+}
+
+/// With basic validation the scheduler state isn't compared between shards.
+#[test]
+fn skipped_validation_doesnt_detect_divergence() {
+    let simulation = all_links_busy(3)
+        .scheduler_fault(
+            2,
+            SchedulerFault {
+                height: 50,
+                link: ShardLink {
+                    from: ShardUId::new(0),
+                    to: ShardUId::new(1),
+                },
+                allowance: 0,
+            },
+        )
+        .validation_level(ValidationLevel::Basic)
+        .build()
+        .run_for(100)
+        .simulation;
+    assert!(simulation.first_scheduler_divergence.is_none());
+}
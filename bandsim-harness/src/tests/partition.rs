@@ -0,0 +1,28 @@
+use crate::chain::MAX_SHARD_BANDWIDTH;
+use crate::experiments::partition::PartitionAndHeal;
+
+/// Half of the shards can't process incoming receipts for 50 heights.
+/// They build up a backlog of about 50 heights of received receipts, which drains at
+/// the spare processing capacity - half of the bandwidth - so it takes about as long as the partition.
+#[test]
+fn partition_and_heal() {
+    let recovery = PartitionAndHeal::new(4, 100..150).run(300);
+    assert_eq!(recovery.peak_backlogs.len(), 2);
+    for peak_backlog in recovery.peak_backlogs.values() {
+        assert!(*peak_backlog > 45 * MAX_SHARD_BANDWIDTH / 2);
+        assert!(*peak_backlog < 52 * MAX_SHARD_BANDWIDTH / 2);
+    }
+    let recovery_time = recovery.recovery_time().unwrap();
+    assert!(recovery_time > 40 && recovery_time < 60);
+}
+
+/// A longer partition or less spare capacity make the recovery take longer.
+#[test]
+fn partition_recovery_time() {
+    let short = PartitionAndHeal::new(4, 100..120).run(300);
+    let long = PartitionAndHeal::new(4, 100..150).run(300);
+    let heavy_load = PartitionAndHeal::new(4, 100..120).load(0.75).run(300);
+    assert!(short.total_peak_backlog() < long.total_peak_backlog());
+    assert!(short.recovery_time().unwrap() < long.recovery_time().unwrap());
+    assert!(short.recovery_time().unwrap() < heavy_load.recovery_time().unwrap());
+}
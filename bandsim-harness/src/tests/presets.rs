@@ -0,0 +1,76 @@
+use crate::chain::{ShardLink, ShardUId, SimulationConfig, MAX_SHARD_BANDWIDTH};
+use crate::scenarios::{self, preset, PRESET_NAMES};
+use crate::simulation::SimulationRun;
+use crate::validation::{TestStats, TotalSent};
+
+use super::DEFAULT_TEST_LENGTH;
+
+fn run_preset(name: &str) -> SimulationRun {
+    preset(name, 4, SimulationConfig::default())
+        .unwrap()
+        .build()
+        .run_for(DEFAULT_TEST_LENGTH)
+}
+
+#[test]
+fn presets_by_name() {
+    for name in PRESET_NAMES {
+        assert!(
+            preset(name, 2, SimulationConfig::default()).is_some(),
+            "{}",
+            name
+        );
+    }
+    assert!(preset("nonexistent", 2, SimulationConfig::default()).is_none());
+}
+
+#[test]
+fn typical_preset() {
+    TestStats::new(&run_preset("typical")).basic_assert();
+}
+
+/// The shards share the incoming bandwidth of the hotspot fairly and use all of it.
+#[test]
+fn hotspot_preset() {
+    let simulation_run = run_preset("hotspot");
+    let total_sent = TotalSent::new(&simulation_run);
+    let sent_to_hotspot: Vec<usize> = (0..4)
+        .map(|from_shard| {
+            total_sent.sent(ShardLink {
+                from: ShardUId::new(from_shard),
+                to: ShardUId::new(0),
+            })
+        })
+        .collect();
+    let max_sent = *sent_to_hotspot.iter().max().unwrap();
+    let min_sent = *sent_to_hotspot.iter().min().unwrap();
+    assert!(
+        max_sent as f64 / min_sent as f64 <= 1.2,
+        "{:?}",
+        sent_to_hotspot
+    );
+    let received_per_height = sent_to_hotspot.iter().sum::<usize>() / total_sent.num_blocks;
+    assert!(
+        received_per_height as f64 > MAX_SHARD_BANDWIDTH as f64 * 0.8,
+        "{}",
+        received_per_height
+    );
+}
+
+#[test]
+fn all_to_all_max_preset() {
+    TestStats::new(&run_preset("all_to_all_max")).basic_assert();
+}
+
+/// Bursts make the receipts wait, but the average load fits and the queues stay bounded.
+#[test]
+fn bursty_preset() {
+    let stats = TestStats::new(
+        &scenarios::bursty(4, SimulationConfig::default())
+            .build()
+            .run_for(DEFAULT_TEST_LENGTH),
+    );
+    assert!(!stats.is_unstable);
+    assert_eq!(stats.shed_bytes, 0);
+    assert!(stats.bandwidth_utilization.utilization > 0.6);
+}
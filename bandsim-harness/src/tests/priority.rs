@@ -0,0 +1,138 @@
+use crate::chain::{ReceiptPriority, MAX_SHARD_BANDWIDTH};
+use crate::rng::DefaultRng;
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::outgoing_queue::{DrainPolicy, OutgoingQueue};
+use crate::simulation::receipt_sender::{
+    ConstantRateReceiptSender, OneSizeReceiptGenerator, PriorityReceiptGenerator, ReceiptSender,
+    TaggedReceiptGenerator, TypicalReceiptGenerator,
+};
+use crate::validation::{LatencySlo, StatsThresholds, TestStats};
+
+type PrioritySender<RG> = ConstantRateReceiptSender<PriorityReceiptGenerator<RG>>;
+
+/// Two kinds of traffic on the same link - a small stream of urgent receipts and a lot of bulk receipts.
+#[derive(Clone, Debug)]
+struct MixedSender {
+    urgent: PrioritySender<TaggedReceiptGenerator<OneSizeReceiptGenerator>>,
+    bulk: PrioritySender<TypicalReceiptGenerator>,
+}
+
+impl MixedSender {
+    fn new(
+        urgent_priority: ReceiptPriority,
+        urgent_bytes_per_height: usize,
+        bulk_priority: ReceiptPriority,
+        bulk_bytes_per_height: usize,
+    ) -> MixedSender {
+        MixedSender {
+            urgent: ConstantRateReceiptSender {
+                generator: PriorityReceiptGenerator {
+                    generator: TaggedReceiptGenerator::new(
+                        OneSizeReceiptGenerator { size: 10_000 },
+                        "urgent",
+                    ),
+                    priority: urgent_priority,
+                },
+                bytes_per_height: urgent_bytes_per_height,
+            },
+            bulk: ConstantRateReceiptSender {
+                generator: PriorityReceiptGenerator {
+                    generator: TypicalReceiptGenerator::new(),
+                    priority: bulk_priority,
+                },
+                bytes_per_height: bulk_bytes_per_height,
+            },
+        }
+    }
+}
+
+impl ReceiptSender for MixedSender {
+    fn send_receipts(&mut self, outgoing_queue: &mut OutgoingQueue, rng: &mut DefaultRng) {
+        self.urgent.send_receipts(outgoing_queue, rng);
+        self.bulk.send_receipts(outgoing_queue, rng);
+    }
+}
+
+/// 0 -> 1 - urgent receipts and more bulk receipts than the link can handle.
+fn saturated_link_stats(urgent_priority: ReceiptPriority) -> TestStats {
+    let simulation_run = SimulationBuilder::new(2)
+        .receipt_sender(
+            0,
+            1,
+            MixedSender::new(
+                urgent_priority,
+                100_000,
+                ReceiptPriority::Low,
+                MAX_SHARD_BANDWIDTH * 3 / 2,
+            ),
+        )
+        .build()
+        .run_for(200);
+    TestStats::new(&simulation_run)
+}
+
+fn urgent_latency(urgent_priority: ReceiptPriority) -> f64 {
+    saturated_link_stats(urgent_priority).tag_stats["urgent"].mean_latency
+}
+
+/// p99 latency of high priority receipts stays within 3 heights even when bulk traffic saturates the link.
+const HIGH_PRIORITY_SLO: LatencySlo = LatencySlo {
+    priority: ReceiptPriority::High,
+    percentile: 0.99,
+    max_latency: 3,
+};
+
+/// With strict priority draining urgent receipts jump over the bulk backlog.
+#[test]
+fn urgent_receipts_jump_the_backlog() {
+    let prioritized = urgent_latency(ReceiptPriority::High);
+    let same_priority = urgent_latency(ReceiptPriority::Low);
+    assert!(prioritized <= 1.0, "{}", prioritized);
+    assert!(same_priority > 10.0, "{}", same_priority);
+}
+
+#[test]
+fn high_priority_latency_slo() {
+    let stats = saturated_link_stats(ReceiptPriority::High);
+    stats.assert_with(StatsThresholds {
+        latency_slos: vec![HIGH_PRIORITY_SLO],
+        allow_unstable: true,
+        ..StatsThresholds::default()
+    });
+}
+
+/// Urgent receipts without a higher priority wait behind the bulk backlog and miss the SLO.
+#[test]
+#[should_panic = "Latency SLO violated"]
+fn latency_slo_violated_without_priority() {
+    let stats = saturated_link_stats(ReceiptPriority::Low);
+    stats.assert_latency_slo(&LatencySlo {
+        priority: ReceiptPriority::Low,
+        ..HIGH_PRIORITY_SLO
+    });
+}
+
+/// 0 -> 1 - two priorities which both want to send the whole bandwidth.
+/// With weighted draining they share the bandwidth according to their weights.
+#[test]
+fn weighted_draining_shares_bandwidth() {
+    let simulation_run = SimulationBuilder::new(2)
+        .receipt_sender(
+            0,
+            1,
+            MixedSender::new(
+                ReceiptPriority::High,
+                MAX_SHARD_BANDWIDTH,
+                ReceiptPriority::Low,
+                MAX_SHARD_BANDWIDTH,
+            ),
+        )
+        .drain_policy(DrainPolicy::Weighted([3, 1, 1]))
+        .build()
+        .run_for(200);
+    let stats = TestStats::new(&simulation_run);
+    let high = stats.priority_stats[&ReceiptPriority::High].sent as f64;
+    let low = stats.priority_stats[&ReceiptPriority::Low].sent as f64;
+    let high_share = high / (high + low);
+    assert!(high_share > 0.7 && high_share < 0.8, "{}", high_share);
+}
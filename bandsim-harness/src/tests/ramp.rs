@@ -0,0 +1,51 @@
+use crate::chain::MAX_SHARD_BANDWIDTH;
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{OneSizeReceiptGenerator, RampReceiptSender};
+use crate::validation::LoadKnee;
+
+/// 0 -> 1 - the load grows by 1% of the link capacity at every height.
+/// The link gets saturated at around height 100, that's where the queue starts growing.
+/// The throughput at the knee is close to the link capacity.
+#[test]
+fn ramp_finds_link_capacity() {
+    let simulation_run = SimulationBuilder::new(2)
+        .receipt_sender(
+            0,
+            1,
+            RampReceiptSender {
+                generator: OneSizeReceiptGenerator { size: 10_000 },
+                start_bytes_per_height: 0,
+                increase_per_height: MAX_SHARD_BANDWIDTH / 100,
+                max_bytes_per_height: MAX_SHARD_BANDWIDTH * 2,
+            },
+        )
+        .build()
+        .run_for(200);
+
+    let knee = LoadKnee::new(&simulation_run, 10);
+    println!("{:?}", knee);
+    let knee_height = knee.height.unwrap();
+    assert!((80..=110).contains(&knee_height));
+    let capacity = knee.throughput.unwrap();
+    assert!(capacity > MAX_SHARD_BANDWIDTH as f64 * 0.9);
+    assert!(capacity <= MAX_SHARD_BANDWIDTH as f64);
+}
+
+/// The load never exceeds half of the link capacity, there's no knee.
+#[test]
+fn no_knee_below_capacity() {
+    let simulation_run = SimulationBuilder::new(2)
+        .receipt_sender(
+            0,
+            1,
+            RampReceiptSender {
+                generator: OneSizeReceiptGenerator { size: 10_000 },
+                start_bytes_per_height: 0,
+                increase_per_height: MAX_SHARD_BANDWIDTH / 100,
+                max_bytes_per_height: MAX_SHARD_BANDWIDTH / 2,
+            },
+        )
+        .build()
+        .run_for(200);
+    assert_eq!(LoadKnee::new(&simulation_run, 10).height, None);
+}
@@ -0,0 +1,56 @@
+use crate::validation::{find_incoming_limit_violations, ValidationLevel};
+
+use super::hot_receiver;
+
+/// Number of chunks which received more than their incoming limit when the receipts grow by `growth_ratio`.
+fn count_violations(growth_ratio: f64) -> usize {
+    let simulation_run = hot_receiver(4)
+        .receipt_size_mutation(0, growth_ratio)
+        .validation_level(ValidationLevel::Off)
+        .build()
+        .run_for(300);
+    let violations = find_incoming_limit_violations(&simulation_run);
+    println!(
+        "growth {:.1}%: {} chunks over the incoming limit, max excess {} bytes",
+        growth_ratio * 100.0,
+        violations.len(),
+        violations
+            .iter()
+            .map(|v| v.received - v.limit)
+            .max()
+            .unwrap_or(0)
+    );
+    violations.len()
+}
+
+/// Receipts don't fill the grants exactly, the unused part of the grants absorbs small size discrepancies.
+/// A few percent of growth is enough to push the hot receiver over its limit.
+#[test]
+fn receipt_size_mutation_sensitivity() {
+    assert_eq!(count_violations(0.0), 0);
+    assert_eq!(count_violations(0.01), 0);
+    let at_5_percent = count_violations(0.05);
+    assert!(at_5_percent > 0);
+    assert!(count_violations(0.1) > at_5_percent);
+}
+
+#[test]
+#[should_panic(expected = "TOO MANY INCOMING RECEIPTS")]
+fn receipt_size_mutation_over_limit_fails_validation() {
+    hot_receiver(4)
+        .receipt_size_mutation(0, 0.1)
+        .build()
+        .run_for(300);
+}
+
+/// Paranoid validation compares what was received with what was sent, it notices any discrepancy,
+/// also one that stays within the limit.
+#[test]
+#[should_panic(expected = "bytes were sent to it")]
+fn receipt_size_mutation_detected_by_paranoid_validation() {
+    hot_receiver(4)
+        .receipt_size_mutation(0, 0.001)
+        .validation_level(ValidationLevel::Paranoid)
+        .build()
+        .run_for(100);
+}
@@ -0,0 +1,111 @@
+use crate::bandwidth_scheduler::{DenialReason, ReceiverQuotas, SchedulerParams};
+use crate::chain::{ShardUId, MAX_SHARD_BANDWIDTH};
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{ConstantRateReceiptSender, OneSizeReceiptGenerator};
+use crate::simulation::SimulationRun;
+
+const MAX_BACKLOG: usize = 2 * MAX_SHARD_BANDWIDTH;
+
+/// 0 -> 2, 1 -> 2 - both send half of the link capacity, but shard 2 can process only a quarter.
+fn run_slow_receiver(receiver_quotas: Option<ReceiverQuotas>) -> SimulationRun {
+    let sender = || ConstantRateReceiptSender {
+        generator: OneSizeReceiptGenerator { size: 100_000 },
+        bytes_per_height: MAX_SHARD_BANDWIDTH / 2,
+    };
+    SimulationBuilder::new(3)
+        .receipt_sender(0, 2, sender())
+        .receipt_sender(1, 2, sender())
+        .incoming_processing_limit(MAX_SHARD_BANDWIDTH / 4)
+        .scheduler_params(SchedulerParams {
+            receiver_quotas,
+            ..SchedulerParams::default()
+        })
+        .build()
+        .run_for(300)
+}
+
+/// Largest incoming backlog of shard 2 over the whole run.
+fn max_incoming_backlog(simulation_run: &SimulationRun) -> usize {
+    simulation_run
+        .simulation
+        .blocks
+        .iter()
+        .flatten()
+        .filter_map(|block| block.chunks.get(&ShardUId::new(2))?.as_ref())
+        .map(|chunk| chunk.congestion_info.incoming_backlog_size)
+        .max()
+        .unwrap()
+}
+
+/// Total size of the receipts received by shard 2.
+fn total_received(simulation_run: &SimulationRun) -> usize {
+    simulation_run
+        .simulation
+        .blocks
+        .iter()
+        .flatten()
+        .flat_map(|block| block.chunks.values().flatten())
+        .filter_map(|chunk| chunk.prev_outgoing_receipts_size.get(&ShardUId::new(2)))
+        .sum()
+}
+
+/// With only the senders' requests the scheduler keeps granting as much as the slow receiver can take
+/// over the network, its backlog grows without a bound. With quotas the receiver pushes back,
+/// the backlog stays close to the limit and the senders keep the receipts in their outgoing queues.
+#[test]
+fn receiver_quotas_bound_incoming_backlog() {
+    let sender_driven = run_slow_receiver(None);
+    let receiver_driven = run_slow_receiver(Some(ReceiverQuotas {
+        max_backlog: MAX_BACKLOG,
+    }));
+
+    let sender_driven_backlog = max_incoming_backlog(&sender_driven);
+    let receiver_driven_backlog = max_incoming_backlog(&receiver_driven);
+    println!(
+        "Max incoming backlog: sender-driven = {}, receiver-driven = {}",
+        sender_driven_backlog, receiver_driven_backlog
+    );
+    assert!(sender_driven_backlog > 50 * MAX_SHARD_BANDWIDTH);
+    assert!(
+        receiver_driven_backlog <= 2 * MAX_BACKLOG,
+        "{}",
+        receiver_driven_backlog
+    );
+
+    // The receiver still gets about as much as it can process.
+    let received = total_received(&receiver_driven);
+    assert!(
+        received > 300 * MAX_SHARD_BANDWIDTH / 4 * 9 / 10,
+        "{}",
+        received
+    );
+    assert!(received < total_received(&sender_driven));
+}
+
+/// The links to the slow receiver are denied because of the quota, the grants never exceed it
+/// (checked at every height by the validation).
+#[test]
+fn receiver_quota_denials() {
+    let simulation_run = run_slow_receiver(Some(ReceiverQuotas {
+        max_backlog: MAX_BACKLOG,
+    }));
+    let quota_denials = simulation_run
+        .simulation
+        .metrics
+        .iter()
+        .flat_map(|height_metrics| &height_metrics.denials)
+        .filter(|denial| denial.reason == DenialReason::ReceiverQuota)
+        .count();
+    assert!(quota_denials > 100, "{}", quota_denials);
+}
+
+/// Without the receiver quotas the chunks don't advertise anything.
+#[test]
+fn no_quotas_by_default() {
+    let simulation_run = run_slow_receiver(None);
+    for block in simulation_run.simulation.blocks.iter().flatten() {
+        for chunk in block.chunks.values().flatten() {
+            assert!(chunk.receiver_quotas.is_empty());
+        }
+    }
+}
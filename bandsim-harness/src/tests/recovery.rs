@@ -0,0 +1,96 @@
+use crate::chain::{ShardUId, MAX_SHARD_BANDWIDTH};
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{ConstantRateReceiptSender, OneSizeReceiptGenerator};
+use crate::validation::{OutageKind, RecoveryStats, TestStats, RECOVERY_WINDOW};
+
+use super::DEFAULT_TEST_LENGTH;
+
+const NUM_SHARDS: usize = 4;
+
+/// Every shard sends 80% of its bandwidth, spread evenly over all links.
+/// The queues stay short in the steady state, but receipts pile up during an outage.
+fn steady_load() -> SimulationBuilder {
+    SimulationBuilder::new(NUM_SHARDS)
+        .default_sender_factory(|_rng| {
+            Box::new(ConstantRateReceiptSender {
+                generator: OneSizeReceiptGenerator { size: 50_000 },
+                bytes_per_height: MAX_SHARD_BANDWIDTH * 8 / 10 / NUM_SHARDS,
+            })
+        })
+        .record_grant_history()
+}
+
+fn assert_catches_up(recovery: &RecoveryStats) {
+    recovery.print();
+    assert!(
+        recovery.recovery.mean_sent() > recovery.steady.mean_sent(),
+        "{:?}",
+        recovery
+    );
+    assert!(recovery.recovery.mean_grant().is_some());
+    assert!(recovery.catch_up_bytes() > 0.0);
+    assert!(recovery.p99_latency_spike() > 0, "{:?}", recovery);
+}
+
+/// Shard 0 misses 5 chunks in a row every 100 heights.
+#[test]
+fn recovery_after_missing_chunks() {
+    let simulation_run = steady_load()
+        .missing_chunk_generator(|height, shard_id, _rng| {
+            shard_id == ShardUId::new(0) && height % 100 >= 95
+        })
+        .build()
+        .run_for(DEFAULT_TEST_LENGTH);
+    let stats = TestStats::new(&simulation_run);
+    assert!(stats.recovery_after_missing_blocks.is_none());
+    let recovery = stats.recovery_after_missing_chunks.unwrap();
+    assert_eq!(recovery.episodes, DEFAULT_TEST_LENGTH / 100);
+    assert_eq!(recovery.window, RECOVERY_WINDOW);
+    assert_catches_up(&recovery);
+}
+
+#[test]
+fn recovery_after_missing_blocks() {
+    let simulation_run = steady_load()
+        .missing_block_probability(0.05)
+        .build()
+        .run_for(DEFAULT_TEST_LENGTH);
+    let stats = TestStats::new(&simulation_run);
+    let recovery = stats.recovery_after_missing_blocks.unwrap();
+    assert!(recovery.episodes > 10, "{}", recovery.episodes);
+    assert_catches_up(&recovery);
+}
+
+/// All links are in the steady state when the run has no outages.
+#[test]
+fn no_outages() {
+    let simulation_run = steady_load().build().run_for(200);
+    for kind in [OutageKind::MissingChunks, OutageKind::MissingBlocks] {
+        assert_eq!(
+            RecoveryStats::new(&simulation_run, kind, RECOVERY_WINDOW),
+            None
+        );
+    }
+}
+
+/// A longer window includes more heights in the recovery.
+#[test]
+fn recovery_window() {
+    let simulation_run = steady_load()
+        .missing_chunk_generator(|height, shard_id, _rng| {
+            shard_id == ShardUId::new(1) && height == 50
+        })
+        .build()
+        .run_for(200);
+    let short = RecoveryStats::new(&simulation_run, OutageKind::MissingChunks, 1).unwrap();
+    let long = RecoveryStats::new(&simulation_run, OutageKind::MissingChunks, 5).unwrap();
+    assert_eq!(short.episodes, 1);
+    // Links from and to shard 1, the self-link is counted once.
+    let links = 2 * NUM_SHARDS - 1;
+    assert_eq!(short.recovery.samples, links);
+    assert_eq!(long.recovery.samples, 5 * links);
+    assert_eq!(
+        short.recovery.samples + short.steady.samples,
+        long.recovery.samples + long.steady.samples
+    );
+}
@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+
+use rand::Rng;
+
+use crate::chain::{MAX_RECEIPT_SIZE, MIN_RECEIPT_SIZE};
+use crate::manifest::RunManifest;
+use crate::rng::RngRecording;
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{FullSpeedReceiptSender, RandomSizeReceiptGenerator};
+use crate::simulation::SimulationRun;
+use crate::validation::TestStats;
+
+/// Random receipt sizes, random missing chunks and blocks - everything depends on the rng.
+fn random_workload(seed: u64) -> SimulationBuilder {
+    SimulationBuilder::new(3)
+        .random_seed(seed)
+        .default_sender_factory(|_rng| {
+            Box::new(FullSpeedReceiptSender(RandomSizeReceiptGenerator {
+                size_range: MIN_RECEIPT_SIZE..=MAX_RECEIPT_SIZE,
+            }))
+        })
+        .missing_block_probability(0.1)
+        .missing_chunk_generator(|_height, _shard_id, rng| rng.gen_bool(0.2))
+}
+
+/// Outgoing receipt sizes of every chunk at every height, `None` for missing blocks and chunks.
+type ChainSummary = Vec<Option<Vec<Option<BTreeMap<String, usize>>>>>;
+
+fn chain_summary(simulation_run: &SimulationRun) -> ChainSummary {
+    simulation_run
+        .simulation
+        .blocks
+        .iter()
+        .map(|block| {
+            let block = block.as_ref()?;
+            Some(
+                block
+                    .chunks
+                    .values()
+                    .map(|chunk| {
+                        let chunk = chunk.as_ref()?;
+                        Some(
+                            chunk
+                                .prev_outgoing_receipts_size
+                                .iter()
+                                .map(|(shard, size)| (format!("{:?}", shard), *size))
+                                .collect(),
+                        )
+                    })
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+/// A run replayed from a saved recording is identical to the recorded one,
+/// even though the replay uses a different seed.
+#[test]
+fn replayed_run_matches_recorded_run() {
+    let mut recorded_run = random_workload(1).record_rng().build().run_for(200);
+    let recording = recorded_run.simulation.rng.take_recording().unwrap();
+    assert!(recording.total_bytes() > 0);
+
+    let path = std::env::temp_dir().join(format!("bandsim_rng_{}.txt", std::process::id()));
+    recording.save(&path).unwrap();
+    let loaded = RngRecording::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded, recording);
+
+    let replayed_run = random_workload(2).replay_rng(loaded).build().run_for(200);
+    assert_eq!(chain_summary(&replayed_run), chain_summary(&recorded_run));
+
+    let live_run = random_workload(2).build().run_for(200);
+    assert_ne!(chain_summary(&live_run), chain_summary(&recorded_run));
+}
+
+/// The manifest of a run describes it well enough that a run with the same configuration gives the same stats.
+#[test]
+fn manifest_reproduces_run() {
+    let path = std::env::temp_dir().join(format!(
+        "bandsim_manifest_reproduces_run_{}.tsv",
+        std::process::id()
+    ));
+    let simulation_run = random_workload(7)
+        .description("random receipt sizes, 3 shards")
+        .build()
+        .run_for(200);
+    RunManifest::new(&simulation_run, &TestStats::new(&simulation_run))
+        .save(&path)
+        .unwrap();
+    let manifest = RunManifest::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(manifest.random_seed, 7);
+    assert_eq!(manifest.description, "random receipt sizes, 3 shards");
+    assert_eq!(manifest.crate_version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(manifest.heights, 201);
+    assert_eq!(manifest.receipt_senders.len(), 9);
+    assert!(manifest.config.contains("seed=7"));
+
+    let reproduced_run = random_workload(manifest.random_seed)
+        .build()
+        .run_for(manifest.heights - 1);
+    let reproduced = RunManifest::new(&reproduced_run, &TestStats::new(&reproduced_run));
+    assert_eq!(reproduced.config, manifest.config);
+    assert_eq!(reproduced.stats, manifest.stats);
+}
@@ -0,0 +1,71 @@
+use crate::bandwidth_request::BandwidthRequest;
+use crate::chain::SimulationConfig;
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::outgoing_queue::{
+    HeadReceipt, Headroom, OutgoingQueue, RequestPolicy, WholeQueue,
+};
+use crate::validation::TestStats;
+
+use super::{full_speed_typical_sender, DEFAULT_TEST_LENGTH};
+
+fn typical_stats(request_policy: impl RequestPolicy + 'static) -> TestStats {
+    let simulation_run = SimulationBuilder::new(4)
+        .default_sender_factory(full_speed_typical_sender)
+        .request_policy(request_policy)
+        .build()
+        .run_for(DEFAULT_TEST_LENGTH);
+    TestStats::new(&simulation_run)
+}
+
+/// All links send typical receipts at full speed.
+/// Requesting only for the next receipt leaves bandwidth unused (~77% utilization instead of ~90%),
+/// leaving half of the shard bandwidth as headroom costs almost nothing.
+#[test]
+fn request_policies_with_typical_receipts() {
+    let whole_queue = typical_stats(WholeQueue);
+    let head_receipt = typical_stats(HeadReceipt);
+    let headroom = typical_stats(Headroom(0.5));
+    whole_queue.basic_assert();
+    headroom.basic_assert();
+    assert!(
+        head_receipt.bandwidth_utilization.utilization
+            < whole_queue.bandwidth_utilization.utilization - 0.05
+    );
+}
+
+/// Requests bandwidth for the first `n` receipts in the queue.
+#[derive(Clone, Debug)]
+struct FirstReceipts(usize);
+
+impl RequestPolicy for FirstReceipts {
+    fn make_bandwidth_request(
+        &self,
+        queue: &OutgoingQueue,
+        base_bandwidth: usize,
+        config: &SimulationConfig,
+    ) -> Option<BandwidthRequest> {
+        BandwidthRequest::from_receipt_sizes(
+            queue.to_shard(),
+            queue.iter_sizes().take(self.0),
+            base_bandwidth,
+            config,
+        )
+    }
+}
+
+/// Request policies can be implemented outside of the simulation,
+/// requesting for the first receipt behaves exactly like `HeadReceipt`.
+#[test]
+fn custom_request_policy() {
+    let head_receipt = typical_stats(HeadReceipt);
+    let first_receipt = typical_stats(FirstReceipts(1));
+    assert_eq!(
+        first_receipt.bandwidth_utilization.utilization,
+        head_receipt.bandwidth_utilization.utilization
+    );
+    let first_receipts = typical_stats(FirstReceipts(3));
+    assert!(
+        first_receipts.bandwidth_utilization.utilization
+            > head_receipt.bandwidth_utilization.utilization
+    );
+}
@@ -0,0 +1,127 @@
+use std::collections::BTreeSet;
+
+use rand::Rng;
+
+use crate::chain::{ShardLink, ShardUId, MAX_SHARD_BANDWIDTH, MIN_RECEIPT_SIZE};
+use crate::rng::{block_hash, GrantRngSource};
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{ConstantRateReceiptSender, RandomSizeReceiptGenerator};
+use crate::simulation::SimulationRun;
+
+fn random_size_sender() -> ConstantRateReceiptSender<RandomSizeReceiptGenerator> {
+    ConstantRateReceiptSender {
+        generator: RandomSizeReceiptGenerator {
+            size_range: MIN_RECEIPT_SIZE..=500_000,
+        },
+        bytes_per_height: MAX_SHARD_BANDWIDTH / 4,
+    }
+}
+
+/// Bytes of receipts generated on the link at every height.
+fn offered_on_link(builder: SimulationBuilder, link: ShardLink) -> Vec<usize> {
+    let simulation_run = builder.build().run_for(100);
+    simulation_run
+        .simulation
+        .metrics
+        .iter()
+        .map(|metrics| metrics.offered[&link])
+        .collect()
+}
+
+/// Every sender has its own stream of random numbers,
+/// adding a sender on another link doesn't change the receipts generated on 0 -> 1.
+#[test]
+fn adding_sender_does_not_change_other_senders() {
+    let link = ShardLink {
+        from: ShardUId::new(0),
+        to: ShardUId::new(1),
+    };
+    let alone = offered_on_link(
+        SimulationBuilder::new(3).receipt_sender(0, 1, random_size_sender()),
+        link,
+    );
+    let with_other_sender = offered_on_link(
+        SimulationBuilder::new(3)
+            .receipt_sender(0, 1, random_size_sender())
+            .receipt_sender(0, 2, random_size_sender())
+            .receipt_sender(2, 1, random_size_sender()),
+        link,
+    );
+    assert_eq!(alone, with_other_sender);
+}
+
+/// 0 -> 1 has its own seed, it generates the same receipts regardless of the simulation seed.
+/// 0 -> 2 uses the simulation seed, its receipts change with the seed.
+#[test]
+fn sender_seed_stays_fixed() {
+    let fixed_link = ShardLink {
+        from: ShardUId::new(0),
+        to: ShardUId::new(1),
+    };
+    let varying_link = ShardLink {
+        from: ShardUId::new(0),
+        to: ShardUId::new(2),
+    };
+    let builder = |seed: u64| {
+        SimulationBuilder::new(3)
+            .random_seed(seed)
+            .receipt_sender_with_seed(0, 1, random_size_sender(), 42)
+            .receipt_sender(0, 2, random_size_sender())
+    };
+    assert_eq!(
+        offered_on_link(builder(1), fixed_link),
+        offered_on_link(builder(2), fixed_link)
+    );
+    assert_ne!(
+        offered_on_link(builder(1), varying_link),
+        offered_on_link(builder(2), varying_link)
+    );
+}
+
+fn random_size_run(seed: u64) -> SimulationRun {
+    SimulationBuilder::new(4)
+        .random_seed(seed)
+        .default_sender_factory(|_rng| Box::new(random_size_sender()))
+        .build()
+        .run_for(20)
+}
+
+/// Every shard derives the scheduler's rng from the previous block on its own,
+/// all of them must get the same stream.
+#[test]
+fn shards_derive_identical_grant_rng() {
+    let simulation_run = random_size_run(0);
+    let simulation = &simulation_run.simulation;
+    for block in simulation.blocks.iter().flatten() {
+        let streams: BTreeSet<Vec<u64>> = simulation
+            .shards
+            .values()
+            .map(|shard| {
+                let mut rng = shard.grant_rng_source.rng_for_block(block);
+                (0..10).map(|_| rng.gen()).collect()
+            })
+            .collect();
+        assert_eq!(streams.len(), 1);
+    }
+}
+
+/// Blocks at the same height with different contents give different streams,
+/// unless the rng is derived only from the height.
+#[test]
+fn grant_rng_depends_on_block_contents() {
+    let run_a = random_size_run(0);
+    let run_b = random_size_run(1);
+    let block_a = run_a.simulation.blocks[10].as_ref().unwrap();
+    let block_b = run_b.simulation.blocks[10].as_ref().unwrap();
+    assert_ne!(block_hash(block_a), block_hash(block_b));
+
+    let first_draw = |source: GrantRngSource, block| source.rng_for_block(block).gen::<u64>();
+    assert_ne!(
+        first_draw(GrantRngSource::BlockHash, block_a),
+        first_draw(GrantRngSource::BlockHash, block_b)
+    );
+    assert_eq!(
+        first_draw(GrantRngSource::Height, block_a),
+        first_draw(GrantRngSource::Height, block_b)
+    );
+}
@@ -0,0 +1,90 @@
+use std::path::PathBuf;
+
+use crate::chain::SimulationConfig;
+use crate::scenario::{Scenario, ScenarioSender};
+use crate::validation::{StatsThresholds, TestStats};
+
+fn scenario_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("scenarios")
+        .join(name)
+}
+
+/// Run every scenario from the `scenarios` directory and check its thresholds.
+#[test]
+fn run_scenario_files() {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(scenario_path(""))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+    for path in paths {
+        println!("Running scenario {}", path.display());
+        Scenario::load(&path).unwrap().run_and_assert();
+    }
+}
+
+#[test]
+fn parse_scenario() {
+    let scenario = Scenario::load(scenario_path("big_vs_small_sender.toml")).unwrap();
+    assert!(!scenario.description.is_empty());
+    assert_eq!(scenario.shards, 2);
+    assert_eq!(scenario.steps, 1000);
+    assert_eq!(scenario.default_sender, ScenarioSender::None);
+    assert_eq!(
+        scenario.senders.into_iter().collect::<Vec<_>>(),
+        vec![
+            ((0, 0), ScenarioSender::Big),
+            ((0, 1), ScenarioSender::Small)
+        ]
+    );
+    assert_eq!(
+        scenario.thresholds,
+        StatsThresholds {
+            max_min_ratio: 1.25,
+            min_bandwidth_utilization: 0.9,
+            max_receipt_age: Some(20),
+            ..StatsThresholds::default()
+        }
+    );
+}
+
+#[test]
+fn invalid_scenarios() {
+    let err = |text: &str| Scenario::parse(text).unwrap_err();
+    assert!(err("shards = two").contains("expected an integer"));
+    assert!(err("shards = 2\n[senders]\n\"0 -> 2\" = \"big\"").contains("outside of the shards"));
+    assert!(err("[senders]\ndefault = \"huge\"").contains("Unknown sender"));
+    assert!(err("speed = 3").contains("unknown key"));
+    assert!(err("missing_block_probability = 1.0").contains("not in [0, 1)"));
+    assert!(
+        err("[senders]\ndefault = \"fixed:500\"").contains("outside of the receipt size limits")
+    );
+    assert!(
+        err("[config]\nmin_receipt_size = 5000\nmax_receipt_size = 4000")
+            .contains("Invalid receipt size limits")
+    );
+}
+
+/// The senders generate receipts within the receipt size limits from the `[config]` section.
+#[test]
+fn scenario_config() {
+    let scenario = Scenario::parse(
+        "shards = 3\nsteps = 200\n[senders]\ndefault = \"typical\"\n\"0 -> 1\" = \"big\"\n\"1 -> 2\" = \"random\"\n\
+         [config]\nmax_shard_bandwidth = 1000000\nmax_receipt_size = 200000",
+    )
+    .unwrap();
+    assert_eq!(
+        scenario.config,
+        SimulationConfig {
+            max_shard_bandwidth: 1_000_000,
+            max_receipt_size: 200_000,
+            ..SimulationConfig::default()
+        }
+    );
+    let simulation_run = scenario.run();
+    assert_eq!(simulation_run.simulation.config, scenario.config);
+    TestStats::new(&simulation_run).basic_assert();
+}
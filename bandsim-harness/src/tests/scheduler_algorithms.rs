@@ -0,0 +1,197 @@
+use crate::bandwidth_scheduler::{SchedulerAlgorithm, SchedulerParams};
+use crate::chain::{MAX_RECEIPT_SIZE, MIN_RECEIPT_SIZE};
+use crate::experiments::comparison::{ComparisonReport, UpgradeComparison};
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{FullSpeedReceiptSender, OneSizeReceiptGenerator};
+
+use super::{all_links_busy, hot_receiver};
+
+/// Shards 0 and 1 send big receipts, shards 2 and 3 small ones, all of them to every shard.
+fn big_and_small_receipts(seed: u64) -> SimulationBuilder {
+    SimulationBuilder::new(4)
+        .random_seed(seed)
+        .default_sender_factory(|_rng| {
+            Box::new(FullSpeedReceiptSender(OneSizeReceiptGenerator {
+                size: MIN_RECEIPT_SIZE,
+            }))
+        })
+        .receipt_sender(
+            0,
+            1,
+            FullSpeedReceiptSender(OneSizeReceiptGenerator {
+                size: MAX_RECEIPT_SIZE,
+            }),
+        )
+        .receipt_sender(
+            1,
+            0,
+            FullSpeedReceiptSender(OneSizeReceiptGenerator {
+                size: MAX_RECEIPT_SIZE,
+            }),
+        )
+}
+
+/// Compare the allowance based scheduler with another algorithm on the same workload.
+fn compare_with_allowance(
+    algorithm: SchedulerAlgorithm,
+    make_workload: impl Fn(u64) -> SimulationBuilder + 'static,
+) -> ComparisonReport {
+    let new_params = SchedulerParams {
+        algorithm,
+        ..SchedulerParams::default()
+    };
+    let report = UpgradeComparison::new(SchedulerParams::default(), new_params, make_workload)
+        .metric("fairness", false, |stats| stats.max_min_ratio.ratio)
+        .metric("fairness over 10 heights", false, |stats| {
+            stats.windowed_fairness[0].worst_ratio
+        })
+        .metric("utilization", true, |stats| {
+            stats.bandwidth_utilization.utilization
+        })
+        .metric("scheduler state", false, |stats| {
+            stats.max_scheduler_state_size as f64
+        })
+        .seeds(0..3)
+        .steps(300)
+        .run();
+    report.print();
+    report
+}
+
+/// Without allowances a link that got unlucky isn't compensated later. Some links get nothing
+/// for many heights and the long-term fairness is worse too.
+#[test]
+fn random_priority_vs_allowance() {
+    let report = compare_with_allowance(SchedulerAlgorithm::RandomPriority, |seed| {
+        all_links_busy(4).random_seed(seed)
+    });
+    let regressions: Vec<&str> = report.regressions().map(|m| m.name.as_str()).collect();
+    assert!(regressions.contains(&"fairness"));
+    assert!(regressions.contains(&"fairness over 10 heights"));
+}
+
+/// Round robin needs no allowance state, but with more links than heights in the window
+/// some links don't get their turn at all. In the long run it's close to the allowances.
+#[test]
+fn round_robin_vs_allowance() {
+    let report = compare_with_allowance(SchedulerAlgorithm::RoundRobin, |seed| {
+        all_links_busy(4).random_seed(seed)
+    });
+    let fairness = &report.metrics[0];
+    assert!(fairness.new_mean > fairness.old_mean);
+    assert!(fairness.new_mean < 1.25);
+    let windowed_fairness = &report.metrics[1];
+    assert!(windowed_fairness.is_regression);
+    let utilization = &report.metrics[2];
+    assert!(utilization.new_mean > utilization.old_mean * 0.9);
+}
+
+const DEFICIT_ROUND_ROBIN: SchedulerAlgorithm =
+    SchedulerAlgorithm::DeficitRoundRobin { quantum: 100_000 };
+
+/// Under uniform load all links are busy, so deficit round robin keeps as much state as the allowances.
+/// The deficits are forgotten as soon as a link is served, so it's less fair than the allowances.
+#[test]
+fn deficit_round_robin_all_links_busy() {
+    let report = compare_with_allowance(DEFICIT_ROUND_ROBIN, |seed| {
+        all_links_busy(4).random_seed(seed)
+    });
+    let [fairness, windowed_fairness, utilization, state] = &report.metrics[..] else {
+        panic!("Unexpected metrics");
+    };
+    assert!(fairness.is_regression);
+    assert!(windowed_fairness.is_regression);
+    assert!(utilization.new_mean > utilization.old_mean * 0.95);
+    assert_eq!(state.new_mean, state.old_mean);
+}
+
+/// Only the links to the hot receiver are busy, deficit round robin keeps state just for them,
+/// while the allowances are stored for every link.
+#[test]
+fn deficit_round_robin_hot_receiver() {
+    let report = compare_with_allowance(DEFICIT_ROUND_ROBIN, |seed| {
+        hot_receiver(4).random_seed(seed)
+    });
+    let [fairness, _windowed_fairness, utilization, state] = &report.metrics[..] else {
+        panic!("Unexpected metrics");
+    };
+    assert!(fairness.new_mean < fairness.old_mean * 1.1);
+    assert!(utilization.new_mean > utilization.old_mean * 0.95);
+    assert_eq!(state.old_mean, 16.0);
+    assert_eq!(state.new_mean, 4.0);
+}
+
+/// A link with big receipts needs a lot of bandwidth at once. A denied link keeps at most one increase
+/// and one quantum of deficit, so unlike an allowance the deficit doesn't grow while the link is waiting
+/// and the links with small receipts keep taking the bandwidth first.
+#[test]
+fn deficit_round_robin_big_and_small_receipts() {
+    let report = compare_with_allowance(DEFICIT_ROUND_ROBIN, big_and_small_receipts);
+    let [fairness, _windowed_fairness, utilization, _state] = &report.metrics[..] else {
+        panic!("Unexpected metrics");
+    };
+    assert!(fairness.new_mean > fairness.old_mean * 2.0);
+    assert!(!utilization.is_regression);
+}
+
+/// Like `all_links_busy`, but with small receipts that fit into any grant.
+/// Max flow gives links arbitrary amounts, a link whose grants are smaller than its next big receipt
+/// would never send anything and the fairness of the run couldn't be computed.
+fn small_receipts_all_links_busy(seed: u64) -> SimulationBuilder {
+    SimulationBuilder::new(4)
+        .random_seed(seed)
+        .default_sender_factory(|_rng| {
+            Box::new(FullSpeedReceiptSender(OneSizeReceiptGenerator {
+                size: MIN_RECEIPT_SIZE,
+            }))
+        })
+}
+
+/// Like `hot_receiver`, but with small receipts.
+fn small_receipts_hot_receiver(seed: u64) -> SimulationBuilder {
+    let mut builder = SimulationBuilder::new(4).random_seed(seed);
+    for from in 0..4 {
+        builder = builder.receipt_sender(
+            from,
+            0,
+            FullSpeedReceiptSender(OneSizeReceiptGenerator {
+                size: MIN_RECEIPT_SIZE,
+            }),
+        );
+    }
+    builder
+}
+
+/// Max flow grants as much of the requested bandwidth as the limits allow, the allowance scheduler
+/// can't beat its utilization.
+#[test]
+fn max_flow_all_links_busy() {
+    let report = compare_with_allowance(SchedulerAlgorithm::MaxFlow, small_receipts_all_links_busy);
+    let [_fairness, _windowed_fairness, utilization, state] = &report.metrics[..] else {
+        panic!("Unexpected metrics");
+    };
+    assert!(utilization.new_mean >= utilization.old_mean * 0.99);
+    assert_eq!(state.new_mean, 0.0);
+}
+
+/// The hot receiver is the bottleneck for both schedulers, they can't do better than filling it up.
+#[test]
+fn max_flow_hot_receiver() {
+    let report = compare_with_allowance(SchedulerAlgorithm::MaxFlow, small_receipts_hot_receiver);
+    let [_fairness, _windowed_fairness, utilization, _state] = &report.metrics[..] else {
+        panic!("Unexpected metrics");
+    };
+    assert!(utilization.new_mean >= utilization.old_mean * 0.99);
+}
+
+/// The allowance scheduler loses some bandwidth when the big receipts don't fit into what's left,
+/// max flow fills it with the small ones. The price is fairness, the big receipts wait much longer.
+#[test]
+fn max_flow_big_and_small_receipts() {
+    let report = compare_with_allowance(SchedulerAlgorithm::MaxFlow, big_and_small_receipts);
+    let [fairness, _windowed_fairness, utilization, _state] = &report.metrics[..] else {
+        panic!("Unexpected metrics");
+    };
+    assert!(utilization.new_mean > utilization.old_mean);
+    assert!(fairness.is_regression);
+}
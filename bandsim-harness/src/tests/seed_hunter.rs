@@ -0,0 +1,100 @@
+use crate::chain::{ShardLink, ShardUId};
+use crate::experiments::seed_hunter::{merge_results, SeedFailure, SeedHunter, Threshold};
+use crate::simulation::builder::SimulationBuilder;
+use crate::validation::TestStats;
+
+use super::constant_rate_sender;
+
+fn sent_0_to_1(stats: &TestStats) -> f64 {
+    let link = ShardLink {
+        from: ShardUId::new(0),
+        to: ShardUId::new(1),
+    };
+    stats.total_sent.sent(link) as f64
+}
+
+/// 0 -> 1 - sends 1MB per height, except for every third seed, where it sends only 10kB.
+/// Seed 4 additionally sends too many unstoppable receipts, which makes the validation panic.
+fn seed_dependent_scenario(seed: u64) -> SimulationBuilder {
+    let bytes_per_height = if seed.is_multiple_of(3) {
+        10_000
+    } else {
+        1_000_000
+    };
+    let builder = SimulationBuilder::new(2).random_seed(seed).receipt_sender(
+        0,
+        1,
+        constant_rate_sender(10_000, bytes_per_height),
+    );
+    if seed == 4 {
+        builder.unstoppable_receipt_sender(0, 0, constant_rate_sender(10_000, 10_000_000))
+    } else {
+        builder
+    }
+}
+
+/// The hunter finds both the seeds with too little throughput and the one that panics.
+#[test]
+fn hunter_finds_low_throughput_and_panicking_seeds() {
+    let repro_dir = std::env::temp_dir().join(format!("bandsim_hunt_{}", std::process::id()));
+    let hits = SeedHunter::new("seed_dependent", seed_dependent_scenario)
+        .threshold("sent_0_to_1", sent_0_to_1, Threshold::AtLeast(1_000_000.0))
+        .seeds(0..7)
+        .steps(50)
+        .threads(2)
+        .repro_dir(&repro_dir)
+        .run();
+
+    let hit_seeds: Vec<u64> = hits.iter().map(|hit| hit.seed).collect();
+    assert_eq!(hit_seeds, vec![0, 3, 4, 6]);
+    assert!(matches!(hits[0].failure, SeedFailure::Violation { .. }));
+    assert!(
+        matches!(hits[2].failure, SeedFailure::Panic { ref message } if message.contains("Unstoppable"))
+    );
+
+    let repro = std::fs::read_to_string(repro_dir.join("seed_dependent_seed_3.repro")).unwrap();
+    assert!(repro.contains("seed = 3"));
+    assert!(repro.contains("metric = sent_0_to_1"));
+    assert!(repro_dir.join("seed_dependent_seed_3.rng").exists());
+    assert!(repro_dir.join("seed_dependent_seed_4.repro").exists());
+    std::fs::remove_dir_all(&repro_dir).unwrap();
+}
+
+/// Three hunters split the seeds between themselves, one of them checks a seed range overlapping
+/// with the others. Merged results contain every failing seed once, grouped by kind of failure.
+#[test]
+fn partitioned_hunters_merge_results() {
+    let results_dir = std::env::temp_dir().join(format!("bandsim_merge_{}", std::process::id()));
+    // Seeds 4 and 5 both panic, with different numbers in the panic message.
+    let scenario = |seed: u64| match seed {
+        5 => seed_dependent_scenario(4).random_seed(5),
+        _ => seed_dependent_scenario(seed),
+    };
+    let hunter = |index: usize, count: usize, seeds| {
+        SeedHunter::new("seed_dependent", scenario)
+            .threshold("sent_0_to_1", sent_0_to_1, Threshold::AtLeast(1_000_000.0))
+            .seeds(seeds)
+            .steps(50)
+            .partition(index, count)
+            .repro_dir(&results_dir)
+    };
+    let first_hits = hunter(0, 2, 0..7).run();
+    let second_hits = hunter(1, 2, 0..7).run();
+    let overlapping_hits = hunter(0, 1, 3..5).run();
+    assert_eq!(first_hits.len() + second_hits.len(), 5);
+    assert_eq!(overlapping_hits.len(), 2);
+
+    let groups = merge_results(&results_dir).unwrap();
+    std::fs::remove_dir_all(&results_dir).unwrap();
+    assert_eq!(groups.len(), 2);
+    let panics = groups
+        .iter()
+        .find(|group| group.signature.starts_with("panic"))
+        .unwrap();
+    assert_eq!(panics.seeds, vec![4, 5]);
+    let violations = groups
+        .iter()
+        .find(|group| group.signature.starts_with("violation: sent_0_to_1"))
+        .unwrap();
+    assert_eq!(violations.seeds, vec![0, 3, 6]);
+}
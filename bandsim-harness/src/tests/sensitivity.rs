@@ -0,0 +1,103 @@
+use crate::bandwidth_scheduler::SchedulerParams;
+use crate::experiments::cache::ResultCache;
+use crate::experiments::sensitivity::SensitivityAnalysis;
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{FullSpeedReceiptSender, OneSizeReceiptGenerator};
+
+use super::full_speed_typical_sender;
+
+/// Sweep over the maximum base bandwidth and see how it affects fairness and utilization.
+#[test]
+fn max_base_bandwidth_sweep() {
+    let result = SensitivityAnalysis::new(
+        "typical",
+        "max_base_bandwidth",
+        [0, 50_000, 100_000, 200_000],
+        |max_base_bandwidth| {
+            SimulationBuilder::new(4)
+                .default_sender_factory(full_speed_typical_sender)
+                .scheduler_params(SchedulerParams {
+                    max_base_bandwidth: *max_base_bandwidth,
+                    ..SchedulerParams::default()
+                })
+        },
+    )
+    .metric("fairness", |stats| stats.max_min_ratio.ratio)
+    .metric("utilization", |stats| {
+        stats.bandwidth_utilization.utilization
+    })
+    .steps(200)
+    .run();
+
+    result.print_table();
+    result.print_plot("utilization");
+    println!("{}", result.to_csv());
+
+    assert_eq!(result.rows.len(), 4);
+    assert_eq!(result.to_csv().lines().count(), 5);
+    for utilization in result.metric_values("utilization") {
+        assert!(utilization > 0.5);
+    }
+}
+
+/// The second sweep over the same configurations takes all results from the cache.
+#[test]
+fn cached_sweep_reuses_results() {
+    let cache_dir =
+        std::env::temp_dir().join(format!("bandsim_cached_sweep_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&cache_dir);
+    let sweep = || {
+        SensitivityAnalysis::new("typical", "num_shards", [1, 2], |num_shards| {
+            SimulationBuilder::new(*num_shards).default_sender_factory(full_speed_typical_sender)
+        })
+        .metric("utilization", |stats| {
+            stats.bandwidth_utilization.utilization
+        })
+        .steps(50)
+        .cache(ResultCache::new(&cache_dir))
+        .run()
+    };
+
+    let first = sweep();
+    assert_eq!(first.cached_rows, 0);
+    let second = sweep();
+    assert_eq!(second.cached_rows, 2);
+    assert_eq!(
+        first.metric_values("utilization"),
+        second.metric_values("utilization")
+    );
+    std::fs::remove_dir_all(&cache_dir).unwrap();
+}
+
+/// Sweeps over the same parameter with different receipt senders don't share the cached results.
+#[test]
+fn cache_separates_scenarios() {
+    let cache_dir = std::env::temp_dir().join(format!(
+        "bandsim_cache_separates_scenarios_{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&cache_dir);
+    let sweep = |scenario: &str, receipt_size: usize| {
+        SensitivityAnalysis::new(scenario, "num_shards", [2], move |num_shards| {
+            SimulationBuilder::new(*num_shards).default_sender_factory(move |_rng| {
+                Box::new(FullSpeedReceiptSender(OneSizeReceiptGenerator {
+                    size: receipt_size,
+                }))
+            })
+        })
+        .metric("max receipt age", |stats| stats.max_receipt_age.age as f64)
+        .steps(50)
+        .cache(ResultCache::new(&cache_dir))
+        .run()
+    };
+
+    let small = sweep("small receipts", 1_000);
+    let big = sweep("big receipts", 4_000_000);
+    assert_eq!(big.cached_rows, 0);
+    assert_ne!(
+        small.metric_values("max receipt age"),
+        big.metric_values("max receipt age")
+    );
+    assert_eq!(sweep("small receipts", 1_000).cached_rows, 1);
+    std::fs::remove_dir_all(&cache_dir).unwrap();
+}
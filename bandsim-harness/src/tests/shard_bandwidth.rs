@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+
+use crate::bandwidth_scheduler::{SchedulerParams, ShardBandwidth};
+use crate::chain::{ShardUId, SimulationConfig, MAX_SHARD_BANDWIDTH};
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{FullSpeedReceiptSender, OneSizeReceiptGenerator};
+use crate::simulation::SimulationRun;
+use crate::validation::{StatsThresholds, TestStats};
+
+use super::DEFAULT_TEST_LENGTH;
+
+/// Four shards sending small receipts at full speed, shard 0 has its own bandwidth.
+/// Receipts are at most 100kB, so that they fit into the bandwidth of a slow shard.
+fn run_with_shard_0_bandwidth(max_bandwidth: usize) -> SimulationRun {
+    SimulationBuilder::new(4)
+        .config(SimulationConfig {
+            max_receipt_size: 100_000,
+            ..SimulationConfig::default()
+        })
+        .default_sender_factory(|_rng| {
+            Box::new(FullSpeedReceiptSender(OneSizeReceiptGenerator {
+                size: 10_000,
+            }))
+        })
+        .scheduler_params(SchedulerParams {
+            shard_bandwidth: BTreeMap::from([(
+                ShardUId::new(0),
+                ShardBandwidth::symmetric(max_bandwidth),
+            )]),
+            ..SchedulerParams::default()
+        })
+        .build()
+        .run_for(DEFAULT_TEST_LENGTH)
+}
+
+/// Most bytes sent and received by the shard in a single chunk.
+fn max_sent_and_received(simulation_run: &SimulationRun, shard: usize) -> (usize, usize) {
+    let chunks = simulation_run
+        .simulation
+        .blocks
+        .iter()
+        .flatten()
+        .filter_map(|block| block.chunks.get(&ShardUId::new(shard))?.as_ref());
+    let (mut max_sent, mut max_received) = (0, 0);
+    for chunk in chunks {
+        max_sent = max_sent.max(chunk.prev_outgoing_receipts_size.values().sum::<usize>());
+        max_received = max_received.max(chunk.prev_incoming_receipts_size);
+    }
+    (max_sent, max_received)
+}
+
+/// Shard 0 has a quarter of the usual bandwidth, the other shards use theirs for the links between them.
+#[test]
+fn slow_shard() {
+    let simulation_run = run_with_shard_0_bandwidth(MAX_SHARD_BANDWIDTH / 4);
+    let (max_sent, max_received) = max_sent_and_received(&simulation_run, 0);
+    assert!(max_sent <= MAX_SHARD_BANDWIDTH / 4, "{}", max_sent);
+    assert!(max_sent > MAX_SHARD_BANDWIDTH / 5, "{}", max_sent);
+    assert!(max_received <= MAX_SHARD_BANDWIDTH / 4, "{}", max_received);
+    let (max_sent, _max_received) = max_sent_and_received(&simulation_run, 1);
+    assert!(max_sent > MAX_SHARD_BANDWIDTH * 9 / 10, "{}", max_sent);
+
+    // The links of shard 0 get less than the others, but the theoretical throughput takes the slow shard
+    // into account, so the utilization is still high.
+    TestStats::new(&simulation_run).assert_with(StatsThresholds {
+        max_min_ratio: 6.0,
+        ..StatsThresholds::default()
+    });
+}
+
+/// Shard 0 has twice the usual bandwidth, it can send more than the other shards.
+#[test]
+fn fast_shard() {
+    let simulation_run = run_with_shard_0_bandwidth(2 * MAX_SHARD_BANDWIDTH);
+    let (max_sent, max_received) = max_sent_and_received(&simulation_run, 0);
+    assert!(max_sent > MAX_SHARD_BANDWIDTH, "{}", max_sent);
+    assert!(max_received > MAX_SHARD_BANDWIDTH, "{}", max_received);
+    assert!(max_sent <= 2 * MAX_SHARD_BANDWIDTH, "{}", max_sent);
+    let (max_sent, _max_received) = max_sent_and_received(&simulation_run, 1);
+    assert!(max_sent <= MAX_SHARD_BANDWIDTH, "{}", max_sent);
+    // The link from shard 0 to itself is the only one which can use the extra bandwidth on both ends.
+    TestStats::new(&simulation_run).assert_with(StatsThresholds {
+        max_min_ratio: 6.0,
+        ..StatsThresholds::default()
+    });
+}
+
+#[test]
+#[should_panic(expected = "doesn't fit the max receipt size")]
+fn shard_bandwidth_smaller_than_receipt() {
+    run_with_shard_0_bandwidth(50_000);
+}
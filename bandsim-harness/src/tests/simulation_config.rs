@@ -0,0 +1,100 @@
+use crate::bandwidth_scheduler::SchedulerParams;
+use crate::chain::SimulationConfig;
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{FullSpeedReceiptSender, RandomSizeReceiptGenerator};
+use crate::simulation::SimulationRun;
+use crate::validation::TestStats;
+
+use super::DEFAULT_TEST_LENGTH;
+
+/// All links send random receipts which fit in the limits of the config.
+fn run_with_config(num_shards: usize, config: SimulationConfig) -> SimulationRun {
+    SimulationBuilder::new(num_shards)
+        .default_sender_factory(move |_rng| {
+            Box::new(FullSpeedReceiptSender(RandomSizeReceiptGenerator {
+                size_range: config.min_receipt_size..=config.max_receipt_size,
+            }))
+        })
+        .config(config)
+        .scheduler_params(SchedulerParams::for_config(&config))
+        .build()
+        .run_for(DEFAULT_TEST_LENGTH)
+}
+
+/// Most bytes sent by one shard in a single chunk.
+fn max_sent_per_chunk(simulation_run: &SimulationRun) -> usize {
+    simulation_run
+        .simulation
+        .blocks
+        .iter()
+        .flatten()
+        .flat_map(|block| block.chunks.values().flatten())
+        .map(|chunk| chunk.prev_outgoing_receipts_size.values().sum::<usize>())
+        .max()
+        .unwrap()
+}
+
+#[test]
+fn smaller_shard_bandwidth() {
+    let config = SimulationConfig {
+        max_shard_bandwidth: 1_000_000,
+        max_shard_incoming_bandwidth: None,
+        min_receipt_size: 1_000,
+        max_receipt_size: 200_000,
+    };
+    let simulation_run = run_with_config(4, config);
+    let max_sent = max_sent_per_chunk(&simulation_run);
+    assert!(max_sent <= config.max_shard_bandwidth, "{}", max_sent);
+    assert!(
+        max_sent > config.max_shard_bandwidth * 9 / 10,
+        "{}",
+        max_sent
+    );
+    TestStats::new(&simulation_run).basic_assert();
+}
+
+#[test]
+fn larger_shard_bandwidth() {
+    let config = SimulationConfig {
+        max_shard_bandwidth: 9_000_000,
+        ..SimulationConfig::default()
+    };
+    let simulation_run = run_with_config(4, config);
+    let max_sent = max_sent_per_chunk(&simulation_run);
+    assert!(max_sent <= config.max_shard_bandwidth, "{}", max_sent);
+    // More than the default limit fits into a chunk.
+    assert!(
+        max_sent > SimulationConfig::default().max_shard_bandwidth,
+        "{}",
+        max_sent
+    );
+    TestStats::new(&simulation_run).basic_assert();
+}
+
+/// Setting the default config explicitly doesn't change anything.
+#[test]
+fn default_config() {
+    let explicit = run_with_config(3, SimulationConfig::default());
+    let implicit = SimulationBuilder::new(3)
+        .default_sender_factory(|_rng| {
+            let config = SimulationConfig::default();
+            Box::new(FullSpeedReceiptSender(RandomSizeReceiptGenerator {
+                size_range: config.min_receipt_size..=config.max_receipt_size,
+            }))
+        })
+        .build()
+        .run_for(DEFAULT_TEST_LENGTH);
+    assert_eq!(
+        explicit.simulation.total_granted,
+        implicit.simulation.total_granted
+    );
+}
+
+#[test]
+#[should_panic(expected = "is larger than the max shard bandwidth")]
+fn receipts_larger_than_shard_bandwidth() {
+    SimulationBuilder::new(2).config(SimulationConfig {
+        max_shard_bandwidth: 1_000_000,
+        ..SimulationConfig::default()
+    });
+}
@@ -0,0 +1,52 @@
+use crate::chain::MAX_SHARD_BANDWIDTH;
+use crate::simulation::builder::SimulationBuilder;
+use crate::validation::TestStats;
+
+use super::constant_rate_sender;
+
+/// 0 -> 1 - sends more than the link can handle
+/// The queue grows forever, the run should be marked as unstable.
+#[test]
+fn overloaded_link_is_unstable() {
+    let simulation_run = SimulationBuilder::new(2)
+        .receipt_sender(
+            0,
+            1,
+            constant_rate_sender(100_000, MAX_SHARD_BANDWIDTH * 3 / 2),
+        )
+        .build()
+        .run_for(300);
+    let stats = TestStats::new(&simulation_run);
+    assert!(stats.is_unstable);
+    assert!(stats.backlog_growth.link.unwrap().is(0, 1));
+}
+
+/// 0 -> 1 - sends half of what the link can handle
+/// Everything is sent right away, the run should be stable.
+#[test]
+fn underloaded_link_is_stable() {
+    let simulation_run = SimulationBuilder::new(2)
+        .receipt_sender(0, 1, constant_rate_sender(100_000, MAX_SHARD_BANDWIDTH / 2))
+        .build()
+        .run_for(300);
+    let stats = TestStats::new(&simulation_run);
+    // Basic assert checks that the run is stable
+    stats.basic_assert();
+    stats.assert_no_queue_growth(0.1);
+}
+
+/// 0 -> 1 - sends more than the link can handle
+/// The queue at the end is much bigger than at the start.
+#[test]
+#[should_panic(expected = "Queues grew by more than 10%")]
+fn overloaded_link_queue_grows() {
+    let simulation_run = SimulationBuilder::new(2)
+        .receipt_sender(
+            0,
+            1,
+            constant_rate_sender(100_000, MAX_SHARD_BANDWIDTH * 3 / 2),
+        )
+        .build()
+        .run_for(300);
+    TestStats::new(&simulation_run).assert_no_queue_growth(0.1);
+}
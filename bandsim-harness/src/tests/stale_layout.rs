@@ -0,0 +1,64 @@
+use crate::bandwidth_scheduler::{BandwidthScheduler, MalformedRequest, SchedulerParams};
+use crate::chain::{ShardLink, ShardUId, SimulationConfig};
+use crate::shard_layout::ShardLayout;
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{FullSpeedReceiptSender, TypicalReceiptGenerator};
+use crate::validation::{validate_scheduler_links, TotalSent};
+
+use super::link;
+
+/// Shard 1 still uses the layout from before a resharding, all of its requests target shards that don't exist.
+/// The requests are dropped, the shard can send only what it gets without requests (base bandwidth and
+/// the remaining bandwidth), while shard 2 gets the rest of shard 3's incoming bandwidth.
+#[test]
+fn requests_to_stale_layout_are_dropped() {
+    let simulation_run = SimulationBuilder::new(4)
+        .receipt_sender(1, 3, FullSpeedReceiptSender(TypicalReceiptGenerator::new()))
+        .receipt_sender(2, 3, FullSpeedReceiptSender(TypicalReceiptGenerator::new()))
+        .stale_shard_layout(1, 1)
+        .build()
+        .run_for(200);
+
+    let stale_link = ShardLink {
+        from: ShardUId::new(1),
+        to: ShardUId {
+            version: 1,
+            shard_id: 3,
+        },
+    };
+    for shard in simulation_run.simulation.shards.values() {
+        assert_eq!(
+            shard.bandwidth_scheduler.malformed_requests(),
+            &[MalformedRequest::UnknownShard(stale_link)]
+        );
+        assert!(shard
+            .bandwidth_scheduler
+            .allowances()
+            .keys()
+            .all(|link| link.to.version == 0));
+    }
+
+    let total_sent = TotalSent::new(&simulation_run);
+    let stale_sent = total_sent.sent(link(1, 3));
+    let honest_sent = total_sent.sent(link(2, 3));
+    assert!(stale_sent > 0);
+    assert!(
+        honest_sent > 2 * stale_sent,
+        "stale: {}, honest: {}",
+        stale_sent,
+        honest_sent
+    );
+}
+
+#[test]
+#[should_panic = "isn't in the shard layout"]
+fn phantom_allowance_is_detected() {
+    let mut scheduler =
+        BandwidthScheduler::new(SchedulerParams::default(), SimulationConfig::default());
+    scheduler.set_allowance(link(0, 5), 1000);
+    validate_scheduler_links(
+        &scheduler,
+        &Default::default(),
+        &ShardLayout::with_num_shards(4),
+    );
+}
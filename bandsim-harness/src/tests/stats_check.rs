@@ -0,0 +1,95 @@
+use crate::bandwidth_scheduler::Maintenance;
+use crate::chain::ReceiptPriority;
+use crate::simulation::builder::SimulationBuilder;
+use crate::validation::{LatencySlo, StatsThresholds, StatsViolation, TestStats};
+
+use super::full_speed_typical_sender;
+
+fn typical_stats() -> TestStats {
+    let simulation_run = SimulationBuilder::new(3)
+        .default_sender_factory(full_speed_typical_sender)
+        .build()
+        .run_for(200);
+    TestStats::new(&simulation_run)
+}
+
+/// Thresholds which no run can meet.
+fn impossible_thresholds() -> StatsThresholds {
+    StatsThresholds {
+        max_min_ratio: 1.0,
+        min_bandwidth_utilization: 1.0,
+        min_optimality_ratio: 1.0,
+        max_receipt_age: Some(0),
+        // Typical senders send only normal priority receipts, the SLO of an unused class isn't met.
+        latency_slos: vec![LatencySlo {
+            priority: ReceiptPriority::Low,
+            percentile: 0.99,
+            max_latency: 100,
+        }],
+        ..StatsThresholds::default()
+    }
+}
+
+#[test]
+fn check_passes() {
+    assert_eq!(typical_stats().check(StatsThresholds::default()), Ok(()));
+}
+
+/// All violated thresholds are reported, not just the first one.
+#[test]
+fn check_reports_all_violations() {
+    let stats = typical_stats();
+    let violations = stats.check(impossible_thresholds()).unwrap_err();
+    assert_eq!(
+        violations,
+        vec![
+            StatsViolation::Unfair(stats.max_min_ratio),
+            StatsViolation::LowBandwidthUtilization(stats.bandwidth_utilization),
+            StatsViolation::FarFromOptimal(stats.optimality_gap),
+            StatsViolation::ReceiptTooOld {
+                max_receipt_age: stats.max_receipt_age,
+                limit: 0,
+            },
+            StatsViolation::LatencySlo {
+                slo: impossible_thresholds().latency_slos[0],
+                measured_latency: None,
+            },
+        ]
+    );
+}
+
+#[test]
+fn assert_with_lists_all_violations() {
+    let stats = typical_stats();
+    let panic = std::panic::catch_unwind(|| stats.assert_with(impossible_thresholds()))
+        .unwrap_err()
+        .downcast::<String>()
+        .unwrap();
+    assert!(panic.starts_with("Unfair"));
+    assert!(panic.contains("\nLow bandwidth utilization"));
+    assert!(panic.contains("\nLatency SLO violated"));
+}
+
+/// Shard 1 can't receive anything during the whole run, so the link 0 -> 1 is starved.
+/// The ratio is infinite and reported as a violation instead of a panic.
+#[test]
+fn check_reports_starved_link() {
+    let simulation_run = SimulationBuilder::new(2)
+        .default_sender_factory(full_speed_typical_sender)
+        .maintenance(1, 0..300, Maintenance::NoIncoming)
+        .build()
+        .run_for(200);
+    let stats = TestStats::new(&simulation_run);
+    assert_eq!(stats.max_min_ratio.min_sent, 0);
+    assert_eq!(stats.max_min_ratio.ratio, f64::INFINITY);
+    let only_fairness = StatsThresholds {
+        min_bandwidth_utilization: 0.0,
+        min_optimality_ratio: 0.0,
+        max_receipt_age: None,
+        ..StatsThresholds::default()
+    };
+    assert_eq!(
+        stats.check(only_fairness),
+        Err(vec![StatsViolation::Unfair(stats.max_min_ratio)])
+    );
+}
@@ -0,0 +1,46 @@
+use crate::chain::MAX_SHARD_BANDWIDTH;
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{LoadPhase, OneSizeReceiptGenerator};
+use crate::validation::PhaseStats;
+
+/// 0 -> 1 - a quarter of the link capacity, then twice the link capacity, then a quarter again.
+/// 2 -> 1 - a quarter of the link capacity, all the time.
+/// The overload phase has a lot bigger queues and latencies than the others.
+#[test]
+fn step_load_phases() {
+    let phases = [
+        LoadPhase {
+            start_height: 0,
+            bytes_per_height: MAX_SHARD_BANDWIDTH / 4,
+        },
+        LoadPhase {
+            start_height: 100,
+            bytes_per_height: MAX_SHARD_BANDWIDTH * 2,
+        },
+        LoadPhase {
+            start_height: 150,
+            bytes_per_height: MAX_SHARD_BANDWIDTH / 4,
+        },
+    ];
+    let simulation_run = SimulationBuilder::new(3)
+        .step_load(0, 1, OneSizeReceiptGenerator { size: 100_000 }, &phases)
+        .step_load(
+            2,
+            1,
+            OneSizeReceiptGenerator { size: 100_000 },
+            &phases[..1],
+        )
+        .build()
+        .run_for(300);
+
+    let phase_stats = PhaseStats::for_load_phases(&simulation_run);
+    let heights: Vec<_> = phase_stats.iter().map(|p| p.heights.clone()).collect();
+    assert_eq!(heights, vec![0..100, 100..150, 150..301]);
+    let [normal, overload, after] = &phase_stats[..] else {
+        unreachable!()
+    };
+    assert!(overload.offered_per_height > overload.delivered_per_height * 1.5);
+    assert!(overload.avg_queued > normal.avg_queued * 10.0);
+    assert!(overload.mean_latency > normal.mean_latency);
+    assert!(after.delivered_per_height > after.offered_per_height);
+}
@@ -0,0 +1,67 @@
+use crate::chain::{ShardUId, MAX_SHARD_BANDWIDTH};
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{
+    ConstantRateReceiptSender, FullSpeedReceiptSender, OneSizeReceiptGenerator,
+    TypicalReceiptGenerator,
+};
+use crate::validation::{SendReceiveSymmetry, TestStats};
+
+/// 0 -> 2 and 1 -> 2 - send at full speed
+/// 2 -> 0 and 2 -> 1 - send a trickle of small receipts
+/// Shard 2 can process only half of the bandwidth, it's a net receiver with a growing incoming backlog.
+#[test]
+fn hotspot_shard_is_persistent_net_receiver() {
+    let trickle = || ConstantRateReceiptSender {
+        generator: OneSizeReceiptGenerator { size: 10_000 },
+        bytes_per_height: 100_000,
+    };
+    let simulation_run = SimulationBuilder::new(3)
+        .receipt_sender(0, 2, FullSpeedReceiptSender(TypicalReceiptGenerator::new()))
+        .receipt_sender(1, 2, FullSpeedReceiptSender(TypicalReceiptGenerator::new()))
+        .receipt_sender(2, 0, trickle())
+        .receipt_sender(2, 1, trickle())
+        .incoming_processing_limit(MAX_SHARD_BANDWIDTH / 2)
+        .build()
+        .run_for(500);
+
+    let stats = TestStats::new(&simulation_run);
+    let symmetry = &stats.send_receive_symmetry;
+    assert_eq!(symmetry.persistent_net_receivers(), vec![ShardUId::new(2)]);
+
+    let hotspot = &symmetry.shards[&ShardUId::new(2)];
+    assert!(hotspot.net_received() > 0);
+    assert!(hotspot.processed < hotspot.received);
+    for shard in [ShardUId::new(0), ShardUId::new(1)] {
+        let traffic = &symmetry.shards[&shard];
+        assert!(traffic.net_received() < 0);
+        assert_eq!(traffic.net_receiver_windows, 0);
+        assert_eq!(traffic.processed, traffic.received);
+    }
+
+    // Everything sent was received, apart from the receipts sent by the last chunks
+    let total_sent: usize = symmetry.shards.values().map(|t| t.sent).sum();
+    let total_received: usize = symmetry.shards.values().map(|t| t.received).sum();
+    assert!(total_received <= total_sent);
+    assert!(total_sent - total_received <= 3 * MAX_SHARD_BANDWIDTH);
+}
+
+/// Shards sending to each other at the same rate aren't net receivers.
+#[test]
+fn symmetric_traffic_has_no_net_receivers() {
+    let simulation_run = SimulationBuilder::new(4)
+        .default_sender_factory(|_rng| {
+            Box::new(ConstantRateReceiptSender {
+                generator: OneSizeReceiptGenerator { size: 10_000 },
+                bytes_per_height: 200_000,
+            })
+        })
+        .build()
+        .run_for(300);
+    let symmetry = SendReceiveSymmetry::new(&simulation_run, 50);
+    let num_windows = simulation_run.simulation.blocks.len().div_ceil(50);
+    assert!(symmetry.persistent_net_receivers().is_empty());
+    for traffic in symmetry.shards.values() {
+        assert_eq!(traffic.windows, num_windows);
+        assert!(traffic.net_received().abs() <= 200_000 * 4);
+    }
+}
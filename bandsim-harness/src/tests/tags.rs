@@ -0,0 +1,88 @@
+use crate::chain::MAX_SHARD_BANDWIDTH;
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{
+    BurstReceiptSender, ConstantRateReceiptSender, OneSizeReceiptGenerator, TaggedReceiptGenerator,
+};
+use crate::validation::TestStats;
+
+fn tagged_sender(
+    bytes_per_height: usize,
+    tag: &str,
+) -> ConstantRateReceiptSender<TaggedReceiptGenerator<OneSizeReceiptGenerator>> {
+    ConstantRateReceiptSender {
+        generator: TaggedReceiptGenerator::new(OneSizeReceiptGenerator { size: 100_000 }, tag),
+        bytes_per_height,
+    }
+}
+
+/// 0 -> 1 - a steady "background" workload, and a "burst" workload at heights 50..60, mixed on the same link.
+/// 2 -> 1 - untagged receipts.
+/// The stats show the two workloads separately, the burst waits a lot longer than the background traffic.
+#[test]
+fn stats_by_tag() {
+    let simulation_run = SimulationBuilder::new(3)
+        .receipt_sender(
+            0,
+            1,
+            BurstReceiptSender {
+                base: tagged_sender(MAX_SHARD_BANDWIDTH / 4, "background"),
+                burst: tagged_sender(MAX_SHARD_BANDWIDTH * 2, "burst"),
+                burst_heights: 50..60,
+            },
+        )
+        .receipt_sender(
+            2,
+            1,
+            ConstantRateReceiptSender {
+                generator: OneSizeReceiptGenerator { size: 100_000 },
+                bytes_per_height: MAX_SHARD_BANDWIDTH / 4,
+            },
+        )
+        .build()
+        .run_for(200);
+    let stats = TestStats::new(&simulation_run);
+
+    let tags: Vec<&str> = stats.tag_stats.keys().map(|tag| &**tag).collect();
+    assert_eq!(tags, vec!["background", "burst"]);
+    let background = &stats.tag_stats["background"];
+    let burst = &stats.tag_stats["burst"];
+    assert_eq!(burst.sent, MAX_SHARD_BANDWIDTH * 2 * 10);
+    assert!(background.throughput > MAX_SHARD_BANDWIDTH as f64 / 4.0 * 0.95);
+    assert!(burst.mean_latency > background.mean_latency * 2.0);
+}
+
+/// Per-application metrics measured by metric observers show up in the stats next to the built-in ones.
+#[test]
+fn custom_metric_per_tag_latency() {
+    let simulation_run = SimulationBuilder::new(2)
+        .receipt_sender(
+            0,
+            1,
+            BurstReceiptSender {
+                base: tagged_sender(MAX_SHARD_BANDWIDTH / 4, "background"),
+                burst: tagged_sender(MAX_SHARD_BANDWIDTH * 2, "burst"),
+                burst_heights: 50..60,
+            },
+        )
+        .metric_observer("burst_latency", |_block, height_metrics| {
+            let latencies = height_metrics.sent_latencies_by_tag.get("burst")?;
+            Some(latencies.mean_latency_by_bytes())
+        })
+        .metric_observer("chunks_in_block", |block, _height_metrics| {
+            Some(block.chunks.values().flatten().count() as f64)
+        })
+        .metric_observer("never_measured", |_block, _height_metrics| None)
+        .build()
+        .run_for(200);
+    let stats = TestStats::new(&simulation_run);
+
+    let names: Vec<&str> = stats.custom_metrics.keys().map(|name| &**name).collect();
+    assert_eq!(names, vec!["burst_latency", "chunks_in_block"]);
+    let burst_latency = &stats.custom_metrics["burst_latency"];
+    assert!(burst_latency.samples >= 10);
+    assert!(burst_latency.max > 1.0);
+    let chunks_in_block = &stats.custom_metrics["chunks_in_block"];
+    assert_eq!(chunks_in_block.samples, 200);
+    assert_eq!(chunks_in_block.min, 2.0);
+    assert_eq!(chunks_in_block.max, 2.0);
+}
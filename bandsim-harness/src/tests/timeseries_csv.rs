@@ -0,0 +1,52 @@
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{ConstantRateReceiptSender, OneSizeReceiptGenerator};
+
+/// 0 -> 1 - sends 100 kB per height, 20% of blocks are missing.
+#[test]
+fn timeseries_csv_has_a_row_for_every_height() {
+    let simulation_run = SimulationBuilder::new(2)
+        .receipt_sender(
+            0,
+            1,
+            ConstantRateReceiptSender {
+                generator: OneSizeReceiptGenerator { size: 10_000 },
+                bytes_per_height: 100_000,
+            },
+        )
+        .missing_block_probability(0.2)
+        .build()
+        .run_for(200);
+    let csv = simulation_run.to_timeseries_csv();
+    let mut lines = csv.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        "height,block_missing,\
+         shard_0_chunk_missing,shard_0_sent,shard_0_received,shard_0_queued,\
+         shard_1_chunk_missing,shard_1_sent,shard_1_received,shard_1_queued"
+    );
+    let rows: Vec<Vec<&str>> = lines.map(|line| line.split(',').collect()).collect();
+    assert_eq!(rows.len(), simulation_run.simulation.blocks.len());
+
+    let column = |row: &[&str], index: usize| row[index].parse::<usize>().unwrap();
+    let mut total_sent = 0;
+    for (height, row) in rows.iter().enumerate() {
+        assert_eq!(row.len(), 10);
+        assert_eq!(column(row, 0), height);
+        let block_missing = column(row, 1) == 1;
+        assert_eq!(
+            block_missing,
+            simulation_run.simulation.blocks[height].is_none()
+        );
+        if block_missing {
+            assert_eq!(column(row, 2), 1);
+            assert_eq!(row[5], "");
+            continue;
+        }
+        // Everything that shard 0 sends goes to shard 1
+        assert_eq!(column(row, 3), column(row, 8));
+        assert_eq!(column(row, 4), 0);
+        total_sent += column(row, 3);
+    }
+    assert!(rows.iter().any(|row| row[1] == "1"));
+    assert!(total_sent > 100 * 100_000);
+}
@@ -0,0 +1,33 @@
+use rand::Rng;
+
+use crate::simulation::builder::SimulationBuilder;
+use crate::validation::{StatsThresholds, TestStats, LITTLES_LAW_TOLERANCE};
+
+use super::{full_speed_typical_sender, DEFAULT_TEST_LENGTH};
+
+/// Typical case - typical receipts, a bit of missing chunks and blocks.
+#[test]
+fn typical_test() {
+    let simulation_run = SimulationBuilder::new(6)
+        .default_sender_factory(full_speed_typical_sender)
+        .missing_block_probability(0.05)
+        .missing_chunk_generator(|_, _, rng| rng.gen_bool(0.05))
+        .build()
+        .run_for(DEFAULT_TEST_LENGTH);
+    let stats = TestStats::new(&simulation_run);
+    stats.assert_with(StatsThresholds {
+        max_min_ratio: 1.20,
+        min_optimality_ratio: 0.82,
+        ..StatsThresholds::default()
+    });
+
+    // Allowance allows some short term unfairness, but it should even out over a hundred heights.
+    let fairness_100 = stats
+        .windowed_fairness
+        .iter()
+        .find(|fairness| fairness.window_size == 100)
+        .unwrap();
+    assert!(fairness_100.worst_ratio <= 1.6);
+    assert!(stats.littles_law.max_relative_error() < LITTLES_LAW_TOLERANCE);
+    stats.assert_no_queue_growth(0.1);
+}
@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::bandwidth_scheduler::{BandwidthScheduler, SchedulerParams};
+use crate::chain::{Block, Chunk, CongestionInfo, ShardUId, SimulationConfig};
+use crate::rng::rng_from_seed;
+use crate::shard_layout::ShardLayout;
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::receipt_sender::{
+    ConstantRateReceiptSender, FullSpeedReceiptSender, OneSizeReceiptGenerator,
+};
+use crate::validation::TestStats;
+
+/// 0 -> 1 - sends as much as possible
+/// 0 -> 0 - 1MB of unstoppable receipts at every height
+fn unstoppable_scenario() -> SimulationBuilder {
+    SimulationBuilder::new(2)
+        .receipt_sender(
+            0,
+            1,
+            FullSpeedReceiptSender(OneSizeReceiptGenerator { size: 50_000 }),
+        )
+        .unstoppable_receipt_sender(
+            0,
+            0,
+            ConstantRateReceiptSender {
+                generator: OneSizeReceiptGenerator { size: 100_000 },
+                bytes_per_height: 1_000_000,
+            },
+        )
+}
+
+/// The scheduler grants all of the bandwidth, there's no room left for unstoppable receipts.
+#[test]
+#[should_panic(expected = "Unstoppable receipts made shard")]
+fn unstoppable_receipts_without_reserve_exceed_limits() {
+    unstoppable_scenario().build().run_for(100);
+}
+
+/// With enough bandwidth reserved the unstoppable receipts fit within the limits.
+#[test]
+fn unstoppable_receipts_fit_in_reserve() {
+    // The max receipt size and the reserve have to fit into the shard bandwidth together.
+    let simulation_run = unstoppable_scenario()
+        .config(SimulationConfig {
+            max_receipt_size: 1_000_000,
+            ..SimulationConfig::default()
+        })
+        .scheduler_params(SchedulerParams {
+            unstoppable_reserve: 1_000_000,
+            ..SchedulerParams::default()
+        })
+        .build()
+        .run_for(200);
+    let stats = TestStats::new(&simulation_run);
+    stats.basic_assert();
+}
+
+/// The base bandwidth leaves room for the reserve, otherwise it doesn't fit into the limits
+/// of the shards and the last links don't get any.
+#[test]
+fn base_bandwidth_fits_with_reserve() {
+    let num_shards = 5;
+    let config = SimulationConfig {
+        max_receipt_size: 100_000,
+        ..SimulationConfig::default()
+    };
+    let params = SchedulerParams {
+        unstoppable_reserve: 500_000,
+        max_base_bandwidth: usize::MAX,
+        ..SchedulerParams::default()
+    };
+    params.validate(&config);
+
+    let shards: Vec<ShardUId> = (0..num_shards).map(ShardUId::new).collect();
+    let chunk = Chunk {
+        prev_incoming_receipts_size: 0,
+        prev_outgoing_receipts_size: BTreeMap::new(),
+        prev_unstoppable_receipts_size: BTreeMap::new(),
+        bandwidth_requests: Vec::new(),
+        congestion_info: CongestionInfo::default(),
+        receiver_quotas: BTreeMap::new(),
+    };
+    let block = Block {
+        height: 1,
+        shard_layout: Arc::new(ShardLayout::new(0, shards.clone())),
+        chunks: shards
+            .iter()
+            .map(|shard| (*shard, Some(chunk.clone())))
+            .collect(),
+    };
+
+    let mut scheduler = BandwidthScheduler::new(params, config);
+    let base_bandwidth = scheduler.get_base_bandwidth(num_shards);
+    let grants = scheduler.run(&block, &mut rng_from_seed(0));
+    assert_eq!(grants.len(), num_shards * num_shards);
+    let first_grant = grants.values().next().copied().unwrap();
+    assert!(first_grant >= base_bandwidth);
+    for (link, grant) in grants {
+        assert_eq!(grant, first_grant, "Link {:?} got a different grant", link);
+    }
+}
+
+/// The reserve is taken from the default bandwidth too, the max receipt size has to fit next to it.
+#[test]
+#[should_panic(
+    expected = "Max bandwidth 4500000 of the default shard doesn't fit the max receipt size 4000000 and the unstoppable reserve 1000000"
+)]
+fn reserve_doesnt_fit_default_bandwidth() {
+    unstoppable_scenario()
+        .scheduler_params(SchedulerParams {
+            unstoppable_reserve: 1_000_000,
+            ..SchedulerParams::default()
+        })
+        .build();
+}
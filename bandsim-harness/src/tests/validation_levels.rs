@@ -0,0 +1,50 @@
+use crate::chain::{MAX_SHARD_BANDWIDTH, MIN_RECEIPT_SIZE};
+use crate::simulation::builder::SimulationBuilder;
+use crate::simulation::outgoing_queue::DropPolicy;
+use crate::simulation::receipt_sender::{ConstantRateReceiptSender, RandomSizeReceiptGenerator};
+use crate::validation::{TotalSent, ValidationLevel};
+
+use super::full_speed_typical_sender;
+
+/// All links busy, missing chunks and a capped queue which drops receipts.
+fn busy_lossy_scenario(level: ValidationLevel) -> SimulationBuilder {
+    SimulationBuilder::new(4)
+        .default_sender_factory(full_speed_typical_sender)
+        .receipt_sender(
+            0,
+            1,
+            ConstantRateReceiptSender {
+                generator: RandomSizeReceiptGenerator {
+                    size_range: MIN_RECEIPT_SIZE..=1_000_000,
+                },
+                bytes_per_height: MAX_SHARD_BANDWIDTH * 3 / 2,
+            },
+        )
+        .queue_cap(10_000_000, DropPolicy::DropOldest)
+        .missing_block_probability(0.1)
+        .validation_level(level)
+}
+
+/// The paranoid checks pass when receipts are dropped and chunks are missing.
+#[test]
+fn paranoid_validation_passes() {
+    let simulation_run = busy_lossy_scenario(ValidationLevel::Paranoid)
+        .build()
+        .run_for(200);
+    let conservation = &simulation_run.simulation.queue_conservation;
+    assert_eq!(conservation.links.len(), 16);
+    assert!(conservation.links.values().any(|flow| flow.shed > 0));
+}
+
+/// The validation level doesn't change the simulation, only how much of it is checked.
+#[test]
+fn validation_level_doesnt_change_results() {
+    let off = busy_lossy_scenario(ValidationLevel::Off)
+        .build()
+        .run_for(100);
+    let paranoid = busy_lossy_scenario(ValidationLevel::Paranoid)
+        .build()
+        .run_for(100);
+    assert_eq!(TotalSent::new(&off), TotalSent::new(&paranoid));
+    assert!(off.simulation.queue_conservation.links.is_empty());
+}
@@ -0,0 +1,2532 @@
+//! Stats of finished simulation runs and the assertions that the tests make on them.
+//! The checks done while the simulation runs are in `bandsim_core::validation`, they're re-exported here.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+pub use bandsim_core::validation::*;
+
+use crate::bandwidth_request::{serialized_requests_size, AggregatedBandwidthRequests};
+use crate::bandwidth_scheduler::{ChunkSizeLimit, DenialReason, SchedulerParams};
+use crate::chain::{
+    serialized_size_map_size, Block, CongestionInfo, ReceiptPriority, ReceiptTag, ShardLink,
+    ShardUId, SimulationConfig,
+};
+use crate::optimal_throughput::optimal_throughput;
+use crate::simulation::metrics::{HeightMetrics, LatencyHistogram};
+use crate::simulation::outgoing_queue::DropPolicy;
+use crate::simulation::SimulationRun;
+
+/// How much was sent between each pair of shards.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TotalSent {
+    total_sent: BTreeMap<ShardLink, usize>,
+    pub num_blocks: usize,
+    /// Limits of the simulation, needed to estimate the theoretical throughput.
+    config: SimulationConfig,
+    /// Parameters of the scheduler, they can give some shards a different bandwidth.
+    scheduler_params: SchedulerParams,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct SentRatio {
+    pub max_link: ShardLink,
+    pub max_sent: usize,
+    pub min_link: ShardLink,
+    pub min_sent: usize,
+    pub ratio: f64,
+}
+
+/// Fairness (max sent / min sent ratio) calculated separately for every window of `window_size` consecutive heights.
+/// The allowance mechanism allows some short term unfairness to achieve better throughput, short windows show how much.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct WindowedFairness {
+    pub window_size: usize,
+    /// The worst ratio among all windows. Infinite when some link didn't send anything during a window.
+    pub worst_ratio: f64,
+    /// First height of the window with the worst ratio
+    pub worst_window_start: usize,
+    /// Median ratio among all windows
+    pub median_ratio: f64,
+}
+
+/// How many bytes the receipt senders generated on a link (offered load) and how many of them were actually
+/// sent over the link (delivered load).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LinkLoad {
+    pub offered: usize,
+    pub delivered: usize,
+    /// Offered bytes which never entered the outgoing queue because of backpressure.
+    pub shed: usize,
+}
+
+/// Offered vs delivered load on every link that has a receipt sender.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct OfferedLoad {
+    pub links: BTreeMap<ShardLink, LinkLoad>,
+}
+
+/// The oldest receipt that was observed waiting in an outgoing queue.
+/// Describes the worst case head-of-line blocking.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MaxReceiptAge {
+    /// How many heights the receipt waited in the queue
+    pub age: usize,
+    pub link: Option<ShardLink>,
+    /// Height at which the receipt had this age
+    pub height: usize,
+}
+
+/// Growth trend of the outgoing queues in the second half of the run.
+/// Under stationary load queue sizes should stay around some level. A sustained positive trend means
+/// that the system can't keep up with the load and the queues will grow forever, even when the
+/// utilization looks fine.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct BacklogGrowth {
+    /// The biggest slope of a linear regression of queue size over height (bytes per height)
+    pub max_slope: f64,
+    /// Link on which the queue grows the fastest
+    pub link: Option<ShardLink>,
+    /// Slope above which the queue is unstable, see `UNSTABLE_BACKLOG_GROWTH_RATIO`.
+    pub unstable_slope: f64,
+}
+
+/// A queue that grows by more than this fraction of the max shard bandwidth per height is considered unstable.
+pub const UNSTABLE_BACKLOG_GROWTH_RATIO: f64 = 0.01;
+
+/// Average outgoing queue sizes at the start and at the end of the run.
+/// A simpler, more direct check than `BacklogGrowth` - in a saturated, but stable scenario
+/// the queues at the end of the run shouldn't be much bigger than at the start.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct QueueGrowth {
+    pub links: BTreeMap<ShardLink, QueueGrowthLink>,
+    /// Number of bytes by which every queue can grow, see `QUEUE_GROWTH_SLACK_HEIGHTS`.
+    pub slack: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct QueueGrowthLink {
+    /// Average number of bytes in the outgoing queue in the first quarter of the run
+    pub first_quarter_avg: f64,
+    /// Average number of bytes in the outgoing queue in the last quarter of the run
+    pub last_quarter_avg: f64,
+}
+
+/// Queues can always grow by what can be sent in this many heights at the max shard bandwidth,
+/// small queues fluctuate a lot relative to their size.
+pub const QUEUE_GROWTH_SLACK_HEIGHTS: f64 = 1.0;
+
+/// Internal consistency check of the queue metrics, based on Little's law: L = λW.
+/// On every link the average queue size (L) should be equal to the throughput (λ) times the average
+/// latency (W). A big mismatch usually means that one of the metrics is calculated incorrectly.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct LittlesLawCheck {
+    pub links: BTreeMap<ShardLink, LittlesLawLink>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct LittlesLawLink {
+    /// Average number of bytes in the outgoing queue
+    pub avg_queue_size: f64,
+    /// Average number of bytes sent at a single height
+    pub throughput: f64,
+    /// Average latency of a sent byte
+    pub avg_latency: f64,
+    /// |L - λW| / max(L, λW)
+    pub relative_error: f64,
+}
+
+/// Little's law is only approximate in the simulation (queue sizes aren't measured at heights with
+/// missing blocks, the receipts still in the queue at the end don't have a latency), a relative
+/// error above this is reported as a warning.
+pub const LITTLES_LAW_TOLERANCE: f64 = 0.25;
+
+/// How long it took for the outgoing queues to go back to normal after a traffic burst.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BurstRecovery {
+    pub burst_heights: Range<usize>,
+    /// The biggest queue size on every link at the heights before the burst.
+    pub pre_burst_levels: BTreeMap<ShardLink, usize>,
+    /// The first height after the burst at which all queues were back at their pre-burst levels.
+    /// `None` when the queues didn't recover until the end of the run.
+    pub recovery_height: Option<usize>,
+}
+
+/// How long it took for the incoming backlogs to drain after some shards couldn't process
+/// incoming receipts for a while.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PartitionRecovery {
+    pub partitioned_shards: Vec<ShardUId>,
+    pub partition_heights: Range<usize>,
+    /// The biggest incoming backlog on every partitioned shard during the partition.
+    pub peak_backlogs: BTreeMap<ShardUId, usize>,
+    /// The first height after the partition at which the incoming backlogs of all partitioned
+    /// shards were empty. `None` when they didn't drain until the end of the run.
+    pub recovery_height: Option<usize>,
+}
+
+/// The point at which an increasing load saturates the network and the queues start to grow.
+/// Below the saturation point the outgoing queues hold about one height worth of new receipts,
+/// the knee is the start of the first window in which they hold more than `KNEE_BACKLOG_HEIGHTS` heights worth.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LoadKnee {
+    /// Height at which the queues started growing, `None` if they never did.
+    pub height: Option<usize>,
+    /// Average number of bytes sent at a single height, in the window starting at the knee.
+    /// The network is saturated there, so that's approximately the practical capacity of the configuration.
+    pub throughput: Option<f64>,
+}
+
+/// Outgoing queues holding more than this many heights worth of offered load are considered saturated.
+pub const KNEE_BACKLOG_HEIGHTS: f64 = 2.0;
+
+/// Chain storage taken by the bandwidth requests, see `serialized_requests_size`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RequestOverhead {
+    /// Average size of the requests in a single non-missing chunk
+    pub per_chunk_avg: f64,
+    pub per_chunk_max: usize,
+    /// Average size of the requests in all chunks of a single non-missing block
+    pub per_block_avg: f64,
+    pub per_block_max: usize,
+    /// Average size of the requests in a single block when using `AggregatedBandwidthRequests`
+    pub aggregated_per_block_avg: f64,
+}
+
+/// Scheduler related metadata carried in the chunks, compared to the receipts that were sent.
+/// All values are averages over non-missing blocks (except genesis), in bytes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProtocolOverhead {
+    pub requests_per_block: f64,
+    pub congestion_info_per_block: f64,
+    /// Maps with sizes of incoming, outgoing and unstoppable receipts
+    pub receipt_sizes_per_block: f64,
+    /// Receipts sent in a block, including the unstoppable ones
+    pub payload_per_block: f64,
+    /// All metadata divided by the payload
+    pub overhead_ratio: f64,
+}
+
+/// Stats calculated over a single phase of the run, e.g. between two load changes of a step load.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PhaseStats {
+    pub heights: Range<usize>,
+    pub total_sent: TotalSent,
+    pub bandwidth_utilization: BandwidthUtilization,
+    /// Fairness within the phase, the ratio is infinite when some link didn't send anything.
+    pub sent_ratio: SentRatio,
+    /// Average number of bytes generated by the receipt senders at a single height
+    pub offered_per_height: f64,
+    /// Average number of bytes sent at a single height
+    pub delivered_per_height: f64,
+    /// Average total size of all outgoing queues
+    pub avg_queued: f64,
+    /// Average latency of a byte sent in this phase
+    pub mean_latency: f64,
+}
+
+/// Window sizes for which fairness is calculated in `TestStats`. Fairness over the whole run is also calculated.
+pub const FAIRNESS_WINDOW_SIZES: [usize; 2] = [10, 100];
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct BandwidthUtilization {
+    pub theoretical_throughput: usize,
+    pub actual_throughput: usize,
+    pub utilization: f64,
+}
+
+/// How much of their sending and receiving capacity the shards used, averaged over the non-missing blocks.
+/// With asymmetric capacities (see `SimulationConfig::max_shard_incoming_bandwidth`) one direction
+/// can be saturated while the other one is mostly idle.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CapacityUtilization {
+    /// Bytes sent by the shard (including unstoppable receipts) divided by its outgoing capacity.
+    pub outgoing: BTreeMap<ShardUId, f64>,
+    /// Bytes sent to the shard (including unstoppable receipts) divided by its incoming capacity.
+    pub incoming: BTreeMap<ShardUId, f64>,
+    pub mean_outgoing: f64,
+    pub mean_incoming: f64,
+}
+
+impl CapacityUtilization {
+    pub fn new(simulation_run: &SimulationRun) -> CapacityUtilization {
+        let simulation = &simulation_run.simulation;
+        let mut sent: BTreeMap<ShardUId, usize> = BTreeMap::new();
+        let mut received: BTreeMap<ShardUId, usize> = BTreeMap::new();
+        let mut num_blocks = 0;
+        for block in simulation.blocks.iter().flatten() {
+            num_blocks += 1;
+            for (shard_id, chunk_opt) in &block.chunks {
+                sent.entry(*shard_id).or_default();
+                received.entry(*shard_id).or_default();
+                let Some(chunk) = chunk_opt else {
+                    continue;
+                };
+                let sent_sizes = chunk
+                    .prev_outgoing_receipts_size
+                    .iter()
+                    .chain(&chunk.prev_unstoppable_receipts_size);
+                for (to_shard, size) in sent_sizes {
+                    *sent.entry(*shard_id).or_default() += size;
+                    *received.entry(*to_shard).or_default() += size;
+                }
+            }
+        }
+
+        let params = &simulation.scheduler_params;
+        let capacity = |max_bandwidth: usize| (num_blocks.max(1) * max_bandwidth) as f64;
+        let outgoing: BTreeMap<ShardUId, f64> = sent
+            .iter()
+            .map(|(shard_id, bytes)| {
+                let max_bandwidth = params.max_outgoing_bandwidth(*shard_id, &simulation.config);
+                (*shard_id, *bytes as f64 / capacity(max_bandwidth))
+            })
+            .collect();
+        let incoming: BTreeMap<ShardUId, f64> = received
+            .iter()
+            .map(|(shard_id, bytes)| {
+                let max_bandwidth = params.max_incoming_bandwidth(*shard_id, &simulation.config);
+                (*shard_id, *bytes as f64 / capacity(max_bandwidth))
+            })
+            .collect();
+        let mean = |utilization: &BTreeMap<ShardUId, f64>| {
+            utilization.values().sum::<f64>() / utilization.len().max(1) as f64
+        };
+        CapacityUtilization {
+            mean_outgoing: mean(&outgoing),
+            mean_incoming: mean(&incoming),
+            outgoing,
+            incoming,
+        }
+    }
+}
+
+/// How much was actually sent compared to the most that could have been sent with the same demand.
+/// Unlike `BandwidthUtilization` this isn't affected by demand skew - if there's only one active link,
+/// the optimal throughput is what this one link can send.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct OptimalityGap {
+    /// Average number of bytes sent at a single height
+    pub achieved_throughput: usize,
+    /// Average number of bytes that could've been sent at a single height
+    pub optimal_throughput: usize,
+    /// achieved / optimal, aggregated over all heights. 1.0 means that nothing better was possible.
+    pub ratio: f64,
+    /// The worst achieved / optimal ratio observed at a single height
+    pub worst_height_ratio: f64,
+}
+
+impl TotalSent {
+    /// Gather information on how much was sent between each pair of shards in these blocks.
+    pub fn new(simulation_run: &SimulationRun) -> TotalSent {
+        let simulation = &simulation_run.simulation;
+        Self::for_heights(
+            simulation_run,
+            simulation.start_height..simulation.end_height(),
+        )
+    }
+
+    /// How much was sent on the link.
+    pub fn sent(&self, shard_link: ShardLink) -> usize {
+        self.total_sent.get(&shard_link).copied().unwrap_or(0)
+    }
+
+    /// Gather information on how much was sent between each pair of shards in blocks at these heights.
+    /// Links which have a receipt sender, but didn't send anything at these heights are reported as 0.
+    pub fn for_heights(simulation_run: &SimulationRun, heights: Range<usize>) -> TotalSent {
+        let blocks = simulation_run
+            .simulation
+            .blocks_at(heights)
+            .iter()
+            .flatten();
+        Self::for_blocks(simulation_run, blocks)
+    }
+
+    /// Gather information on how much was sent between each pair of shards in these blocks.
+    fn for_blocks<'a>(
+        simulation_run: &SimulationRun,
+        blocks: impl Iterator<Item = &'a Block>,
+    ) -> TotalSent {
+        let simulation = &simulation_run.simulation;
+
+        let mut total_sent = BTreeMap::new();
+        for (shard_id, shard) in &simulation.shards {
+            for to_shard in shard.receipt_senders.keys() {
+                let shard_link = ShardLink {
+                    from: *shard_id,
+                    to: *to_shard,
+                };
+                total_sent.insert(shard_link, 0);
+            }
+        }
+        for shard_link in simulation.initial_backlog.keys() {
+            total_sent.insert(*shard_link, 0);
+        }
+        let mut num_blocks = 0;
+        for block in blocks {
+            num_blocks += 1;
+
+            for (shard_id, chunk_opt) in &block.chunks {
+                let Some(chunk) = chunk_opt else {
+                    continue;
+                };
+
+                for (to_shard, sent) in &chunk.prev_outgoing_receipts_size {
+                    let shard_link = ShardLink {
+                        from: *shard_id,
+                        to: *to_shard,
+                    };
+                    *total_sent.entry(shard_link).or_insert(0) += sent;
+                }
+            }
+        }
+
+        // Links with a backlog from the initial state send it even without a receipt sender.
+        let is_active = |shard_link: &ShardLink| {
+            simulation
+                .shards
+                .get(&shard_link.from)
+                .unwrap()
+                .receipt_senders
+                .contains_key(&shard_link.to)
+                || simulation.initial_backlog.contains_key(shard_link)
+        };
+
+        // Remove results for links that didn't have any receipt senders, they mess up the metrics.
+        let mut final_result: BTreeMap<ShardLink, usize> = BTreeMap::new();
+        for (link, sent) in total_sent {
+            if is_active(&link) {
+                final_result.insert(link, sent);
+            } else {
+                assert_eq!(sent, 0);
+            }
+        }
+
+        TotalSent {
+            total_sent: final_result,
+            num_blocks,
+            config: simulation.config,
+            scheduler_params: simulation.scheduler_params.clone(),
+        }
+    }
+
+    /// Calculate the ratio between the link that sent the most and the link that sent the least.
+    /// This ratio should stay reasonable to keep things fair.
+    /// It can't be exactly fair because large receipts can't be sent at the same throughput as the
+    /// small ones, but it should stay below 2x.
+    pub fn max_min_ratio(&self) -> SentRatio {
+        let sent_ratio = self.sent_ratio();
+        if sent_ratio.min_sent == 0 {
+            panic!(
+                "TotalSent - can't calculate max/min ratio, link {:?} had 0 bytes sent",
+                sent_ratio.min_link
+            );
+        }
+        sent_ratio
+    }
+
+    /// Same as `max_min_ratio`, but returns an infinite ratio when some link didn't send anything.
+    fn sent_ratio(&self) -> SentRatio {
+        assert!(!self.total_sent.is_empty());
+
+        let mut max_link = None;
+        let mut max_sent = 0;
+
+        let mut min_link = None;
+        let mut min_sent = usize::MAX;
+
+        for (link, sent) in &self.total_sent {
+            if max_link.is_none() || *sent > max_sent {
+                max_sent = *sent;
+                max_link = Some(*link);
+            }
+            if *sent < min_sent {
+                min_sent = *sent;
+                min_link = Some(*link);
+            }
+        }
+
+        let ratio = if min_sent == 0 {
+            f64::INFINITY
+        } else {
+            max_sent as f64 / min_sent as f64
+        };
+
+        SentRatio {
+            max_link: max_link.unwrap(),
+            max_sent,
+            min_link: min_link.unwrap(),
+            min_sent,
+            ratio,
+        }
+    }
+
+    /// Calculate the ratio between the theoretic throughput when small receipts are sent on all links
+    /// and the actual number of bytes that was sent during the test.
+    /// The real utilization should be at least 50% of the theoretic one (real can be lower because of
+    /// large receipts).
+    pub fn bandwidth_utilization(&self) -> BandwidthUtilization {
+        let theoretical_throughput =
+            estimate_total_throughput(self.total_sent.keys(), &self.scheduler_params, &self.config);
+        let actual_throughput = self.total_sent.values().sum::<usize>() / self.num_blocks;
+
+        BandwidthUtilization {
+            theoretical_throughput,
+            actual_throughput,
+            utilization: actual_throughput as f64 / theoretical_throughput as f64,
+        }
+    }
+}
+
+impl MaxReceiptAge {
+    pub fn new(simulation_run: &SimulationRun) -> MaxReceiptAge {
+        let mut res = MaxReceiptAge {
+            age: 0,
+            link: None,
+            height: 0,
+        };
+        for height_metrics in &simulation_run.simulation.metrics {
+            for (link, age) in &height_metrics.oldest_receipt_age {
+                if *age > res.age {
+                    res = MaxReceiptAge {
+                        age: *age,
+                        link: Some(*link),
+                        height: height_metrics.height,
+                    };
+                }
+            }
+        }
+        res
+    }
+}
+
+impl LoadKnee {
+    pub fn new(simulation_run: &SimulationRun, window_size: usize) -> LoadKnee {
+        let metrics = &simulation_run.simulation.metrics;
+        for window in metrics.windows(window_size) {
+            let offered: usize = window.iter().flat_map(|m| m.offered.values()).sum();
+            let queued: usize = window
+                .iter()
+                .flat_map(|m| m.queued_before_send.values())
+                .sum();
+            if offered > 0 && queued as f64 > offered as f64 * KNEE_BACKLOG_HEIGHTS {
+                let sent: usize = window
+                    .iter()
+                    .flat_map(|m| m.sent_latencies.values())
+                    .map(LatencyHistogram::total_bytes)
+                    .sum();
+                return LoadKnee {
+                    height: Some(window[0].height),
+                    throughput: Some(sent as f64 / window_size as f64),
+                };
+            }
+        }
+        LoadKnee {
+            height: None,
+            throughput: None,
+        }
+    }
+}
+
+impl RequestOverhead {
+    pub fn new(simulation_run: &SimulationRun) -> RequestOverhead {
+        let mut num_chunks = 0;
+        let mut num_blocks = 0;
+        let mut total = 0;
+        let mut per_chunk_max = 0;
+        let mut per_block_max = 0;
+        let mut aggregated_total = 0;
+        // Skip genesis, it doesn't have any requests
+        for block in simulation_run.simulation.blocks.iter().skip(1).flatten() {
+            let mut block_size = 0;
+            for chunk in block.chunks.values().flatten() {
+                aggregated_total += AggregatedBandwidthRequests::serialized_size(
+                    block.shard_layout.num_shards(),
+                    chunk.bandwidth_requests.len(),
+                );
+                let chunk_size = serialized_requests_size(&chunk.bandwidth_requests);
+                per_chunk_max = per_chunk_max.max(chunk_size);
+                block_size += chunk_size;
+                num_chunks += 1;
+            }
+            per_block_max = per_block_max.max(block_size);
+            total += block_size;
+            num_blocks += 1;
+        }
+        RequestOverhead {
+            per_chunk_avg: total as f64 / num_chunks.max(1) as f64,
+            per_chunk_max,
+            per_block_avg: total as f64 / num_blocks.max(1) as f64,
+            per_block_max,
+            aggregated_per_block_avg: aggregated_total as f64 / num_blocks.max(1) as f64,
+        }
+    }
+}
+
+impl ProtocolOverhead {
+    pub fn new(simulation_run: &SimulationRun) -> ProtocolOverhead {
+        let mut num_blocks = 0;
+        let mut requests = 0;
+        let mut congestion_info = 0;
+        let mut receipt_sizes = 0;
+        let mut payload = 0;
+        for block in simulation_run.simulation.blocks.iter().skip(1).flatten() {
+            num_blocks += 1;
+            for chunk in block.chunks.values().flatten() {
+                requests += serialized_requests_size(&chunk.bandwidth_requests);
+                congestion_info += CongestionInfo::SERIALIZED_SIZE;
+                // prev_incoming_receipts_size is a single u64
+                receipt_sizes += std::mem::size_of::<u64>()
+                    + serialized_size_map_size(&chunk.prev_outgoing_receipts_size)
+                    + serialized_size_map_size(&chunk.prev_unstoppable_receipts_size);
+                payload += chunk.prev_outgoing_receipts_size.values().sum::<usize>()
+                    + chunk.prev_unstoppable_receipts_size.values().sum::<usize>();
+            }
+        }
+        let per_block = |bytes: usize| bytes as f64 / num_blocks.max(1) as f64;
+        ProtocolOverhead {
+            requests_per_block: per_block(requests),
+            congestion_info_per_block: per_block(congestion_info),
+            receipt_sizes_per_block: per_block(receipt_sizes),
+            payload_per_block: per_block(payload),
+            overhead_ratio: (requests + congestion_info + receipt_sizes) as f64
+                / payload.max(1) as f64,
+        }
+    }
+}
+
+impl PhaseStats {
+    /// Calculate the stats for blocks at these heights.
+    pub fn new(simulation_run: &SimulationRun, heights: Range<usize>) -> PhaseStats {
+        let total_sent = TotalSent::for_heights(simulation_run, heights.clone());
+        let phase_metrics: Vec<&HeightMetrics> = simulation_run
+            .simulation
+            .metrics
+            .iter()
+            .filter(|m| heights.contains(&m.height))
+            .collect();
+        let num_heights = phase_metrics.len().max(1) as f64;
+
+        let offered: usize = phase_metrics.iter().flat_map(|m| m.offered.values()).sum();
+        let queued: usize = phase_metrics
+            .iter()
+            .flat_map(|m| m.queued_before_send.values())
+            .sum();
+        let mut latencies = LatencyHistogram::default();
+        for histogram in phase_metrics.iter().flat_map(|m| m.sent_latencies.values()) {
+            latencies.merge(histogram);
+        }
+
+        PhaseStats {
+            heights,
+            bandwidth_utilization: total_sent.bandwidth_utilization(),
+            sent_ratio: total_sent.sent_ratio(),
+            offered_per_height: offered as f64 / num_heights,
+            delivered_per_height: total_sent.total_sent.values().sum::<usize>() as f64
+                / total_sent.num_blocks.max(1) as f64,
+            avg_queued: queued as f64 / num_heights,
+            mean_latency: latencies.mean_latency_by_bytes(),
+            total_sent,
+        }
+    }
+
+    /// Stats for every load phase defined in the builder (see `SimulationBuilder::step_load`).
+    /// Heights before the first phase are also reported as a separate phase.
+    pub fn for_load_phases(simulation_run: &SimulationRun) -> Vec<PhaseStats> {
+        let simulation = &simulation_run.simulation;
+        let end = simulation.end_height();
+        let mut boundaries: Vec<usize> = std::iter::once(simulation.start_height)
+            .chain(simulation.load_phase_starts.iter().copied())
+            .chain(std::iter::once(end))
+            .filter(|height| (simulation.start_height..=end).contains(height))
+            .collect();
+        boundaries.dedup();
+
+        let phases: Vec<PhaseStats> = boundaries
+            .windows(2)
+            .map(|window| PhaseStats::new(simulation_run, window[0]..window[1]))
+            .collect();
+        for phase in &phases {
+            println!(
+                "Phase {:?}: offered = {:.0} B/height, delivered = {:.0} B/height, utilization = {:.2}%, fairness = {:.2}, avg queued = {:.0} B, mean latency = {:.2}",
+                phase.heights,
+                phase.offered_per_height,
+                phase.delivered_per_height,
+                phase.bandwidth_utilization.utilization * 100.0,
+                phase.sent_ratio.ratio,
+                phase.avg_queued,
+                phase.mean_latency
+            );
+        }
+        phases
+    }
+}
+
+impl BurstRecovery {
+    /// Measure the recovery from a burst of traffic sent at `burst_heights`.
+    /// The run must have some heights before the burst to know the normal queue sizes.
+    pub fn new(simulation_run: &SimulationRun, burst_heights: Range<usize>) -> BurstRecovery {
+        let metrics = &simulation_run.simulation.metrics;
+        let mut pre_burst_levels: BTreeMap<ShardLink, usize> = BTreeMap::new();
+        for height_metrics in metrics.iter().filter(|m| m.height < burst_heights.start) {
+            for (link, queued) in &height_metrics.queued_before_send {
+                let level = pre_burst_levels.entry(*link).or_default();
+                *level = (*level).max(*queued);
+            }
+        }
+        assert!(
+            !pre_burst_levels.is_empty(),
+            "No queue measurements before the burst at {:?}",
+            burst_heights
+        );
+
+        let recovery_height = metrics
+            .iter()
+            .filter(|m| m.height >= burst_heights.end)
+            .find(|m| {
+                m.queued_before_send.iter().all(|(link, queued)| {
+                    *queued <= pre_burst_levels.get(link).copied().unwrap_or(0)
+                })
+            })
+            .map(|m| m.height);
+        BurstRecovery {
+            burst_heights,
+            pre_burst_levels,
+            recovery_height,
+        }
+    }
+
+    /// Number of heights between the end of the burst and the recovery.
+    pub fn recovery_time(&self) -> Option<usize> {
+        self.recovery_height
+            .map(|height| height - self.burst_heights.end)
+    }
+}
+
+impl PartitionRecovery {
+    /// Measure the backlog built up on `partitioned_shards` during `partition_heights`
+    /// and the time needed to drain it.
+    pub fn new(
+        simulation_run: &SimulationRun,
+        partitioned_shards: Vec<ShardUId>,
+        partition_heights: Range<usize>,
+    ) -> PartitionRecovery {
+        let backlogs_at = |block: &Block| -> Vec<Option<usize>> {
+            partitioned_shards
+                .iter()
+                .map(|shard_id| {
+                    let chunk = block.chunks.get(shard_id)?.as_ref()?;
+                    Some(chunk.congestion_info.incoming_backlog_size)
+                })
+                .collect()
+        };
+        let blocks = simulation_run.simulation.blocks.iter().flatten();
+
+        let mut peak_backlogs: BTreeMap<ShardUId, usize> = BTreeMap::new();
+        for block in blocks
+            .clone()
+            .filter(|block| partition_heights.contains(&block.height))
+        {
+            for (shard_id, backlog) in partitioned_shards.iter().zip(backlogs_at(block)) {
+                let peak = peak_backlogs.entry(*shard_id).or_default();
+                *peak = (*peak).max(backlog.unwrap_or(0));
+            }
+        }
+
+        // A missing chunk doesn't say anything about the backlog, wait for a height where all are known.
+        let recovery_height = blocks
+            .filter(|block| block.height >= partition_heights.end)
+            .find(|block| backlogs_at(block).iter().all(|backlog| *backlog == Some(0)))
+            .map(|block| block.height);
+        PartitionRecovery {
+            partitioned_shards,
+            partition_heights,
+            peak_backlogs,
+            recovery_height,
+        }
+    }
+
+    /// Total size of the incoming backlogs built up during the partition.
+    pub fn total_peak_backlog(&self) -> usize {
+        self.peak_backlogs.values().sum()
+    }
+
+    /// Number of heights between the end of the partition and the recovery.
+    pub fn recovery_time(&self) -> Option<usize> {
+        self.recovery_height
+            .map(|height| height - self.partition_heights.end)
+    }
+}
+
+impl BacklogGrowth {
+    pub fn new(simulation_run: &SimulationRun) -> BacklogGrowth {
+        let metrics = &simulation_run.simulation.metrics;
+        let second_half = &metrics[metrics.len() / 2..];
+
+        let mut queue_sizes: BTreeMap<ShardLink, Vec<(f64, f64)>> = BTreeMap::new();
+        for height_metrics in second_half {
+            for (link, queued) in &height_metrics.queued_before_send {
+                queue_sizes
+                    .entry(*link)
+                    .or_default()
+                    .push((height_metrics.height as f64, *queued as f64));
+            }
+        }
+
+        let config = &simulation_run.simulation.config;
+        let mut res = BacklogGrowth {
+            max_slope: 0.0,
+            link: None,
+            unstable_slope: config.max_shard_bandwidth as f64 * UNSTABLE_BACKLOG_GROWTH_RATIO,
+        };
+        for (link, points) in queue_sizes {
+            let slope = linear_regression_slope(&points);
+            if slope > res.max_slope {
+                res.max_slope = slope;
+                res.link = Some(link);
+            }
+        }
+        res
+    }
+
+    pub fn is_unstable(&self) -> bool {
+        self.max_slope > self.unstable_slope
+    }
+}
+
+impl QueueGrowth {
+    pub fn new(simulation_run: &SimulationRun) -> QueueGrowth {
+        let metrics = &simulation_run.simulation.metrics;
+        let quarter = metrics.len() / 4;
+        let average_queue_sizes = |heights: &[HeightMetrics]| {
+            let mut sums: BTreeMap<ShardLink, (f64, usize)> = BTreeMap::new();
+            for height_metrics in heights {
+                for (link, queued) in &height_metrics.queued_before_send {
+                    let (sum, count) = sums.entry(*link).or_default();
+                    *sum += *queued as f64;
+                    *count += 1;
+                }
+            }
+            sums.into_iter()
+                .map(|(link, (sum, count))| (link, sum / count as f64))
+                .collect::<BTreeMap<_, _>>()
+        };
+        let first_quarter = average_queue_sizes(&metrics[..quarter]);
+        let last_quarter = average_queue_sizes(&metrics[metrics.len() - quarter..]);
+
+        let links = first_quarter
+            .keys()
+            .chain(last_quarter.keys())
+            .map(|link| {
+                let growth_link = QueueGrowthLink {
+                    first_quarter_avg: first_quarter.get(link).copied().unwrap_or(0.0),
+                    last_quarter_avg: last_quarter.get(link).copied().unwrap_or(0.0),
+                };
+                (*link, growth_link)
+            })
+            .collect();
+        let config = &simulation_run.simulation.config;
+        QueueGrowth {
+            links,
+            slack: config.max_shard_bandwidth as f64 * QUEUE_GROWTH_SLACK_HEIGHTS,
+        }
+    }
+
+    /// Links on which the queue grew by more than `tolerance` (relative to the first quarter),
+    /// plus `slack` bytes.
+    pub fn growing_links(&self, tolerance: f64) -> Vec<(ShardLink, QueueGrowthLink)> {
+        self.links
+            .iter()
+            .filter(|(_, growth)| {
+                growth.last_quarter_avg > growth.first_quarter_avg * (1.0 + tolerance) + self.slack
+            })
+            .map(|(link, growth)| (*link, *growth))
+            .collect()
+    }
+}
+
+/// Slope of the least squares line fitted to the points.
+fn linear_regression_slope(points: &[(f64, f64)]) -> f64 {
+    if points.len() < 2 {
+        return 0.0;
+    }
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let covariance: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let variance: f64 = points
+        .iter()
+        .map(|(x, _)| (x - mean_x) * (x - mean_x))
+        .sum();
+    if variance == 0.0 {
+        return 0.0;
+    }
+    covariance / variance
+}
+
+impl LittlesLawCheck {
+    pub fn new(simulation_run: &SimulationRun) -> LittlesLawCheck {
+        let simulation = &simulation_run.simulation;
+        let num_heights = simulation.blocks.len().saturating_sub(1).max(1) as f64;
+
+        let mut queue_size_sums: BTreeMap<ShardLink, usize> = BTreeMap::new();
+        let mut latencies: BTreeMap<ShardLink, LatencyHistogram> = BTreeMap::new();
+        for height_metrics in &simulation.metrics {
+            for (link, queued) in &height_metrics.queued_before_send {
+                *queue_size_sums.entry(*link).or_default() += queued;
+            }
+            for (link, histogram) in &height_metrics.sent_latencies {
+                latencies.entry(*link).or_default().merge(histogram);
+            }
+        }
+
+        let mut links = BTreeMap::new();
+        for (link, histogram) in latencies {
+            let avg_queue_size =
+                queue_size_sums.get(&link).copied().unwrap_or(0) as f64 / num_heights;
+            let throughput = histogram.total_bytes() as f64 / num_heights;
+            let avg_latency = histogram.mean_latency_by_bytes();
+            let expected_queue_size = throughput * avg_latency;
+            let relative_error = (avg_queue_size - expected_queue_size).abs()
+                / avg_queue_size.max(expected_queue_size);
+            links.insert(
+                link,
+                LittlesLawLink {
+                    avg_queue_size,
+                    throughput,
+                    avg_latency,
+                    relative_error,
+                },
+            );
+        }
+        LittlesLawCheck { links }
+    }
+
+    pub fn max_relative_error(&self) -> f64 {
+        self.links
+            .values()
+            .map(|link| link.relative_error)
+            .fold(0.0, f64::max)
+    }
+
+    pub fn print_warnings(&self) {
+        for (link, check) in &self.links {
+            if check.relative_error > LITTLES_LAW_TOLERANCE {
+                println!(
+                    "WARNING: Little's law doesn't hold on {:?}, metrics might be wrong! {:?}",
+                    link, check
+                );
+            }
+        }
+    }
+}
+
+impl OfferedLoad {
+    pub fn new(simulation_run: &SimulationRun) -> OfferedLoad {
+        let simulation = &simulation_run.simulation;
+        let total_sent = TotalSent::new(simulation_run);
+
+        let mut links: BTreeMap<ShardLink, LinkLoad> = BTreeMap::new();
+        for (link, delivered) in &total_sent.total_sent {
+            links.insert(
+                *link,
+                LinkLoad {
+                    offered: 0,
+                    delivered: *delivered,
+                    shed: 0,
+                },
+            );
+        }
+        for height_metrics in &simulation.metrics {
+            for (link, offered) in &height_metrics.offered {
+                if let Some(link_load) = links.get_mut(link) {
+                    link_load.offered += offered;
+                }
+            }
+            for (link, shed) in &height_metrics.shed {
+                if let Some(link_load) = links.get_mut(link) {
+                    link_load.shed += shed;
+                }
+            }
+        }
+
+        // Everything that was offered (or was in the queue at the genesis) and not delivered
+        // must still be in the queue, or was shed.
+        for (link, link_load) in &links {
+            let queued = simulation.shards[&link.from].outgoing_queues[&link.to].total_size();
+            let initial_backlog = simulation.initial_backlog.get(link).copied().unwrap_or(0);
+            assert_eq!(
+                link_load.offered + initial_backlog,
+                link_load.delivered + queued + link_load.shed,
+                "Offered load doesn't match delivered load on {:?}",
+                link
+            );
+        }
+
+        OfferedLoad { links }
+    }
+
+    /// Total number of offered bytes on all links.
+    pub fn total_offered(&self) -> usize {
+        self.links.values().map(|link_load| link_load.offered).sum()
+    }
+
+    /// Total number of bytes shed on all links.
+    pub fn total_shed(&self) -> usize {
+        self.links.values().map(|link_load| link_load.shed).sum()
+    }
+
+    /// Fraction of the offered bytes that was shed.
+    pub fn shed_ratio(&self) -> f64 {
+        self.total_shed() as f64 / self.total_offered().max(1) as f64
+    }
+}
+
+impl WindowedFairness {
+    /// Calculate fairness for every full window of `window_size` heights.
+    /// Returns None when the simulation is shorter than one window.
+    pub fn new(simulation_run: &SimulationRun, window_size: usize) -> Option<WindowedFairness> {
+        let simulation = &simulation_run.simulation;
+        let mut window_ratios: Vec<(f64, usize)> = Vec::new();
+        let mut window_start = simulation.start_height;
+        while window_start + window_size <= simulation.end_height() {
+            let window = window_start..(window_start + window_size);
+            let window_sent = TotalSent::for_heights(simulation_run, window);
+            if window_sent.num_blocks > 0 {
+                window_ratios.push((window_sent.sent_ratio().ratio, window_start));
+            }
+            window_start += window_size;
+        }
+
+        if window_ratios.is_empty() {
+            return None;
+        }
+
+        let (worst_ratio, worst_window_start) = window_ratios
+            .iter()
+            .copied()
+            .reduce(|worst, window| if window.0 > worst.0 { window } else { worst })
+            .unwrap();
+        let mut sorted_ratios: Vec<f64> = window_ratios.iter().map(|(ratio, _)| *ratio).collect();
+        sorted_ratios.sort_by(f64::total_cmp);
+        let median_ratio = sorted_ratios[sorted_ratios.len() / 2];
+
+        Some(WindowedFairness {
+            window_size,
+            worst_ratio,
+            worst_window_start,
+            median_ratio,
+        })
+    }
+}
+
+impl OptimalityGap {
+    /// Compare the number of bytes sent at every height with the optimal throughput for the demand at that height.
+    pub fn new(simulation_run: &SimulationRun) -> OptimalityGap {
+        let simulation = &simulation_run.simulation;
+        let params = &simulation.scheduler_params;
+
+        let mut total_achieved = 0;
+        let mut total_optimal = 0;
+        let mut worst_height_ratio: f64 = 1.0;
+        let mut num_heights = 0;
+        for height_metrics in &simulation.metrics {
+            let height = height_metrics.height;
+            let index = height - simulation.start_height;
+            let block = simulation.blocks[index]
+                .as_ref()
+                .expect("Metrics are only collected for non-missing blocks");
+            // The scheduler doesn't allow sending to shards that had a missing chunk in the previous block
+            let prev_block = simulation.blocks[..index]
+                .iter()
+                .rev()
+                .flatten()
+                .next()
+                .expect("Genesis block is never missing");
+
+            let mut outgoing_limits = BTreeMap::new();
+            for (shard_id, chunk_opt) in &block.chunks {
+                if chunk_opt.is_some() {
+                    outgoing_limits.insert(
+                        *shard_id,
+                        params.max_sent_per_chunk(*shard_id, &simulation.config),
+                    );
+                }
+            }
+            let mut incoming_limits = BTreeMap::new();
+            for (shard_id, chunk_opt) in &prev_block.chunks {
+                let limit = if chunk_opt.is_some() {
+                    params.max_incoming_bandwidth(*shard_id, &simulation.config)
+                } else {
+                    0
+                };
+                incoming_limits.insert(*shard_id, limit);
+            }
+
+            let optimal = optimal_throughput(
+                &height_metrics.queued_before_send,
+                &outgoing_limits,
+                &incoming_limits,
+            );
+            let achieved: usize = block
+                .chunks
+                .values()
+                .flatten()
+                .map(|chunk| chunk.prev_outgoing_receipts_size.values().sum::<usize>())
+                .sum();
+            assert!(achieved <= optimal);
+
+            num_heights += 1;
+            total_achieved += achieved;
+            total_optimal += optimal;
+            if optimal > 0 {
+                worst_height_ratio = worst_height_ratio.min(achieved as f64 / optimal as f64);
+            }
+        }
+
+        let ratio = if total_optimal > 0 {
+            total_achieved as f64 / total_optimal as f64
+        } else {
+            1.0
+        };
+        OptimalityGap {
+            achieved_throughput: total_achieved / num_heights.max(1),
+            optimal_throughput: total_optimal / num_heights.max(1),
+            ratio,
+            worst_height_ratio,
+        }
+    }
+}
+
+/// Estimate the total throughput that can be sent over the provided links in a single block.
+/// The function simulates a scenario where small receipts are sent as fast as possible
+/// on all links.
+/// Every link is equally important, so the function grants some bandwidth
+/// on every link as long as senders/receivers have more bandwidth to spare.
+pub fn estimate_total_throughput<'a, LinksIter: Iterator<Item = &'a ShardLink> + Clone>(
+    active_links: LinksIter,
+    params: &SchedulerParams,
+    config: &SimulationConfig,
+) -> usize {
+    let mut outgoing_limits = BTreeMap::new();
+    let mut incoming_limits = BTreeMap::new();
+
+    for link in active_links.clone() {
+        outgoing_limits.insert(link.from, params.max_sent_per_chunk(link.from, config));
+        incoming_limits.insert(link.to, params.max_incoming_bandwidth(link.to, config));
+    }
+
+    let mut total = 0;
+    let grant_size = config.min_receipt_size;
+    loop {
+        let mut granted_some = false;
+        for link in active_links.clone() {
+            let outgoing_limit = outgoing_limits.get_mut(&link.from).unwrap();
+            let incoming_limit = incoming_limits.get_mut(&link.to).unwrap();
+            if *incoming_limit >= grant_size && *outgoing_limit >= grant_size {
+                total += grant_size;
+                *outgoing_limit -= grant_size;
+                *incoming_limit -= grant_size;
+                granted_some = true;
+            }
+        }
+        if !granted_some {
+            break;
+        }
+    }
+
+    total
+}
+
+/// Limits checked by `TestStats::assert_with`.
+/// Scenarios where some unfairness or inefficiency is expected should relax the thresholds
+/// instead of skipping the checks.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatsThresholds {
+    /// Maximum allowed max sent/min sent ratio
+    pub max_min_ratio: f64,
+    pub min_bandwidth_utilization: f64,
+    /// Minimum allowed achieved/optimal throughput ratio
+    pub min_optimality_ratio: f64,
+    /// Maximum number of heights that a receipt can wait in an outgoing queue
+    pub max_receipt_age: Option<usize>,
+    /// Whether the queues are allowed to grow without bounds
+    pub allow_unstable: bool,
+    /// Maximum fraction of the offered load that can be shed.
+    /// Utilization of a run that sheds load isn't comparable with a run that doesn't, so by default
+    /// nothing can be shed.
+    pub max_shed_ratio: f64,
+    /// Latency objectives of priority classes, see `LatencySlo`.
+    pub latency_slos: Vec<LatencySlo>,
+}
+
+impl Default for StatsThresholds {
+    fn default() -> Self {
+        StatsThresholds {
+            max_min_ratio: 2.15,
+            min_bandwidth_utilization: 0.49,
+            min_optimality_ratio: 0.7,
+            max_receipt_age: None,
+            allow_unstable: false,
+            max_shed_ratio: 0.0,
+            latency_slos: Vec::new(),
+        }
+    }
+}
+
+/// A threshold which the stats of a run didn't meet, see `TestStats::check`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StatsViolation {
+    /// The max sent/min sent ratio is above `max_min_ratio`.
+    Unfair(SentRatio),
+    /// Bandwidth utilization isn't above `min_bandwidth_utilization`.
+    LowBandwidthUtilization(BandwidthUtilization),
+    /// The achieved/optimal throughput ratio isn't above `min_optimality_ratio`.
+    FarFromOptimal(OptimalityGap),
+    /// A receipt waited for longer than `max_receipt_age`.
+    ReceiptTooOld {
+        max_receipt_age: MaxReceiptAge,
+        limit: usize,
+    },
+    /// The queues grow without bounds and `allow_unstable` isn't set.
+    Unstable(BacklogGrowth),
+    /// More than `max_shed_ratio` of the offered load was shed.
+    LoadShed {
+        shed_ratio: f64,
+        shed_bytes: usize,
+        limit: f64,
+    },
+    /// A latency objective wasn't met, `measured_latency` is `None` when the class didn't send anything.
+    LatencySlo {
+        slo: LatencySlo,
+        measured_latency: Option<usize>,
+    },
+}
+
+impl std::fmt::Display for StatsViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StatsViolation::Unfair(ratio) => write!(f, "Unfair: {:?}", ratio),
+            StatsViolation::LowBandwidthUtilization(utilization) => {
+                write!(f, "Low bandwidth utilization: {:?}", utilization)
+            }
+            StatsViolation::FarFromOptimal(gap) => {
+                write!(f, "Far from the optimal throughput: {:?}", gap)
+            }
+            StatsViolation::ReceiptTooOld {
+                max_receipt_age,
+                limit,
+            } => write!(
+                f,
+                "Receipt waited for too long: {:?} (limit {} heights)",
+                max_receipt_age, limit
+            ),
+            StatsViolation::Unstable(growth) => {
+                write!(f, "Queues grow without bounds: {:?}", growth)
+            }
+            StatsViolation::LoadShed {
+                shed_ratio,
+                shed_bytes,
+                limit,
+            } => write!(
+                f,
+                "Shed {:.2}% of the offered load ({} bytes), limit {:.2}%",
+                shed_ratio * 100.0,
+                shed_bytes,
+                limit * 100.0
+            ),
+            StatsViolation::LatencySlo {
+                slo,
+                measured_latency,
+            } => write!(
+                f,
+                "Latency SLO violated: {:?}, measured latency: {:?}",
+                slo, measured_latency
+            ),
+        }
+    }
+}
+
+pub struct TestStats {
+    pub total_sent: TotalSent,
+    pub max_min_ratio: SentRatio,
+    /// Fairness over short windows of `FAIRNESS_WINDOW_SIZES` heights and over the whole run (the last entry).
+    pub windowed_fairness: Vec<WindowedFairness>,
+    pub bandwidth_utilization: BandwidthUtilization,
+    /// Utilization of the outgoing and incoming capacity of the shards, measured separately.
+    pub capacity_utilization: CapacityUtilization,
+    pub optimality_gap: OptimalityGap,
+    pub offered_load: OfferedLoad,
+    pub max_receipt_age: MaxReceiptAge,
+    pub backlog_growth: BacklogGrowth,
+    pub queue_growth: QueueGrowth,
+    /// True when some queue keeps growing in the second half of the run, see `BacklogGrowth`.
+    pub is_unstable: bool,
+    /// Number of offered bytes which were shed, on all links.
+    pub shed_bytes: usize,
+    /// Fraction of the offered load that was shed.
+    pub shed_ratio: f64,
+    pub littles_law: LittlesLawCheck,
+    pub missing_chunks_ratio: f64,
+    pub missing_blocks: MissingBlockStats,
+    /// Fraction of (link, height) pairs at which the link's allowance was at the cap.
+    /// When it's high, the cap truncates the scheduler's memory of past usage.
+    pub allowance_at_max_ratio: f64,
+    /// Fraction of (link, height) pairs at which the link's allowance was zero.
+    pub allowance_at_zero_ratio: f64,
+    pub request_overhead: RequestOverhead,
+    pub protocol_overhead: ProtocolOverhead,
+    /// Throughput and latency of receipts with each tag, empty when no receipts were tagged.
+    pub tag_stats: BTreeMap<ReceiptTag, TagStats>,
+    /// Throughput and latency of receipts with each priority that was sent during the run.
+    pub priority_stats: BTreeMap<ReceiptPriority, TagStats>,
+    pub drop_stats: DropStats,
+    pub allowance_concentration: AllowanceConcentrationStats,
+    /// The largest number of per-link values that the scheduler kept between heights.
+    /// Every one of them would have to be stored in the state of every shard.
+    pub max_scheduler_state_size: usize,
+    pub send_receive_symmetry: SendReceiveSymmetry,
+    /// Stats of the custom metrics measured by metric observers, by metric name.
+    pub custom_metrics: BTreeMap<String, CustomMetricStats>,
+    pub denial_stats: DenialStats,
+    /// Stats of the traffic under the global budget, `None` when there's no budget.
+    pub global_budget: Option<GlobalBudgetStats>,
+    /// How often the chunk size limit was hit, `None` when there's no limit.
+    pub chunk_size: Option<ChunkSizeStats>,
+    /// Traffic in the first `RECOVERY_WINDOW` heights after missing chunks, `None` when no chunk was missing.
+    pub recovery_after_missing_chunks: Option<RecoveryStats>,
+    /// Traffic in the first `RECOVERY_WINDOW` heights after missing blocks, `None` when no block was missing.
+    pub recovery_after_missing_blocks: Option<RecoveryStats>,
+}
+
+/// How concentrated the allowances were in the richest 10% of links during the run.
+/// A share that stays high (or keeps growing) means that a few links perpetually out-prioritize the rest.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AllowanceConcentrationStats {
+    /// Average share of the total allowance held by the top 10% of links.
+    pub mean_top_decile_share: f64,
+    /// Average share held by the top 10% of links in the second half of the run.
+    /// Higher than the overall average when the allowances drift towards the rich links.
+    pub second_half_mean_top_decile_share: f64,
+    /// The highest share held by the top 10% of links at any height.
+    pub max_top_decile_share: f64,
+}
+
+impl AllowanceConcentrationStats {
+    pub fn new(simulation_run: &SimulationRun) -> AllowanceConcentrationStats {
+        let metrics = &simulation_run.simulation.metrics;
+        let mean_share = |metrics: &[HeightMetrics]| {
+            let shares: Vec<f64> = metrics
+                .iter()
+                .filter_map(|m| m.allowance_concentration.top_decile_share())
+                .collect();
+            shares.iter().sum::<f64>() / shares.len().max(1) as f64
+        };
+        AllowanceConcentrationStats {
+            mean_top_decile_share: mean_share(metrics),
+            second_half_mean_top_decile_share: mean_share(&metrics[metrics.len() / 2..]),
+            max_top_decile_share: metrics
+                .iter()
+                .filter_map(|m| m.allowance_concentration.top_decile_share())
+                .fold(0.0, f64::max),
+        }
+    }
+}
+
+/// Number of heights in a window of `SendReceiveSymmetry` used by `TestStats`.
+pub const SYMMETRY_WINDOW_SIZE: usize = 100;
+
+/// A shard which received more than it sent in at least this fraction of the windows is a persistent net receiver.
+pub const PERSISTENT_NET_RECEIVER_RATIO: f64 = 0.9;
+
+/// Bytes sent, received and processed by every shard during the run.
+/// Bandwidth moves the work around - a shard which keeps receiving more than it sends has to execute
+/// the receipts of other shards and can become an execution hotspot.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SendReceiveSymmetry {
+    pub window_size: usize,
+    pub shards: BTreeMap<ShardUId, ShardTraffic>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ShardTraffic {
+    /// Bytes of receipts (also unstoppable ones) sent to all shards, including itself.
+    pub sent: usize,
+    /// Bytes of receipts received from all shards, including itself.
+    pub received: usize,
+    /// Bytes of received receipts which were processed, the rest is still in the incoming backlog.
+    pub processed: usize,
+    /// Number of windows in which the shard received more than it sent.
+    pub net_receiver_windows: usize,
+    pub windows: usize,
+}
+
+impl ShardTraffic {
+    /// Received minus sent, positive for shards which receive more work than they hand out.
+    pub fn net_received(&self) -> i64 {
+        self.received as i64 - self.sent as i64
+    }
+
+    /// Fraction of the windows in which the shard was a net receiver.
+    pub fn net_receiver_ratio(&self) -> f64 {
+        self.net_receiver_windows as f64 / self.windows.max(1) as f64
+    }
+}
+
+impl SendReceiveSymmetry {
+    /// The run is split into windows of `window_size` heights (the last one can be shorter)
+    /// to tell apart shards that are net receivers all the time from ones that received a single burst.
+    pub fn new(simulation_run: &SimulationRun, window_size: usize) -> SendReceiveSymmetry {
+        assert!(window_size > 0, "Window size must be positive!");
+        let mut shards: BTreeMap<ShardUId, ShardTraffic> = BTreeMap::new();
+        // Received minus sent in the current window
+        let mut window_net: BTreeMap<ShardUId, i64> = BTreeMap::new();
+        // Incoming backlog reported by the previous chunk of every shard
+        let mut prev_backlogs: BTreeMap<ShardUId, usize> = BTreeMap::new();
+        let blocks = &simulation_run.simulation.blocks;
+        for (window_idx, window) in blocks.chunks(window_size).enumerate() {
+            window_net.clear();
+            for block in window.iter().flatten() {
+                for (shard_id, chunk) in block
+                    .chunks
+                    .iter()
+                    .filter_map(|(shard_id, chunk_opt)| Some((shard_id, chunk_opt.as_ref()?)))
+                {
+                    let sent: usize = chunk.prev_outgoing_receipts_size.values().sum::<usize>()
+                        + chunk.prev_unstoppable_receipts_size.values().sum::<usize>();
+                    let received = chunk.prev_incoming_receipts_size;
+                    let backlog = chunk.congestion_info.incoming_backlog_size;
+                    let prev_backlog = prev_backlogs.insert(*shard_id, backlog).unwrap_or(0);
+
+                    let traffic = shards.entry(*shard_id).or_default();
+                    traffic.sent += sent;
+                    traffic.received += received;
+                    traffic.processed += prev_backlog + received - backlog;
+                    *window_net.entry(*shard_id).or_default() += received as i64 - sent as i64;
+                }
+            }
+            for (shard_id, traffic) in shards.iter_mut() {
+                traffic.windows = window_idx + 1;
+                if window_net.get(shard_id).is_some_and(|net| *net > 0) {
+                    traffic.net_receiver_windows += 1;
+                }
+            }
+        }
+        SendReceiveSymmetry {
+            window_size,
+            shards,
+        }
+    }
+
+    /// Shards which were net receivers in at least `PERSISTENT_NET_RECEIVER_RATIO` of the windows.
+    pub fn persistent_net_receivers(&self) -> Vec<ShardUId> {
+        self.shards
+            .iter()
+            .filter(|(_shard_id, traffic)| {
+                traffic.net_receiver_ratio() >= PERSISTENT_NET_RECEIVER_RATIO
+            })
+            .map(|(shard_id, _traffic)| *shard_id)
+            .collect()
+    }
+
+    pub fn print(&self) {
+        println!("Sent vs received vs processed:");
+        let persistent = self.persistent_net_receivers();
+        for (shard_id, traffic) in &self.shards {
+            println!(
+                "{:?}: sent = {}, received = {}, processed = {}, net received = {}, net receiver in {:.2}% of {}-height windows{}",
+                shard_id,
+                traffic.sent,
+                traffic.received,
+                traffic.processed,
+                traffic.net_received(),
+                traffic.net_receiver_ratio() * 100.0,
+                self.window_size,
+                if persistent.contains(shard_id) {
+                    "  PERSISTENT NET RECEIVER"
+                } else {
+                    ""
+                }
+            );
+        }
+    }
+}
+
+/// Shades of the heatmap cells, from an idle link to a link that uses all of the shard's bandwidth.
+const HEATMAP_SHADES: [char; 10] = [' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'];
+
+/// Average utilization of every link over a range of heights. Utilization is the number of bytes sent
+/// on the link at a height (including unstoppable receipts) divided by the max shard bandwidth.
+/// Rendered as an N x N grid it shows skew between links that aggregated ratios hide.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinkUtilization {
+    pub heights: Range<usize>,
+    pub shards: Vec<ShardUId>,
+    /// Average utilization of every link between `shards`, including links without any traffic.
+    pub utilization: BTreeMap<ShardLink, f64>,
+}
+
+impl LinkUtilization {
+    /// Averages are taken over non-missing blocks at these heights.
+    pub fn new(simulation_run: &SimulationRun, heights: Range<usize>) -> LinkUtilization {
+        let simulation = &simulation_run.simulation;
+        let shards: Vec<ShardUId> = simulation.shards.keys().copied().collect();
+        let mut sent: BTreeMap<ShardLink, usize> = BTreeMap::new();
+        let mut num_blocks = 0;
+        for block in simulation.blocks_at(heights.clone()).iter().flatten() {
+            num_blocks += 1;
+            for (shard_id, chunk_opt) in &block.chunks {
+                let Some(chunk) = chunk_opt else {
+                    continue;
+                };
+                let sent_sizes = chunk
+                    .prev_outgoing_receipts_size
+                    .iter()
+                    .chain(&chunk.prev_unstoppable_receipts_size);
+                for (to_shard, size) in sent_sizes {
+                    let shard_link = ShardLink {
+                        from: *shard_id,
+                        to: *to_shard,
+                    };
+                    *sent.entry(shard_link).or_default() += size;
+                }
+            }
+        }
+
+        let mut utilization = BTreeMap::new();
+        for from in &shards {
+            for to in &shards {
+                let shard_link = ShardLink {
+                    from: *from,
+                    to: *to,
+                };
+                let link_sent = sent.get(&shard_link).copied().unwrap_or(0);
+                let link_utilization = link_sent as f64
+                    / (num_blocks.max(1) * simulation.config.max_shard_bandwidth) as f64;
+                utilization.insert(shard_link, link_utilization);
+            }
+        }
+        LinkUtilization {
+            heights,
+            shards,
+            utilization,
+        }
+    }
+
+    /// Text heatmap with a row for every sending shard and a column for every receiving shard.
+    /// Every cell is two characters wide, the denser the shade the higher the utilization.
+    /// Column headers show the last digit of the receiving shard's id.
+    pub fn to_text_grid(&self) -> String {
+        let label_width = self
+            .shards
+            .iter()
+            .map(|shard| shard.shard_id.to_string().len())
+            .max()
+            .unwrap_or(0);
+        let mut res = format!(
+            "Link utilization at heights {}..{} (rows: sender, columns: receiver)\n",
+            self.heights.start, self.heights.end
+        );
+        res.push_str(&" ".repeat(label_width + 1));
+        for to in &self.shards {
+            res.push_str(&format!("{:>2}", to.shard_id % 10));
+        }
+        res.push('\n');
+        for from in &self.shards {
+            res.push_str(&format!("{:>w$} ", from.shard_id, w = label_width));
+            for to in &self.shards {
+                let link_utilization = self.utilization[&ShardLink {
+                    from: *from,
+                    to: *to,
+                }];
+                let shade = heatmap_shade(link_utilization);
+                res.push(shade);
+                res.push(shade);
+            }
+            res.push('\n');
+        }
+        let scale: Vec<String> = HEATMAP_SHADES
+            .iter()
+            .enumerate()
+            .map(|(i, shade)| format!("'{}' >= {}%", shade, i * 10))
+            .collect();
+        res.push_str(&format!("Scale: {}\n", scale.join(", ")));
+        res
+    }
+
+    pub fn print_heatmap(&self) {
+        print!("{}", self.to_text_grid());
+    }
+}
+
+/// Shade of a heatmap cell, every shade covers 10% of the utilization range.
+fn heatmap_shade(utilization: f64) -> char {
+    let idx = (utilization * HEATMAP_SHADES.len() as f64) as usize;
+    HEATMAP_SHADES[idx.min(HEATMAP_SHADES.len() - 1)]
+}
+
+/// Aggregated values of a custom metric, measured by a metric observer (see `SimulationBuilder::metric_observer`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct CustomMetricStats {
+    /// Number of heights at which the metric was measured.
+    pub samples: usize,
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Value measured at the last height with a measurement.
+    pub last: f64,
+}
+
+impl CustomMetricStats {
+    /// Stats of every custom metric that was measured at least once.
+    pub fn for_all_metrics(simulation_run: &SimulationRun) -> BTreeMap<String, CustomMetricStats> {
+        let mut values: BTreeMap<&String, Vec<f64>> = BTreeMap::new();
+        for height_metrics in &simulation_run.simulation.metrics {
+            for (name, value) in &height_metrics.custom {
+                values.entry(name).or_default().push(*value);
+            }
+        }
+        values
+            .into_iter()
+            .map(|(name, values)| {
+                let stats = CustomMetricStats {
+                    samples: values.len(),
+                    mean: values.iter().sum::<f64>() / values.len() as f64,
+                    min: values.iter().copied().fold(f64::INFINITY, f64::min),
+                    max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                    last: *values.last().unwrap(),
+                };
+                (name.clone(), stats)
+            })
+            .collect()
+    }
+}
+
+/// Bandwidth increases denied by the scheduler during the run, by the reason of the denial.
+/// Points at the bottleneck: shards that keep running out of incoming bandwidth limit the receivers,
+/// shards that run out of outgoing bandwidth limit the senders.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DenialStats {
+    pub total: usize,
+    pub by_reason: BTreeMap<DenialReason, usize>,
+    /// Denials caused by the receiving shard's limit (alone or together with the sender's) or its quota,
+    /// by receiving shard.
+    pub receiver_limited: BTreeMap<ShardUId, usize>,
+    /// Denials caused by the sending shard's limit (alone or together with the receiver's), by sending shard.
+    pub sender_limited: BTreeMap<ShardUId, usize>,
+}
+
+impl DenialStats {
+    pub fn new(simulation_run: &SimulationRun) -> DenialStats {
+        let mut stats = DenialStats {
+            total: 0,
+            by_reason: BTreeMap::new(),
+            receiver_limited: BTreeMap::new(),
+            sender_limited: BTreeMap::new(),
+        };
+        for height_metrics in &simulation_run.simulation.metrics {
+            for denial in &height_metrics.denials {
+                stats.total += 1;
+                *stats.by_reason.entry(denial.reason).or_default() += 1;
+                if denial.reason != DenialReason::SenderLimit {
+                    *stats.receiver_limited.entry(denial.link.to).or_default() += 1;
+                }
+                if matches!(
+                    denial.reason,
+                    DenialReason::SenderLimit | DenialReason::BothLimits
+                ) {
+                    *stats.sender_limited.entry(denial.link.from).or_default() += 1;
+                }
+            }
+        }
+        stats
+    }
+
+    /// Fraction of the denials with this reason.
+    pub fn share(&self, reason: DenialReason) -> f64 {
+        self.by_reason.get(&reason).copied().unwrap_or(0) as f64 / self.total.max(1) as f64
+    }
+
+    /// The shard and side which caused the most denials, with the fraction of all denials that it caused.
+    /// `DenialReason::ReceiverLimit` means that the shard ran out of incoming bandwidth,
+    /// `DenialReason::SenderLimit` that it ran out of outgoing bandwidth.
+    pub fn bottleneck(&self) -> Option<(ShardUId, DenialReason, f64)> {
+        let receivers = self
+            .receiver_limited
+            .iter()
+            .map(|(shard_id, count)| (*shard_id, DenialReason::ReceiverLimit, *count));
+        let senders = self
+            .sender_limited
+            .iter()
+            .map(|(shard_id, count)| (*shard_id, DenialReason::SenderLimit, *count));
+        receivers
+            .chain(senders)
+            .max_by_key(|(_shard_id, _reason, count)| *count)
+            .map(|(shard_id, reason, count)| (shard_id, reason, count as f64 / self.total as f64))
+    }
+}
+
+/// Traffic under the global budget, see `SchedulerParams::global_budget`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GlobalBudgetStats {
+    pub budget: usize,
+    /// Number of heights at which the grants were scaled down to fit into the budget.
+    pub capped_heights: usize,
+    /// Fraction of the scheduler runs at which the grants were scaled down.
+    pub capped_ratio: f64,
+    /// The most bytes sent on all links in a single block.
+    pub max_sent_per_block: usize,
+    /// Average number of bytes sent in a block divided by the budget.
+    pub mean_budget_usage: f64,
+    /// Fairness of the bytes sent with grants that were scaled down, `None` when the budget was never hit.
+    /// Proportional scaling keeps the shares of the links, so it shouldn't be much worse than without the cap.
+    pub capped_fairness: Option<SentRatio>,
+}
+
+impl GlobalBudgetStats {
+    /// `None` when the simulation doesn't have a global budget.
+    pub fn new(simulation_run: &SimulationRun) -> Option<GlobalBudgetStats> {
+        let simulation = &simulation_run.simulation;
+        let budget = simulation.scheduler_params.global_budget?;
+
+        let capped: BTreeSet<usize> = simulation
+            .metrics
+            .iter()
+            .filter(|height_metrics| height_metrics.over_budget.is_some())
+            .map(|height_metrics| height_metrics.height)
+            .collect();
+
+        // Chunks send receipts with the grants computed at the previous block.
+        let mut capped_blocks = Vec::new();
+        let mut max_sent_per_block = 0;
+        let mut total_sent = 0;
+        let mut num_blocks = 0;
+        let mut prev_height = None;
+        for block in simulation.blocks.iter().flatten() {
+            let sent: usize = block
+                .chunks
+                .values()
+                .flatten()
+                .flat_map(|chunk| chunk.prev_outgoing_receipts_size.values())
+                .sum();
+            max_sent_per_block = max_sent_per_block.max(sent);
+            total_sent += sent;
+            num_blocks += 1;
+            if prev_height.is_some_and(|height| capped.contains(&height)) {
+                capped_blocks.push(block);
+            }
+            prev_height = Some(block.height);
+        }
+
+        let capped_fairness = if capped_blocks.is_empty() {
+            None
+        } else {
+            Some(TotalSent::for_blocks(simulation_run, capped_blocks.into_iter()).sent_ratio())
+        };
+        Some(GlobalBudgetStats {
+            budget,
+            capped_heights: capped.len(),
+            capped_ratio: capped.len() as f64 / simulation.metrics.len().max(1) as f64,
+            max_sent_per_block,
+            mean_budget_usage: total_sent as f64 / num_blocks.max(1) as f64 / budget as f64,
+            capped_fairness,
+        })
+    }
+}
+
+/// How often the size of the chunk rather than the grants limited the receipts that a shard could send,
+/// see `SchedulerParams::chunk_size_limit`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChunkSizeStats {
+    pub limit: ChunkSizeLimit,
+    /// Number of chunks produced during the run.
+    pub chunks: usize,
+    /// Fraction of the chunks which left a receipt in the queue because the chunk was full.
+    pub chunk_size_bound_ratio: f64,
+    /// Fraction of the chunks which weren't full, but left a receipt in the queue because the grant
+    /// of its link was too small.
+    pub bandwidth_bound_ratio: f64,
+    /// Average size of the receipts sent in a chunk divided by the space for receipts.
+    pub mean_space_usage: f64,
+}
+
+impl ChunkSizeStats {
+    /// `None` when the simulation doesn't have a chunk size limit.
+    pub fn new(simulation_run: &SimulationRun) -> Option<ChunkSizeStats> {
+        let simulation = &simulation_run.simulation;
+        let limit = simulation.scheduler_params.chunk_size_limit?;
+
+        let mut chunks = 0;
+        let mut total_sent = 0;
+        for chunk in simulation
+            .blocks
+            .iter()
+            .flatten()
+            .flat_map(|block| block.chunks.values().flatten())
+        {
+            chunks += 1;
+            total_sent += chunk.prev_outgoing_receipts_size.values().sum::<usize>()
+                + chunk.prev_unstoppable_receipts_size.values().sum::<usize>();
+        }
+        let chunk_size_bound: usize = simulation
+            .metrics
+            .iter()
+            .map(|height_metrics| height_metrics.chunk_size_bound.len())
+            .sum();
+        let bandwidth_bound: usize = simulation
+            .metrics
+            .iter()
+            .map(|height_metrics| height_metrics.bandwidth_bound.len())
+            .sum();
+        Some(ChunkSizeStats {
+            limit,
+            chunks,
+            chunk_size_bound_ratio: chunk_size_bound as f64 / chunks.max(1) as f64,
+            bandwidth_bound_ratio: bandwidth_bound as f64 / chunks.max(1) as f64,
+            mean_space_usage: total_sent as f64
+                / chunks.max(1) as f64
+                / limit.receipts_space().max(1) as f64,
+        })
+    }
+}
+
+/// Receipts dropped from over-full outgoing queues, on all links.
+/// Allows to compare the damage done by different drop policies in the same overload scenario.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DropStats {
+    /// Drop policy used in the run, `None` when the queues weren't capped.
+    pub policy: Option<DropPolicy>,
+    pub dropped_receipts: usize,
+    pub dropped_bytes: usize,
+    /// Average number of heights that a dropped byte waited in the queue before it was dropped.
+    /// This time is wasted, the receipt never gets delivered.
+    pub mean_dropped_age: f64,
+    pub max_dropped_age: usize,
+}
+
+impl DropStats {
+    pub fn new(simulation_run: &SimulationRun) -> DropStats {
+        let simulation = &simulation_run.simulation;
+        let mut dropped = LatencyHistogram::default();
+        for height_metrics in &simulation.metrics {
+            for histogram in height_metrics.dropped.values() {
+                dropped.merge(histogram);
+            }
+        }
+        DropStats {
+            policy: simulation
+                .shards
+                .values()
+                .find_map(|shard| shard.queue_cap)
+                .map(|queue_cap| queue_cap.policy),
+            dropped_receipts: dropped.total_receipts(),
+            dropped_bytes: dropped.total_bytes(),
+            mean_dropped_age: dropped.mean_latency_by_bytes(),
+            max_dropped_age: dropped.buckets.keys().last().copied().unwrap_or(0),
+        }
+    }
+}
+
+/// Number of heights after an outage that `TestStats` counts as recovery.
+pub const RECOVERY_WINDOW: usize = 10;
+
+/// Kind of outage after which `RecoveryStats` look at the recovery.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutageKind {
+    /// A run of consecutive missing blocks. Nothing is sent during it, all links are affected.
+    MissingBlocks,
+    /// A run of consecutive missing chunks on one shard. Affects the links from and to this shard.
+    MissingChunks,
+}
+
+/// Traffic on the links during one phase of the run, counted over (link, height) pairs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RecoveryPhaseStats {
+    /// Number of (link, height) pairs in the phase.
+    pub samples: usize,
+    /// Total number of bytes sent.
+    pub sent: usize,
+    /// Sum of the grants given to the links at these heights, `None` when the simulation doesn't record grant history.
+    pub granted: Option<usize>,
+    /// Latencies of all receipts sent during the phase.
+    pub latencies: LatencyHistogram,
+}
+
+impl RecoveryPhaseStats {
+    fn add_sample(&mut self, height_metrics: &HeightMetrics, link: ShardLink) {
+        self.samples += 1;
+        if let Some(latencies) = height_metrics.sent_latencies.get(&link) {
+            self.sent += latencies.total_bytes();
+            self.latencies.merge(latencies);
+        }
+        if let Some(granted) = &mut self.granted {
+            *granted += height_metrics.grants.get(&link).copied().unwrap_or(0);
+        }
+    }
+
+    /// Average number of bytes sent on a link at a single height.
+    pub fn mean_sent(&self) -> f64 {
+        self.sent as f64 / self.samples.max(1) as f64
+    }
+
+    /// Average grant of a link at a single height.
+    pub fn mean_grant(&self) -> Option<f64> {
+        self.granted
+            .map(|granted| granted as f64 / self.samples.max(1) as f64)
+    }
+}
+
+/// Behaviour of the scheduler in the first `window` heights after every outage, separated from the steady state.
+/// After an outage the links catch up on the receipts that piled up in the meantime - they should send more than
+/// usual, while the latency of the delayed receipts spikes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecoveryStats {
+    pub kind: OutageKind,
+    pub window: usize,
+    /// Number of outages in the run.
+    pub episodes: usize,
+    /// The affected links in the first `window` heights with a block (and chunks) after an outage.
+    /// Windows of outages that follow each other closely overlap, a height is counted only once.
+    pub recovery: RecoveryPhaseStats,
+    /// All other links and heights, except for the outages themselves.
+    pub steady: RecoveryPhaseStats,
+}
+
+impl RecoveryStats {
+    /// `None` when there were no outages of this kind.
+    pub fn new(
+        simulation_run: &SimulationRun,
+        kind: OutageKind,
+        window: usize,
+    ) -> Option<RecoveryStats> {
+        let simulation = &simulation_run.simulation;
+        let metrics: BTreeMap<usize, &HeightMetrics> = simulation
+            .metrics
+            .iter()
+            .map(|height_metrics| (height_metrics.height, height_metrics))
+            .collect();
+        let phase = || RecoveryPhaseStats {
+            granted: simulation.record_grant_history.then_some(0),
+            ..RecoveryPhaseStats::default()
+        };
+        let mut recovery = phase();
+        let mut steady = phase();
+        let mut episodes = 0;
+        // Per shard for missing chunks, a single entry for missing blocks.
+        let mut in_outage: BTreeMap<Option<ShardUId>, bool> = BTreeMap::new();
+        let mut remaining_window: BTreeMap<Option<ShardUId>, usize> = BTreeMap::new();
+
+        for block_opt in simulation.blocks.iter().skip(1) {
+            let Some(block) = block_opt else {
+                if kind == OutageKind::MissingBlocks {
+                    in_outage.insert(None, true);
+                }
+                continue;
+            };
+            let Some(height_metrics) = metrics.get(&block.height) else {
+                continue;
+            };
+
+            let mut recovering: BTreeSet<Option<ShardUId>> = BTreeSet::new();
+            let mut update = |key: Option<ShardUId>, missing: bool| {
+                if missing {
+                    in_outage.insert(key, true);
+                    remaining_window.insert(key, 0);
+                    return;
+                }
+                if in_outage.insert(key, false) == Some(true) {
+                    episodes += 1;
+                    remaining_window.insert(key, window);
+                }
+                let remaining = remaining_window.entry(key).or_insert(0);
+                if *remaining > 0 {
+                    *remaining -= 1;
+                    recovering.insert(key);
+                }
+            };
+            match kind {
+                OutageKind::MissingBlocks => update(None, false),
+                OutageKind::MissingChunks => {
+                    for (shard_id, chunk_opt) in &block.chunks {
+                        update(Some(*shard_id), chunk_opt.is_none());
+                    }
+                }
+            }
+
+            for (from, from_chunk) in &block.chunks {
+                for (to, to_chunk) in &block.chunks {
+                    if from_chunk.is_none() || to_chunk.is_none() {
+                        continue;
+                    }
+                    let link = ShardLink {
+                        from: *from,
+                        to: *to,
+                    };
+                    let is_recovering = recovering.contains(&None)
+                        || recovering.contains(&Some(*from))
+                        || recovering.contains(&Some(*to));
+                    if is_recovering {
+                        recovery.add_sample(height_metrics, link);
+                    } else {
+                        steady.add_sample(height_metrics, link);
+                    }
+                }
+            }
+        }
+
+        if episodes == 0 {
+            return None;
+        }
+        Some(RecoveryStats {
+            kind,
+            window,
+            episodes,
+            recovery,
+            steady,
+        })
+    }
+
+    /// Bytes sent during the recovery on top of what the links would send in the steady state.
+    pub fn catch_up_bytes(&self) -> f64 {
+        (self.recovery.mean_sent() - self.steady.mean_sent()) * self.recovery.samples as f64
+    }
+
+    /// How much longer the p99 latency was during the recovery than in the steady state.
+    pub fn p99_latency_spike(&self) -> isize {
+        let p99 =
+            |phase: &RecoveryPhaseStats| phase.latencies.percentile(0.99).unwrap_or(0) as isize;
+        p99(&self.recovery) - p99(&self.steady)
+    }
+
+    pub fn print(&self) {
+        let describe = |phase: &RecoveryPhaseStats| {
+            let grant = match phase.mean_grant() {
+                Some(mean_grant) => format!(", mean grant = {:.0}", mean_grant),
+                None => String::new(),
+            };
+            format!(
+                "mean sent = {:.0}{}, p99 latency = {}, max latency = {}",
+                phase.mean_sent(),
+                grant,
+                phase.latencies.percentile(0.99).unwrap_or(0),
+                phase.latencies.buckets.keys().last().copied().unwrap_or(0)
+            )
+        };
+        println!(
+            "  recovery after {} episodes of {:?} ({} heights): {}",
+            self.episodes,
+            self.kind,
+            self.window,
+            describe(&self.recovery)
+        );
+        println!("    steady state: {}", describe(&self.steady));
+    }
+}
+
+/// Stats of all receipts with the same tag (or the same priority), across all links.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TagStats {
+    /// Total number of bytes sent
+    pub sent: usize,
+    /// Average number of bytes sent at a single height
+    pub throughput: f64,
+    /// Average latency of a sent byte
+    pub mean_latency: f64,
+    /// The longest time that a receipt with this tag waited in the outgoing queue
+    pub max_latency: usize,
+    /// Latencies of all sent receipts, allows to calculate percentiles.
+    pub latencies: LatencyHistogram,
+}
+
+/// Service level objective for the latency of one priority class, e.g. "99% of high priority
+/// receipts are sent within 3 heights". Checked in `TestStats::assert_latency_slo`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LatencySlo {
+    pub priority: ReceiptPriority,
+    /// Fraction of the receipts that have to meet the objective, e.g. 0.99 for p99 latency.
+    pub percentile: f64,
+    pub max_latency: usize,
+}
+
+impl LatencySlo {
+    /// Latency of the priority class at the SLO percentile, `None` when no receipt of this priority was sent.
+    pub fn measured_latency(&self, stats: &TestStats) -> Option<usize> {
+        stats
+            .priority_stats
+            .get(&self.priority)
+            .and_then(|priority_stats| priority_stats.latencies.percentile(self.percentile))
+    }
+
+    /// The SLO is met when the latency at the percentile is within the limit.
+    /// A class which didn't send anything doesn't meet the SLO.
+    pub fn is_met_by(&self, stats: &TestStats) -> bool {
+        self.measured_latency(stats)
+            .is_some_and(|latency| latency <= self.max_latency)
+    }
+}
+
+impl TagStats {
+    pub fn for_all_tags(simulation_run: &SimulationRun) -> BTreeMap<ReceiptTag, TagStats> {
+        Self::for_all_classes(simulation_run, |height_metrics| {
+            &height_metrics.sent_latencies_by_tag
+        })
+    }
+
+    /// Stats of receipts with each priority, the same as stats of tags.
+    pub fn for_all_priorities(
+        simulation_run: &SimulationRun,
+    ) -> BTreeMap<ReceiptPriority, TagStats> {
+        Self::for_all_classes(simulation_run, |height_metrics| {
+            &height_metrics.sent_latencies_by_priority
+        })
+    }
+
+    fn for_all_classes<K: Ord + Clone>(
+        simulation_run: &SimulationRun,
+        class_latencies: impl Fn(&HeightMetrics) -> &BTreeMap<K, LatencyHistogram>,
+    ) -> BTreeMap<K, TagStats> {
+        let simulation = &simulation_run.simulation;
+        let num_heights = simulation.blocks.len().saturating_sub(1).max(1) as f64;
+        let mut latencies: BTreeMap<K, LatencyHistogram> = BTreeMap::new();
+        for height_metrics in &simulation.metrics {
+            for (class, histogram) in class_latencies(height_metrics) {
+                latencies.entry(class.clone()).or_default().merge(histogram);
+            }
+        }
+        latencies
+            .into_iter()
+            .map(|(class, histogram)| {
+                let class_stats = TagStats {
+                    sent: histogram.total_bytes(),
+                    throughput: histogram.total_bytes() as f64 / num_heights,
+                    mean_latency: histogram.mean_latency_by_bytes(),
+                    max_latency: histogram.buckets.keys().last().copied().unwrap_or(0),
+                    latencies: histogram,
+                };
+                (class, class_stats)
+            })
+            .collect()
+    }
+}
+
+impl TestStats {
+    pub fn new(simulation_run: &SimulationRun) -> TestStats {
+        let total_sent = TotalSent::new(simulation_run);
+
+        println!("Total sent:\n{:#?}", total_sent.total_sent);
+
+        // Infinite when a link was starved, `check` reports it instead of panicking here.
+        let max_min_ratio = total_sent.sent_ratio();
+        let bandwidth_utilization = total_sent.bandwidth_utilization();
+        let capacity_utilization = CapacityUtilization::new(simulation_run);
+        let optimality_gap = OptimalityGap::new(simulation_run);
+        let offered_load = OfferedLoad::new(simulation_run);
+        let max_receipt_age = MaxReceiptAge::new(simulation_run);
+        let backlog_growth = BacklogGrowth::new(simulation_run);
+        let is_unstable = backlog_growth.is_unstable();
+        let queue_growth = QueueGrowth::new(simulation_run);
+        let littles_law = LittlesLawCheck::new(simulation_run);
+        // Little's law only holds when the queues are stable
+        if !is_unstable {
+            littles_law.print_warnings();
+        }
+        let num_heights = simulation_run.simulation.blocks.len();
+        let windowed_fairness: Vec<WindowedFairness> = FAIRNESS_WINDOW_SIZES
+            .iter()
+            .copied()
+            .filter(|window_size| *window_size < num_heights)
+            .chain(std::iter::once(num_heights))
+            .flat_map(|window_size| WindowedFairness::new(simulation_run, window_size))
+            .collect();
+
+        let mut missing_chunks = 0;
+        let mut all_chunks = 0;
+        for block in simulation_run.simulation.blocks.iter().flatten() {
+            for chunk_opt in block.chunks.values() {
+                all_chunks += 1;
+                if !chunk_opt.is_some() {
+                    missing_chunks += 1;
+                }
+            }
+        }
+        let missing_chunks_ratio = missing_chunks as f64 / all_chunks as f64;
+        let missing_blocks = MissingBlockStats::new(&simulation_run.simulation);
+        let (mut allowance_samples, mut allowance_at_max, mut allowance_at_zero) = (0, 0, 0);
+        for height_metrics in &simulation_run.simulation.metrics {
+            let saturation = &height_metrics.allowance_saturation;
+            allowance_samples += saturation.links;
+            allowance_at_max += saturation.at_max;
+            allowance_at_zero += saturation.at_zero;
+        }
+        let allowance_at_max_ratio = allowance_at_max as f64 / allowance_samples.max(1) as f64;
+        let allowance_at_zero_ratio = allowance_at_zero as f64 / allowance_samples.max(1) as f64;
+        let shed_bytes = offered_load.total_shed();
+        let shed_ratio = offered_load.shed_ratio();
+        let drop_stats = DropStats::new(simulation_run);
+        let allowance_concentration = AllowanceConcentrationStats::new(simulation_run);
+        let max_scheduler_state_size = simulation_run
+            .simulation
+            .metrics
+            .iter()
+            .map(|m| m.scheduler_state_size)
+            .max()
+            .unwrap_or(0);
+        let send_receive_symmetry = SendReceiveSymmetry::new(simulation_run, SYMMETRY_WINDOW_SIZE);
+        let custom_metrics = CustomMetricStats::for_all_metrics(simulation_run);
+        let denial_stats = DenialStats::new(simulation_run);
+        let global_budget = GlobalBudgetStats::new(simulation_run);
+        let chunk_size = ChunkSizeStats::new(simulation_run);
+        let recovery_after_missing_chunks =
+            RecoveryStats::new(simulation_run, OutageKind::MissingChunks, RECOVERY_WINDOW);
+        let recovery_after_missing_blocks =
+            RecoveryStats::new(simulation_run, OutageKind::MissingBlocks, RECOVERY_WINDOW);
+        let tag_stats = TagStats::for_all_tags(simulation_run);
+        let priority_stats = TagStats::for_all_priorities(simulation_run);
+        let request_overhead = RequestOverhead::new(simulation_run);
+        let protocol_overhead = ProtocolOverhead::new(simulation_run);
+
+        println!("Offered vs delivered load:");
+        for (link, link_load) in &offered_load.links {
+            println!(
+                "{:?}: offered = {}, delivered = {} ({:.2}%), shed = {}",
+                link,
+                link_load.offered,
+                link_load.delivered,
+                link_load.delivered as f64 / link_load.offered.max(1) as f64 * 100.0,
+                link_load.shed
+            );
+        }
+        send_receive_symmetry.print();
+        println!("{:#?}", max_min_ratio);
+        println!("{:#?}", bandwidth_utilization);
+        println!("{:#?}", optimality_gap);
+
+        println!("\n=== Main metrics: ======================================================");
+        let description = &simulation_run.simulation.description;
+        if !description.is_empty() {
+            println!("  {}", description);
+        }
+        println!(
+            "  max sent/min sent ratio (fairness) = {:.2}% (the smaller the better)",
+            max_min_ratio.ratio * 100.0
+        );
+        for fairness in &windowed_fairness {
+            println!(
+                "  fairness over {} heights: worst = {:.2}% (at height {}), median = {:.2}%",
+                fairness.window_size,
+                fairness.worst_ratio * 100.0,
+                fairness.worst_window_start,
+                fairness.median_ratio * 100.0
+            );
+        }
+        println!(
+            "  bandwidth utilization = {:.2}% (the bigger the better)",
+            bandwidth_utilization.utilization * 100.0
+        );
+        println!(
+            "  capacity utilization: outgoing = {:.2}%, incoming = {:.2}%",
+            capacity_utilization.mean_outgoing * 100.0,
+            capacity_utilization.mean_incoming * 100.0
+        );
+        println!(
+            "  achieved/optimal throughput = {:.2}% (the bigger the better)",
+            optimality_gap.ratio * 100.0
+        );
+        println!(
+            "  max receipt age = {} heights (on {:?} at height {})",
+            max_receipt_age.age, max_receipt_age.link, max_receipt_age.height
+        );
+        println!(
+            "  max backlog growth = {:.0} bytes per height (on {:?}), unstable: {}",
+            backlog_growth.max_slope, backlog_growth.link, is_unstable
+        );
+        println!(
+            "  missing chunk ratio: {:.2}%",
+            missing_chunks_ratio * 100.0
+        );
+        println!(
+            "  missing block ratio: {:.2}%, longest run of missing blocks: {}",
+            missing_blocks.missing_blocks_ratio * 100.0,
+            missing_blocks.longest_missing_streak
+        );
+        for recovery in [
+            &recovery_after_missing_chunks,
+            &recovery_after_missing_blocks,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            recovery.print();
+        }
+        println!(
+            "  allowance at the cap: {:.2}%, at zero: {:.2}% (of links at all heights)",
+            allowance_at_max_ratio * 100.0,
+            allowance_at_zero_ratio * 100.0
+        );
+        println!(
+            "  allowance held by the top 10% of links: mean {:.2}%, second half mean {:.2}%, max {:.2}%",
+            allowance_concentration.mean_top_decile_share * 100.0,
+            allowance_concentration.second_half_mean_top_decile_share * 100.0,
+            allowance_concentration.max_top_decile_share * 100.0
+        );
+        println!(
+            "  scheduler state: max {} link values",
+            max_scheduler_state_size
+        );
+        println!(
+            "  shed load = {} bytes ({:.2}% of the offered load)",
+            shed_bytes,
+            shed_ratio * 100.0
+        );
+        println!(
+            "  persistent net receivers: {:?}",
+            send_receive_symmetry.persistent_net_receivers()
+        );
+        if let Some(policy) = drop_stats.policy {
+            println!(
+                "  dropped with {:?}: {} receipts, {} bytes, mean age of a dropped byte = {:.2}, max age = {}",
+                policy,
+                drop_stats.dropped_receipts,
+                drop_stats.dropped_bytes,
+                drop_stats.mean_dropped_age,
+                drop_stats.max_dropped_age
+            );
+        }
+        println!(
+            "  denied bandwidth increases: {} (sender limit {:.2}%, receiver limit {:.2}%, both limits {:.2}%, receiver quota {:.2}%)",
+            denial_stats.total,
+            denial_stats.share(DenialReason::SenderLimit) * 100.0,
+            denial_stats.share(DenialReason::ReceiverLimit) * 100.0,
+            denial_stats.share(DenialReason::BothLimits) * 100.0,
+            denial_stats.share(DenialReason::ReceiverQuota) * 100.0
+        );
+        if let Some((shard_id, reason, share)) = denial_stats.bottleneck() {
+            println!(
+                "  bottleneck: {:?} on {:?} ({:.2}% of denials)",
+                reason,
+                shard_id,
+                share * 100.0
+            );
+        }
+        println!(
+            "  bandwidth requests size: {:.0} bytes per chunk (max {}), {:.0} bytes per block (max {}), {:.0} bytes per block when aggregated",
+            request_overhead.per_chunk_avg,
+            request_overhead.per_chunk_max,
+            request_overhead.per_block_avg,
+            request_overhead.per_block_max,
+            request_overhead.aggregated_per_block_avg
+        );
+        println!(
+            "  protocol overhead: {:.0} bytes of metadata per block ({:.0} requests, {:.0} congestion info, {:.0} receipt sizes), {:.0} bytes of receipts, overhead ratio = {:.4}%",
+            protocol_overhead.requests_per_block
+                + protocol_overhead.congestion_info_per_block
+                + protocol_overhead.receipt_sizes_per_block,
+            protocol_overhead.requests_per_block,
+            protocol_overhead.congestion_info_per_block,
+            protocol_overhead.receipt_sizes_per_block,
+            protocol_overhead.payload_per_block,
+            protocol_overhead.overhead_ratio * 100.0
+        );
+        if let Some(budget_stats) = &global_budget {
+            println!(
+                "  global budget of {} bytes: exceeded at {:.2}% of heights, max {} bytes sent in a block, mean usage = {:.2}%",
+                budget_stats.budget,
+                budget_stats.capped_ratio * 100.0,
+                budget_stats.max_sent_per_block,
+                budget_stats.mean_budget_usage * 100.0
+            );
+            if let Some(fairness) = &budget_stats.capped_fairness {
+                println!(
+                    "  fairness under the global budget = {:.2}%",
+                    fairness.ratio * 100.0
+                );
+            }
+        }
+        if let Some(chunk_size_stats) = &chunk_size {
+            println!(
+                "  chunk size limit of {} bytes ({} for transactions): chunk full in {:.2}% of chunks, grant too small in {:.2}%, mean space usage = {:.2}%",
+                chunk_size_stats.limit.max_chunk_size,
+                chunk_size_stats.limit.transactions_size,
+                chunk_size_stats.chunk_size_bound_ratio * 100.0,
+                chunk_size_stats.bandwidth_bound_ratio * 100.0,
+                chunk_size_stats.mean_space_usage * 100.0
+            );
+        }
+        for (tag, stats) in &tag_stats {
+            println!(
+                "  tag {}: throughput = {:.0} bytes per height, mean latency = {:.2}, max latency = {}",
+                tag, stats.throughput, stats.mean_latency, stats.max_latency
+            );
+        }
+        // With a single priority there's nothing to compare
+        if priority_stats.len() > 1 {
+            for (priority, stats) in &priority_stats {
+                println!(
+                    "  priority {:?}: throughput = {:.0} bytes per height, mean latency = {:.2}, p99 latency = {}, max latency = {}",
+                    priority,
+                    stats.throughput,
+                    stats.mean_latency,
+                    stats.latencies.percentile(0.99).unwrap_or(0),
+                    stats.max_latency
+                );
+            }
+        }
+        for (name, stats) in &custom_metrics {
+            println!(
+                "  {}: mean = {:.4}, min = {:.4}, max = {:.4}, last = {:.4} ({} samples)",
+                name, stats.mean, stats.min, stats.max, stats.last, stats.samples
+            );
+        }
+        let performance = &simulation_run.performance;
+        println!(
+            "  simulated {} heights in {:.2?} ({:.0} heights per second), {:.2}% of the time spent in the scheduler, {:.2}% in validation",
+            performance.heights,
+            performance.total_time,
+            performance.heights_per_second(),
+            performance.scheduler_time_share() * 100.0,
+            performance.validation_time_share() * 100.0
+        );
+        println!("========================================================================");
+
+        TestStats {
+            total_sent,
+            max_min_ratio,
+            windowed_fairness,
+            bandwidth_utilization,
+            capacity_utilization,
+            optimality_gap,
+            offered_load,
+            max_receipt_age,
+            backlog_growth,
+            queue_growth,
+            is_unstable,
+            shed_bytes,
+            shed_ratio,
+            littles_law,
+            missing_chunks_ratio,
+            missing_blocks,
+            allowance_at_max_ratio,
+            allowance_at_zero_ratio,
+            request_overhead,
+            protocol_overhead,
+            tag_stats,
+            priority_stats,
+            drop_stats,
+            allowance_concentration,
+            max_scheduler_state_size,
+            send_receive_symmetry,
+            custom_metrics,
+            denial_stats,
+            global_budget,
+            chunk_size,
+            recovery_after_missing_chunks,
+            recovery_after_missing_blocks,
+        }
+    }
+
+    /// Basic assertion that should be true for all tests, same as `assert_with` with the default thresholds.
+    pub fn basic_assert(&self) {
+        self.assert_with(StatsThresholds::default());
+    }
+
+    /// Assert that the stats are within the thresholds.
+    pub fn assert_with(&self, thresholds: StatsThresholds) {
+        if let Err(violations) = self.check(thresholds) {
+            let messages: Vec<String> = violations.iter().map(ToString::to_string).collect();
+            panic!("{}", messages.join("\n"));
+        }
+    }
+
+    /// Compare the stats with the thresholds, like `assert_with`, but return all thresholds
+    /// that weren't met instead of panicking at the first one.
+    pub fn check(&self, thresholds: StatsThresholds) -> Result<(), Vec<StatsViolation>> {
+        let mut violations = Vec::new();
+        if self.max_min_ratio.ratio > thresholds.max_min_ratio {
+            violations.push(StatsViolation::Unfair(self.max_min_ratio));
+        }
+        if self.bandwidth_utilization.utilization <= thresholds.min_bandwidth_utilization {
+            violations.push(StatsViolation::LowBandwidthUtilization(
+                self.bandwidth_utilization,
+            ));
+        }
+        if self.optimality_gap.ratio <= thresholds.min_optimality_ratio {
+            violations.push(StatsViolation::FarFromOptimal(self.optimality_gap));
+        }
+        if let Some(limit) = thresholds.max_receipt_age {
+            if self.max_receipt_age.age > limit {
+                violations.push(StatsViolation::ReceiptTooOld {
+                    max_receipt_age: self.max_receipt_age,
+                    limit,
+                });
+            }
+        }
+        if !thresholds.allow_unstable && self.is_unstable {
+            violations.push(StatsViolation::Unstable(self.backlog_growth));
+        }
+        if self.shed_ratio > thresholds.max_shed_ratio {
+            violations.push(StatsViolation::LoadShed {
+                shed_ratio: self.shed_ratio,
+                shed_bytes: self.shed_bytes,
+                limit: thresholds.max_shed_ratio,
+            });
+        }
+        for slo in &thresholds.latency_slos {
+            if !slo.is_met_by(self) {
+                violations.push(StatsViolation::LatencySlo {
+                    slo: *slo,
+                    measured_latency: slo.measured_latency(self),
+                });
+            }
+        }
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Assert that the receipts of a priority class met their latency objective.
+    pub fn assert_latency_slo(&self, slo: &LatencySlo) {
+        assert!(
+            slo.is_met_by(self),
+            "Latency SLO violated: {:?}, measured latency: {:?}",
+            slo,
+            slo.measured_latency(self)
+        );
+    }
+
+    /// Assert that at most `max_ratio` of the offered load was shed.
+    pub fn assert_max_shed_ratio(&self, max_ratio: f64) {
+        assert!(
+            self.shed_ratio <= max_ratio,
+            "Shed {:.2}% of the offered load ({} bytes)",
+            self.shed_ratio * 100.0,
+            self.shed_bytes
+        );
+    }
+
+    /// Assert that the average queue sizes in the last quarter of the run aren't bigger than in the
+    /// first quarter by more than `tolerance` (0.1 = 10%), see `QueueGrowth::growing_links`.
+    /// Useful in saturation scenarios, where the queues are never empty, but shouldn't keep growing.
+    pub fn assert_no_queue_growth(&self, tolerance: f64) {
+        let growing_links = self.queue_growth.growing_links(tolerance);
+        assert!(
+            growing_links.is_empty(),
+            "Queues grew by more than {:.0}%: {:#?}",
+            tolerance * 100.0,
+            growing_links
+        );
+    }
+
+    /// Assert that no receipt waited in an outgoing queue for more than `max_age` heights.
+    pub fn assert_max_receipt_age(&self, max_age: usize) {
+        assert!(
+            self.max_receipt_age.age <= max_age,
+            "Receipt waited for too long: {:?}",
+            self.max_receipt_age
+        );
+    }
+}
@@ -1,7 +0,0 @@
-pub mod bandwidth_request;
-pub mod bandwidth_scheduler;
-pub mod chain;
-pub mod rng;
-pub mod simulation;
-pub mod tests;
-pub mod validation;
@@ -1,8 +0,0 @@
-pub mod big_vs_small;
-pub mod distribute_remaining;
-pub mod medium_vs_small;
-pub mod missing_chunks;
-pub mod randomized;
-pub mod typical;
-
-pub const DEFAULT_TEST_LENGTH: usize = 1000;